@@ -243,7 +243,9 @@ deploy_docker_local() {
 
     # Build Docker image
     print_status "INFO" "Building Docker image..."
-    if ! docker build --network=host -t "$DOCKER_IMAGE_NAME" .; then
+    local git_hash
+    git_hash=$(git rev-parse --short=12 HEAD 2>/dev/null || echo unknown)
+    if ! docker build --network=host --build-arg GIT_HASH="$git_hash" -t "$DOCKER_IMAGE_NAME" .; then
         print_status "ERROR" "Failed to build Docker image"
         exit 1
     fi
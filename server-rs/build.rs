@@ -0,0 +1,26 @@
+/**
+ * Build script
+ *
+ * Embeds the git commit the binary was built from as GIT_HASH (shown in
+ * /api/admin/info). Builds outside a git checkout (tarballs, packaging)
+ * can pass GIT_HASH in the environment; otherwise it is "unknown".
+ */
+use std::{env, process::Command};
+
+fn main() {
+    let hash = env::var("GIT_HASH").ok().or_else(|| {
+        let output = Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    });
+    println!(
+        "cargo:rustc-env=GIT_HASH={}",
+        hash.as_deref().unwrap_or("unknown")
+    );
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+}
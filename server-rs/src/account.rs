@@ -0,0 +1,316 @@
+/**
+ * The caller's own account: data export and erasure
+ *
+ * Self-service versions of the privacy requests in users.rs, for the
+ * member making the request (X-User-Id or a session).
+ *
+ * - GET    /api/me/export                  everything about the caller, as a zip archive
+ * - DELETE /api/me?mode=anonymize|delete   erase the account
+ *
+ * The archive holds:
+ *
+ * ```text
+ * account.json        profile, webhooks, memberships, notifications, settings, changes
+ * todos.json          todos the member created (deleted ones too)
+ * comments.json       comments they wrote
+ * time_entries.json   time they tracked
+ * sessions.json       signed-in devices
+ * attachments.json    files on their todos, stored as attachments/<id>/<filename>
+ * ```
+ *
+ * `anonymize` (default) keeps the household's data: the member row is
+ * scrubbed as by `POST /api/users/{id}/anonymize`, and their todos,
+ * comments and audit entries stay, credited to "Former member".
+ * `delete` also removes, in the same transaction, the todos they created
+ * (with their comments, checklists, attachments, tracked time and
+ * history), their comments on other todos and every audit entry they
+ * caused; it needs SQLite storage. Time they tracked on other todos stays
+ * (it is part of those todos' totals). Both end every session of the
+ * member.
+ */
+use std::io::Write;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+    model::{Attachment, Comment, Session, TimeEntry},
+    outbox::event,
+    routes::AppState,
+    services::TodoFilter,
+    users,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/me", delete(erase_me))
+        .route("/api/me/export", get(export_me))
+}
+
+/// The caller's user id; 404 once the account is gone.
+async fn me(pool: &SqlitePool, actor: &Actor) -> ApiResult<String> {
+    let id = actor
+        .user_id()
+        .ok_or_else(|| ApiError::BadRequest("/api/me requests need an X-User-Id header".into()))?;
+    let found: Option<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE id=?1 AND deleted=0")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+    found.ok_or(ApiError::NotFound)
+}
+
+/// Ids of the todos `user_id` created, from the audit log.
+async fn created_todos(pool: &SqlitePool, user_id: &str) -> ApiResult<Vec<String>> {
+    Ok(sqlx::query_scalar(
+        "SELECT DISTINCT entity_id FROM audit_log WHERE entity='todo' AND action='created' AND actor=?1",
+    )
+    .bind(Actor::user(user_id).as_str())
+    .fetch_all(pool)
+    .await?)
+}
+
+/// `ids` as a JSON array, for `IN (SELECT value FROM json_each(?))`.
+fn id_list(ids: &[String]) -> String {
+    Value::from(ids.to_vec()).to_string()
+}
+
+/// Attachments on the given todos.
+async fn attachments_of(pool: &SqlitePool, todo_ids: &[String]) -> ApiResult<Vec<Attachment>> {
+    let rows = sqlx::query_as::<_, Attachment>(
+        "SELECT * FROM attachments WHERE todo_id IN (SELECT value FROM json_each(?1)) ORDER BY created_at",
+    )
+    .bind(id_list(todo_ids))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(Attachment::opened).collect())
+}
+
+async fn export_me(State(st): State<AppState>, actor: Actor) -> ApiResult<Response> {
+    let id = me(&st.pool, &actor).await?;
+    let account = users::export_user(&st.pool, &id).await?;
+    let created = created_todos(&st.pool, &id).await?;
+    let filter = TodoFilter {
+        include_deleted: true,
+        ..Default::default()
+    };
+    let mut todos = st.todos.list(&filter).await?;
+    todos.retain(|t| created.contains(&t.id));
+    let author = Actor::user(&id);
+    let comments = sqlx::query_as::<_, Comment>(
+        "SELECT * FROM todo_comments WHERE actor=?1 ORDER BY created_at",
+    )
+    .bind(author.as_str())
+    .fetch_all(&st.pool)
+    .await?;
+    let time_entries = sqlx::query_as::<_, TimeEntry>(
+        "SELECT * FROM time_entries WHERE actor=?1 ORDER BY started_at",
+    )
+    .bind(author.as_str())
+    .fetch_all(&st.pool)
+    .await?;
+    let sessions =
+        sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE user_id=?1 ORDER BY created_at")
+            .bind(&id)
+            .fetch_all(&st.pool)
+            .await?;
+    let attachments = attachments_of(&st.pool, &created).await?;
+    let mut files = Vec::with_capacity(attachments.len());
+    for a in &attachments {
+        match tokio::fs::read(st.attachments.path(&a.id)).await {
+            Ok(bytes) => files.push((format!("attachments/{}/{}", a.id, a.filename), bytes)),
+            Err(e) => {
+                tracing::warn!(attachment = %a.id, error = %e, "attachment missing from export")
+            }
+        }
+    }
+
+    let archive = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let json = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        // Photos and PDFs are compressed already
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let documents: [(&str, Value); 6] = [
+            ("account.json", account),
+            ("todos.json", serde_json::to_value(&todos)?),
+            ("comments.json", serde_json::to_value(&comments)?),
+            ("time_entries.json", serde_json::to_value(&time_entries)?),
+            ("sessions.json", serde_json::to_value(&sessions)?),
+            ("attachments.json", serde_json::to_value(&attachments)?),
+        ];
+        for (name, doc) in documents {
+            zip.start_file(name, json)?;
+            zip.write_all(&serde_json::to_vec_pretty(&doc)?)?;
+        }
+        for (name, bytes) in files {
+            zip.start_file(name, stored)?;
+            zip.write_all(&bytes)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
+
+    let filename = format!("todo-export-{}.zip", Utc::now().format("%Y%m%d"));
+    let mut response = archive.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    if let Ok(v) = HeaderValue::from_str(&format!("attachment; filename=\"{filename}\"")) {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    Ok(response)
+}
+
+#[derive(Deserialize)]
+struct EraseParams {
+    mode: Option<String>, // "anonymize" (default) or "delete"
+}
+
+/**
+ * What an erasure removed
+ */
+#[derive(Debug, Default, Serialize)]
+struct Erased {
+    mode: &'static str,
+    todos: u64,         // Todos the member created, hard-deleted
+    comments: u64,      // Comments deleted (theirs, and others' on their todos)
+    attachments: u64,   // Attachment files removed
+    time_entries: u64,  // Time tracked on their todos, deleted
+    audit_entries: u64, // History entries deleted
+}
+
+async fn erase_me(
+    State(st): State<AppState>,
+    actor: Actor,
+    Query(p): Query<EraseParams>,
+) -> ApiResult<Json<Value>> {
+    let id = me(&st.pool, &actor).await?;
+    let mode = match p.mode.as_deref().unwrap_or("anonymize") {
+        "anonymize" => "anonymize",
+        "delete" => "delete",
+        other => {
+            return Err(ApiError::BadRequest(format!(
+                "mode must be `anonymize` or `delete`, got `{other}`"
+            )));
+        }
+    };
+    if mode == "delete" && st.integrations.storage != "sqlite" {
+        return Err(ApiError::BadRequest(format!(
+            "mode=delete needs SQLite storage (todos are in {}); use mode=anonymize",
+            st.integrations.storage
+        )));
+    }
+
+    let mut erased = Erased {
+        mode,
+        ..Default::default()
+    };
+    let mut files = Vec::new();
+    let mut tx = st.pool.begin().await?;
+    if mode == "delete" {
+        let created = created_todos(&st.pool, &id).await?;
+        files = attachments_of(&st.pool, &created)
+            .await?
+            .into_iter()
+            .map(|a| a.id)
+            .collect();
+        erased.attachments = files.len() as u64;
+        let todos = id_list(&created);
+        let author = Actor::user(&id);
+
+        // Everything hanging off the member's todos, then the todos
+        for table in [
+            "todo_comments",
+            "checklist_items",
+            "attachments",
+            "time_entries",
+            "pomodoro_sessions",
+            "reminder_log",
+            "escalation_log",
+            "notifications",
+            "import_rows",
+        ] {
+            // `table` is one of the names above, never user input
+            let res = sqlx::query(&format!(
+                "DELETE FROM {table} WHERE todo_id IN (SELECT value FROM json_each(?1))"
+            ))
+            .bind(&todos)
+            .execute(&mut *tx)
+            .await?;
+            match table {
+                "todo_comments" => erased.comments += res.rows_affected(),
+                "time_entries" => erased.time_entries = res.rows_affected(),
+                _ => {}
+            }
+        }
+        sqlx::query(
+            r#"
+            DELETE FROM todo_links WHERE blocker_id IN (SELECT value FROM json_each(?1))
+                                      OR blocked_id IN (SELECT value FROM json_each(?1))
+        "#,
+        )
+        .bind(&todos)
+        .execute(&mut *tx)
+        .await?;
+        erased.audit_entries += sqlx::query(
+            "DELETE FROM audit_log WHERE entity='todo' AND entity_id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&todos)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        erased.todos =
+            sqlx::query("DELETE FROM todos WHERE id IN (SELECT value FROM json_each(?1))")
+                .bind(&todos)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        for todo_id in &created {
+            sqlx::query("INSERT INTO outbox (message, created_at) VALUES (?1, ?2)")
+                .bind(event("todo.deleted", &json!({"id": todo_id})))
+                .bind(Utc::now())
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // What the member left on other todos
+        erased.comments += sqlx::query("DELETE FROM todo_comments WHERE actor=?1")
+            .bind(author.as_str())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        erased.audit_entries += sqlx::query("DELETE FROM audit_log WHERE actor=?1")
+            .bind(author.as_str())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+    users::scrub(&mut tx, &id, None).await?;
+    tx.commit().await?;
+    st.outbox.notify();
+
+    for file in files {
+        if let Err(e) = tokio::fs::remove_file(st.attachments.path(&file)).await
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!(attachment = %file, error = %e, "failed to delete attachment file");
+        }
+    }
+    tracing::info!(user = %id, mode, todos = erased.todos, "account erased");
+    Ok(Json(json!({"ok": true, "erased": erased})))
+}
@@ -0,0 +1,472 @@
+/**
+ * ACME (Let's Encrypt) certificate automation
+ *
+ * When ACME_DOMAINS is set the server obtains its own TLS certificate,
+ * keeps it in a cache directory under ./data, renews it in the background
+ * and hot-swaps it into the running HTTPS listener without a restart.
+ *
+ * Challenges are solved through the ChallengeResolver trait:
+ * - Http01Resolver answers /.well-known/acme-challenge/{token} itself
+ * - Dns01HookResolver delegates TXT record management to an external script
+ *   (handy for dynamic-DNS providers that expose a TXT update API)
+ */
+use std::{
+    collections::HashMap,
+    env,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, anyhow};
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use futures::future::BoxFuture;
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, KeyAuthorization,
+    LetsEncrypt, NewAccount, NewOrder, OrderStatus, RetryPolicy,
+};
+
+/**
+ * ACME settings read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct AcmeConfig {
+    pub domains: Vec<String>,      // ACME_DOMAINS (comma separated)
+    pub contact: Option<String>,   // ACME_CONTACT e-mail address
+    pub directory_url: String,     // ACME_DIRECTORY ("staging", "production" or a URL)
+    pub challenge: String,         // ACME_CHALLENGE: http-01 (default) or dns-01
+    pub cache_dir: PathBuf,        // ACME_CACHE_DIR, default ./data/acme
+    pub http_port: u16,            // ACME_HTTP_PORT for http-01, default 80
+    pub renew_before: Duration,    // ACME_RENEW_DAYS: renew when less validity than this is left
+    pub dns_hook: Option<String>,  // ACME_DNS_HOOK script for dns-01
+    pub dns_propagation: Duration, // ACME_DNS_PROPAGATION_SECS to wait after the hook
+}
+
+impl AcmeConfig {
+    /// Returns None when ACME is not enabled (ACME_DOMAINS unset or empty).
+    pub fn from_env() -> Option<Self> {
+        let domains: Vec<String> = env::var("ACME_DOMAINS")
+            .ok()?
+            .split(',')
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty())
+            .collect();
+        if domains.is_empty() {
+            return None;
+        }
+
+        let directory_url = match env::var("ACME_DIRECTORY").ok().as_deref() {
+            None | Some("production") => LetsEncrypt::Production.url().to_string(),
+            Some("staging") => LetsEncrypt::Staging.url().to_string(),
+            Some(url) => url.to_string(),
+        };
+        let renew_days: u64 = env::var("ACME_RENEW_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(30);
+
+        Some(Self {
+            domains,
+            contact: env::var("ACME_CONTACT").ok(),
+            directory_url,
+            challenge: env::var("ACME_CHALLENGE").unwrap_or_else(|_| "http-01".into()),
+            cache_dir: env::var("ACME_CACHE_DIR")
+                .unwrap_or_else(|_| "./data/acme".into())
+                .into(),
+            http_port: env::var("ACME_HTTP_PORT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(80),
+            renew_before: Duration::from_secs(renew_days * 24 * 60 * 60),
+            dns_hook: env::var("ACME_DNS_HOOK").ok(),
+            dns_propagation: Duration::from_secs(
+                env::var("ACME_DNS_PROPAGATION_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(60),
+            ),
+        })
+    }
+}
+
+/**
+ * Pluggable challenge solver
+ *
+ * present() must make the challenge response visible to the ACME server,
+ * cleanup() removes it again once the authorization has been processed.
+ */
+pub trait ChallengeResolver: Send + Sync {
+    fn challenge_type(&self) -> ChallengeType;
+
+    fn present<'a>(
+        &'a self,
+        domain: &'a str,
+        token: &'a str,
+        key_auth: &'a KeyAuthorization,
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+
+    fn cleanup<'a>(&'a self, domain: &'a str, token: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+/**
+ * HTTP-01 resolver - keeps pending tokens in memory and serves them
+ * through challenge_router() on the plain HTTP port.
+ */
+#[derive(Clone, Default)]
+pub struct Http01Resolver {
+    tokens: Arc<RwLock<HashMap<String, String>>>, // token -> key authorization
+}
+
+impl Http01Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Router answering /.well-known/acme-challenge/{token}
+    pub fn challenge_router(&self) -> Router {
+        Router::new()
+            .route("/.well-known/acme-challenge/{token}", get(serve_challenge))
+            .with_state(self.clone())
+    }
+}
+
+async fn serve_challenge(
+    State(resolver): State<Http01Resolver>,
+    Path(token): Path<String>,
+) -> Result<String, StatusCode> {
+    let tokens = resolver.tokens.read().unwrap();
+    tokens.get(&token).cloned().ok_or(StatusCode::NOT_FOUND)
+}
+
+impl ChallengeResolver for Http01Resolver {
+    fn challenge_type(&self) -> ChallengeType {
+        ChallengeType::Http01
+    }
+
+    fn present<'a>(
+        &'a self,
+        _domain: &'a str,
+        token: &'a str,
+        key_auth: &'a KeyAuthorization,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        let value = key_auth.as_str().to_string();
+        Box::pin(async move {
+            self.tokens
+                .write()
+                .unwrap()
+                .insert(token.to_string(), value);
+            Ok(())
+        })
+    }
+
+    fn cleanup<'a>(
+        &'a self,
+        _domain: &'a str,
+        token: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.tokens.write().unwrap().remove(token);
+            Ok(())
+        })
+    }
+}
+
+/**
+ * DNS-01 resolver - runs `<hook> present|cleanup <domain> <txt-value>`
+ *
+ * The hook is expected to create/remove the `_acme-challenge.<domain>` TXT
+ * record with the given value at the DNS provider.
+ */
+pub struct Dns01HookResolver {
+    hook: String,
+    propagation: Duration,
+    values: RwLock<HashMap<String, String>>, // token -> TXT value (needed for cleanup)
+}
+
+impl Dns01HookResolver {
+    pub fn new(hook: String, propagation: Duration) -> Self {
+        Self {
+            hook,
+            propagation,
+            values: RwLock::new(HashMap::new()),
+        }
+    }
+
+    async fn run_hook(&self, action: &str, domain: &str, value: &str) -> anyhow::Result<()> {
+        let status = tokio::process::Command::new(&self.hook)
+            .arg(action)
+            .arg(domain)
+            .arg(value)
+            .status()
+            .await
+            .with_context(|| format!("failed to run ACME DNS hook {}", self.hook))?;
+        if !status.success() {
+            return Err(anyhow!("ACME DNS hook `{action}` failed with {status}"));
+        }
+        Ok(())
+    }
+}
+
+impl ChallengeResolver for Dns01HookResolver {
+    fn challenge_type(&self) -> ChallengeType {
+        ChallengeType::Dns01
+    }
+
+    fn present<'a>(
+        &'a self,
+        domain: &'a str,
+        token: &'a str,
+        key_auth: &'a KeyAuthorization,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        let value = key_auth.dns_value();
+        Box::pin(async move {
+            self.run_hook("present", domain, &value).await?;
+            self.values
+                .write()
+                .unwrap()
+                .insert(token.to_string(), value);
+            // Give the record time to reach the authoritative servers
+            tokio::time::sleep(self.propagation).await;
+            Ok(())
+        })
+    }
+
+    fn cleanup<'a>(&'a self, domain: &'a str, token: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let value = self.values.write().unwrap().remove(token);
+            match value {
+                Some(v) => self.run_hook("cleanup", domain, &v).await,
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+/**
+ * Certificate manager - issuance, caching and renewal
+ */
+pub struct AcmeManager {
+    config: AcmeConfig,
+    resolver: Arc<dyn ChallengeResolver>,
+}
+
+impl AcmeManager {
+    pub fn new(config: AcmeConfig, resolver: Arc<dyn ChallengeResolver>) -> Self {
+        Self { config, resolver }
+    }
+
+    fn cert_path(&self) -> PathBuf {
+        self.config.cache_dir.join("cert.pem")
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.config.cache_dir.join("key.pem")
+    }
+
+    fn account_path(&self) -> PathBuf {
+        self.config.cache_dir.join("account.json")
+    }
+
+    /// True when no readable cached certificate exists or it expires within the threshold.
+    fn needs_renewal(&self) -> bool {
+        match self.remaining_validity() {
+            Some(left) => left < self.config.renew_before,
+            None => true,
+        }
+    }
+
+    /// Time until the cached certificate's notAfter; None when missing, unparsable or expired.
+    fn remaining_validity(&self) -> Option<Duration> {
+        let raw = std::fs::read(self.cert_path()).ok()?;
+        let (_, pem) = x509_parser::pem::parse_x509_pem(&raw).ok()?;
+        let cert = pem.parse_x509().ok()?;
+        let not_after = cert.validity().not_after.timestamp();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let left = not_after.checked_sub(i64::try_from(now).ok()?)?;
+        u64::try_from(left).ok().map(Duration::from_secs)
+    }
+
+    /// Load the cached certificate (issuing one first if necessary).
+    pub async fn load_or_issue(&self) -> anyhow::Result<RustlsConfig> {
+        if self.needs_renewal() {
+            self.issue().await?;
+        }
+        RustlsConfig::from_pem_file(self.cert_path(), self.key_path())
+            .await
+            .context("failed to load ACME certificate")
+    }
+
+    /// Background loop: re-check daily and hot reload renewed certificates.
+    pub async fn renewal_loop(self: Arc<Self>, tls: RustlsConfig) {
+        let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+        interval.tick().await; // first tick fires immediately; startup already checked
+        loop {
+            interval.tick().await;
+            if !self.needs_renewal() {
+                continue;
+            }
+            match self.issue().await {
+                Ok(()) => match tls
+                    .reload_from_pem_file(self.cert_path(), self.key_path())
+                    .await
+                {
+                    Ok(()) => tracing::info!("ACME certificate renewed and reloaded"),
+                    Err(e) => tracing::error!(error = %e, "failed to reload renewed certificate"),
+                },
+                // Retry on the next tick; the old certificate is still valid for a while
+                Err(e) => tracing::error!(error = %e, "ACME renewal failed"),
+            }
+        }
+    }
+
+    async fn account(&self) -> anyhow::Result<Account> {
+        let path = self.account_path();
+        if let Ok(raw) = tokio::fs::read(&path).await {
+            let credentials: AccountCredentials = serde_json::from_slice(&raw)?;
+            return Ok(Account::builder()?.from_credentials(credentials).await?);
+        }
+
+        let contact = self.config.contact.as_ref().map(|c| format!("mailto:{c}"));
+        let contact: Vec<&str> = contact.iter().map(String::as_str).collect();
+        let (account, credentials) = Account::builder()?
+            .create(
+                &NewAccount {
+                    contact: &contact,
+                    terms_of_service_agreed: true,
+                    only_return_existing: false,
+                },
+                self.config.directory_url.clone(),
+                None,
+            )
+            .await?;
+        write_file(&path, serde_json::to_vec(&credentials)?, PRIVATE).await?;
+        Ok(account)
+    }
+
+    /// Run a full ACME order and write cert.pem/key.pem into the cache dir.
+    async fn issue(&self) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.config.cache_dir).await?;
+        tracing::info!(domains = ?self.config.domains, "requesting ACME certificate");
+
+        let account = self.account().await?;
+        let identifiers: Vec<Identifier> = self
+            .config
+            .domains
+            .iter()
+            .map(|d| Identifier::Dns(d.clone()))
+            .collect();
+        let mut order = account.new_order(&NewOrder::new(&identifiers)).await?;
+
+        let mut presented = Vec::new();
+        let mut authorizations = order.authorizations();
+        while let Some(authz) = authorizations.next().await {
+            let mut authz = authz?;
+            match authz.status {
+                AuthorizationStatus::Pending => {}
+                AuthorizationStatus::Valid => continue,
+                status => return Err(anyhow!("unexpected authorization status {status:?}")),
+            }
+
+            let mut challenge = authz
+                .challenge(self.resolver.challenge_type())
+                .ok_or_else(|| anyhow!("ACME server offered no matching challenge"))?;
+            let domain = challenge
+                .identifier()
+                .to_string()
+                .trim_start_matches("*.")
+                .to_string();
+            let token = challenge.token.clone();
+            self.resolver
+                .present(&domain, &token, &challenge.key_authorization())
+                .await?;
+            presented.push((domain, token));
+            challenge.set_ready().await?;
+        }
+
+        let status = order.poll_ready(&RetryPolicy::default()).await;
+        for (domain, token) in &presented {
+            if let Err(e) = self.resolver.cleanup(domain, token).await {
+                tracing::warn!(error = %e, domain, "ACME challenge cleanup failed");
+            }
+        }
+        let status = status?;
+        if status != OrderStatus::Ready {
+            return Err(anyhow!("ACME order ended in status {status:?}"));
+        }
+
+        let key_pem = order.finalize().await?;
+        let cert_pem = order.poll_certificate(&RetryPolicy::default()).await?;
+        // Key first: a reload between the two renames still finds the old
+        // certificate, fails to match it and keeps the current pair
+        write_file(&self.key_path(), key_pem.into_bytes(), PRIVATE).await?;
+        write_file(&self.cert_path(), cert_pem.into_bytes(), 0o644).await?;
+        tracing::info!("ACME certificate issued");
+        Ok(())
+    }
+}
+
+/// Mode of the account credentials and the private key: owner only.
+const PRIVATE: u32 = 0o600;
+
+/**
+ * Replace `path` with `contents` atomically
+ *
+ * The data goes to a temporary file with `mode` first, which is then
+ * renamed over `path`, so readers see either the old or the new file and
+ * secrets are never readable by others, not even while being written.
+ */
+async fn write_file(path: &std::path::Path, contents: Vec<u8>, mode: u32) -> anyhow::Result<()> {
+    let target = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+        let tmp = target.with_extension("tmp");
+        // A leftover from a crash may have other permissions; mode only applies on creation
+        let _ = std::fs::remove_file(&tmp);
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&tmp)?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &target)
+    })
+    .await?
+    .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/**
+ * Build the resolver selected by ACME_CHALLENGE
+ *
+ * Returns the resolver plus, for http-01, the router that must be reachable
+ * on port 80 of every domain.
+ */
+pub fn build_resolver(
+    config: &AcmeConfig,
+) -> anyhow::Result<(Arc<dyn ChallengeResolver>, Option<Router>)> {
+    match config.challenge.as_str() {
+        "http-01" => {
+            let resolver = Http01Resolver::new();
+            let router = resolver.challenge_router();
+            Ok((Arc::new(resolver), Some(router)))
+        }
+        "dns-01" => {
+            let hook = config
+                .dns_hook
+                .clone()
+                .ok_or_else(|| anyhow!("ACME_CHALLENGE=dns-01 requires ACME_DNS_HOOK"))?;
+            Ok((
+                Arc::new(Dns01HookResolver::new(hook, config.dns_propagation)),
+                None,
+            ))
+        }
+        other => Err(anyhow!("unsupported ACME_CHALLENGE `{other}`")),
+    }
+}
@@ -0,0 +1,146 @@
+/**
+ * Per-todo activity feed
+ *
+ * GET /api/todos/{id}/activity merges the todo's audit entries and its
+ * comments into one chronological list, for a "ticket history" side
+ * panel. Each item has a `kind`:
+ *
+ * - "status"   a change that moved the todo to another status (not its creation)
+ * - "change"   any other audit entry (created, updated, snoozed, ...)
+ * - "comment"  a comment (see comments.rs)
+ *
+ * ```text
+ * {"kind": "status", "at": "...", "actor": "user:ann", "action": "status",
+ *  "changes": {"status": {"from": "todo", "to": "done"}}}
+ * {"kind": "comment", "at": "...", "actor": "api", "comment": {"id", "body", ...}}
+ * ```
+ *
+ * Oldest first; `?limit=` (default 50, max 200) and `?offset=` page
+ * through it, and `next_offset` is None on the last page. Audit entries
+ * carry the field diff only; the full snapshots stay at
+ * /api/todos/{id}/history.
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    comments::comments,
+    error::ApiResult,
+    model::{AuditEntry, Comment},
+    routes::AppState,
+};
+
+/// Items per page unless `?limit=` says otherwise.
+const DEFAULT_LIMIT: usize = 50;
+/// Largest accepted `?limit=`.
+const MAX_LIMIT: usize = 200;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/todos/{id}/activity", get(todo_activity))
+}
+
+#[derive(Deserialize)]
+struct ActivityParams {
+    limit: Option<usize>,  // Items per page (default 50, max 200)
+    offset: Option<usize>, // Items to skip (the previous page's next_offset)
+}
+
+/**
+ * One entry of the activity feed
+ */
+#[derive(Debug, Serialize)]
+pub struct ActivityItem {
+    pub kind: &'static str, // "status", "change" or "comment"
+    pub at: DateTime<Utc>,
+    pub actor: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>, // Audit action (status/change)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<Value>, // {"field": {"from", "to"}} (status/change)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<Comment>, // The comment (comment)
+}
+
+impl From<AuditEntry> for ActivityItem {
+    fn from(e: AuditEntry) -> Self {
+        let changes = e.changes.0;
+        let moved = changes["status"]["from"].is_string();
+        let kind = if moved { "status" } else { "change" };
+        Self {
+            kind,
+            at: e.created_at,
+            actor: e.actor,
+            action: Some(e.action),
+            changes: Some(changes),
+            comment: None,
+        }
+    }
+}
+
+impl From<Comment> for ActivityItem {
+    fn from(c: Comment) -> Self {
+        Self {
+            kind: "comment",
+            at: c.created_at,
+            actor: c.actor.clone(),
+            action: None,
+            changes: None,
+            comment: Some(c),
+        }
+    }
+}
+
+/**
+ * Response for GET /api/todos/{id}/activity
+ */
+#[derive(Debug, Serialize)]
+pub struct ActivityPage {
+    pub items: Vec<ActivityItem>,
+    pub total: usize,               // Items in the whole feed
+    pub next_offset: Option<usize>, // `offset` of the next page; None on the last one
+}
+
+async fn todo_activity(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+    Query(p): Query<ActivityParams>,
+) -> ApiResult<Json<ActivityPage>> {
+    st.todos.get(&id).await?;
+    let audit = sqlx::query_as::<_, AuditEntry>(
+        "SELECT * FROM audit_log WHERE entity='todo' AND entity_id=?1 ORDER BY id ASC",
+    )
+    .bind(&id)
+    .fetch_all(&st.pool)
+    .await?;
+
+    // Both lists are sorted; the stable sort keeps a change before a
+    // comment written in the same instant
+    let mut items: Vec<ActivityItem> = audit
+        .into_iter()
+        .map(|e| ActivityItem::from(e.opened()))
+        .collect();
+    items.extend(
+        comments(&st, &id)
+            .await?
+            .into_iter()
+            .map(ActivityItem::from),
+    );
+    items.sort_by_key(|i| i.at);
+
+    let total = items.len();
+    let limit = p.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = p.offset.unwrap_or(0);
+    let items: Vec<ActivityItem> = items.into_iter().skip(offset).take(limit).collect();
+    let end = offset.saturating_add(items.len());
+    Ok(Json(ActivityPage {
+        items,
+        total,
+        next_offset: (end < total).then_some(end),
+    }))
+}
@@ -0,0 +1,198 @@
+/**
+ * Admin endpoints
+ *
+ * Read-only introspection for the admin page. The GET endpoints are cheap
+ * to compute so they can be polled; the integrity check reads the whole
+ * database and only runs on request.
+ *
+ * Everything under /api/admin/ needs admin access (the [`require`] layer on
+ * each admin router), granted by either of:
+ *
+ * ```text
+ * X-Admin-Token: <admin_token>                  config admin_token / ADMIN_TOKEN
+ * Authorization: Bearer <session access token>  of a member in admin_users / ADMIN_USERS
+ * ```
+ *
+ * Sessions only count with OIDC: without it POST /api/sessions signs in as
+ * any member for the asking, so the server refuses to start with
+ * admin_users but no OIDC. X-User-Id alone never counts either: anyone on
+ * the LAN can send it. Without
+ * credentials the answer is 401, with the wrong ones 403; with neither
+ * setting configured the admin endpoints are closed to everyone.
+ */
+use std::time::Instant;
+
+use axum::{
+    Json, Router,
+    extract::{Query, Request, State},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audit::USER_HEADER,
+    db::{self, IntegrityCheck},
+    error::{ApiError, ApiResult},
+    jobs::JobsOverview,
+    model::{AdminInfo, AdminOverview, DbStats},
+    routes::AppState,
+    sessions::CurrentSession,
+};
+
+/// Header carrying the configured admin token.
+pub const TOKEN_HEADER: &str = "x-admin-token";
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/overview", get(overview))
+        .route("/api/admin/jobs", get(jobs))
+        .route("/api/admin/info", get(info))
+        .route("/api/admin/integrity-check", post(integrity_check))
+        .route_layer(middleware::from_fn(require))
+}
+
+/**
+ * Whether a request may use the admin endpoints (request extension, set by
+ * [`identify`])
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct AdminAccess {
+    granted: bool,    // Valid admin token or an admin member's session
+    presented: bool,  // Some credential was sent
+    configured: bool, // admin_token or admin_users is set
+}
+
+/// Middleware recording the caller's [`AdminAccess`]; runs after
+/// sessions::authenticate, which resolves bearer tokens to their member.
+pub async fn identify(State(st): State<AppState>, mut req: Request, next: Next) -> Response {
+    let config = &st.config;
+    let token = req
+        .headers()
+        .get(TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim);
+    // Comparing digests keeps the comparison from leaking the token
+    let token_ok = match (token, &config.admin_token) {
+        (Some(sent), Some(expected)) => {
+            Sha256::digest(sent.as_bytes()) == Sha256::digest(expected.trim().as_bytes())
+        }
+        _ => false,
+    };
+    let session_user = req
+        .extensions()
+        .get::<CurrentSession>()
+        .and_then(|_| req.headers().get(USER_HEADER))
+        .and_then(|v| v.to_str().ok());
+    // Only an identity provider makes a session proof of who the member is
+    let member_ok = st.oidc.is_some()
+        && session_user.is_some_and(|user| config.admin_users.iter().any(|u| u == user));
+    let access = AdminAccess {
+        granted: token_ok || member_ok,
+        presented: token.is_some() || session_user.is_some(),
+        configured: config.admin_token.is_some() || !config.admin_users.is_empty(),
+    };
+    req.extensions_mut().insert(access);
+    next.run(req).await
+}
+
+/// Route layer for admin routers: 401/403 unless [`identify`] granted access.
+pub async fn require(req: Request, next: Next) -> Response {
+    let access = req.extensions().get::<AdminAccess>().copied();
+    let denied = match access {
+        Some(a) if a.granted => return next.run(req).await,
+        Some(a) if !a.configured => ApiError::Forbidden(
+            "admin access is not configured; set admin_token or admin_users".into(),
+        ),
+        Some(a) if a.presented => ApiError::Forbidden("admin access required".into()),
+        _ => ApiError::Unauthorized(
+            "admin endpoints need X-Admin-Token or an admin member's session".into(),
+        ),
+    };
+    denied.into_response()
+}
+
+async fn info(State(st): State<AppState>) -> ApiResult<Json<AdminInfo>> {
+    let pragma = |name: &'static str| sqlx::query_scalar::<_, i64>(name).fetch_one(&st.pool);
+    let page_size = pragma("PRAGMA page_size").await?;
+    let db = DbStats {
+        sqlite_version: sqlx::query_scalar("SELECT sqlite_version()")
+            .fetch_one(&st.pool)
+            .await?,
+        journal_mode: sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&st.pool)
+            .await?,
+        size_bytes: pragma("PRAGMA page_count").await? * page_size,
+        free_bytes: pragma("PRAGMA freelist_count").await? * page_size,
+        pool_connections: st.pool.size(),
+        pool_idle: st.pool.num_idle(),
+    };
+    Ok(Json(AdminInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: option_env!("GIT_HASH").unwrap_or("unknown").to_string(),
+        uptime_secs: st.started_at.elapsed().as_secs(),
+        ws_clients: st.hub.connections(),
+        config: st.config.redacted(),
+        db,
+    }))
+}
+
+#[derive(Deserialize)]
+struct IntegrityParams {
+    full: Option<bool>, // PRAGMA integrity_check instead of quick_check
+}
+
+/**
+ * Result of an on-demand integrity check
+ */
+#[derive(Serialize)]
+struct IntegrityReport {
+    ok: bool,
+    check: &'static str,   // "quick" or "full"
+    problems: Vec<String>, // As reported by SQLite, at most 100
+    duration_ms: u64,
+}
+
+/// Verify the SQLite database now (todos too unless they live in Postgres).
+async fn integrity_check(
+    State(st): State<AppState>,
+    Query(params): Query<IntegrityParams>,
+) -> ApiResult<Json<IntegrityReport>> {
+    let check = match params.full {
+        Some(true) => IntegrityCheck::Full,
+        _ => IntegrityCheck::Quick,
+    };
+    let started = Instant::now();
+    let problems = db::integrity_problems(&st.pool, check).await?;
+    if !problems.is_empty() {
+        tracing::error!(?problems, "database integrity check failed");
+    }
+    Ok(Json(IntegrityReport {
+        ok: problems.is_empty(),
+        check: check.as_str(),
+        problems,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }))
+}
+
+async fn overview(State(st): State<AppState>) -> ApiResult<Json<AdminOverview>> {
+    let counts = st.todos.counts().await?;
+    let categories = st.categories.list().await?.len() as i64;
+
+    Ok(Json(AdminOverview {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: st.started_at.elapsed().as_secs(),
+        todos_active: counts.active,
+        todos_deleted: counts.deleted,
+        categories,
+        ws_clients: st.hub.connections(),
+        ddns: st.ddns.as_ref().map(|d| d.status()),
+        port_mapping: st.port_mapper.as_ref().map(|m| m.status()),
+    }))
+}
+
+async fn jobs(State(st): State<AppState>) -> ApiResult<Json<Option<JobsOverview>>> {
+    Ok(Json(st.jobs.as_ref().map(|j| j.overview())))
+}
@@ -0,0 +1,399 @@
+/**
+ * File attachments on todos (receipts, photos)
+ *
+ * Files live on disk under `attachments_dir`, named by attachment id; the
+ * `attachments` table keeps the original name, type and size. Uploads are
+ * multipart/form-data with any number of file fields, each checked against
+ * `max_attachment_bytes` and the `attachment_types` allow list.
+ *
+ * - POST   /api/todos/{id}/attachments  upload, returns the new attachments
+ * - GET    /api/todos/{id}/attachments  list
+ * - GET    /api/attachments/{id}        download (Range requests supported)
+ * - DELETE /api/attachments/{id}
+ *
+ * Attachments of soft-deleted todos are kept, since the todo can still be
+ * restored. AttachmentCleanupJob removes them once the todo row itself is
+ * purged, together with stray files that have no row.
+ */
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use axum::{
+    Json, Router,
+    extract::{
+        Multipart, Path, Request, State,
+        multipart::{Field, MultipartRejection},
+    },
+    http::{HeaderValue, header},
+    response::Response,
+    routing::get,
+};
+use chrono::Utc;
+use futures::future::BoxFuture;
+use serde_json::json;
+use tokio::{fs, io::AsyncWriteExt};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+use uuid::Uuid;
+
+use crate::{
+    config::ServerConfig,
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+    jobs::Job,
+    model::Attachment,
+    routes::AppState,
+    services::TodoService,
+};
+
+/**
+ * Where attachments are stored and what is accepted
+ */
+#[derive(Debug, Clone)]
+pub struct AttachmentStore {
+    pub dir: PathBuf,       // attachments_dir
+    pub max_bytes: usize,   // max_attachment_bytes, per file
+    pub types: Vec<String>, // attachment_types; "image/*" matches any image type
+}
+
+impl AttachmentStore {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            dir: PathBuf::from(&config.attachments_dir),
+            max_bytes: config.max_attachment_bytes,
+            types: config.attachment_types.clone(),
+        }
+    }
+
+    pub(crate) fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn allows(&self, content_type: &str) -> bool {
+        let (kind, _) = content_type.split_once('/').unwrap_or((content_type, ""));
+        self.types.iter().any(|t| match t.strip_suffix("/*") {
+            Some(prefix) => prefix.eq_ignore_ascii_case(kind),
+            None => t.eq_ignore_ascii_case(content_type),
+        })
+    }
+}
+
+impl Default for AttachmentStore {
+    fn default() -> Self {
+        Self::from_config(&ServerConfig::default())
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/todos/{id}/attachments",
+            get(list_attachments).post(upload),
+        )
+        .route(
+            "/api/attachments/{id}",
+            get(download).delete(delete_attachment),
+        )
+}
+
+async fn list_attachments(
+    State(st): State<AppState>,
+    Path(todo_id): Path<String>,
+) -> ApiResult<Json<Vec<Attachment>>> {
+    st.todos.get(&todo_id).await?;
+    let rows = sqlx::query_as::<_, Attachment>(
+        "SELECT * FROM attachments WHERE todo_id=?1 ORDER BY created_at ASC",
+    )
+    .bind(&todo_id)
+    .fetch_all(&st.pool)
+    .await?;
+    Ok(Json(rows.into_iter().map(Attachment::opened).collect()))
+}
+
+/// Store every file field; if one is rejected, none of the request's files are kept.
+async fn upload(
+    State(st): State<AppState>,
+    Path(todo_id): Path<String>,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> ApiResult<Json<Vec<Attachment>>> {
+    let mut multipart = multipart?;
+    st.todos.get(&todo_id).await?;
+    fs::create_dir_all(&st.attachments.dir)
+        .await
+        .context("creating attachments directory")?;
+
+    let mut saved = Vec::new();
+    let result = async {
+        while let Some(field) = multipart.next_field().await? {
+            // Plain form fields (no filename) are ignored
+            if field.file_name().is_none() {
+                continue;
+            }
+            saved.push(save(&st, &todo_id, field).await?);
+        }
+        ApiResult::Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        for attachment in &saved {
+            remove(&st.pool, &st.attachments, &attachment.id).await;
+        }
+        return Err(e);
+    }
+    if saved.is_empty() {
+        return Err(ApiError::BadRequest("no file in upload".into()));
+    }
+    Ok(Json(saved))
+}
+
+/// Stream one file field to disk, then record it.
+async fn save(st: &AppState, todo_id: &str, mut field: Field<'_>) -> ApiResult<Attachment> {
+    let store = &st.attachments;
+    let filename = clean_filename(field.file_name().unwrap_or_default());
+    let content_type = field
+        .content_type()
+        .unwrap_or("application/octet-stream")
+        .to_ascii_lowercase();
+    if !store.allows(&content_type) {
+        return Err(ApiError::BadRequest(format!(
+            "file type `{content_type}` is not allowed"
+        )));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let partial = store.dir.join(format!("{id}.part"));
+    let mut file = fs::File::create(&partial)
+        .await
+        .context("creating attachment file")?;
+    let mut size = 0;
+    let written = async {
+        while let Some(chunk) = field.chunk().await? {
+            size += chunk.len();
+            if size > store.max_bytes {
+                return Err(ApiError::PayloadTooLarge);
+            }
+            file.write_all(&chunk)
+                .await
+                .context("writing attachment file")?;
+        }
+        file.flush().await.context("writing attachment file")?;
+        ApiResult::Ok(())
+    }
+    .await;
+    if let Err(e) = written {
+        let _ = fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    fs::rename(&partial, store.path(&id))
+        .await
+        .context("storing attachment file")?;
+
+    let attachment = Attachment {
+        id,
+        todo_id: todo_id.to_string(),
+        filename,
+        content_type,
+        size: size as i64,
+        created_at: Utc::now(),
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO attachments (id,todo_id,filename,content_type,size,created_at)
+        VALUES (?1,?2,?3,?4,?5,?6)
+    "#,
+    )
+    .bind(&attachment.id)
+    .bind(&attachment.todo_id)
+    .bind(attachment.stored_filename())
+    .bind(&attachment.content_type)
+    .bind(attachment.size)
+    .bind(attachment.created_at)
+    .execute(&st.pool)
+    .await?;
+    tracing::info!(todo = %todo_id, attachment = %attachment.id, size, "attachment stored");
+    Ok(attachment)
+}
+
+/// The file with its original name and type; images open inline, everything else downloads.
+async fn download(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+    req: Request,
+) -> ApiResult<Response> {
+    let attachment = get_attachment(&st.pool, &id).await?;
+    let mut response = ServeFile::new(st.attachments.path(&id))
+        .oneshot(req)
+        .await
+        .context("reading attachment file")?
+        .map(axum::body::Body::new);
+
+    let inline =
+        attachment.content_type.starts_with("image/") && !attachment.content_type.contains("svg");
+    let disposition = format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        if inline { "inline" } else { "attachment" },
+        ascii_filename(&attachment.filename),
+        percent_encode(&attachment.filename),
+    );
+    let headers = response.headers_mut();
+    if let Ok(v) = HeaderValue::from_str(&attachment.content_type) {
+        headers.insert(header::CONTENT_TYPE, v);
+    }
+    if let Ok(v) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    Ok(response)
+}
+
+async fn delete_attachment(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    get_attachment(&st.pool, &id).await?;
+    remove(&st.pool, &st.attachments, &id).await;
+    Ok(Json(json!({"ok": true})))
+}
+
+async fn get_attachment(pool: &SqlitePool, id: &str) -> ApiResult<Attachment> {
+    sqlx::query_as::<_, Attachment>("SELECT * FROM attachments WHERE id=?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .map(Attachment::opened)
+        .ok_or(ApiError::NotFound)
+}
+
+/// Delete the row and the file; failures are logged, a missing file is fine.
+async fn remove(pool: &SqlitePool, store: &AttachmentStore, id: &str) {
+    if let Err(e) = sqlx::query("DELETE FROM attachments WHERE id=?1")
+        .bind(id)
+        .execute(pool)
+        .await
+    {
+        tracing::warn!(attachment = %id, error = %e, "failed to delete attachment row");
+    }
+    match fs::remove_file(store.path(id)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            tracing::warn!(attachment = %id, error = %e, "failed to delete attachment file");
+        }
+        _ => {}
+    }
+}
+
+/// Last path component without control characters, at most 255 characters.
+fn clean_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).take(255).collect();
+    match cleaned.trim() {
+        "" | "." | ".." => "file".into(),
+        name => name.to_string(),
+    }
+}
+
+/// Fallback `filename=` for old clients: printable ASCII without quotes.
+fn ascii_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// RFC 8187 encoding for `filename*=`.
+fn percent_encode(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/**
+ * Removes attachments of purged todos and files without a row
+ *
+ * Soft-deleted todos keep theirs; only todos that no longer exist at all
+ * (in whichever backend stores them) lose their attachments.
+ */
+pub struct AttachmentCleanupJob {
+    pool: SqlitePool,
+    todos: TodoService,
+    store: Arc<AttachmentStore>,
+}
+
+impl AttachmentCleanupJob {
+    pub fn new(pool: SqlitePool, todos: TodoService, store: Arc<AttachmentStore>) -> Self {
+        Self { pool, todos, store }
+    }
+
+    async fn cleanup(&self) -> anyhow::Result<()> {
+        let todo_ids = sqlx::query_scalar::<_, String>("SELECT DISTINCT todo_id FROM attachments")
+            .fetch_all(&self.pool)
+            .await?;
+        for todo_id in todo_ids {
+            match self.todos.get(&todo_id).await {
+                Err(ApiError::NotFound) => {}
+                Err(e) => return Err(anyhow::anyhow!(e)),
+                Ok(_) => continue,
+            }
+            let ids =
+                sqlx::query_scalar::<_, String>("SELECT id FROM attachments WHERE todo_id=?1")
+                    .bind(&todo_id)
+                    .fetch_all(&self.pool)
+                    .await?;
+            tracing::info!(todo = %todo_id, count = ids.len(), "removing attachments of purged todo");
+            for id in ids {
+                remove(&self.pool, &self.store, &id).await;
+            }
+        }
+
+        // Files left behind by interrupted uploads or manual row deletes
+        let known: HashSet<String> = sqlx::query_scalar("SELECT id FROM attachments")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+        let Ok(mut entries) = fs::read_dir(&self.store.dir).await else {
+            return Ok(());
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stale = entry
+                .metadata()
+                .await?
+                .modified()?
+                .elapsed()
+                .is_ok_and(|age| age > Duration::from_secs(3600));
+            // Recent files may belong to an upload still in progress
+            if !known.contains(&name) && stale {
+                tracing::info!(file = %name, "removing stray attachment file");
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Job for AttachmentCleanupJob {
+    fn name(&self) -> &'static str {
+        "attachment-cleanup"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(24 * 3600)
+    }
+
+    fn run(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(self.cleanup())
+    }
+}
@@ -0,0 +1,436 @@
+/**
+ * Audit log of todo and category changes
+ *
+ * Every mutation that goes through the services is written to `audit_log`:
+ * who made it, when, the action and a field-level diff, plus full
+ * before/after snapshots so a change can be inspected (and reverted).
+ *
+ * Actors are plain strings:
+ * - "user:<id>"       API calls carrying an `X-User-Id` header
+ * - "api"             anonymous API calls
+ * - "mqtt", "telegram", "inbound:<name>", "import:<id>", "system"
+ *
+ * - GET  /api/audit                       ?entity=&entity_id=&actor=&action=&since=&limit=
+ * - GET  /api/todos/{id}/history          every change to one todo, oldest first
+ * - POST /api/undo                        undo the caller's latest change
+ * - POST /api/todos/{id}/revert/{version} restore the todo as it was at `version`
+ *
+ * Undo and revert never delete history: they write the old snapshot back
+ * as a new version (logged as "undo" / "reverted") and broadcast the usual
+ * WS event. Undo refuses (409) when someone changed the record since.
+ */
+use std::convert::Infallible;
+
+use axum::{
+    Json, Router,
+    extract::{FromRequestParts, Path, Query, State},
+    http::request::Parts,
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+use crate::{
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+    model::{AuditEntry, Todo},
+    routes::AppState,
+};
+
+/// Request header naming the acting household member.
+pub const USER_HEADER: &str = "x-user-id";
+
+/// Bookkeeping fields left out of diffs (they change on every write).
+const UNTRACKED: [&str; 3] = ["created_at", "updated_at", "version"];
+
+/**
+ * Who performed a change
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor(String);
+
+impl Actor {
+    /// Background jobs and CLI commands.
+    pub fn system() -> Self {
+        Self("system".into())
+    }
+
+    /// An integration or channel, e.g. "mqtt" or "inbound:shortcuts".
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    /// A household member.
+    pub fn user(id: &str) -> Self {
+        Self(format!("user:{id}"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The household member's id, for actors made by `user`.
+    pub fn user_id(&self) -> Option<&str> {
+        self.0.strip_prefix("user:")
+    }
+}
+
+impl Default for Actor {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+/// API callers: the X-User-Id header, or "api" without one.
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .headers
+            .get(USER_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map_or_else(|| Actor::new("api"), Actor::user))
+    }
+}
+
+/// `{"field": {"from": old, "to": new}}` for every tracked field that changed.
+fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+    let empty = Map::new();
+    let fields = |v: Option<&Value>| v.and_then(Value::as_object).unwrap_or(&empty).clone();
+    let (before, after) = (fields(before), fields(after));
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        if UNTRACKED.contains(&key.as_str()) || changes.contains_key(key) {
+            continue;
+        }
+        let old = before.get(key).unwrap_or(&Value::Null);
+        let new = after.get(key).unwrap_or(&Value::Null);
+        if old != new {
+            changes.insert(key.clone(), json!({"from": old, "to": new}));
+        }
+    }
+    Value::Object(changes)
+}
+
+/**
+ * One row to be written to audit_log
+ */
+struct Change<'a> {
+    entity: &'a str,
+    entity_id: &'a str,
+    action: &'a str,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl<'a> Change<'a> {
+    fn new<T: Serialize>(
+        entity: &'a str,
+        entity_id: &'a str,
+        action: &'a str,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Self {
+        Self {
+            entity,
+            entity_id,
+            action,
+            before: before.and_then(|v| serde_json::to_value(v).ok()),
+            after: after.and_then(|v| serde_json::to_value(v).ok()),
+        }
+    }
+}
+
+/**
+ * Writer for the audit_log table
+ */
+#[derive(Clone)]
+pub struct AuditLog {
+    pool: SqlitePool,
+}
+
+impl AuditLog {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /**
+     * Record one change; `before` is None for creates
+     *
+     * Runs after the change itself succeeded; a failure to write the log
+     * is reported but does not undo the change.
+     */
+    pub async fn record<T: Serialize>(
+        &self,
+        actor: &Actor,
+        entity: &str,
+        entity_id: &str,
+        action: &str,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) {
+        let change = Change::new(entity, entity_id, action, before, after);
+        self.write(actor, None, change).await;
+    }
+
+    /// Record several changes made by one request; undo reverts them together.
+    pub async fn record_batch<T: Serialize>(
+        &self,
+        actor: &Actor,
+        entity: &str,
+        action: &str,
+        changes: &[(&str, Option<&T>, Option<&T>)],
+    ) {
+        let batch = Uuid::new_v4().to_string();
+        for &(id, before, after) in changes {
+            let change = Change::new(entity, id, action, before, after);
+            self.write(actor, Some(&batch), change).await;
+        }
+    }
+
+    async fn write(&self, actor: &Actor, batch: Option<&str>, change: Change<'_>) {
+        let Change {
+            entity,
+            entity_id,
+            action,
+            mut before,
+            mut after,
+        } = change;
+        let mut changes = diff(before.as_ref(), after.as_ref());
+        let version = after.as_ref().and_then(|v| v["version"].as_i64());
+        // Notes are stored encrypted when a key is set (crypto.rs)
+        for doc in [Some(&mut changes), before.as_mut(), after.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            AuditEntry::seal_snapshot(doc);
+        }
+        let result = sqlx::query(
+            r#"
+            INSERT INTO audit_log (entity,entity_id,action,actor,changes,before,after,version,batch,created_at)
+            VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)
+        "#,
+        )
+        .bind(entity)
+        .bind(entity_id)
+        .bind(action)
+        .bind(actor.as_str())
+        .bind(changes.to_string())
+        .bind(before.map(|v| v.to_string()))
+        .bind(after.map(|v| v.to_string()))
+        .bind(version)
+        .bind(batch)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await;
+        if let Err(e) = result {
+            tracing::warn!(error = %e, entity, entity_id, action, "failed to write audit log");
+        }
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/audit", get(list_audit))
+        .route("/api/todos/{id}/history", get(todo_history))
+        .route("/api/undo", post(undo))
+        .route("/api/todos/{id}/revert/{version}", post(revert_todo))
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    entity: Option<String>,
+    entity_id: Option<String>,
+    actor: Option<String>,
+    action: Option<String>,
+    since: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+}
+
+/// Newest first.
+async fn list_audit(
+    State(st): State<AppState>,
+    Query(q): Query<AuditQuery>,
+) -> ApiResult<Json<Vec<AuditEntry>>> {
+    let rows = sqlx::query_as::<_, AuditEntry>(
+        r#"
+        SELECT * FROM audit_log
+        WHERE (?1 IS NULL OR entity = ?1)
+          AND (?2 IS NULL OR entity_id = ?2)
+          AND (?3 IS NULL OR actor = ?3)
+          AND (?4 IS NULL OR action = ?4)
+          AND (?5 IS NULL OR created_at >= ?5)
+        ORDER BY id DESC
+        LIMIT ?6
+    "#,
+    )
+    .bind(&q.entity)
+    .bind(&q.entity_id)
+    .bind(&q.actor)
+    .bind(&q.action)
+    .bind(q.since)
+    .bind(q.limit.unwrap_or(100).clamp(1, 1000))
+    .fetch_all(&st.pool)
+    .await?;
+    Ok(Json(rows.into_iter().map(AuditEntry::opened).collect()))
+}
+
+/// Oldest first, so the list reads as the todo's story.
+async fn todo_history(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Vec<AuditEntry>>> {
+    let rows = sqlx::query_as::<_, AuditEntry>(
+        "SELECT * FROM audit_log WHERE entity='todo' AND entity_id=?1 ORDER BY id ASC",
+    )
+    .bind(&id)
+    .fetch_all(&st.pool)
+    .await?;
+    Ok(Json(rows.into_iter().map(AuditEntry::opened).collect()))
+}
+
+/// The state an entry's change replaced: `before`, or a deleted copy of `after` for creates.
+fn undo_target(entry: &AuditEntry) -> ApiResult<Value> {
+    if let Some(before) = &entry.before {
+        return Ok(before.0.clone());
+    }
+    let mut snapshot = entry
+        .after
+        .as_ref()
+        .map(|v| v.0.clone())
+        .ok_or_else(|| ApiError::Conflict(format!("entry {} has no snapshot", entry.id)))?;
+    snapshot["deleted"] = json!(1);
+    Ok(snapshot)
+}
+
+/// Decode a stored snapshot; fields added since it was taken keep their current values.
+fn snapshot<T: Serialize + serde::de::DeserializeOwned>(value: Value, current: &T) -> ApiResult<T> {
+    let mut value = value;
+    if let (Some(fields), Ok(Value::Object(now))) =
+        (value.as_object_mut(), serde_json::to_value(current))
+    {
+        for (key, v) in now {
+            fields.entry(key).or_insert(v);
+        }
+    }
+    serde_json::from_value(value)
+        .map_err(|e| ApiError::Conflict(format!("stored snapshot no longer applies: {e}")))
+}
+
+/**
+ * Undo the caller's most recent change
+ *
+ * Changes recorded together (a reorder) are undone together. Repeating
+ * the call walks further back; undo entries themselves are skipped, as
+ * are checklist progress updates (the entries are not in the snapshots).
+ */
+async fn undo(State(st): State<AppState>, actor: Actor) -> ApiResult<Json<Value>> {
+    let latest = sqlx::query_as::<_, AuditEntry>(
+        r#"
+        SELECT * FROM audit_log
+        WHERE actor=?1 AND action NOT IN ('undo', 'checklist') AND undone_at IS NULL
+        ORDER BY id DESC LIMIT 1
+    "#,
+    )
+    .bind(actor.as_str())
+    .fetch_optional(&st.pool)
+    .await?
+    .map(AuditEntry::opened)
+    .ok_or(ApiError::NotFound)?;
+    let entries = match &latest.batch {
+        Some(batch) => sqlx::query_as::<_, AuditEntry>(
+            "SELECT * FROM audit_log WHERE batch=?1 AND undone_at IS NULL ORDER BY id DESC",
+        )
+        .bind(batch)
+        .fetch_all(&st.pool)
+        .await?
+        .into_iter()
+        .map(AuditEntry::opened)
+        .collect(),
+        None => vec![latest],
+    };
+
+    // Check every record first so a batch is undone completely or not at all
+    for entry in &entries {
+        let current = match entry.entity.as_str() {
+            "todo" => serde_json::to_value(st.todos.get(&entry.entity_id).await?),
+            "category" => serde_json::to_value(st.categories.get(&entry.entity_id).await?),
+            other => return Err(ApiError::BadRequest(format!("cannot undo {other} changes"))),
+        }
+        .map_err(anyhow::Error::from)?;
+        // Only fields the snapshot knows about; newer columns cannot conflict
+        let after = entry.after.as_ref().and_then(|v| v.0.as_object());
+        let changed = after.is_some_and(|fields| {
+            fields.iter().any(|(key, value)| {
+                !UNTRACKED.contains(&key.as_str()) && current.get(key).is_some_and(|c| c != value)
+            })
+        });
+        if changed {
+            return Err(ApiError::Conflict(format!(
+                "{} {} has changed since; revert it from its history instead",
+                entry.entity, entry.entity_id
+            )));
+        }
+    }
+
+    let todos = st.todos.as_actor(actor.clone());
+    let categories = st.categories.as_actor(actor);
+    let (mut restored_todos, mut restored_categories) = (Vec::new(), Vec::new());
+    for entry in &entries {
+        let target = undo_target(entry)?;
+        if entry.entity == "todo" {
+            let current = todos.get(&entry.entity_id).await?;
+            let todo = todos.restore(snapshot(target, &current)?, "undo").await?;
+            restored_todos.push(todo);
+        } else {
+            let current = categories.get(&entry.entity_id).await?;
+            let category = categories
+                .restore(snapshot(target, &current)?, "undo")
+                .await?;
+            restored_categories.push(category);
+        }
+        sqlx::query("UPDATE audit_log SET undone_at=?2 WHERE id=?1")
+            .bind(entry.id)
+            .bind(Utc::now())
+            .execute(&st.pool)
+            .await?;
+    }
+    Ok(Json(json!({
+        "undone": entries.iter().map(|e| e.id).collect::<Vec<_>>(),
+        "todos": restored_todos,
+        "categories": restored_categories,
+    })))
+}
+
+/// Write a todo's state at `version` back as its newest version.
+async fn revert_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((id, version)): Path<(String, i64)>,
+) -> ApiResult<Json<Todo>> {
+    let entry = sqlx::query_as::<_, AuditEntry>(
+        r#"
+        SELECT * FROM audit_log
+        WHERE entity='todo' AND entity_id=?1 AND version=?2 AND after IS NOT NULL
+        ORDER BY id DESC LIMIT 1
+    "#,
+    )
+    .bind(&id)
+    .bind(version)
+    .fetch_optional(&st.pool)
+    .await?
+    .map(AuditEntry::opened)
+    .ok_or(ApiError::NotFound)?;
+    let target = entry.after.map(|v| v.0).unwrap_or_default();
+    let todos = st.todos.as_actor(actor);
+    let current = todos.get(&id).await?;
+    let todo = todos
+        .restore(snapshot(target, &current)?, "reverted")
+        .await?;
+    Ok(Json(todo))
+}
@@ -0,0 +1,590 @@
+/**
+ * Online database backups
+ *
+ * Snapshots of the live SQLite database taken with VACUUM INTO, so they
+ * are consistent without stopping the server. Each backup is a complete
+ * database file named `todos-YYYYMMDD-HHMMSS.db` under `backups_dir`;
+ * only the newest `backup_keep` are kept.
+ *
+ * - POST /api/admin/backup          take a backup now, returns its info
+ * - GET  /api/admin/backups         list, newest first
+ * - GET  /api/admin/backups/{name}  download one
+ *
+ * A backup holds every member's data, so all three need admin access
+ * (see admin.rs).
+ *
+ * `server-rs backup` without --out does the same from the command line.
+ * Postgres deployments back up with pg_dump instead.
+ *
+ * With `backup_schedule` set (cron syntax, local time, e.g. "0 3 * * *")
+ * backups are also taken automatically and, when BACKUP_UPLOAD is set,
+ * copied off the SD card:
+ * - s3: PUT to BACKUP_S3_ENDPOINT/BACKUP_S3_BUCKET/BACKUP_S3_PREFIX<name>,
+ *   signed with BACKUP_S3_ACCESS_KEY/BACKUP_S3_SECRET_KEY (BACKUP_S3_REGION,
+ *   default us-east-1); works with AWS, MinIO, Backblaze B2, ...
+ * - webdav: PUT to BACKUP_WEBDAV_URL/<name> (BACKUP_WEBDAV_USER/_PASSWORD)
+ * - sftp: `sftp` to BACKUP_SFTP_TARGET ("user@host:dir"), key-based auth
+ *
+ * The outcome of the last run is shown in /api/health and /metrics.
+ */
+use std::{
+    env,
+    path::{Path as FsPath, PathBuf},
+    process::Stdio,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{Context, anyhow};
+use axum::{
+    Json, Router,
+    extract::{Path, Request, State},
+    http::{HeaderValue, header},
+    middleware,
+    response::Response,
+    routing::{get, post},
+};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, Timelike, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use crate::{
+    admin,
+    config::ServerConfig,
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+    maintenance,
+    routes::AppState,
+};
+
+const PREFIX: &str = "todos-";
+const SUFFIX: &str = ".db";
+
+/**
+ * One backup file
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub name: String,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/**
+ * Outcome of scheduled backups (serialized into /api/health)
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupStatus {
+    pub ok: bool,                               // False while the last run failed
+    pub schedule: String,                       // backup_schedule
+    pub upload: Option<&'static str>,           // Remote target kind, None = local only
+    pub next_run: Option<DateTime<Utc>>,        // When the next backup is due
+    pub last_backup: Option<BackupInfo>,        // Last backup written by the schedule
+    pub last_success_at: Option<DateTime<Utc>>, // Last run that wrote (and uploaded) a backup
+    pub last_failure_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub failures: u64, // Failed runs since startup
+}
+
+/**
+ * Where backups are written and how many are kept
+ */
+#[derive(Debug)]
+pub struct BackupStore {
+    pub dir: PathBuf,                     // backups_dir
+    pub keep: usize,                      // backup_keep; older backups are deleted
+    pub schedule: Option<Schedule>,       // backup_schedule; None = manual backups only
+    pub upload: Option<BackupUpload>,     // BACKUP_UPLOAD target for scheduled backups
+    status: RwLock<Option<BackupStatus>>, // Some once the schedule is running
+}
+
+impl Default for BackupStore {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("./data/backups"),
+            keep: 7,
+            schedule: None,
+            upload: None,
+            status: RwLock::new(None),
+        }
+    }
+}
+
+impl BackupStore {
+    /// Errors on an invalid schedule or incomplete upload settings.
+    pub fn from_config(config: &ServerConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            dir: PathBuf::from(&config.backups_dir),
+            keep: config.backup_keep,
+            schedule: config
+                .backup_schedule
+                .as_deref()
+                .map(Schedule::parse)
+                .transpose()?,
+            upload: BackupUpload::from_env()?,
+            status: RwLock::new(None),
+        })
+    }
+
+    /// State of scheduled backups; None when no schedule is configured.
+    pub fn status(&self) -> Option<BackupStatus> {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Path of a backup, None unless `name` is one of our file names.
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        let stamp = name.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+        let valid = stamp.len() == 15 && stamp.chars().all(|c| c.is_ascii_digit() || c == '-');
+        valid.then(|| self.dir.join(name))
+    }
+
+    /// Snapshot the database into a new timestamped file, then apply retention.
+    pub async fn create(&self, pool: &SqlitePool) -> anyhow::Result<BackupInfo> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("creating {}", self.dir.display()))?;
+        let now = Utc::now();
+        let name = format!("{PREFIX}{}{SUFFIX}", now.format("%Y%m%d-%H%M%S"));
+        let path = self.dir.join(&name);
+        if path.exists() {
+            return Err(anyhow!("a backup was already taken this second"));
+        }
+        maintenance::backup_sqlite(pool, &path).await?;
+        let size = tokio::fs::metadata(&path).await?.len();
+        self.prune().await?;
+        Ok(BackupInfo {
+            name,
+            size,
+            created_at: now,
+        })
+    }
+
+    /// Backups on disk, newest first.
+    pub async fn list(&self) -> anyhow::Result<Vec<BackupInfo>> {
+        let mut backups = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(backups),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if self.path(&name).is_none() {
+                continue;
+            }
+            let meta = entry.metadata().await?;
+            backups.push(BackupInfo {
+                name,
+                size: meta.len(),
+                created_at: meta
+                    .modified()
+                    .map(DateTime::from)
+                    .unwrap_or_else(|_| Utc::now()),
+            });
+        }
+        // Names sort by their timestamp
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(backups)
+    }
+
+    /// Delete all but the newest `keep` backups.
+    async fn prune(&self) -> anyhow::Result<()> {
+        for old in self.list().await?.into_iter().skip(self.keep.max(1)) {
+            tokio::fs::remove_file(self.dir.join(&old.name)).await?;
+            tracing::info!(backup = %old.name, "old backup removed");
+        }
+        Ok(())
+    }
+
+    /// Take backups on `schedule` until shutdown; does nothing without one.
+    pub async fn run(self: Arc<Self>, pool: SqlitePool) {
+        let Some(schedule) = &self.schedule else {
+            return;
+        };
+        *self.status.write().unwrap() = Some(BackupStatus {
+            ok: true,
+            schedule: schedule.source.clone(),
+            upload: self.upload.as_ref().map(BackupUpload::kind),
+            next_run: None,
+            last_backup: None,
+            last_success_at: None,
+            last_failure_at: None,
+            last_error: None,
+            failures: 0,
+        });
+        loop {
+            let Some(next) = schedule.next_after(Local::now()) else {
+                tracing::warn!(schedule = %schedule.source, "backup schedule never fires");
+                return;
+            };
+            self.update_status(|s| s.next_run = Some(next.with_timezone(&Utc)));
+            let wait = (next - Local::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            self.run_scheduled(&pool).await;
+        }
+    }
+
+    /// One scheduled run: snapshot, then upload when configured.
+    async fn run_scheduled(&self, pool: &SqlitePool) {
+        let result = async {
+            let backup = self.create(pool).await?;
+            self.update_status(|s| s.last_backup = Some(backup.clone()));
+            if let Some(upload) = &self.upload {
+                upload
+                    .upload(&self.dir.join(&backup.name), &backup.name)
+                    .await
+                    .with_context(|| format!("uploading to {}", upload.kind()))?;
+            }
+            anyhow::Ok(backup)
+        }
+        .await;
+        let now = Utc::now();
+        match result {
+            Ok(backup) => {
+                tracing::info!(backup = %backup.name, size = backup.size, "scheduled backup written");
+                self.update_status(|s| {
+                    s.ok = true;
+                    s.last_success_at = Some(now);
+                    s.last_error = None;
+                });
+            }
+            Err(e) => {
+                tracing::error!(error = format!("{e:#}"), "scheduled backup failed");
+                self.update_status(|s| {
+                    s.ok = false;
+                    s.last_failure_at = Some(now);
+                    s.last_error = Some(format!("{e:#}"));
+                    s.failures += 1;
+                });
+            }
+        }
+    }
+
+    fn update_status(&self, f: impl FnOnce(&mut BackupStatus)) {
+        if let Some(status) = self.status.write().unwrap().as_mut() {
+            f(status);
+        }
+    }
+}
+
+/**
+ * Parsed cron expression: minute hour day-of-month month day-of-week
+ *
+ * Fields accept `*`, numbers, ranges (`1-5`), steps (`0-30/10`, or a star
+ * followed by `/15` for every 15) and comma-separated lists. Day of week
+ * is 0-7 with 0 and 7 = Sunday.
+ * As in cron, when both day fields are restricted either one may match.
+ */
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,     // Day of month is `*`
+    any_weekday: bool, // Day of week is `*`
+}
+
+impl Schedule {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = source.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(anyhow!(
+                "`{source}` needs 5 fields: minute hour day month weekday"
+            ));
+        };
+        let mut weekdays = cron_field(weekday, 0, 7)?;
+        // 7 is another name for Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            minutes: cron_field(minute, 0, 59)?,
+            hours: cron_field(hour, 0, 23)?,
+            days: cron_field(day, 1, 31)?,
+            months: cron_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn day_matches(&self, t: &NaiveDateTime) -> bool {
+        let day = self.days & (1 << t.day()) != 0;
+        let weekday = self.weekdays & (1 << t.weekday().num_days_from_sunday()) != 0;
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        day && self.months & (1 << t.month()) != 0
+    }
+
+    /// First matching minute after `now` (within a year), in local time.
+    pub fn next_after(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = now.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut t = start;
+        while t < start + Duration::days(366) {
+            if !self.day_matches(&t) {
+                t = (t.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << t.hour()) != 0 && self.minutes & (1 << t.minute()) != 0 {
+                // Skips times that do not exist on DST changes
+                if let Some(local) = t.and_local_timezone(Local).earliest() {
+                    return Some(local);
+                }
+            }
+            t += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+/// Bit set of the values one cron field allows.
+fn cron_field(field: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)),
+            None => (part, Some(1)),
+        };
+        let step = step.ok_or_else(|| anyhow!("invalid step in `{part}`"))?;
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (a.parse()?, b.parse()?),
+                None if step > 1 => (range.parse()?, max),
+                None => {
+                    let n = range.parse()?;
+                    (n, n)
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return Err(anyhow!("`{part}` is outside {min}-{max}"));
+        }
+        for n in (from..=to).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+/**
+ * Remote copy target for scheduled backups, read from the environment
+ */
+#[derive(Debug)]
+pub enum BackupUpload {
+    S3 {
+        endpoint: reqwest::Url, // BACKUP_S3_ENDPOINT, e.g. https://s3.eu-central-1.amazonaws.com
+        bucket: String,         // BACKUP_S3_BUCKET
+        prefix: String,         // BACKUP_S3_PREFIX, prepended to the file name
+        region: String,         // BACKUP_S3_REGION, default us-east-1
+        access_key: String,     // BACKUP_S3_ACCESS_KEY
+        secret_key: String,     // BACKUP_S3_SECRET_KEY
+    },
+    WebDav {
+        url: String,              // BACKUP_WEBDAV_URL, the target collection
+        user: Option<String>,     // BACKUP_WEBDAV_USER
+        password: Option<String>, // BACKUP_WEBDAV_PASSWORD
+    },
+    Sftp {
+        target: String, // BACKUP_SFTP_TARGET, "user@host:dir"
+    },
+}
+
+impl BackupUpload {
+    /// Returns None when BACKUP_UPLOAD is not set; errors on incomplete settings.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(kind) = env::var("BACKUP_UPLOAD") else {
+            return Ok(None);
+        };
+        let var = |name: &str| {
+            env::var(name).with_context(|| format!("BACKUP_UPLOAD={kind} requires {name}"))
+        };
+        let upload = match kind.as_str() {
+            "s3" => Self::S3 {
+                endpoint: var("BACKUP_S3_ENDPOINT")?
+                    .parse()
+                    .context("BACKUP_S3_ENDPOINT must be a URL")?,
+                bucket: var("BACKUP_S3_BUCKET")?,
+                prefix: env::var("BACKUP_S3_PREFIX").unwrap_or_default(),
+                region: env::var("BACKUP_S3_REGION").unwrap_or_else(|_| "us-east-1".into()),
+                access_key: var("BACKUP_S3_ACCESS_KEY")?,
+                secret_key: var("BACKUP_S3_SECRET_KEY")?,
+            },
+            "webdav" => Self::WebDav {
+                url: var("BACKUP_WEBDAV_URL")?,
+                user: env::var("BACKUP_WEBDAV_USER").ok(),
+                password: env::var("BACKUP_WEBDAV_PASSWORD").ok(),
+            },
+            "sftp" => Self::Sftp {
+                target: var("BACKUP_SFTP_TARGET")?,
+            },
+            other => return Err(anyhow!("unsupported BACKUP_UPLOAD `{other}`")),
+        };
+        Ok(Some(upload))
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::S3 { .. } => "s3",
+            Self::WebDav { .. } => "webdav",
+            Self::Sftp { .. } => "sftp",
+        }
+    }
+
+    /// Copy the backup at `path` to the target as `name`.
+    pub async fn upload(&self, path: &FsPath, name: &str) -> anyhow::Result<()> {
+        match self {
+            Self::S3 {
+                endpoint,
+                bucket,
+                prefix,
+                region,
+                access_key,
+                secret_key,
+            } => {
+                let body = tokio::fs::read(path).await?;
+                let url = endpoint.join(&format!("{bucket}/{prefix}{name}"))?;
+                let request = s3_put(&url, region, access_key, secret_key, body)?;
+                request.send().await?.error_for_status()?;
+            }
+            Self::WebDav {
+                url,
+                user,
+                password,
+            } => {
+                let body = tokio::fs::read(path).await?;
+                let mut request = reqwest::Client::new()
+                    .put(format!("{}/{name}", url.trim_end_matches('/')))
+                    .body(body);
+                if let Some(user) = user {
+                    request = request.basic_auth(user, password.as_ref());
+                }
+                request.send().await?.error_for_status()?;
+            }
+            Self::Sftp { target } => {
+                let mut child = tokio::process::Command::new("sftp")
+                    .args(["-b", "-", "-o", "BatchMode=yes", target])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .context("running sftp")?;
+                let mut stdin = child.stdin.take().context("sftp stdin")?;
+                let path = path.canonicalize()?;
+                stdin
+                    .write_all(format!("put \"{}\" \"{name}\"\n", path.display()).as_bytes())
+                    .await?;
+                drop(stdin);
+                let output = child.wait_with_output().await?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(anyhow!("sftp failed: {}", stderr.trim()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// PUT request signed with AWS Signature Version 4.
+fn s3_put(
+    url: &reqwest::Url,
+    region: &str,
+    access_key: &str,
+    secret_key: &str,
+    body: Vec<u8>,
+) -> anyhow::Result<reqwest::RequestBuilder> {
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        _ => return Err(anyhow!("BACKUP_S3_ENDPOINT has no host")),
+    };
+    let now = Utc::now();
+    let date = now.format("%Y%m%d").to_string();
+    let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let payload_hash = hex::encode(Sha256::digest(&body));
+    let canonical = format!(
+        "PUT\n{}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{timestamp}\n\nhost;x-amz-content-sha256;x-amz-date\n{payload_hash}",
+        url.path()
+    );
+    let scope = format!("{date}/{region}/s3/aws4_request");
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical.as_bytes()))
+    );
+    let mut key = format!("AWS4{secret_key}").into_bytes();
+    for part in [date.as_str(), region, "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes())?;
+    }
+    let signature = hex::encode(hmac_sha256(&key, to_sign.as_bytes())?);
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={access_key}/{scope}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={signature}"
+    );
+    Ok(reqwest::Client::new()
+        .put(url.clone())
+        .header("x-amz-content-sha256", payload_hash)
+        .header("x-amz-date", timestamp)
+        .header(header::AUTHORIZATION, authorization)
+        .body(body))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/backup", post(create_backup))
+        .route("/api/admin/backups", get(list_backups))
+        .route("/api/admin/backups/{name}", get(download_backup))
+        .route_layer(middleware::from_fn(admin::require))
+}
+
+async fn create_backup(State(st): State<AppState>) -> ApiResult<Json<BackupInfo>> {
+    if st.integrations.storage == "postgres" {
+        return Err(ApiError::BadRequest(
+            "backups only cover SQLite databases; use pg_dump for Postgres".into(),
+        ));
+    }
+    let backup = st.backups.create(&st.pool).await?;
+    tracing::info!(backup = %backup.name, size = backup.size, "backup written");
+    Ok(Json(backup))
+}
+
+async fn list_backups(State(st): State<AppState>) -> ApiResult<Json<Vec<BackupInfo>>> {
+    Ok(Json(st.backups.list().await?))
+}
+
+async fn download_backup(
+    State(st): State<AppState>,
+    Path(name): Path<String>,
+    req: Request,
+) -> ApiResult<Response> {
+    let path = st.backups.path(&name).ok_or(ApiError::NotFound)?;
+    if !path.is_file() {
+        return Err(ApiError::NotFound);
+    }
+    let mut response = ServeFile::new(path)
+        .oneshot(req)
+        .await
+        .context("reading backup file")?
+        .map(axum::body::Body::new);
+    let disposition = format!("attachment; filename=\"{name}\"");
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    Ok(response)
+}
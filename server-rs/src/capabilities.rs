@@ -0,0 +1,230 @@
+/**
+ * Capability discovery and deprecation notices
+ *
+ * GET /api/capabilities tells clients what this particular instance
+ * supports, so they can adapt their UI instead of probing endpoints and
+ * interpreting 404s. Features that do not exist in this build are listed
+ * as `false` rather than omitted.
+ *
+ * Deprecated endpoints are listed there as well, and responses from them
+ * carry `Deprecation`, `Sunset` and `Link: <...>; rel="successor-version"`
+ * headers (see deprecation_headers). The whole unversioned /api/... tree
+ * is one of them: clients should use /api/v1/... (see versions.rs).
+ */
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+    routing::get,
+};
+use serde::Serialize;
+
+use crate::{crypto, routes::AppState, versions};
+
+/// Version of the REST API contract (bumped on breaking changes).
+pub const API_VERSION: &str = "1";
+
+/**
+ * Deprecated endpoint: method + path prefix, with its replacement
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Deprecation {
+    pub method: &'static str,              // HTTP method, "*" for all
+    pub path: &'static str,                // Path prefix, e.g. "/api/old"
+    pub since: &'static str,               // Date the deprecation was announced (YYYY-MM-DD)
+    pub sunset: Option<&'static str>,      // HTTP date after which it may be removed
+    pub replacement: Option<&'static str>, // Successor endpoint
+}
+
+/// The unversioned /api/... alias of /api/v1/... (see versions.rs).
+pub const UNVERSIONED_API: Deprecation = Deprecation {
+    method: "*",
+    path: "/api/",
+    since: "2026-10-16",
+    sunset: None,
+    replacement: Some("/api/v1/"),
+};
+
+/// Currently deprecated endpoints.
+pub const DEPRECATIONS: &[Deprecation] = &[UNVERSIONED_API];
+
+/**
+ * Optional integrations started by the binary (filled in by main)
+ */
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Integrations {
+    pub storage: &'static str, // "sqlite", "postgres" or "memory" (empty = custom repositories)
+    pub tls: bool,             // HTTPS served directly (ACME)
+    pub push: bool,            // ntfy/Gotify reminder channels
+    pub email: bool,           // SMTP reminders/digest
+    pub mqtt: bool,            // MQTT bridge
+    pub telegram: bool,        // Telegram bot
+    pub oidc: bool,            // Sign-in at an OpenID Connect provider
+    pub read_only: bool,       // Database failed its integrity check and was opened read-only
+}
+
+/**
+ * Response for GET /api/capabilities
+ */
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub api_version: &'static str,
+    pub server_version: &'static str,
+    pub auth: &'static str, // Authentication mode; "none" = trusted LAN
+    pub storage: &'static str,
+    pub features: Features,
+    pub deprecations: &'static [Deprecation],
+}
+
+/**
+ * Feature flags clients can branch on
+ */
+#[derive(Debug, Serialize)]
+pub struct Features {
+    pub realtime: bool,      // WebSocket updates at /ws/updates
+    pub long_poll: bool,     // The same events at /api/events?since=&wait=
+    pub binary_ws: bool,     // todo.msgpack / todo.cbor WebSocket subprotocols
+    pub presence: bool,      // WebSocket hello + presence.join/leave, /api/presence
+    pub editing: bool,       // WebSocket `editing` indicators relayed between clients
+    pub note_patches: bool,  // Collaborative notes at /api/todos/{id}/note/patches
+    pub categories: bool,    // /api/categories
+    pub locations: bool,     // Todo coordinates, GeoJSON and errand routes
+    pub imports: bool,       // /api/imports
+    pub inbound: bool,       // Inbound quick-capture webhooks
+    pub webhooks: bool,      // Outgoing webhooks
+    pub kiosk: bool,         // Wall display rotation
+    pub audit: bool,         // /api/audit and per-todo history
+    pub checklists: bool,    // /api/todos/{id}/checklist
+    pub comments: bool,      // /api/todos/{id}/comments
+    pub activity: bool,      // /api/todos/{id}/activity (changes + comments)
+    pub shares: bool,        // Read-only guest links at /api/shares
+    pub assignment: bool,    // assignee_id, ?assignee=me, category members
+    pub mentions: bool,      // @mentions in comments and notes
+    pub notifications: bool, // In-app notification center at /api/notifications
+    pub sessions: bool,      // Device sessions with refresh tokens at /api/sessions
+    pub account: bool,       // GET /api/me/export (zip) and DELETE /api/me
+    pub settings: bool,      // Per-user preferences at /api/users/{id}/settings
+    pub encryption: bool,    // Notes and attachment names encrypted at rest (no API change)
+    pub json_patch: bool,    // PATCH /api/todos/{id} with application/json-patch+json
+    pub pagination: bool,    // ?limit=&offset= with X-Total-Count and Link on todo/category lists
+    pub timezones: bool, // ?tz=, X-Timezone or the timezone setting for "today" and calendar days
+    pub links: bool,     // "Blocks" dependencies, ?blocked= filter
+    pub stats: bool,     // /api/stats/completion
+    pub statuses: bool,  // Custom statuses at /api/statuses
+    pub reminders: bool, // Due-soon/overdue alerts also reach push/e-mail (always in-app)
+    pub push: bool,      // ntfy/Gotify
+    pub email: bool,     // SMTP
+    pub mqtt: bool,      // MQTT bridge
+    pub telegram: bool,  // Telegram bot
+    pub oidc: bool,      // /api/auth/oidc/login (POST /api/sessions disabled)
+    pub tls: bool,       // HTTPS without a reverse proxy
+    pub ddns: bool,      // Dynamic DNS updater
+    pub port_mapping: bool, // UPnP/NAT-PMP
+    pub attachments: bool, // /api/todos/{id}/attachments
+    pub caldav: bool,    // CalDAV sync (not available yet)
+    pub workspaces: bool, // Multiple boards: /api/projects, ?project_id= scoping
+    pub time_tracking: bool, // Timers at /api/todos/{id}/timer, /api/time/report
+    pub pomodoro: bool,  // Shared pomodoro clock at /api/pomodoro
+    pub simple_ui: bool, // Server-rendered HTML at /simple (old browsers, e-readers)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/capabilities", get(capabilities))
+}
+
+async fn capabilities(State(st): State<AppState>) -> Json<Capabilities> {
+    let i = &st.integrations;
+    Json(Capabilities {
+        api_version: API_VERSION,
+        server_version: env!("CARGO_PKG_VERSION"),
+        auth: "none",
+        storage: if i.storage.is_empty() {
+            "custom"
+        } else {
+            i.storage
+        },
+        features: Features {
+            realtime: true,
+            long_poll: true,
+            binary_ws: true,
+            presence: true,
+            editing: true,
+            note_patches: true,
+            categories: true,
+            locations: true,
+            imports: true,
+            inbound: true,
+            webhooks: true,
+            kiosk: true,
+            audit: true,
+            checklists: true,
+            comments: true,
+            activity: true,
+            shares: true,
+            assignment: true,
+            mentions: true,
+            notifications: true,
+            sessions: true,
+            account: true,
+            settings: true,
+            encryption: crypto::enabled(),
+            json_patch: true,
+            pagination: true,
+            timezones: true,
+            links: true,
+            stats: true,
+            statuses: true,
+            reminders: i.push || i.email,
+            push: i.push,
+            email: i.email,
+            mqtt: i.mqtt,
+            telegram: i.telegram,
+            oidc: i.oidc,
+            tls: i.tls,
+            ddns: st.ddns.is_some(),
+            port_mapping: st.port_mapper.is_some(),
+            attachments: true,
+            caldav: false,
+            workspaces: true,
+            time_tracking: true,
+            pomodoro: true,
+            simple_ui: st.config.simple_ui,
+        },
+        deprecations: DEPRECATIONS,
+    })
+}
+
+/// Middleware adding RFC 8594 style deprecation headers to deprecated endpoints.
+pub async fn deprecation_headers(req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    let deprecation = DEPRECATIONS
+        .iter()
+        .find(|d| {
+            d.path != UNVERSIONED_API.path
+                && (d.method == "*" || d.method == req.method().as_str())
+                && path.starts_with(d.path)
+        })
+        .or_else(|| versions::is_unversioned(&path).then_some(&UNVERSIONED_API));
+    let mut response = next.run(req).await;
+    if let Some(d) = deprecation {
+        let headers = response.headers_mut();
+        headers.insert("Deprecation", HeaderValue::from_static("true"));
+        if let Some(sunset) = d.sunset {
+            headers.insert("Sunset", HeaderValue::from_static(sunset));
+        }
+        // A prefix replacement ("/api/v1/") points at the same endpoint there
+        let successor = d.replacement.map(|r| match path.strip_prefix(d.path) {
+            Some(rest) if r.ends_with('/') && d.path.ends_with('/') => format!("{r}{rest}"),
+            _ => r.to_string(),
+        });
+        if let Some(successor) = successor
+            && let Ok(link) =
+                HeaderValue::from_str(&format!("<{successor}>; rel=\"successor-version\""))
+        {
+            headers.append("Link", link); // Keeps paging links
+        }
+    }
+    response
+}
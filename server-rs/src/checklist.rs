@@ -0,0 +1,266 @@
+/**
+ * Checklist entries inside a todo
+ *
+ * Lightweight steps (text + done flag + order) for todos that do not
+ * deserve subtasks. After every change the todo's `checklist_done` /
+ * `checklist_total` are updated, so list views can show "2/5" without
+ * loading the entries, and a `checklist.updated` event carries the full
+ * list to clients that have the todo open.
+ *
+ * - GET    /api/todos/{id}/checklist
+ * - POST   /api/todos/{id}/checklist                  add
+ * - PUT    /api/todos/{id}/checklist/{item}           edit text/done/order
+ * - POST   /api/todos/{id}/checklist/{item}/toggle
+ * - DELETE /api/todos/{id}/checklist/{item}
+ * - POST   /api/todos/{id}/checklist/reorder          [{"id", "sort_order"}]
+ *
+ * POST /api/todos/{id}/duplicate can copy the entries (unchecked) too.
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post, put},
+};
+use chrono::Utc;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    audit::Actor,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{ChecklistItem, ChecklistItemCreate, ChecklistItemUpdate, ReorderItem},
+    routes::AppState,
+    services::emit,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/todos/{id}/checklist", get(list_items).post(add_item))
+        .route("/api/todos/{id}/checklist/reorder", post(reorder_items))
+        .route(
+            "/api/todos/{id}/checklist/{item}",
+            put(update_item).delete(delete_item),
+        )
+        .route("/api/todos/{id}/checklist/{item}/toggle", post(toggle_item))
+}
+
+async fn items(st: &AppState, todo_id: &str) -> ApiResult<Vec<ChecklistItem>> {
+    Ok(sqlx::query_as::<_, ChecklistItem>(
+        "SELECT * FROM checklist_items WHERE todo_id=?1 ORDER BY sort_order ASC, created_at ASC",
+    )
+    .bind(todo_id)
+    .fetch_all(&st.pool)
+    .await?)
+}
+
+async fn get_item(st: &AppState, todo_id: &str, id: &str) -> ApiResult<ChecklistItem> {
+    sqlx::query_as::<_, ChecklistItem>("SELECT * FROM checklist_items WHERE id=?1 AND todo_id=?2")
+        .bind(id)
+        .bind(todo_id)
+        .fetch_optional(&st.pool)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+fn validate(text: &str, done: i64) -> ApiResult<()> {
+    if text.trim().is_empty() {
+        return Err(ApiError::BadRequest("text must not be empty".into()));
+    }
+    if !(0..=1).contains(&done) {
+        return Err(ApiError::BadRequest("done must be 0 or 1".into()));
+    }
+    Ok(())
+}
+
+/// Copy every entry of `from` to `to`, unchecked, and update `to`'s progress.
+pub async fn copy(st: &AppState, actor: Actor, from: &str, to: &str) -> ApiResult<()> {
+    let now = Utc::now();
+    for item in items(st, from).await? {
+        sqlx::query(
+            r#"
+            INSERT INTO checklist_items (id,todo_id,text,done,sort_order,created_at,updated_at)
+            VALUES (?1,?2,?3,0,?4,?5,?5)
+        "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(to)
+        .bind(&item.text)
+        .bind(item.sort_order)
+        .bind(now)
+        .execute(&st.pool)
+        .await?;
+    }
+    sync(st, actor, to).await?;
+    Ok(())
+}
+
+/// Mirror progress onto the todo and broadcast the new list.
+async fn sync(st: &AppState, actor: Actor, todo_id: &str) -> ApiResult<Vec<ChecklistItem>> {
+    let items = items(st, todo_id).await?;
+    let done = items.iter().filter(|i| i.done != 0).count() as i64;
+    st.todos
+        .as_actor(actor)
+        .set_checklist_progress(todo_id, done, items.len() as i64)
+        .await?;
+    emit(
+        &st.hub,
+        "checklist.updated",
+        &json!({"todo_id": todo_id, "items": items}),
+    );
+    Ok(items)
+}
+
+async fn list_items(
+    State(st): State<AppState>,
+    Path(todo_id): Path<String>,
+) -> ApiResult<Json<Vec<ChecklistItem>>> {
+    st.todos.get(&todo_id).await?;
+    Ok(Json(items(&st, &todo_id).await?))
+}
+
+async fn add_item(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(todo_id): Path<String>,
+    JsonBody(body): JsonBody<ChecklistItemCreate>,
+) -> ApiResult<Json<ChecklistItem>> {
+    st.todos.get(&todo_id).await?;
+    let done = body.done.unwrap_or(0);
+    validate(&body.text, done)?;
+    let sort_order = match body.sort_order {
+        Some(v) => v,
+        None => {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COALESCE(MAX(sort_order) + 1, 0) FROM checklist_items WHERE todo_id=?1",
+            )
+            .bind(&todo_id)
+            .fetch_one(&st.pool)
+            .await?
+        }
+    };
+    let now = Utc::now();
+    let item = ChecklistItem {
+        id: Uuid::new_v4().to_string(),
+        todo_id,
+        text: body.text.trim().to_string(),
+        done,
+        sort_order,
+        created_at: now,
+        updated_at: now,
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO checklist_items (id,todo_id,text,done,sort_order,created_at,updated_at)
+        VALUES (?1,?2,?3,?4,?5,?6,?7)
+    "#,
+    )
+    .bind(&item.id)
+    .bind(&item.todo_id)
+    .bind(&item.text)
+    .bind(item.done)
+    .bind(item.sort_order)
+    .bind(item.created_at)
+    .bind(item.updated_at)
+    .execute(&st.pool)
+    .await?;
+    sync(&st, actor, &item.todo_id).await?;
+    Ok(Json(item))
+}
+
+async fn save(st: &AppState, item: &ChecklistItem) -> ApiResult<()> {
+    sqlx::query(
+        "UPDATE checklist_items SET text=?2, done=?3, sort_order=?4, updated_at=?5 WHERE id=?1",
+    )
+    .bind(&item.id)
+    .bind(&item.text)
+    .bind(item.done)
+    .bind(item.sort_order)
+    .bind(item.updated_at)
+    .execute(&st.pool)
+    .await?;
+    Ok(())
+}
+
+async fn update_item(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((todo_id, id)): Path<(String, String)>,
+    JsonBody(body): JsonBody<ChecklistItemUpdate>,
+) -> ApiResult<Json<ChecklistItem>> {
+    let mut item = get_item(&st, &todo_id, &id).await?;
+    if let Some(v) = body.text {
+        item.text = v.trim().to_string();
+    }
+    if let Some(v) = body.done {
+        item.done = v;
+    }
+    if let Some(v) = body.sort_order {
+        item.sort_order = v;
+    }
+    validate(&item.text, item.done)?;
+    item.updated_at = Utc::now();
+    save(&st, &item).await?;
+    sync(&st, actor, &todo_id).await?;
+    Ok(Json(item))
+}
+
+async fn toggle_item(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((todo_id, id)): Path<(String, String)>,
+) -> ApiResult<Json<ChecklistItem>> {
+    let mut item = get_item(&st, &todo_id, &id).await?;
+    item.done = 1 - item.done;
+    item.updated_at = Utc::now();
+    save(&st, &item).await?;
+    sync(&st, actor, &todo_id).await?;
+    Ok(Json(item))
+}
+
+async fn delete_item(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((todo_id, id)): Path<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let res = sqlx::query("DELETE FROM checklist_items WHERE id=?1 AND todo_id=?2")
+        .bind(&id)
+        .bind(&todo_id)
+        .execute(&st.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    sync(&st, actor, &todo_id).await?;
+    Ok(Json(json!({"ok": true})))
+}
+
+/// New positions in one transaction; every id must belong to the todo.
+async fn reorder_items(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(todo_id): Path<String>,
+    JsonBody(order): JsonBody<Vec<ReorderItem>>,
+) -> ApiResult<Json<Vec<ChecklistItem>>> {
+    st.todos.get(&todo_id).await?;
+    let now = Utc::now();
+    let mut tx = st.pool.begin().await?;
+    for it in &order {
+        let res = sqlx::query(
+            "UPDATE checklist_items SET sort_order=?3, updated_at=?4 WHERE id=?1 AND todo_id=?2",
+        )
+        .bind(&it.id)
+        .bind(&todo_id)
+        .bind(it.sort_order)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(ApiError::BadRequest(format!(
+                "`{}` is not an entry of this checklist",
+                it.id
+            )));
+        }
+    }
+    tx.commit().await?;
+    Ok(Json(sync(&st, actor, &todo_id).await?))
+}
@@ -0,0 +1,125 @@
+/**
+ * Comments on todos
+ *
+ * Short remarks ("bought the wrong size, returning it Monday") that are
+ * not part of the note. They are never edited, only added and deleted,
+ * and show up in the todo's activity feed next to its changes.
+ *
+ * - GET    /api/todos/{id}/comments              oldest first
+ * - POST   /api/todos/{id}/comments              {"body"}
+ * - DELETE /api/todos/{id}/comments/{comment}
+ *
+ * Changes are broadcast as `comment.created` / `comment.deleted` with the
+ * todo's project_id, so scoped WebSocket clients only see their board.
+ * `@name` mentions notify those members (see notifications.rs).
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{delete, get},
+};
+use chrono::Utc;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    audit::Actor,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{Comment, CommentCreate},
+    notifications,
+    routes::AppState,
+    services::emit,
+};
+
+/// Longest accepted comment (characters).
+const MAX_BODY_CHARS: usize = 10_000;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/todos/{id}/comments",
+            get(list_comments).post(add_comment),
+        )
+        .route("/api/todos/{id}/comments/{comment}", delete(delete_comment))
+}
+
+/// Comments of one todo, oldest first.
+pub async fn comments(st: &AppState, todo_id: &str) -> ApiResult<Vec<Comment>> {
+    Ok(sqlx::query_as::<_, Comment>(
+        "SELECT * FROM todo_comments WHERE todo_id=?1 ORDER BY created_at ASC, id ASC",
+    )
+    .bind(todo_id)
+    .fetch_all(&st.pool)
+    .await?)
+}
+
+async fn list_comments(
+    State(st): State<AppState>,
+    Path(todo_id): Path<String>,
+) -> ApiResult<Json<Vec<Comment>>> {
+    st.todos.get(&todo_id).await?;
+    Ok(Json(comments(&st, &todo_id).await?))
+}
+
+async fn add_comment(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(todo_id): Path<String>,
+    JsonBody(body): JsonBody<CommentCreate>,
+) -> ApiResult<Json<Comment>> {
+    let todo = st.todos.get(&todo_id).await?;
+    let text = body.body.trim();
+    if text.is_empty() {
+        return Err(ApiError::BadRequest("body must not be empty".into()));
+    }
+    if text.chars().count() > MAX_BODY_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "body must be at most {MAX_BODY_CHARS} characters"
+        )));
+    }
+    let comment = Comment {
+        id: Uuid::new_v4().to_string(),
+        todo_id,
+        actor: actor.as_str().to_string(),
+        body: text.to_string(),
+        created_at: Utc::now(),
+    };
+    sqlx::query(
+        "INSERT INTO todo_comments (id,todo_id,actor,body,created_at) VALUES (?1,?2,?3,?4,?5)",
+    )
+    .bind(&comment.id)
+    .bind(&comment.todo_id)
+    .bind(&comment.actor)
+    .bind(&comment.body)
+    .bind(comment.created_at)
+    .execute(&st.pool)
+    .await?;
+    emit(
+        &st.hub,
+        "comment.created",
+        &json!({"project_id": todo.project_id, "comment": comment}),
+    );
+    notifications::comment_mentions(&st, &actor, &todo, &comment.body).await;
+    Ok(Json(comment))
+}
+
+async fn delete_comment(
+    State(st): State<AppState>,
+    Path((todo_id, id)): Path<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let todo = st.todos.get(&todo_id).await?;
+    let res = sqlx::query("DELETE FROM todo_comments WHERE id=?1 AND todo_id=?2")
+        .bind(&id)
+        .bind(&todo_id)
+        .execute(&st.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    emit(
+        &st.hub,
+        "comment.deleted",
+        &json!({"project_id": todo.project_id, "todo_id": todo_id, "id": id}),
+    );
+    Ok(Json(json!({"ok": true})))
+}
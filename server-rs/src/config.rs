@@ -0,0 +1,588 @@
+/**
+ * Server configuration file
+ *
+ * Core settings are read from a TOML file (CONFIG_FILE, default
+ * ./config.toml; optional unless CONFIG_FILE is set), then overridden by
+ * environment variables of the same name in upper case (PORT,
+ * DATABASE_URL, ...). Every setting has a default, so an empty or missing
+ * file gives the same behaviour as before.
+ *
+ * ```toml
+ * port = 8000
+ * database_url = "sqlite://./data/todos.db"
+ * static_dir = "../server/static"
+ * cors_origins = ["http://raspberrypi.local:3000"]   # "*" or unset = any origin (env: ALLOWED_ORIGINS)
+ * cors_methods = ["GET", "POST", "PUT", "DELETE"]     # only used with listed origins
+ * cors_headers = ["content-type", "authorization"]
+ * ws_buffer_size = 256
+ * ws_max_connections = 64                            # further WebSocket upgrades get 503; 0 = no limit
+ * ws_send_queue = 64                                 # messages queued per client before it is dropped
+ * db_pool_size = 5
+ * reminder_interval_secs = 60
+ * reminder_lead_minutes = 60
+ * timezone = "Europe/Berlin"                          # household default for "today"; unset = system zone
+ * vacuum_interval_hours = 168
+ * wal_checkpoint_minutes = 15                         # 0 = leave checkpoints to SQLite
+ * low_write_mode = false                              # SD card friendly: fewer fsyncs, batched WAL writes
+ * integrity_check = "quick"                           # on startup: off, quick or full
+ * on_corruption = "refuse"                            # or "read-only": serve what is left, reject writes
+ * read_only = false                                   # reject all writes (kiosk deployments, maintenance)
+ * simple_ui = true                                    # server-rendered fallback UI at /simple
+ * admin_token = "..."                                 # X-Admin-Token for /api/admin/...; unset = no token
+ * admin_users = ["ann"]                               # members whose OIDC sessions may use /api/admin/...
+ * auto_archive_days = 30                              # 0 = keep finished todos on the board
+ * dedupe_todos = "off"                                # same-title creates: off, return or conflict
+ * tls_cert = "/etc/letsencrypt/live/todo.example.com/fullchain.pem"
+ * tls_key = "/etc/letsencrypt/live/todo.example.com/privkey.pem"
+ * tls_redirect_port = 80                              # HTTP -> HTTPS redirect listener
+ * listen = "unix:/run/todo/todo.sock"                 # or "127.0.0.1:8000"; default 0.0.0.0:port
+ * socket_mode = "660"                                 # permissions of the Unix socket
+ * max_body_bytes = 8388608                            # request body limit (413 above)
+ * request_timeout_secs = 10                           # 504 for handlers taking longer; 0 = none
+ * slow_request_ms = 1000                              # log requests slower than this; 0 = off
+ * frame_options = "DENY"                              # X-Frame-Options; "" = not sent
+ * content_security_policy = "default-src 'self'"      # "" = not sent
+ * referrer_policy = "same-origin"                     # "" = not sent
+ * hsts_max_age_secs = 31536000                        # Strict-Transport-Security with TLS; 0 = off
+ * backups_dir = "./data/backups"                      # POST /api/admin/backup, `server-rs backup`
+ * backup_keep = 7                                     # older backups are deleted
+ * backup_schedule = "0 3 * * *"                       # cron, local time; unset = manual only
+ *
+ * [status_transitions]                                # per status: where a todo may move next
+ * archived = ["todo", "doing"]                        # entries replace the default for that status
+ * doing = ["*"]                                       # "*" = anywhere
+ *
+ * [[escalation_rules]]                                # overdue todos: empty list = off
+ * after_hours = 0
+ * tag = "overdue"                                     # add a tag ...
+ * [[escalation_rules]]
+ * after_hours = 24
+ * priority = 2                                        # ... or raise the priority to at least 2
+ * ```
+ *
+ * Integrations (MQTT, SMTP, Telegram, ...) keep their own env variables.
+ * Invalid values fail startup with an error naming the field.
+ */
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+use axum::http::{HeaderName, HeaderValue, Method};
+use chrono_tz::Tz;
+use figment::{
+    Figment,
+    providers::{Env, Format, Serialized, Toml},
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::{
+    db::IntegrityCheck,
+    escalation::{self, EscalationRule},
+    services::Dedupe,
+    timezone,
+};
+
+/// Environment variables that override file settings (lower-cased = field name).
+const ENV_KEYS: &[&str] = &[
+    "PORT",
+    "DATABASE_URL",
+    "LOCAL_DATABASE_URL",
+    "STATIC_DIR",
+    "CORS_ORIGINS",
+    "CORS_METHODS",
+    "CORS_HEADERS",
+    "WS_BUFFER_SIZE",
+    "WS_MAX_CONNECTIONS",
+    "WS_SEND_QUEUE",
+    "DB_POOL_SIZE",
+    "REMINDER_INTERVAL_SECS",
+    "REMINDER_LEAD_MINUTES",
+    "TIMEZONE",
+    "VACUUM_INTERVAL_HOURS",
+    "WAL_CHECKPOINT_MINUTES",
+    "LOW_WRITE_MODE",
+    "INTEGRITY_CHECK",
+    "ON_CORRUPTION",
+    "READ_ONLY",
+    "SIMPLE_UI",
+    "ADMIN_TOKEN",
+    "ADMIN_USERS",
+    "AUTO_ARCHIVE_DAYS",
+    "DEDUPE_TODOS",
+    "TLS_CERT",
+    "TLS_KEY",
+    "TLS_REDIRECT_PORT",
+    "LISTEN",
+    "SOCKET_MODE",
+    "MAX_BODY_BYTES",
+    "REQUEST_TIMEOUT_SECS",
+    "SLOW_REQUEST_MS",
+    "FRAME_OPTIONS",
+    "CONTENT_SECURITY_POLICY",
+    "REFERRER_POLICY",
+    "HSTS_MAX_AGE_SECS",
+    "ATTACHMENTS_DIR",
+    "MAX_ATTACHMENT_BYTES",
+    "ATTACHMENT_TYPES",
+    "BACKUPS_DIR",
+    "BACKUP_KEEP",
+    "BACKUP_SCHEDULE",
+    "STATUS_TRANSITIONS",
+    "ESCALATION_RULES",
+];
+
+/// Content-Security-Policy for the bundled frontend: own origin only, plus
+/// inline styles, data:/blob: images (attachment previews) and WebSockets.
+const DEFAULT_CSP: &str = "default-src 'self'; img-src 'self' data: blob:; style-src 'self' 'unsafe-inline'; connect-src 'self' ws: wss:; frame-ancestors 'none'";
+
+/// Response headers browsers may read cross-origin.
+const EXPOSE_HEADERS: [&str; 6] = [
+    "deprecation",
+    "sunset",
+    "link",
+    "etag",
+    "idempotent-replayed",
+    "x-total-count",
+];
+
+/**
+ * Core server settings
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub port: u16,                  // HTTP(S) listen port, default 8000
+    pub database_url: String,       // sqlite://... or postgres://... (postgres feature)
+    pub local_database_url: String, // Server-local SQLite when database_url is Postgres
+    pub static_dir: String,         // Built web frontend, served when present
+    #[serde(deserialize_with = "string_or_list")]
+    pub cors_origins: Vec<String>, // Allowed browser origins; empty or "*" = any
+    #[serde(deserialize_with = "string_or_list")]
+    pub cors_methods: Vec<String>, // Methods allowed cross-origin (with listed origins)
+    #[serde(deserialize_with = "string_or_list")]
+    pub cors_headers: Vec<String>, // Request headers allowed cross-origin (with listed origins)
+    pub ws_buffer_size: usize,      // WebSocket broadcast buffer (events per client)
+    pub ws_max_connections: usize,  // Concurrent WebSocket clients; 0 = unlimited
+    pub ws_send_queue: usize,       // Frames queued per client; a client this far behind is dropped
+    pub db_pool_size: u32,          // Max SQLite connections
+    pub reminder_interval_secs: u64, // How often the reminder scheduler checks
+    pub reminder_lead_minutes: i64, // "Due soon" window before due_at
+    pub timezone: Option<String>,   // IANA zone for days without a user setting; None = system
+    pub vacuum_interval_hours: u64, // VACUUM job interval
+    pub wal_checkpoint_minutes: u64, // PRAGMA wal_checkpoint(TRUNCATE) interval; 0 = off
+    pub low_write_mode: bool,       // synchronous=NORMAL and batched WAL checkpoints (SD cards)
+    pub integrity_check: String,    // Startup check: "off", "quick" (default) or "full"
+    pub on_corruption: String,      // Failed check: "refuse" to start (default) or open "read-only"
+    pub read_only: bool,            // Start in read-only mode (toggle: /api/admin/read-only)
+    pub simple_ui: bool,            // Serve the server-rendered HTML UI at /simple
+    pub admin_token: Option<String>, // X-Admin-Token value granting /api/admin/...
+    #[serde(deserialize_with = "string_or_list")]
+    pub admin_users: Vec<String>, // Users whose session (bearer token) grants /api/admin/...; needs OIDC
+    pub auto_archive_days: u64, // Archive todos completed this long ago; 0 = never
+    pub dedupe_todos: String,   // Same-title POST /api/todos: "off", "return" or "conflict"
+    pub tls_cert: Option<String>, // PEM certificate chain; enables HTTPS on `port`
+    pub tls_key: Option<String>, // PEM private key for tls_cert
+    pub tls_redirect_port: Option<u16>, // Plain HTTP port answering with redirects to HTTPS
+    pub listen: Option<String>, // "unix:/path.sock" or "ip:port"; overrides `port`'s 0.0.0.0 bind
+    #[serde(deserialize_with = "string_or_number")]
+    pub socket_mode: String, // Octal permissions for the Unix socket, default 660
+    pub max_body_bytes: usize,  // Largest accepted request body, default 8 MiB
+    pub request_timeout_secs: u64, // Handlers running longer answer 504; 0 = no limit
+    pub slow_request_ms: u64,   // Requests slower than this are logged; 0 = off
+    pub frame_options: String,  // X-Frame-Options; empty = not sent
+    pub content_security_policy: String, // Content-Security-Policy; empty = not sent
+    pub referrer_policy: String, // Referrer-Policy; empty = not sent
+    pub hsts_max_age_secs: u64, // Strict-Transport-Security max-age when serving TLS; 0 = off
+    pub attachments_dir: String, // Where uploaded attachment files are stored
+    pub max_attachment_bytes: usize, // Largest attachment file, at most max_body_bytes
+    #[serde(deserialize_with = "string_or_list")]
+    pub attachment_types: Vec<String>, // Accepted MIME types; "image/*" matches a whole family
+    pub backups_dir: String,    // Where timestamped database backups are written
+    pub backup_keep: usize,     // Backups kept in backups_dir; older ones are deleted
+    pub backup_schedule: Option<String>, // Cron expression for automatic backups; None = manual only
+    pub status_transitions: BTreeMap<String, Vec<String>>, // Status -> statuses it may move to
+    pub escalation_rules: Vec<EscalationRule>, // Priority/tag bumps for overdue todos
+}
+
+/**
+ * Where the main listener binds
+ */
+#[derive(Debug, Clone)]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 8000,
+            database_url: "sqlite://./data/todos.db".into(),
+            local_database_url: "sqlite://./data/local.db?mode=rwc".into(),
+            static_dir: "../server/static".into(),
+            cors_origins: Vec::new(),
+            cors_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(String::from)
+                .to_vec(),
+            cors_headers: [
+                "content-type",
+                "authorization",
+                "if-match",
+                "if-none-match",
+                "idempotency-key",
+                "x-user-id",
+                "x-admin-token",
+            ]
+            .map(String::from)
+            .to_vec(),
+            ws_buffer_size: 256,
+            ws_max_connections: 64,
+            ws_send_queue: 64,
+            db_pool_size: 5,
+            reminder_interval_secs: 60,
+            reminder_lead_minutes: 60,
+            timezone: None,
+            vacuum_interval_hours: 24 * 7,
+            wal_checkpoint_minutes: 15,
+            low_write_mode: false,
+            integrity_check: "quick".into(),
+            on_corruption: "refuse".into(),
+            read_only: false,
+            simple_ui: true,
+            admin_token: None,
+            admin_users: Vec::new(),
+            auto_archive_days: 30,
+            dedupe_todos: "off".into(),
+            tls_cert: None,
+            tls_key: None,
+            tls_redirect_port: None,
+            listen: None,
+            socket_mode: "660".into(),
+            max_body_bytes: crate::DEFAULT_BODY_LIMIT,
+            request_timeout_secs: 10,
+            slow_request_ms: 1000,
+            frame_options: "DENY".into(),
+            content_security_policy: DEFAULT_CSP.into(),
+            referrer_policy: "same-origin".into(),
+            hsts_max_age_secs: 365 * 24 * 3600,
+            attachments_dir: "./data/attachments".into(),
+            max_attachment_bytes: 6 * 1024 * 1024,
+            attachment_types: [
+                "image/jpeg",
+                "image/png",
+                "image/gif",
+                "image/webp",
+                "image/heic",
+                "application/pdf",
+                "text/plain",
+            ]
+            .map(String::from)
+            .to_vec(),
+            backups_dir: "./data/backups".into(),
+            backup_keep: 7,
+            backup_schedule: None,
+            status_transitions: [
+                ("todo", &["doing", "done", "archived"][..]),
+                ("doing", &["todo", "done", "archived"]),
+                ("done", &["todo", "doing", "archived"]),
+                ("archived", &["todo"]),
+            ]
+            .into_iter()
+            .map(|(from, to)| (from.to_string(), to.iter().map(|s| s.to_string()).collect()))
+            .collect(),
+            escalation_rules: escalation::default_rules(),
+        }
+    }
+}
+
+/// Accept both `["a", "b"]` (TOML) and `"a,b"` (environment).
+fn string_or_list<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    let list = match OneOrMany::deserialize(d)? {
+        OneOrMany::One(s) => s.split(',').map(str::to_string).collect(),
+        OneOrMany::Many(v) => v,
+    };
+    Ok(list
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect())
+}
+
+/// Accept `"660"` and `660` alike (figment types numeric env values as integers).
+fn string_or_number<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+    Ok(match StringOrNumber::deserialize(d)? {
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Number(n) => n.to_string(),
+    })
+}
+
+impl ServerConfig {
+    /// Load defaults, then the config file, then environment overrides.
+    pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(std::env::var("CONFIG_FILE").ok())
+    }
+
+    /// Like load(), with an explicit file that must exist (None = optional ./config.toml).
+    pub fn load_from(file: Option<String>) -> anyhow::Result<Self> {
+        let (path, explicit) = match file {
+            Some(path) => (path, true),
+            None => ("config.toml".to_string(), false),
+        };
+        if explicit && !Path::new(&path).exists() {
+            return Err(anyhow!("config file `{path}` does not exist"));
+        }
+        let config: Self = Figment::from(Serialized::defaults(Self::default()))
+            .merge(Toml::file(&path))
+            .merge(Env::raw().only(ENV_KEYS))
+            .merge(
+                Env::raw()
+                    .only(&["ALLOWED_ORIGINS"])
+                    .map(|_| "cors_origins".into()),
+            )
+            .extract()
+            .with_context(|| format!("invalid configuration (file `{path}` or environment)"))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reject values that would only fail later (or silently misbehave).
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let field = |name: &str, msg: &str| Err(anyhow!("config field `{name}`: {msg}"));
+        if self.port == 0 {
+            return field("port", "must be between 1 and 65535");
+        }
+        if !self.database_url.starts_with("sqlite:") && !self.database_url.starts_with("postgres") {
+            return field("database_url", "must be a sqlite:// or postgres:// URL");
+        }
+        if !self.local_database_url.starts_with("sqlite:") {
+            return field("local_database_url", "must be a sqlite:// URL");
+        }
+        if self
+            .admin_token
+            .as_ref()
+            .is_some_and(|t| t.trim().len() < 16)
+        {
+            return field("admin_token", "must be at least 16 characters");
+        }
+        if self.ws_buffer_size == 0 {
+            return field("ws_buffer_size", "must be at least 1");
+        }
+        if self.ws_send_queue == 0 {
+            return field("ws_send_queue", "must be at least 1");
+        }
+        if !(1..=100).contains(&self.db_pool_size) {
+            return field("db_pool_size", "must be between 1 and 100");
+        }
+        if self.reminder_interval_secs == 0 {
+            return field("reminder_interval_secs", "must be at least 1");
+        }
+        if self.reminder_lead_minutes < 0 {
+            return field("reminder_lead_minutes", "must not be negative");
+        }
+        if let Some(tz) = &self.timezone
+            && tz.parse::<Tz>().is_err()
+        {
+            return field(
+                "timezone",
+                "must be an IANA time zone such as Europe/Berlin",
+            );
+        }
+        if self.vacuum_interval_hours == 0 {
+            return field("vacuum_interval_hours", "must be at least 1");
+        }
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                for (name, path) in [("tls_cert", cert), ("tls_key", key)] {
+                    if !Path::new(path).is_file() {
+                        return field(name, &format!("file `{path}` does not exist"));
+                    }
+                }
+            }
+            (None, None) => {
+                if self.tls_redirect_port.is_some() {
+                    return field("tls_redirect_port", "requires tls_cert and tls_key");
+                }
+            }
+            (Some(_), None) => return field("tls_key", "required when tls_cert is set"),
+            (None, Some(_)) => return field("tls_cert", "required when tls_key is set"),
+        }
+        if self.tls_redirect_port == Some(self.port) {
+            return field("tls_redirect_port", "must differ from `port`");
+        }
+        if let Some(listen) = &self.listen {
+            match listen.strip_prefix("unix:") {
+                Some("") => {
+                    return field("listen", "unix: needs a socket path");
+                }
+                Some(_) if self.tls_cert.is_some() => {
+                    return field("listen", "TLS is not supported on a Unix socket");
+                }
+                Some(_) => {}
+                None if listen.parse::<SocketAddr>().is_err() => {
+                    return field("listen", "must be unix:/path or ip:port");
+                }
+                None => {}
+            }
+        }
+        if !u32::from_str_radix(&self.socket_mode, 8).is_ok_and(|m| m <= 0o777) {
+            return field("socket_mode", "must be an octal mode like 660");
+        }
+        if self.max_body_bytes < 1024 {
+            return field("max_body_bytes", "must be at least 1024");
+        }
+        if self.max_attachment_bytes == 0 || self.max_attachment_bytes > self.max_body_bytes {
+            return field(
+                "max_attachment_bytes",
+                "must be between 1 and max_body_bytes (uploads are request bodies)",
+            );
+        }
+        if Dedupe::parse(&self.dedupe_todos).is_none() {
+            return field("dedupe_todos", "must be off, return or conflict");
+        }
+        if IntegrityCheck::parse(&self.integrity_check).is_none() {
+            return field("integrity_check", "must be off, quick or full");
+        }
+        if !matches!(self.on_corruption.as_str(), "refuse" | "read-only") {
+            return field("on_corruption", "must be refuse or read-only");
+        }
+        if self.backup_keep == 0 {
+            return field("backup_keep", "must be at least 1");
+        }
+        if let Some(schedule) = &self.backup_schedule
+            && let Err(e) = crate::backups::Schedule::parse(schedule)
+        {
+            return field("backup_schedule", &e.to_string());
+        }
+        for kind in &self.attachment_types {
+            if !kind.contains('/') {
+                return field("attachment_types", &format!("`{kind}` is not a MIME type"));
+            }
+        }
+        for (from, to) in &self.status_transitions {
+            if from.trim().is_empty() || to.iter().any(|s| s.trim().is_empty()) {
+                return field("status_transitions", "statuses must not be empty");
+            }
+        }
+        for rule in &self.escalation_rules {
+            if let Some(problem) = rule.problem() {
+                return field("escalation_rules", problem);
+            }
+        }
+        for origin in self.cors_origins.iter().filter(|o| *o != "*") {
+            let valid = reqwest::Url::parse(origin)
+                .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.path() == "/");
+            if !valid || origin.ends_with('/') {
+                return Err(anyhow!(
+                    "config field `cors_origins`: `{origin}` is not an origin like https://todo.example.com"
+                ));
+            }
+        }
+        for method in &self.cors_methods {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                return field("cors_methods", &format!("`{method}` is not an HTTP method"));
+            }
+        }
+        for header in &self.cors_headers {
+            if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                return field("cors_headers", &format!("`{header}` is not a header name"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Main listener address (`listen`, else 0.0.0.0:`port`).
+    pub fn listen(&self) -> Listen {
+        match self.listen.as_deref() {
+            Some(l) if l.starts_with("unix:") => Listen::Unix(PathBuf::from(&l["unix:".len()..])),
+            Some(l) => Listen::Tcp(
+                l.parse()
+                    .unwrap_or(SocketAddr::from(([0, 0, 0, 0], self.port))),
+            ),
+            None => Listen::Tcp(SocketAddr::from(([0, 0, 0, 0], self.port))),
+        }
+    }
+
+    /// Unix socket permissions as a mode bit set (validated in validate()).
+    pub fn socket_mode(&self) -> u32 {
+        u32::from_str_radix(&self.socket_mode, 8).unwrap_or(0o660)
+    }
+
+    /// Household time zone (`timezone`, else the system's; validated in validate()).
+    pub fn zone(&self) -> Tz {
+        self.timezone
+            .as_deref()
+            .and_then(|tz| tz.parse().ok())
+            .unwrap_or_else(timezone::system)
+    }
+
+    /// Settings as JSON with credentials masked: passwords in database URLs and
+    /// any field named like a secret (admin info endpoint, logs).
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(fields) = value.as_object_mut() {
+            for (name, field) in fields.iter_mut() {
+                let secret = ["password", "secret", "token"]
+                    .iter()
+                    .any(|word| name.contains(word));
+                match field {
+                    serde_json::Value::String(_) if secret => *field = "***".into(),
+                    serde_json::Value::String(s) if name.ends_with("_url") => {
+                        if let Ok(mut url) = reqwest::Url::parse(s)
+                            && url.password().is_some()
+                        {
+                            let _ = url.set_password(Some("***"));
+                            *s = url.to_string();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        value
+    }
+
+    /// CORS policy for the API: permissive unless origins are listed, then
+    /// restricted to the listed origins, methods and headers (with credentials).
+    pub fn cors_layer(&self) -> CorsLayer {
+        if self.cors_origins.is_empty() || self.cors_origins.iter().any(|o| o == "*") {
+            return CorsLayer::very_permissive();
+        }
+        let origins: Vec<HeaderValue> = self
+            .cors_origins
+            .iter()
+            .filter_map(|o| HeaderValue::from_str(o).ok())
+            .collect();
+        let methods: Vec<Method> = self
+            .cors_methods
+            .iter()
+            .filter_map(|m| Method::from_bytes(m.as_bytes()).ok())
+            .collect();
+        let headers: Vec<HeaderName> = self
+            .cors_headers
+            .iter()
+            .filter_map(|h| HeaderName::from_bytes(h.as_bytes()).ok())
+            .collect();
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .expose_headers(EXPOSE_HEADERS.map(HeaderName::from_static))
+            .allow_credentials(true)
+    }
+}
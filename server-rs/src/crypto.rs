@@ -0,0 +1,209 @@
+/**
+ * Field-level encryption at rest (AES-256-GCM)
+ *
+ * With a key configured, the fields most likely to hold something private
+ * are stored encrypted, so a copied SD card or backup file does not reveal
+ * them:
+ *
+ * ```text
+ * todos.note              also inside audit_log snapshots and changes
+ * attachments.filename    the file contents themselves are not encrypted
+ * ```
+ *
+ * The key is 32 bytes, from (first match wins):
+ *
+ * ```text
+ * ENCRYPTION_KEY=<64 hex digits>
+ * ENCRYPTION_KEY_FILE=/etc/todo/key     64 hex digits, or 32 raw bytes
+ * ```
+ *
+ * Generate one with `openssl rand -hex 32`. Keep it off the SD card (a USB
+ * stick, a tmpfs filled at boot, systemd credentials); losing it loses the
+ * encrypted fields.
+ *
+ * Stored values look like `enc:v1:<base64 of nonce || ciphertext || tag>`.
+ * Values without the prefix are read as they are, so existing data stays
+ * readable after a key is added and is encrypted on its next write
+ * (`todo-server encrypt` does all rows at once). Encryption and decryption
+ * happen where rows are read and written (repositories, attachments, audit
+ * log); the API and the rest of the server only ever see plaintext.
+ *
+ * Not covered: titles and tags (needed for sorting, filters and reminder
+ * texts), comments, the attachment files themselves, and outbox events
+ * (plaintext, pruned after about a day). While a key is set, `note:`
+ * filters are evaluated in memory instead of in SQL.
+ */
+use std::{env, sync::OnceLock};
+
+use anyhow::{Context, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde_json::Value;
+
+/// Prefix of an encrypted value (format version 1).
+const PREFIX: &str = "enc:v1:";
+/// Key length in bytes.
+const KEY_LEN: usize = 32;
+
+/// The installed cipher; unset = encryption off.
+static CIPHER: OnceLock<Cipher> = OnceLock::new();
+
+/**
+ * AES-256-GCM with random 96-bit nonces
+ */
+pub struct Cipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Cipher(..)") // Never print the key
+    }
+}
+
+impl Cipher {
+    pub fn new(key: &[u8]) -> anyhow::Result<Self> {
+        if key.len() != KEY_LEN {
+            return Err(anyhow!(
+                "encryption key must be {KEY_LEN} bytes, got {}",
+                key.len()
+            ));
+        }
+        let key =
+            UnboundKey::new(&AES_256_GCM, key).map_err(|_| anyhow!("invalid encryption key"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Returns None when neither ENCRYPTION_KEY nor ENCRYPTION_KEY_FILE is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        if let Ok(hex_key) = env::var("ENCRYPTION_KEY") {
+            let key =
+                hex::decode(hex_key.trim()).context("ENCRYPTION_KEY must be 64 hex digits")?;
+            return Self::new(&key).map(Some);
+        }
+        let Ok(path) = env::var("ENCRYPTION_KEY_FILE") else {
+            return Ok(None);
+        };
+        let bytes = std::fs::read(&path)
+            .with_context(|| format!("cannot read ENCRYPTION_KEY_FILE {path}"))?;
+        let key = match std::str::from_utf8(&bytes).ok().map(str::trim) {
+            Some(text) if text.len() == KEY_LEN * 2 => {
+                hex::decode(text).context("ENCRYPTION_KEY_FILE is not valid hex")?
+            }
+            _ => bytes,
+        };
+        Self::new(&key).map(Some)
+    }
+
+    /// `plaintext` as a stored value.
+    pub fn seal(&self, plaintext: &str) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .expect("system random number generator failed");
+        let mut data = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .expect("AES-GCM input is within limits");
+        let mut out = nonce.to_vec();
+        out.append(&mut data);
+        format!("{PREFIX}{}", STANDARD.encode(out))
+    }
+
+    /// A stored value as plaintext; None when it is damaged or from another key.
+    pub fn open(&self, stored: &str) -> Option<String> {
+        let Some(encoded) = stored.strip_prefix(PREFIX) else {
+            return Some(stored.to_string());
+        };
+        let mut data = STANDARD.decode(encoded).ok()?;
+        if data.len() < NONCE_LEN {
+            return None;
+        }
+        let mut sealed = data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&data).ok()?;
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .ok()?;
+        String::from_utf8(plain.to_vec()).ok()
+    }
+}
+
+/// Turn encryption on for the rest of the process (once, at startup).
+pub fn install(cipher: Cipher) {
+    if CIPHER.set(cipher).is_err() {
+        tracing::warn!("encryption key already installed; ignoring the new one");
+    }
+}
+
+/// Whether fields are being encrypted.
+pub fn enabled() -> bool {
+    CIPHER.get().is_some()
+}
+
+/// Whether `stored` is an encrypted value.
+pub fn is_sealed(stored: &str) -> bool {
+    stored.starts_with(PREFIX)
+}
+
+/// `value` as it should be stored: encrypted when a key is installed.
+pub fn seal(value: &str) -> String {
+    match CIPHER.get() {
+        Some(cipher) if !is_sealed(value) => cipher.seal(value),
+        _ => value.to_string(),
+    }
+}
+
+/// A stored value as plaintext. Values that cannot be decrypted (no key,
+/// or another key) are returned as stored, with a warning.
+pub fn open(stored: &str) -> String {
+    if !is_sealed(stored) {
+        return stored.to_string();
+    }
+    match CIPHER.get().and_then(|c| c.open(stored)) {
+        Some(plain) => plain,
+        None => {
+            tracing::warn!("cannot decrypt a stored field; is ENCRYPTION_KEY set and correct?");
+            stored.to_string()
+        }
+    }
+}
+
+/// [`seal`] for optional fields.
+pub fn seal_opt(value: Option<&str>) -> Option<String> {
+    value.map(seal)
+}
+
+/// [`open`] for optional fields.
+pub fn open_opt(stored: Option<String>) -> Option<String> {
+    stored.map(|s| open(&s))
+}
+
+/// Apply `f` to the string `key` of a JSON object, and to `from`/`to`
+/// when it is an audit change (`{"note": {"from": .., "to": ..}}`).
+pub fn map_field(doc: &mut Value, key: &str, f: fn(&str) -> String) {
+    let Some(field) = doc.get_mut(key) else {
+        return;
+    };
+    let apply = |v: &mut Value| {
+        if let Some(s) = v.as_str() {
+            *v = Value::String(f(s));
+        }
+    };
+    if field.is_object() {
+        for side in ["from", "to"] {
+            if let Some(v) = field.get_mut(side) {
+                apply(v);
+            }
+        }
+    } else {
+        apply(field);
+    }
+}
@@ -1,13 +1,414 @@
-use anyhow::Result;
-use sqlx::{Pool, Sqlite, sqlite::SqlitePoolOptions};
+use std::{future::Future, str::FromStr, time::Duration};
+
+use anyhow::{Context, Result};
+use sqlx::{
+    Pool, Sqlite,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteSynchronous},
+};
+
+use crate::error::{ApiError, ApiResult};
 
 pub type SqlitePool = Pool<Sqlite>;
 
-pub async fn init_pool(database_url: &str) -> Result<SqlitePool> {
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(database_url)
+/// How long SQLite itself waits for a lock before reporting SQLITE_BUSY.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// WAL size (in pages) that triggers SQLite's own checkpoint in low-write mode;
+/// large enough that the periodic checkpoint normally gets there first.
+const LOW_WRITE_AUTOCHECKPOINT_PAGES: u32 = 10_000;
+
+/// Backoff between retries of a write that still found the database busy.
+const BUSY_RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(50),
+    Duration::from_millis(200),
+    Duration::from_millis(800),
+];
+
+/**
+ * Where todos and categories are stored, chosen by the DATABASE_URL scheme
+ *
+ * Server-local state (users, inbound tokens, reminder log, settings) always
+ * lives in SQLite; with Postgres it goes to LOCAL_DATABASE_URL.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Sqlite,
+    #[cfg(feature = "postgres")]
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(database_url: &str) -> Result<Self> {
+        if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            #[cfg(feature = "postgres")]
+            return Ok(Backend::Postgres);
+            #[cfg(not(feature = "postgres"))]
+            anyhow::bail!(
+                "DATABASE_URL points at Postgres, but this build lacks the `postgres` feature"
+            );
+        }
+        Ok(Backend::Sqlite)
+    }
+}
+
+/// Categories seeded into a fresh database: (name, color, description).
+pub const DEFAULT_CATEGORIES: [(&str, &str, &str); 5] = [
+    ("General", "#6B7280", "General tasks and items"),
+    ("Work", "#3B82F6", "Work-related tasks"),
+    ("Personal", "#EF4444", "Personal tasks and reminders"),
+    ("Shopping", "#10B981", "Shopping lists and items"),
+    ("Health", "#F59E0B", "Health and fitness related"),
+];
+
+/// Indexes for the common todo/category queries (same DDL on SQLite and Postgres):
+/// category counts and delete guard, reminders and calendar (due_at), project
+/// boards and the category order.
+const INDEXES: [&str; 4] = [
+    "CREATE INDEX IF NOT EXISTS idx_todos_project ON todos(project_id)",
+    "CREATE INDEX IF NOT EXISTS idx_todos_category ON todos(category_id)",
+    "CREATE INDEX IF NOT EXISTS idx_todos_due ON todos(due_at)",
+    "CREATE INDEX IF NOT EXISTS idx_categories_sort ON categories(sort_order)",
+];
+
+/// The todo list's index: its filter (deleted, status), then its ORDER BY, so
+/// one status column comes back without sorting. The two databases spell
+/// "undated last" differently, hence one per dialect; keep each in step with
+/// the list query of its repository.
+const SQLITE_LIST_INDEX: &str = "CREATE INDEX IF NOT EXISTS idx_todos_list ON todos(deleted, status, \
+    pinned DESC, priority DESC, COALESCE(due_at, '9999-12-31T00:00:00Z'), rank, created_at)";
+#[cfg(feature = "postgres")]
+const PG_LIST_INDEX: &str = "CREATE INDEX IF NOT EXISTS idx_todos_list ON todos(deleted, status, \
+    pinned DESC, priority DESC, due_at ASC NULLS LAST, rank, created_at)";
+
+/// Indexes idx_todos_list replaced; sort_order is no longer what the list sorts by.
+const DROPPED_INDEXES: [&str; 2] = [
+    "DROP INDEX IF EXISTS idx_todos_status_deleted",
+    "DROP INDEX IF EXISTS idx_todos_sort",
+];
+
+/**
+ * Integrity check init_pool runs before touching the schema
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IntegrityCheck {
+    Off,
+    #[default]
+    Quick, // PRAGMA quick_check: structure only, seconds even on a Pi
+    Full, // PRAGMA integrity_check: also verifies indexes, much slower
+}
+
+impl IntegrityCheck {
+    /// "off", "quick" or "full" (the integrity_check setting).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(Self::Off),
+            "quick" => Some(Self::Quick),
+            "full" => Some(Self::Full),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Quick => "quick",
+            Self::Full => "full",
+        }
+    }
+}
+
+/**
+ * The integrity check found problems (returned by init_pool)
+ */
+#[derive(Debug, thiserror::Error)]
+#[error("database integrity check failed: {}", .problems.join("; "))]
+pub struct DatabaseCorrupt {
+    pub problems: Vec<String>,
+}
+
+/// First bytes of every unencrypted SQLite database file.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/**
+ * Passphrase of an encrypted (SQLCipher) database
+ *
+ * From DATABASE_KEY or DATABASE_KEY_FILE; a build with the `sqlcipher`
+ * feature asks on the terminal when the database file is encrypted and
+ * neither is set. The same key opens LOCAL_DATABASE_URL with Postgres.
+ */
+#[derive(Clone)]
+pub struct DatabaseKey(String);
+
+impl std::fmt::Debug for DatabaseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DatabaseKey(***)") // Never print the key
+    }
+}
+
+impl DatabaseKey {
+    pub fn new(passphrase: impl Into<String>) -> Result<Self> {
+        let passphrase = passphrase.into();
+        if passphrase.is_empty() {
+            anyhow::bail!("database key must not be empty");
+        }
+        if !cfg!(feature = "sqlcipher") {
+            anyhow::bail!("a database key is set, but this build lacks the `sqlcipher` feature");
+        }
+        Ok(Self(passphrase))
+    }
+
+    /// Returns None when neither DATABASE_KEY nor DATABASE_KEY_FILE is set.
+    pub fn from_env() -> Result<Option<Self>> {
+        if let Ok(key) = std::env::var("DATABASE_KEY") {
+            return Self::new(key).map(Some);
+        }
+        let Ok(path) = std::env::var("DATABASE_KEY_FILE") else {
+            return Ok(None);
+        };
+        let key = std::fs::read_to_string(&path)
+            .with_context(|| format!("cannot read DATABASE_KEY_FILE {path}"))?;
+        Self::new(key.trim_end_matches(['\r', '\n'])).map(Some)
+    }
+
+    /// Ask on the terminal (input hidden).
+    pub fn prompt(question: &str) -> Result<Self> {
+        use std::io::IsTerminal;
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!("{question} needed, but there is no terminal to ask on");
+        }
+        #[cfg(feature = "sqlcipher")]
+        return Self::new(rpassword::prompt_password(format!("{question}: "))?);
+        #[cfg(not(feature = "sqlcipher"))]
+        anyhow::bail!("{question} needed, but this build lacks the `sqlcipher` feature");
+    }
+
+    /**
+     * The key for the database at `database_url`
+     *
+     * The environment wins; otherwise an encrypted file is asked for on the
+     * terminal (`sqlcipher` builds) and an unencrypted one needs no key.
+     */
+    pub fn resolve(database_url: &str) -> Result<Option<Self>> {
+        if let Some(key) = Self::from_env()? {
+            return Ok(Some(key));
+        }
+        let options = SqliteConnectOptions::from_str(database_url)?;
+        let path = options.get_filename();
+        // Without SQLCipher a bad header is damage, reported when opening
+        if cfg!(feature = "sqlcipher") && is_encrypted_file(path) {
+            return Self::prompt(&format!("Key for {}", path.display())).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// The key `todo-server rekey` switches to: DATABASE_NEW_KEY, else asked
+    /// twice on the terminal.
+    pub fn replacement() -> Result<Self> {
+        if let Ok(key) = std::env::var("DATABASE_NEW_KEY") {
+            return Self::new(key);
+        }
+        let key = Self::prompt("New database key")?;
+        if Self::prompt("Repeat the new key")?.0 != key.0 {
+            anyhow::bail!("the keys do not match");
+        }
+        Ok(key)
+    }
+
+    /// The key as a PRAGMA value (a quoted passphrase).
+    fn pragma(&self) -> String {
+        format!("'{}'", self.0.replace('\'', "''"))
+    }
+}
+
+/// Whether `path` holds a database that does not start with the SQLite
+/// header, i.e. one encrypted by SQLCipher. Missing or empty files are not.
+pub fn is_encrypted_file(path: &std::path::Path) -> bool {
+    use std::io::Read;
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|()| &header != SQLITE_HEADER)
+}
+
+/// PRAGMA rekey: encrypt the open database with `key` from now on.
+///
+/// `pool` must be the only pool on the file and hold a single connection;
+/// other connections keep using the old key and fail.
+pub async fn rekey(pool: &SqlitePool, key: &DatabaseKey) -> Result<()> {
+    sqlx::query(&format!("PRAGMA rekey = {}", key.pragma()))
+        .execute(pool)
+        .await
+        .context("rekey failed")?;
+    Ok(())
+}
+
+/// Write an encrypted copy of the (unencrypted) database to `out`.
+pub async fn export_encrypted(
+    pool: &SqlitePool,
+    out: &std::path::Path,
+    key: &DatabaseKey,
+) -> Result<()> {
+    if out.exists() {
+        anyhow::bail!("{} already exists", out.display());
+    }
+    let target = out
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("output path must be valid UTF-8"))?;
+    // ATTACH and the export must run on the same connection
+    let mut conn = pool.acquire().await?;
+    sqlx::query(&format!(
+        "ATTACH DATABASE ?1 AS encrypted KEY {}",
+        key.pragma()
+    ))
+    .bind(target)
+    .execute(&mut *conn)
+    .await?;
+    let exported = sqlx::query("SELECT sqlcipher_export('encrypted')")
+        .execute(&mut *conn)
+        .await;
+    sqlx::query("DETACH DATABASE encrypted")
+        .execute(&mut *conn)
         .await?;
+    exported.with_context(|| format!("export to {} failed", out.display()))?;
+    Ok(())
+}
+
+/// SQLITE_BUSY or SQLITE_LOCKED (including their extended codes).
+pub fn is_busy(e: &sqlx::Error) -> bool {
+    sqlite_code(e).is_some_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+/// SQLITE_READONLY: the database was opened read-only (see open_read_only).
+pub fn is_read_only(e: &sqlx::Error) -> bool {
+    sqlite_code(e).is_some_and(|code| code & 0xff == 8)
+}
+
+/// SQLITE_CORRUPT or SQLITE_NOTADB: damaged beyond what the check can report.
+fn is_corrupt(e: &sqlx::Error) -> bool {
+    sqlite_code(e).is_some_and(|code| matches!(code & 0xff, 11 | 26))
+}
+
+fn sqlite_code(e: &sqlx::Error) -> Option<i32> {
+    e.as_database_error()
+        .and_then(|d| d.code())
+        .and_then(|code| code.parse::<i32>().ok())
+}
+
+/// Problems reported by the check, at most 100; empty when the database is fine.
+pub async fn integrity_problems(pool: &SqlitePool, check: IntegrityCheck) -> Result<Vec<String>> {
+    let pragma = match check {
+        IntegrityCheck::Off => return Ok(Vec::new()),
+        IntegrityCheck::Quick => "PRAGMA quick_check(100)",
+        IntegrityCheck::Full => "PRAGMA integrity_check(100)",
+    };
+    match sqlx::query_scalar::<_, String>(pragma)
+        .fetch_all(pool)
+        .await
+    {
+        Ok(rows) => Ok(rows.into_iter().filter(|row| row != "ok").collect()),
+        Err(e) if is_corrupt(&e) => Ok(vec![e.to_string()]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/**
+ * Run a write, retrying with backoff while SQLite reports the database busy
+ *
+ * `op` must be safe to repeat: a whole transaction, or a single statement.
+ * A write still busy after the last retry is returned as is (a 503 with
+ * Retry-After, see ApiError).
+ */
+pub async fn retry_busy<T, F, Fut>(mut op: F) -> ApiResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ApiResult<T>>,
+{
+    for delay in BUSY_RETRY_DELAYS {
+        match op().await {
+            Err(ApiError::Sqlx(e)) if is_busy(&e) => {
+                tracing::debug!(error = %e, ?delay, "database busy, retrying write");
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+    op().await
+}
+
+pub async fn init_pool(database_url: &str) -> Result<SqlitePool> {
+    init_pool_with_size(database_url, 5).await
+}
+
+/// Same as init_pool with a custom connection limit.
+pub async fn init_pool_with_size(database_url: &str, max_connections: u32) -> Result<SqlitePool> {
+    init_pool_with_options(
+        database_url,
+        max_connections,
+        false,
+        IntegrityCheck::default(),
+        None,
+    )
+    .await
+}
+
+/**
+ * Same as init_pool_with_size, optionally tuned for SD cards
+ *
+ * `low_write` trades durability of the last commits on power loss for
+ * fewer flash writes: synchronous=NORMAL (no fsync per commit in WAL
+ * mode) and a large auto-checkpoint threshold, so changes collect in the
+ * WAL and reach the database file in batches (see spawn_wal_checkpoints).
+ *
+ * `integrity` runs before any schema change; on problems the pool is
+ * closed and a DatabaseCorrupt error returned (callers may fall back to
+ * open_read_only).
+ */
+pub async fn init_pool_with_options(
+    database_url: &str,
+    max_connections: u32,
+    low_write: bool,
+    integrity: IntegrityCheck,
+    key: Option<&DatabaseKey>,
+) -> Result<SqlitePool> {
+    let mut options = SqliteConnectOptions::from_str(database_url)?.busy_timeout(BUSY_TIMEOUT);
+    if let Some(key) = key {
+        options = options.pragma("key", key.pragma());
+    }
+    if low_write {
+        options = options.synchronous(SqliteSynchronous::Normal).pragma(
+            "wal_autocheckpoint",
+            LOW_WRITE_AUTOCHECKPOINT_PAGES.to_string(),
+        );
+    }
+    let pool = match SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(options)
+        .await
+    {
+        Err(e) if integrity != IntegrityCheck::Off && is_corrupt(&e) => {
+            let problems = vec![e.to_string()];
+            return Err(DatabaseCorrupt { problems }.into());
+        }
+        result => result?,
+    };
+
+    // SQLCipher only notices a wrong key on the first read; say so instead
+    // of reporting a corrupt database
+    if key.is_some()
+        && let Err(e) = sqlx::query("SELECT count(*) FROM sqlite_master")
+            .execute(&pool)
+            .await
+        && sqlite_code(&e).is_some_and(|code| code & 0xff == 26)
+    {
+        pool.close().await;
+        anyhow::bail!("cannot open the database: wrong key, or the file is not encrypted");
+    }
+
+    let problems = integrity_problems(&pool, integrity).await?;
+    if !problems.is_empty() {
+        pool.close().await;
+        return Err(DatabaseCorrupt { problems }.into());
+    }
 
     // Create categories table first (referenced by todos)
     sqlx::query(
@@ -50,18 +451,531 @@ pub async fn init_pool(database_url: &str) -> Result<SqlitePool> {
     .execute(&pool)
     .await?;
 
-    // Check if category_id column exists in todos table (for existing databases)
-    let column_exists = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM pragma_table_info('todos') WHERE name='category_id'",
+    // Household members (notification recipients)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS users (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            email TEXT,
+            email_reminders INTEGER NOT NULL DEFAULT 0,
+            email_digest INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            deleted INTEGER NOT NULL DEFAULT 0
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Small server-wide settings stored as JSON documents (kiosk rotation, ...)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+    "#,
     )
-    .fetch_one(&pool)
+    .execute(&pool)
     .await?;
 
-    // Add category_id column if it doesn't exist (migration for existing data)
-    if column_exists == 0 {
-        sqlx::query("ALTER TABLE todos ADD COLUMN category_id TEXT")
-            .execute(&pool)
-            .await?;
+    // Tokens for the inbound webhook endpoint (quick capture from other services)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS inbound_tokens (
+            id TEXT PRIMARY KEY,
+            token TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            category_id TEXT,
+            tags TEXT,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (category_id) REFERENCES categories(id)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Users allowed to change a category's todos (see members.rs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS category_members (
+            category_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            role TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (category_id, user_id)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Per-user notifications, e.g. @mentions (see notifications.rs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notifications (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            todo_id TEXT,
+            actor TEXT NOT NULL,
+            title TEXT NOT NULL,
+            message TEXT NOT NULL,
+            read_at TEXT,
+            created_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_notifications_user ON notifications(user_id, created_at)",
+    )
+    .execute(&pool)
+    .await?;
+
+    // Signed-in devices; tokens are stored as SHA-256 hashes (see sessions.rs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sessions (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            access_hash TEXT NOT NULL UNIQUE,
+            refresh_hash TEXT NOT NULL UNIQUE,
+            prev_refresh_hash TEXT,
+            user_agent TEXT,
+            ip TEXT,
+            created_at TEXT NOT NULL,
+            last_seen_at TEXT NOT NULL,
+            access_expires_at TEXT NOT NULL,
+            refresh_expires_at TEXT NOT NULL,
+            revoked_at TEXT
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions(user_id)")
+        .execute(&pool)
+        .await?;
+
+    // OpenID Connect accounts linked to members (see oidc.rs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS user_identities (
+            issuer TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (issuer, subject)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Per-user preferences, JSON values by key (see settings.rs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS user_settings (
+            user_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (user_id, key)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Secret read-only links to a filtered todo list (see shares.rs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS shares (
+            id TEXT PRIMARY KEY,
+            token TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            project_id TEXT,
+            category_id TEXT,
+            filter TEXT,
+            expires_at TEXT,
+            created_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Outgoing webhooks with server-side event filters
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhooks (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            url TEXT NOT NULL,
+            user_id TEXT,
+            event_types TEXT,
+            category_ids TEXT,
+            min_priority INTEGER,
+            assignee_id TEXT,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Bulk imports: one job row plus its source rows with pre-assigned todo ids
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS imports (
+            id TEXT PRIMARY KEY,
+            source TEXT NOT NULL,
+            status TEXT NOT NULL,
+            total INTEGER NOT NULL,
+            processed INTEGER NOT NULL DEFAULT 0,
+            failed INTEGER NOT NULL DEFAULT 0,
+            error TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS import_rows (
+            import_id TEXT NOT NULL,
+            idx INTEGER NOT NULL,
+            todo_id TEXT NOT NULL,
+            item TEXT NOT NULL,
+            done INTEGER NOT NULL DEFAULT 0,
+            error TEXT,
+            PRIMARY KEY (import_id, idx),
+            FOREIGN KEY (import_id) REFERENCES imports(id) ON DELETE CASCADE
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Who changed what and when (todos and categories)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            entity_id TEXT NOT NULL,
+            action TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            changes TEXT NOT NULL,
+            before TEXT,
+            after TEXT,
+            version INTEGER,
+            created_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    add_column_if_missing(&pool, "audit_log", "batch", "TEXT").await?;
+    add_column_if_missing(&pool, "audit_log", "undone_at", "TEXT").await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_entity ON audit_log(entity, entity_id)")
+        .execute(&pool)
+        .await?;
+
+    // "blocker_id blocks blocked_id" dependencies between todos
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS todo_links (
+            blocker_id TEXT NOT NULL,
+            blocked_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (blocker_id, blocked_id)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_todo_links_blocked ON todo_links(blocked_id)")
+        .execute(&pool)
+        .await?;
+
+    // Checklist entries inside todos; progress is mirrored onto the todo row
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS checklist_items (
+            id TEXT PRIMARY KEY,
+            todo_id TEXT NOT NULL,
+            text TEXT NOT NULL,
+            done INTEGER NOT NULL DEFAULT 0,
+            sort_order INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_checklist_todo ON checklist_items(todo_id)")
+        .execute(&pool)
+        .await?;
+
+    // Time tracked on todos (start/stop timer)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS time_entries (
+            id TEXT PRIMARY KEY,
+            todo_id TEXT NOT NULL,
+            started_at TEXT NOT NULL,
+            ended_at TEXT,
+            actor TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_time_entries_todo ON time_entries(todo_id)")
+        .execute(&pool)
+        .await?;
+
+    // Pomodoro sessions (work + break) bound to a todo
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS pomodoro_sessions (
+            id TEXT PRIMARY KEY,
+            todo_id TEXT NOT NULL,
+            work_secs INTEGER NOT NULL,
+            break_secs INTEGER NOT NULL,
+            started_at TEXT NOT NULL,
+            status TEXT NOT NULL,
+            finished_at TEXT,
+            actor TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_pomodoro_sessions_todo ON pomodoro_sessions(todo_id)",
+    )
+    .execute(&pool)
+    .await?;
+
+    // Files attached to todos; the bytes are stored on disk
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS attachments (
+            id TEXT PRIMARY KEY,
+            todo_id TEXT NOT NULL,
+            filename TEXT NOT NULL,
+            content_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            created_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_attachments_todo ON attachments(todo_id)")
+        .execute(&pool)
+        .await?;
+
+    // Comments on todos, shown in the activity feed
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS todo_comments (
+            id TEXT PRIMARY KEY,
+            todo_id TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            body TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_todo_comments_todo ON todo_comments(todo_id)")
+        .execute(&pool)
+        .await?;
+
+    // Projects (independent boards); todos and categories refer to them by id
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS projects (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            color TEXT,
+            sort_order INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Workflow statuses (board columns); seeded once with the classic four
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS statuses (
+            name TEXT PRIMARY KEY,
+            color TEXT,
+            sort_order INTEGER NOT NULL DEFAULT 0,
+            is_done INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO statuses (name, sort_order, is_done, created_at, updated_at)
+        SELECT column1, column2, column3, ?1, ?1
+        FROM (VALUES ('todo', 0, 0), ('doing', 1, 0), ('done', 2, 1), ('archived', 3, 1))
+        WHERE NOT EXISTS (SELECT 1 FROM statuses)
+    "#,
+    )
+    .bind(chrono::Utc::now())
+    .execute(&pool)
+    .await?;
+
+    // Idempotency-Key -> stored response (response NULL while the request runs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            key TEXT PRIMARY KEY,
+            scope TEXT NOT NULL,
+            request TEXT NOT NULL,
+            status INTEGER,
+            response TEXT,
+            created_at TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created ON idempotency_keys(created_at)",
+    )
+    .execute(&pool)
+    .await?;
+
+    // Delivered reminders, so each due-soon/overdue alert is sent only once
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS reminder_log (
+            todo_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            due_at TEXT NOT NULL,
+            sent_at TEXT NOT NULL,
+            PRIMARY KEY (todo_id, kind, due_at)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Applied overdue escalation rules, so each fires once per due date
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS escalation_log (
+            todo_id TEXT NOT NULL,
+            rule INTEGER NOT NULL,
+            due_at TEXT NOT NULL,
+            applied_at TEXT NOT NULL,
+            PRIMARY KEY (todo_id, rule, due_at)
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // WebSocket events stored with the change they describe (see outbox.rs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS outbox (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            message TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            published_at TEXT
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_outbox_pending ON outbox(published_at, seq)")
+        .execute(&pool)
+        .await?;
+
+    // Columns added after the first release (migrations for existing data)
+    add_column_if_missing(&pool, "todos", "category_id", "TEXT").await?;
+    add_column_if_missing(&pool, "todos", "latitude", "REAL").await?;
+    add_column_if_missing(&pool, "todos", "longitude", "REAL").await?;
+    add_column_if_missing(&pool, "todos", "location_name", "TEXT").await?;
+    add_column_if_missing(&pool, "todos", "version", "INTEGER NOT NULL DEFAULT 1").await?;
+    add_column_if_missing(
+        &pool,
+        "todos",
+        "checklist_done",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+    .await?;
+    add_column_if_missing(
+        &pool,
+        "todos",
+        "checklist_total",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+    .await?;
+    add_column_if_missing(&pool, "todos", "completed_at", "TEXT").await?;
+    add_column_if_missing(&pool, "todos", "project_id", "TEXT").await?;
+    add_column_if_missing(&pool, "categories", "project_id", "TEXT").await?;
+    add_column_if_missing(&pool, "todos", "snooze_count", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(&pool, "todos", "start_at", "TEXT").await?;
+    add_column_if_missing(&pool, "todos", "pinned", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(&pool, "todos", "timer_started_at", "TEXT").await?;
+    add_column_if_missing(&pool, "todos", "tracked_secs", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(&pool, "todos", "estimate_minutes", "INTEGER").await?;
+    add_column_if_missing(&pool, "categories", "parent_id", "TEXT").await?;
+    add_column_if_missing(&pool, "todos", "assignee_id", "TEXT").await?;
+    // Fractional ranks start out as the integer positions
+    add_column_if_missing(&pool, "todos", "rank", "REAL").await?;
+    sqlx::query("UPDATE todos SET rank = sort_order WHERE rank IS NULL")
+        .execute(&pool)
+        .await?;
+    add_column_if_missing(
+        &pool,
+        "categories",
+        "is_default",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+    .await?;
+    add_column_if_missing(
+        &pool,
+        "categories",
+        "archived",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+    .await?;
+    for ddl in INDEXES
+        .into_iter()
+        .chain([SQLITE_LIST_INDEX])
+        .chain(DROPPED_INDEXES)
+    {
+        sqlx::query(ddl).execute(&pool).await?;
     }
 
     // Insert default categories if none exist
@@ -71,15 +985,7 @@ pub async fn init_pool(database_url: &str) -> Result<SqlitePool> {
             .await?;
 
     if category_count == 0 {
-        let default_categories = vec![
-            ("General", "#6B7280", "General tasks and items"),
-            ("Work", "#3B82F6", "Work-related tasks"),
-            ("Personal", "#EF4444", "Personal tasks and reminders"),
-            ("Shopping", "#10B981", "Shopping lists and items"),
-            ("Health", "#F59E0B", "Health and fitness related"),
-        ];
-
-        for (name, color, description) in default_categories {
+        for (name, color, description) in DEFAULT_CATEGORIES {
             let id = uuid::Uuid::new_v4().to_string();
             let now = chrono::Utc::now().to_rfc3339();
             sqlx::query(
@@ -105,3 +1011,226 @@ pub async fn init_pool(database_url: &str) -> Result<SqlitePool> {
 
     Ok(pool)
 }
+
+/**
+ * Open an existing database without writing to it
+ *
+ * For serving a database that failed its integrity check: no migrations
+ * run and every write fails with SQLITE_READONLY (a 503, see ApiError).
+ */
+pub async fn open_read_only(
+    database_url: &str,
+    max_connections: u32,
+    key: Option<&DatabaseKey>,
+) -> Result<SqlitePool> {
+    let mut options = SqliteConnectOptions::from_str(database_url)?
+        .busy_timeout(BUSY_TIMEOUT)
+        .read_only(true);
+    if let Some(key) = key {
+        options = options.pragma("key", key.pragma());
+    }
+    Ok(SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(options)
+        .await?)
+}
+
+/**
+ * Checkpoint and truncate the WAL every `every`
+ *
+ * Keeps the -wal file from growing between SQLite's own checkpoints and
+ * writes the accumulated pages to the database file in one go.
+ */
+pub fn spawn_wal_checkpoints(pool: SqlitePool, every: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.tick().await; // The first tick is immediate
+        loop {
+            interval.tick().await;
+            match sqlx::query_as::<_, (i64, i64, i64)>("PRAGMA wal_checkpoint(TRUNCATE)")
+                .fetch_one(&pool)
+                .await
+            {
+                // busy = 1: readers kept the checkpoint from finishing; retried next time
+                Ok((busy, log, checkpointed)) => {
+                    tracing::debug!(busy, log, checkpointed, "WAL checkpoint")
+                }
+                Err(e) => tracing::warn!(error = %e, "WAL checkpoint failed"),
+            }
+        }
+    });
+}
+
+/// Add a column to an existing table unless it is already there.
+async fn add_column_if_missing(
+    pool: &SqlitePool,
+    table: &str,
+    column: &str,
+    decl: &str,
+) -> Result<()> {
+    let column_exists =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name=?2")
+            .bind(table)
+            .bind(column)
+            .fetch_one(pool)
+            .await?;
+
+    if column_exists == 0 {
+        sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Load a JSON setting; None when it was never stored.
+pub async fn get_setting<T: serde::de::DeserializeOwned>(
+    pool: &SqlitePool,
+    key: &str,
+) -> Result<Option<T>> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key=?1")
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+    Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+}
+
+/// Store (insert or replace) a JSON setting.
+pub async fn put_setting<T: serde::Serialize>(
+    pool: &SqlitePool,
+    key: &str,
+    value: &T,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET value=excluded.value, updated_at=excluded.updated_at",
+    )
+    .bind(key)
+    .bind(serde_json::to_string(value)?)
+    .bind(chrono::Utc::now())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/**
+ * Connect to Postgres and create the todo/category schema
+ *
+ * Type differences from SQLite: timestamps are TIMESTAMPTZ, integers BIGINT,
+ * coordinates DOUBLE PRECISION and `deleted` a real BOOLEAN (the
+ * repository converts it to the models' 0/1).
+ */
+#[cfg(feature = "postgres")]
+pub async fn init_pg_pool(database_url: &str) -> Result<sqlx::PgPool> {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(10)
+        .connect(database_url)
+        .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS categories (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            color TEXT,
+            description TEXT,
+            sort_order BIGINT NOT NULL DEFAULT 0,
+            created_at TIMESTAMPTZ NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL,
+            deleted BOOLEAN NOT NULL DEFAULT FALSE
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS todos (
+            id TEXT PRIMARY KEY,
+            title TEXT NOT NULL,
+            note TEXT,
+            status TEXT NOT NULL,
+            priority BIGINT NOT NULL,
+            due_at TIMESTAMPTZ,
+            tags TEXT,
+            category_id TEXT REFERENCES categories(id),
+            sort_order BIGINT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL,
+            deleted BOOLEAN NOT NULL DEFAULT FALSE
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS outbox (
+            seq BIGSERIAL PRIMARY KEY,
+            message TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            published_at TIMESTAMPTZ
+        )
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Columns added after the first release; Postgres supports IF NOT EXISTS
+    for ddl in [
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS latitude DOUBLE PRECISION",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS longitude DOUBLE PRECISION",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS location_name TEXT",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS checklist_done BIGINT NOT NULL DEFAULT 0",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS checklist_total BIGINT NOT NULL DEFAULT 0",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS completed_at TIMESTAMPTZ",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS project_id TEXT",
+        "ALTER TABLE categories ADD COLUMN IF NOT EXISTS project_id TEXT",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS snooze_count BIGINT NOT NULL DEFAULT 0",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS start_at TIMESTAMPTZ",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS pinned BOOLEAN NOT NULL DEFAULT FALSE",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS timer_started_at TIMESTAMPTZ",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS tracked_secs BIGINT NOT NULL DEFAULT 0",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS estimate_minutes BIGINT",
+        "ALTER TABLE categories ADD COLUMN IF NOT EXISTS parent_id TEXT",
+        "ALTER TABLE categories ADD COLUMN IF NOT EXISTS is_default BOOLEAN NOT NULL DEFAULT FALSE",
+        "ALTER TABLE categories ADD COLUMN IF NOT EXISTS archived BOOLEAN NOT NULL DEFAULT FALSE",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS rank DOUBLE PRECISION",
+        "UPDATE todos SET rank = sort_order WHERE rank IS NULL",
+        "ALTER TABLE todos ADD COLUMN IF NOT EXISTS assignee_id TEXT",
+    ] {
+        sqlx::query(ddl).execute(&pool).await?;
+    }
+    for ddl in INDEXES
+        .into_iter()
+        .chain([PG_LIST_INDEX])
+        .chain(DROPPED_INDEXES)
+    {
+        sqlx::query(ddl).execute(&pool).await?;
+    }
+
+    let category_count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM categories WHERE NOT deleted")
+            .fetch_one(&pool)
+            .await?;
+    if category_count == 0 {
+        for (name, color, description) in DEFAULT_CATEGORIES {
+            let now = chrono::Utc::now();
+            sqlx::query(
+                "INSERT INTO categories (id, name, color, description, sort_order, created_at, updated_at, deleted) VALUES ($1, $2, $3, $4, 0, $5, $5, FALSE)",
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(name)
+            .bind(color)
+            .bind(description)
+            .bind(now)
+            .execute(&pool)
+            .await?;
+        }
+    }
+
+    Ok(pool)
+}
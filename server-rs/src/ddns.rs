@@ -0,0 +1,216 @@
+/**
+ * Dynamic DNS updater
+ *
+ * Optional background task that keeps a DuckDNS or Cloudflare record
+ * pointing at the Pi's current public IP - replacing the usual cron script.
+ *
+ * The public IP is looked up every DDNS_INTERVAL_SECS and the provider is
+ * only contacted when it changes (or on the first check after startup).
+ * The latest outcome is kept in DdnsStatus and shown in /api/admin/overview.
+ */
+use std::{
+    env,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+
+/**
+ * Supported DNS providers
+ */
+#[derive(Debug, Clone)]
+pub enum DdnsProvider {
+    DuckDns,
+    Cloudflare { zone_id: String },
+}
+
+/**
+ * DDNS settings read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct DdnsConfig {
+    pub provider: DdnsProvider, // DDNS_PROVIDER: duckdns or cloudflare
+    pub domain: String,         // DDNS_DOMAIN (DuckDNS subdomain or full record name)
+    pub token: String,          // DDNS_TOKEN (DuckDNS token / Cloudflare API token)
+    pub interval: Duration,     // DDNS_INTERVAL_SECS, default 300
+    pub ip_url: String,         // DDNS_IP_URL - plain-text "what is my IP" service
+}
+
+impl DdnsConfig {
+    /// Returns None when DDNS is not configured; errors on incomplete settings.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(provider) = env::var("DDNS_PROVIDER") else {
+            return Ok(None);
+        };
+        let provider = match provider.as_str() {
+            "duckdns" => DdnsProvider::DuckDns,
+            "cloudflare" => DdnsProvider::Cloudflare {
+                zone_id: env::var("DDNS_ZONE_ID")
+                    .context("DDNS_PROVIDER=cloudflare requires DDNS_ZONE_ID")?,
+            },
+            other => return Err(anyhow!("unsupported DDNS_PROVIDER `{other}`")),
+        };
+        Ok(Some(Self {
+            provider,
+            domain: env::var("DDNS_DOMAIN").context("DDNS_PROVIDER requires DDNS_DOMAIN")?,
+            token: env::var("DDNS_TOKEN").context("DDNS_PROVIDER requires DDNS_TOKEN")?,
+            interval: Duration::from_secs(
+                env::var("DDNS_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(300),
+            ),
+            ip_url: env::var("DDNS_IP_URL").unwrap_or_else(|_| "https://api.ipify.org".into()),
+        }))
+    }
+}
+
+/**
+ * Last known state of the updater (serialized into the admin overview)
+ */
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DdnsStatus {
+    pub provider: String,
+    pub domain: String,
+    pub current_ip: Option<String>, // Last IP successfully published
+    pub last_checked_at: Option<DateTime<Utc>>, // Last public IP lookup
+    pub last_updated_at: Option<DateTime<Utc>>, // Last successful provider update
+    pub last_error: Option<String>, // Most recent failure, cleared on success
+}
+
+/**
+ * Background updater - owns the HTTP client and shared status
+ */
+pub struct DdnsUpdater {
+    config: DdnsConfig,
+    client: reqwest::Client,
+    status: RwLock<DdnsStatus>,
+}
+
+impl DdnsUpdater {
+    pub fn new(config: DdnsConfig) -> Self {
+        let provider = match config.provider {
+            DdnsProvider::DuckDns => "duckdns",
+            DdnsProvider::Cloudflare { .. } => "cloudflare",
+        };
+        let status = DdnsStatus {
+            provider: provider.into(),
+            domain: config.domain.clone(),
+            ..Default::default()
+        };
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            status: RwLock::new(status),
+        }
+    }
+
+    pub fn status(&self) -> DdnsStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Check the public IP forever, publishing it whenever it changes.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.config.interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.check_once().await {
+                tracing::warn!(error = %e, "dynamic DNS update failed");
+                self.status.write().unwrap().last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    async fn check_once(&self) -> anyhow::Result<()> {
+        let ip = self.public_ip().await?;
+        let previous = {
+            let mut status = self.status.write().unwrap();
+            status.last_checked_at = Some(Utc::now());
+            status.current_ip.clone()
+        };
+        if previous.as_deref() == Some(ip.as_str()) {
+            return Ok(());
+        }
+
+        match &self.config.provider {
+            DdnsProvider::DuckDns => self.update_duckdns(&ip).await?,
+            DdnsProvider::Cloudflare { zone_id } => self.update_cloudflare(zone_id, &ip).await?,
+        }
+        tracing::info!(domain = %self.config.domain, %ip, "dynamic DNS record updated");
+
+        let mut status = self.status.write().unwrap();
+        status.current_ip = Some(ip);
+        status.last_updated_at = Some(Utc::now());
+        status.last_error = None;
+        Ok(())
+    }
+
+    async fn public_ip(&self) -> anyhow::Result<String> {
+        let ip = self
+            .client
+            .get(&self.config.ip_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let ip = ip.trim().to_string();
+        ip.parse::<std::net::IpAddr>()
+            .with_context(|| format!("IP lookup returned `{ip}`"))?;
+        Ok(ip)
+    }
+
+    async fn update_duckdns(&self, ip: &str) -> anyhow::Result<()> {
+        let body = self
+            .client
+            .get("https://www.duckdns.org/update")
+            .query(&[
+                ("domains", self.config.domain.as_str()),
+                ("token", self.config.token.as_str()),
+                ("ip", ip),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        // DuckDNS answers 200 with "OK" or "KO"
+        if body.trim() != "OK" {
+            return Err(anyhow!("DuckDNS rejected the update ({})", body.trim()));
+        }
+        Ok(())
+    }
+
+    async fn update_cloudflare(&self, zone_id: &str, ip: &str) -> anyhow::Result<()> {
+        let base = format!("https://api.cloudflare.com/client/v4/zones/{zone_id}/dns_records");
+        let record_type = if ip.contains(':') { "AAAA" } else { "A" };
+
+        // Look up the record id by name
+        let lookup: serde_json::Value = self
+            .client
+            .get(&base)
+            .bearer_auth(&self.config.token)
+            .query(&[("type", record_type), ("name", self.config.domain.as_str())])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let record_id = lookup["result"][0]["id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Cloudflare record {} not found", self.config.domain))?;
+
+        self.client
+            .patch(format!("{base}/{record_id}"))
+            .bearer_auth(&self.config.token)
+            .json(&json!({"type": record_type, "name": self.config.domain, "content": ip}))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
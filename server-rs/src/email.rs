@@ -0,0 +1,259 @@
+/**
+ * E-mail notifications and daily digest (SMTP via lettre)
+ *
+ * - EmailNotifier plugs into the reminder scheduler like any other
+ *   Notifier and mails every user with `email_reminders = 1`.
+ * - The digest task mails users with `email_digest = 1` a summary of
+ *   overdue, today's and upcoming todos once a day at DIGEST_HOUR in
+ *   their time zone (`timezone` setting, else the household zone).
+ */
+use std::{env, sync::Arc};
+
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Days, Duration, DurationRound, Timelike, Utc};
+use chrono_tz::Tz;
+use futures::future::BoxFuture;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+};
+
+use crate::{
+    db::SqlitePool,
+    model::Todo,
+    notify::{Notification, Notifier},
+    services::TodoService,
+    timezone,
+};
+
+/**
+ * SMTP settings read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    pub host: String,             // SMTP_HOST - enables e-mail when set
+    pub port: Option<u16>,        // SMTP_PORT, defaults per security mode
+    pub security: String,         // SMTP_SECURITY: starttls (default), tls or none
+    pub username: Option<String>, // SMTP_USERNAME
+    pub password: Option<String>, // SMTP_PASSWORD
+    pub from: String,             // SMTP_FROM, e.g. "Todo <todo@example.com>"
+    pub digest_hour: Option<u32>, // DIGEST_HOUR (0-23), digest disabled when unset
+}
+
+impl EmailConfig {
+    /// Returns None when SMTP_HOST is not set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(host) = env::var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let digest_hour = match env::var("DIGEST_HOUR") {
+            Ok(h) => {
+                let h: u32 = h.parse().context("DIGEST_HOUR must be a number")?;
+                if h > 23 {
+                    return Err(anyhow!("DIGEST_HOUR must be between 0 and 23"));
+                }
+                Some(h)
+            }
+            Err(_) => None,
+        };
+        Ok(Some(Self {
+            host,
+            port: env::var("SMTP_PORT").ok().and_then(|s| s.parse().ok()),
+            security: env::var("SMTP_SECURITY").unwrap_or_else(|_| "starttls".into()),
+            username: env::var("SMTP_USERNAME").ok(),
+            password: env::var("SMTP_PASSWORD").ok(),
+            from: env::var("SMTP_FROM").context("SMTP_HOST requires SMTP_FROM")?,
+            digest_hour,
+        }))
+    }
+}
+
+/**
+ * Thin wrapper around the lettre transport
+ */
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+}
+
+impl Mailer {
+    pub fn new(config: &EmailConfig) -> anyhow::Result<Self> {
+        let mut builder = match config.security.as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            other => return Err(anyhow!("unsupported SMTP_SECURITY `{other}`")),
+        };
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(user.clone(), pass.clone()));
+        }
+        // Validate the sender address once at startup
+        config
+            .from
+            .parse::<lettre::message::Mailbox>()
+            .context("SMTP_FROM is not a valid address")?;
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.clone(),
+        })
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.parse()?)
+            .to(to.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// (user id, address) of active users that opted into the given mail type.
+async fn recipients(pool: &SqlitePool, column: &str) -> anyhow::Result<Vec<(String, String)>> {
+    // `column` is one of our own constants, never user input. Members who
+    // turned e-mail off in their notification_channels setting are skipped.
+    let sql = format!(
+        r#"
+        SELECT id, email FROM users WHERE deleted = 0 AND {column} = 1 AND email IS NOT NULL AND email != ''
+        AND NOT EXISTS (
+            SELECT 1 FROM user_settings s WHERE s.user_id = users.id AND s.key = 'notification_channels'
+            AND NOT EXISTS (SELECT 1 FROM json_each(s.value) WHERE json_each.value = 'email')
+        )
+    "#
+    );
+    Ok(sqlx::query_as(&sql).fetch_all(pool).await?)
+}
+
+/**
+ * Reminder channel delivering to every opted-in user
+ */
+pub struct EmailNotifier {
+    mailer: Arc<Mailer>,
+    pool: SqlitePool,
+}
+
+impl EmailNotifier {
+    pub fn new(mailer: Arc<Mailer>, pool: SqlitePool) -> Self {
+        Self { mailer, pool }
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            for (_, to) in recipients(&self.pool, "email_reminders").await? {
+                self.mailer.send(&to, &n.title, n.message.clone()).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Start the daily digest loop. `pool` holds the users (recipients);
+/// `zone` is the household zone for users without their own.
+pub fn spawn_digest(
+    mailer: Arc<Mailer>,
+    pool: SqlitePool,
+    todos: TodoService,
+    hour: u32,
+    zone: Tz,
+) {
+    tokio::spawn(async move {
+        loop {
+            // Wake on every full hour: members may be in different zones
+            tokio::time::sleep(until_next_hour()).await;
+            if let Err(e) = send_digest(&mailer, &pool, &todos, hour, zone).await {
+                tracing::warn!(error = %e, "daily digest failed");
+            }
+        }
+    });
+}
+
+/// Time left until the next full hour (UTC; every zone we care about is
+/// whole hours or half past, and the hour is compared per recipient).
+fn until_next_hour() -> std::time::Duration {
+    let now = Utc::now();
+    let next = now
+        .duration_trunc(Duration::hours(1))
+        .map(|h| h + Duration::hours(1))
+        .unwrap_or(now + Duration::hours(1));
+    (next - now).to_std().unwrap_or_default()
+}
+
+/// Mail the digest to recipients for whom it is now `hour` o'clock.
+async fn send_digest(
+    mailer: &Mailer,
+    pool: &SqlitePool,
+    todos: &TodoService,
+    hour: u32,
+    zone: Tz,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let mut due = Vec::new();
+    for (user_id, address) in recipients(pool, "email_digest").await? {
+        let zone = timezone::of_user(pool, &user_id, zone).await?;
+        if now.with_timezone(&zone).hour() == hour {
+            due.push((address, zone));
+        }
+    }
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    let week = now + Duration::days(8);
+    let open = todos.open_due_before(week).await?;
+    for (address, zone) in due {
+        let (subject, body) = digest(&open, now, zone);
+        mailer.send(&address, &subject, body).await?;
+    }
+    tracing::info!("daily digest sent");
+    Ok(())
+}
+
+/// Subject and body of the digest, with days and times in `zone`.
+fn digest(open: &[Todo], now: DateTime<Utc>, zone: Tz) -> (String, String) {
+    let today = now.with_timezone(&zone).date_naive();
+    let end_of_today = timezone::midnight(zone, today + Days::new(1));
+    let week = end_of_today + Duration::days(7);
+
+    let section = |title: &str, items: Vec<&Todo>| -> String {
+        let mut out = format!("{title} ({})\n", items.len());
+        for t in &items {
+            let due = t
+                .due_at
+                .map(|d| d.with_timezone(&zone).format("%a %d %b %H:%M").to_string())
+                .unwrap_or_default();
+            out.push_str(&format!("  - {} [{}]\n", t.title, due));
+        }
+        out
+    };
+    let overdue: Vec<&Todo> = open.iter().filter(|t| t.due_at < Some(now)).collect();
+    let due_today: Vec<&Todo> = open
+        .iter()
+        .filter(|t| t.due_at >= Some(now) && t.due_at < Some(end_of_today))
+        .collect();
+    let upcoming: Vec<&Todo> = open
+        .iter()
+        .filter(|t| t.due_at >= Some(end_of_today) && t.due_at < Some(week))
+        .collect();
+
+    let body = [
+        section("Overdue", overdue),
+        section("Due today", due_today),
+        section("Upcoming (7 days)", upcoming),
+    ]
+    .join("\n");
+    (
+        format!("Todo digest for {}", today.format("%A %d %B")),
+        body,
+    )
+}
@@ -1,5 +1,10 @@
 use axum::{
-    http::StatusCode,
+    extract::{
+        FromRequest,
+        multipart::{MultipartError, MultipartRejection},
+        rejection::JsonRejection,
+    },
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use thiserror::Error;
@@ -10,6 +15,16 @@ pub enum ApiError {
     NotFound,
     #[error("bad request: {0}")]
     BadRequest(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    #[error("request body too large")]
+    PayloadTooLarge,
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
     #[error(transparent)]
     Sqlx(#[from] sqlx::Error),
     #[error(transparent)]
@@ -19,8 +34,32 @@ pub enum ApiError {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let (status, msg) = match &self {
+            // Lock contention outlasted busy_timeout and the write retries
+            ApiError::Sqlx(e) if crate::db::is_busy(e) => {
+                let mut response = (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "database is busy, retry shortly",
+                )
+                    .into_response();
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+                return response;
+            }
+            // Served read-only after a failed integrity check
+            ApiError::Sqlx(e) if crate::db::is_read_only(e) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "database is read-only".to_string(),
+            ),
             ApiError::NotFound => (StatusCode::NOT_FOUND, self.to_string()),
             ApiError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            ApiError::Conflict(_) => (StatusCode::CONFLICT, self.to_string()),
+            ApiError::Unauthorized(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
+            ApiError::Forbidden(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            ApiError::UnsupportedMediaType(_) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, self.to_string())
+            }
             ApiError::Sqlx(_) | ApiError::Anyhow(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
             }
@@ -29,4 +68,43 @@ impl IntoResponse for ApiError {
     }
 }
 
+/// Malformed or oversized JSON bodies, with serde's message (field, line, column).
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            ApiError::PayloadTooLarge
+        } else {
+            ApiError::BadRequest(rejection.body_text())
+        }
+    }
+}
+
+/// Non-multipart requests to upload endpoints.
+impl From<MultipartRejection> for ApiError {
+    fn from(rejection: MultipartRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+/// Broken multipart streams; hitting the body limit mid-upload is a 413.
+impl From<MultipartError> for ApiError {
+    fn from(e: MultipartError) -> Self {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            ApiError::PayloadTooLarge
+        } else {
+            ApiError::BadRequest(e.body_text())
+        }
+    }
+}
+
+/**
+ * JSON request body extractor that rejects with ApiError
+ *
+ * Use instead of axum::Json for request bodies so parse errors look like
+ * every other API error rather than axum's bare 415/422 texts.
+ */
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct JsonBody<T>(pub T);
+
 pub type ApiResult<T> = Result<T, ApiError>;
@@ -0,0 +1,173 @@
+/**
+ * Overdue escalation
+ *
+ * Background task that raises the priority of (and/or tags) open todos
+ * that stay overdue, so they surface on the board instead of rotting at
+ * their original priority. Rules come from `escalation_rules`:
+ *
+ * ```toml
+ * [[escalation_rules]]
+ * after_hours = 0        # as soon as it is overdue ...
+ * tag = "overdue"        # ... add this tag
+ * [[escalation_rules]]
+ * after_hours = 24
+ * priority = 2           # raise priority to at least 2
+ * ```
+ *
+ * Each (todo, rule, due_at) is applied once and recorded in
+ * `escalation_log`, so lowering the priority again by hand sticks - until
+ * the due date is moved and the todo becomes overdue again. Changes go
+ * through TodoService and are broadcast as `todo.updated`.
+ */
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{db::SqlitePool, model::TodoUpdate, services::TodoService};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/**
+ * One escalation step
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EscalationRule {
+    pub after_hours: i64,      // Hours past due_at before the rule applies
+    pub priority: Option<i64>, // Raise priority to at least this (0-3)
+    pub tag: Option<String>,   // Add this tag
+}
+
+impl EscalationRule {
+    /// Problem with the rule, if any (for config validation).
+    pub fn problem(&self) -> Option<&'static str> {
+        if self.after_hours < 0 {
+            return Some("after_hours must not be negative");
+        }
+        if self.priority.is_some_and(|p| !(0..=3).contains(&p)) {
+            return Some("priority must be between 0 and 3");
+        }
+        match &self.tag {
+            Some(tag) if tag.trim().is_empty() || tag.contains(',') => {
+                Some("tag must be non-empty and without commas")
+            }
+            None if self.priority.is_none() => Some("needs a priority or a tag"),
+            _ => None,
+        }
+    }
+}
+
+/// Default rules: tag when overdue, priority 2 after a day, 3 after three days.
+pub fn default_rules() -> Vec<EscalationRule> {
+    vec![
+        EscalationRule {
+            after_hours: 0,
+            priority: None,
+            tag: Some("overdue".into()),
+        },
+        EscalationRule {
+            after_hours: 24,
+            priority: Some(2),
+            tag: None,
+        },
+        EscalationRule {
+            after_hours: 72,
+            priority: Some(3),
+            tag: None,
+        },
+    ]
+}
+
+/// Start the escalation loop; does nothing without rules. `pool` holds the log.
+pub fn spawn(pool: SqlitePool, todos: TodoService, rules: Vec<EscalationRule>) {
+    if rules.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = check_once(&pool, &todos, &rules).await {
+                tracing::warn!(error = %e, "overdue escalation failed");
+            }
+        }
+    });
+}
+
+/// Apply every due rule that has not been applied to the todo's current due date.
+async fn check_once(
+    pool: &SqlitePool,
+    todos: &TodoService,
+    rules: &[EscalationRule],
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    for todo in todos.open_due_before(now).await? {
+        let Some(due_at) = todo.due_at else { continue };
+        let overdue_hours = (now - due_at).num_hours();
+        let mut priority = todo.priority;
+        let mut tags: Vec<String> = todo
+            .tags
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let mut applied = Vec::new();
+        for (index, rule) in rules.iter().enumerate() {
+            if overdue_hours < rule.after_hours {
+                continue;
+            }
+            let done: Option<i64> = sqlx::query_scalar(
+                "SELECT 1 FROM escalation_log WHERE todo_id = ?1 AND rule = ?2 AND due_at = ?3",
+            )
+            .bind(&todo.id)
+            .bind(index as i64)
+            .bind(due_at)
+            .fetch_optional(pool)
+            .await?;
+            if done.is_some() {
+                continue;
+            }
+            if let Some(p) = rule.priority {
+                priority = priority.max(p);
+            }
+            if let Some(tag) = &rule.tag
+                && !tags.iter().any(|t| t == tag)
+            {
+                tags.push(tag.clone());
+            }
+            applied.push(index as i64);
+        }
+        if applied.is_empty() {
+            continue;
+        }
+
+        let tags = tags.join(",");
+        if priority != todo.priority || Some(&tags) != todo.tags.as_ref() {
+            todos
+                .update(
+                    &todo.id,
+                    TodoUpdate {
+                        priority: (priority != todo.priority).then_some(priority),
+                        tags: Some(tags),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+        for rule in applied {
+            sqlx::query(
+                "INSERT OR IGNORE INTO escalation_log (todo_id, rule, due_at, applied_at) VALUES (?1, ?2, ?3, ?4)",
+            )
+            .bind(&todo.id)
+            .bind(rule)
+            .bind(due_at)
+            .bind(Utc::now())
+            .execute(pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+/**
+ * ETags and conditional GET
+ *
+ * Polling clients send the last ETag back in `If-None-Match` and get an
+ * empty 304 when nothing changed, instead of the whole list again.
+ * Validators are cheap and derived from data already loaded:
+ * - collections: item count + newest updated_at (a soft delete or reorder
+ *   bumps updated_at, a new item changes the count)
+ * - single records: version + updated_at
+ *
+ * ETags are weak (`W/"..."`) because the JSON may be re-encoded (key
+ * order, compression) without the data changing.
+ */
+use axum::{
+    Json,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Validator for a list of records given their updated_at timestamps.
+pub fn collection<'a>(updated: impl IntoIterator<Item = &'a DateTime<Utc>>) -> String {
+    let (count, newest) = updated.into_iter().fold((0usize, 0i64), |(n, max), t| {
+        (n + 1, max.max(t.timestamp_micros()))
+    });
+    format!("W/\"{count}-{newest:x}\"")
+}
+
+/// Validator for one record.
+pub fn item(version: i64, updated_at: DateTime<Utc>) -> String {
+    format!("W/\"{version}-{:x}\"", updated_at.timestamp_micros())
+}
+
+/// Whether `If-None-Match` already names this ETag (weak comparison).
+fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let current = opaque(etag);
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == current)
+}
+
+/// 304 when the client is up to date, otherwise the JSON body; both carry the ETag.
+pub fn respond<T: Serialize>(headers: &HeaderMap, etag: String, body: T) -> Response {
+    let mut response = if not_modified(headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(body).into_response()
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
+}
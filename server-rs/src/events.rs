@@ -0,0 +1,75 @@
+/**
+ * Long-polling events endpoint
+ *
+ * Fallback transport for clients behind proxies that break WebSockets:
+ * `GET /api/events?since=<seq>&wait=30` returns the events published after
+ * `seq`, or holds the request open for up to `wait` seconds until one is.
+ * The events are the same `{"type": ..., "data": ..., "seq": ...}` messages
+ * /ws/updates sends:
+ *
+ * ```text
+ * {"events": [{"seq": 42, "type": "todo.created", "data": {...}}], "next": 42}
+ * ```
+ *
+ * Clients pass `next` as `since` on the following request. Without `since`
+ * polling starts at the newest event, so a client loads the lists over
+ * REST first and then follows changes from there. `?project_id=` scopes
+ * the events like the WebSocket does.
+ *
+ * Events are kept for a day (see outbox.rs); a client away for longer
+ * should reload its lists.
+ */
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{error::ApiResult, outbox::sequenced, routes::AppState, ws::WsScope};
+
+/// Route path (exempt from the request timeout, see layers.rs).
+pub const PATH: &str = "/api/events";
+/// Longest a request may wait; proxies tend to cut idle requests at 60s.
+const MAX_WAIT_SECS: u64 = 55;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route(PATH, get(poll_events))
+}
+
+#[derive(Deserialize)]
+struct EventsParams {
+    since: Option<i64>,         // Last seq seen; default: the newest event
+    wait: Option<u64>,          // Seconds to wait for an event (default 0, max 55)
+    project_id: Option<String>, // Only events of this project (plus global ones)
+}
+
+/**
+ * Response for GET /api/events
+ */
+#[derive(Debug, Serialize)]
+pub struct EventsPage {
+    pub events: Vec<Value>, // Messages as sent on /ws/updates, with their "seq"
+    pub next: i64,          // `since` for the next request
+}
+
+async fn poll_events(
+    State(st): State<AppState>,
+    Query(p): Query<EventsParams>,
+) -> ApiResult<Json<EventsPage>> {
+    let since = match p.since {
+        Some(since) => since,
+        None => st.outbox.last_seq().await?,
+    };
+    let wait = Duration::from_secs(p.wait.unwrap_or(0).min(MAX_WAIT_SECS));
+    let scope = WsScope {
+        project_id: p.project_id,
+        ..Default::default()
+    };
+    let (events, next) = st.outbox.wait_events(since, wait, &scope).await?;
+    let events = events.iter().map(sequenced).collect();
+    Ok(Json(EventsPage { events, next }))
+}
@@ -0,0 +1,1043 @@
+/**
+ * Compact filter expressions for todo lists
+ *
+ * `GET /api/todos?filter=status:todo AND (tag:urgent OR priority>=2) AND due<+7d`
+ * is parsed into a FilterExpr, which the SQL backends compile into a WHERE
+ * fragment with bound parameters and the memory backend evaluates directly.
+ *
+ * Grammar (keywords are case-insensitive, AND may be left out):
+ *
+ * ```text
+ * expr       = and ("OR" and)*
+ * and        = unary ("AND"? unary)*
+ * unary      = ("NOT" | "-") unary | "(" expr ")" | condition
+ * condition  = field op value
+ * op         = ":" | "=" | "!=" | "<" | "<=" | ">" | ">="
+ * value      = word | "quoted string"
+ * ```
+ *
+ * Fields:
+ * - status, category, project      exact match (`category:none` = unset)
+ * - title, note                    `:` substring (case-insensitive), `=` exact
+ * - tag                            has the tag; `tag!=x` lacks it, `tag:none` untagged
+ * - priority, estimate             numbers, all comparisons
+ * - pinned                         true/false
+ * - due, start, completed, created, updated
+ *   dates: YYYY-MM-DD, RFC 3339, now, today, tomorrow, yesterday,
+ *   +7d / -12h / 2w (relative to now), none
+ *
+ * A calendar day (2026-05-01, today) covers the whole day in the
+ * request's time zone (see timezone.rs), so `due:today` matches any time
+ * today and `due<today` means before it.
+ * Comparisons never match todos where the field is unset.
+ */
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use crate::{model::Todo, timezone};
+
+/// Longest accepted expression (characters).
+const MAX_LEN: usize = 1000;
+/// Deepest accepted nesting of NOT and parentheses.
+const MAX_DEPTH: usize = 32;
+
+const FIELDS: &[(&str, Field)] = &[
+    ("status", Field::Status),
+    ("title", Field::Title),
+    ("note", Field::Note),
+    ("tag", Field::Tag),
+    ("priority", Field::Priority),
+    ("estimate", Field::Estimate),
+    ("pinned", Field::Pinned),
+    ("category", Field::Category),
+    ("project", Field::Project),
+    ("due", Field::Due),
+    ("start", Field::Start),
+    ("completed", Field::Completed),
+    ("created", Field::Created),
+    ("updated", Field::Updated),
+];
+
+/**
+ * Parse error with the (1-based, in characters) column it refers to
+ */
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message} at column {column}")]
+pub struct FilterError {
+    pub column: usize,
+    pub message: String,
+}
+
+/**
+ * Parsed filter expression
+ */
+#[derive(Debug, Clone)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Cond(Field, Test),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Status,
+    Title,
+    Note,
+    Tag,
+    Priority,
+    Estimate,
+    Pinned,
+    Category,
+    Project,
+    Due,
+    Start,
+    Completed,
+    Created,
+    Updated,
+}
+
+/**
+ * What a condition checks on its field
+ */
+#[derive(Debug, Clone)]
+pub enum Test {
+    Text(Cmp, String), // Eq/Ne on a text column (unset counts as "")
+    Contains(String),  // Case-insensitive substring
+    HasTag(String),    // Comma-separated tags contain this one
+    Unset(bool),       // Field is (true) or is not (false) empty
+    Int(Cmp, i64),
+    Bool(bool),
+    Time(Cmp, DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/**
+ * SQL flavour to compile to
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,   // ?N placeholders, instr()
+    Postgres, // $N placeholders, strpos()
+}
+
+/**
+ * Parameter to bind for a compiled expression, in placeholder order
+ */
+#[derive(Debug, Clone)]
+pub enum SqlValue {
+    Text(String),
+    Int(i64),
+    Bool(bool),
+    Time(DateTime<Utc>),
+}
+
+/// A date value: one instant or a whole local day [start, end).
+enum When {
+    At(DateTime<Utc>),
+    Day(DateTime<Utc>, DateTime<Utc>),
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        FIELDS
+            .iter()
+            .find(|(_, f)| *f == self)
+            .map_or("", |(n, _)| n)
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Field::Status => "status",
+            Field::Title => "title",
+            Field::Note => "note",
+            Field::Tag => "tags",
+            Field::Priority => "priority",
+            Field::Estimate => "estimate_minutes",
+            Field::Pinned => "pinned",
+            Field::Category => "category_id",
+            Field::Project => "project_id",
+            Field::Due => "due_at",
+            Field::Start => "start_at",
+            Field::Completed => "completed_at",
+            Field::Created => "created_at",
+            Field::Updated => "updated_at",
+        }
+    }
+
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            Field::Status
+                | Field::Title
+                | Field::Note
+                | Field::Tag
+                | Field::Category
+                | Field::Project
+        )
+    }
+
+    /// Numeric and date columns that can be NULL.
+    fn is_nullable(self) -> bool {
+        matches!(
+            self,
+            Field::Estimate | Field::Due | Field::Start | Field::Completed
+        )
+    }
+
+    fn text(self, t: &Todo) -> Option<&str> {
+        match self {
+            Field::Status => Some(&t.status),
+            Field::Title => Some(&t.title),
+            Field::Note => t.note.as_deref(),
+            Field::Tag => t.tags.as_deref(),
+            Field::Category => t.category_id.as_deref(),
+            Field::Project => t.project_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn int(self, t: &Todo) -> Option<i64> {
+        match self {
+            Field::Priority => Some(t.priority),
+            Field::Estimate => t.estimate_minutes,
+            _ => None,
+        }
+    }
+
+    fn time(self, t: &Todo) -> Option<DateTime<Utc>> {
+        match self {
+            Field::Due => t.due_at,
+            Field::Start => t.start_at,
+            Field::Completed => t.completed_at,
+            Field::Created => Some(t.created_at),
+            Field::Updated => Some(t.updated_at),
+            _ => None,
+        }
+    }
+}
+
+impl Cmp {
+    fn sql(self) -> &'static str {
+        match self {
+            Cmp::Eq => "=",
+            Cmp::Ne => "<>",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        }
+    }
+
+    fn holds<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+impl FilterExpr {
+    /// Parse an expression; relative dates are resolved against `now`,
+    /// calendar days in `tz`.
+    pub fn parse(src: &str, now: DateTime<Utc>, tz: Tz) -> Result<Self, FilterError> {
+        if src.chars().count() > MAX_LEN {
+            return Err(FilterError {
+                column: MAX_LEN + 1,
+                message: format!("filter is longer than {MAX_LEN} characters"),
+            });
+        }
+        let mut p = Parser {
+            src,
+            pos: 0,
+            depth: 0,
+            now,
+            tz,
+        };
+        p.skip_ws();
+        if p.at_end() {
+            return Err(p.error("empty filter"));
+        }
+        let expr = p.parse_or()?;
+        p.skip_ws();
+        match p.peek() {
+            None => Ok(expr),
+            Some(')') => Err(p.error("unmatched `)`")),
+            Some(_) => Err(p.error("expected AND, OR or the end of the filter")),
+        }
+    }
+
+    /// Whether any condition looks at `field`.
+    pub fn uses(&self, field: Field) -> bool {
+        match self {
+            FilterExpr::And(a, b) | FilterExpr::Or(a, b) => a.uses(field) || b.uses(field),
+            FilterExpr::Not(e) => e.uses(field),
+            FilterExpr::Cond(f, _) => *f == field,
+        }
+    }
+
+    /// Whether a todo matches (memory backend; same semantics as the SQL).
+    pub fn matches(&self, t: &Todo) -> bool {
+        match self {
+            FilterExpr::And(a, b) => a.matches(t) && b.matches(t),
+            FilterExpr::Or(a, b) => a.matches(t) || b.matches(t),
+            FilterExpr::Not(e) => !e.matches(t),
+            FilterExpr::Cond(field, test) => {
+                let field = *field;
+                match test {
+                    Test::Text(cmp, v) => cmp.holds(field.text(t).unwrap_or(""), v.as_str()),
+                    Test::Contains(v) => field
+                        .text(t)
+                        .unwrap_or("")
+                        .to_lowercase()
+                        .contains(&v.to_lowercase()),
+                    Test::HasTag(v) => {
+                        let wanted = normalize_tag(v);
+                        field
+                            .text(t)
+                            .unwrap_or("")
+                            .split(',')
+                            .any(|tag| normalize_tag(tag) == wanted)
+                    }
+                    Test::Unset(want) => {
+                        let unset = if field.is_text() {
+                            field.text(t).is_none_or(str::is_empty)
+                        } else {
+                            field.int(t).is_none() && field.time(t).is_none()
+                        };
+                        unset == *want
+                    }
+                    Test::Int(cmp, v) => field.int(t).is_some_and(|n| cmp.holds(n, *v)),
+                    Test::Bool(v) => t.pinned == *v,
+                    Test::Time(cmp, v) => field.time(t).is_some_and(|at| cmp.holds(at, *v)),
+                }
+            }
+        }
+    }
+
+    /**
+     * Compile to a boolean SQL fragment over the todos table
+     *
+     * Placeholders are numbered from `first_param`, so the fragment can be
+     * appended to a query that already binds `first_param - 1` values.
+     */
+    pub fn to_sql(&self, dialect: Dialect, first_param: usize) -> (String, Vec<SqlValue>) {
+        let mut binds = Vec::new();
+        let sql = self.compile(dialect, first_param, &mut binds);
+        (sql, binds)
+    }
+
+    fn compile(&self, dialect: Dialect, first: usize, binds: &mut Vec<SqlValue>) -> String {
+        let param = |binds: &mut Vec<SqlValue>, v: SqlValue| {
+            binds.push(v);
+            match dialect {
+                Dialect::Sqlite => format!("?{}", first + binds.len() - 1),
+                Dialect::Postgres => format!("${}", first + binds.len() - 1),
+            }
+        };
+        let instr = match dialect {
+            Dialect::Sqlite => "instr",
+            Dialect::Postgres => "strpos",
+        };
+        match self {
+            FilterExpr::And(a, b) => format!(
+                "({} AND {})",
+                a.compile(dialect, first, binds),
+                b.compile(dialect, first, binds)
+            ),
+            FilterExpr::Or(a, b) => format!(
+                "({} OR {})",
+                a.compile(dialect, first, binds),
+                b.compile(dialect, first, binds)
+            ),
+            FilterExpr::Not(e) => format!("(NOT {})", e.compile(dialect, first, binds)),
+            FilterExpr::Cond(field, test) => {
+                let col = field.column();
+                match test {
+                    Test::Text(cmp, v) => {
+                        let p = param(binds, SqlValue::Text(v.clone()));
+                        format!("COALESCE({col}, '') {} {p}", cmp.sql())
+                    }
+                    Test::Contains(v) => {
+                        let p = param(binds, SqlValue::Text(v.clone()));
+                        format!("{instr}(lower(COALESCE({col}, '')), lower({p})) > 0")
+                    }
+                    Test::HasTag(v) => {
+                        let p = param(binds, SqlValue::Text(v.clone()));
+                        format!(
+                            "{instr}(',' || lower(replace(COALESCE({col}, ''), ' ', '')) || ',', \
+                             ',' || lower(replace({p}, ' ', '')) || ',') > 0"
+                        )
+                    }
+                    Test::Unset(true) if field.is_text() => format!("COALESCE({col}, '') = ''"),
+                    Test::Unset(false) if field.is_text() => format!("COALESCE({col}, '') <> ''"),
+                    Test::Unset(true) => format!("{col} IS NULL"),
+                    Test::Unset(false) => format!("{col} IS NOT NULL"),
+                    Test::Int(cmp, v) => {
+                        let p = param(binds, SqlValue::Int(*v));
+                        compare(*field, *cmp, &p)
+                    }
+                    Test::Bool(v) => {
+                        let p = param(binds, SqlValue::Bool(*v));
+                        format!("{col} = {p}")
+                    }
+                    Test::Time(cmp, v) => {
+                        let p = param(binds, SqlValue::Time(*v));
+                        compare(*field, *cmp, &p)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `col cmp p`, false rather than NULL when the column is unset, so that
+/// NOT around it matches unset todos like the memory backend does.
+fn compare(field: Field, cmp: Cmp, p: &str) -> String {
+    let col = field.column();
+    if field.is_nullable() {
+        format!("({col} IS NOT NULL AND {col} {} {p})", cmp.sql())
+    } else {
+        format!("{col} {} {p}", cmp.sql())
+    }
+}
+
+/// Tags compare case-insensitively and ignoring spaces, like the SQL.
+fn normalize_tag(tag: &str) -> String {
+    tag.chars()
+        .filter(|c| *c != ' ')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/**
+ * Recursive-descent parser over the source string (byte positions)
+ */
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+    now: DateTime<Utc>,
+    tz: Tz,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.src.len()
+    }
+
+    fn skip_ws(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.src.len() - trimmed.len();
+    }
+
+    fn error_at(&self, pos: usize, message: impl Into<String>) -> FilterError {
+        FilterError {
+            column: self.src[..pos].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> FilterError {
+        self.error_at(self.pos, message)
+    }
+
+    /// Consume `kw` if it is the next whole word (case-insensitive).
+    fn keyword(&mut self, kw: &str) -> bool {
+        self.skip_ws();
+        let rest = self.rest();
+        let matched = rest.len() >= kw.len()
+            && rest.is_char_boundary(kw.len())
+            && rest[..kw.len()].eq_ignore_ascii_case(kw)
+            && rest[kw.len()..]
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_whitespace() || c == '(');
+        if matched {
+            self.pos += kw.len();
+        }
+        matched
+    }
+
+    fn enter(&mut self) -> Result<(), FilterError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error(format!("nested deeper than {MAX_DEPTH} levels")));
+        }
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut left = self.parse_and()?;
+        while self.keyword("OR") {
+            let right = self.parse_and()?;
+            left = FilterExpr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            self.skip_ws();
+            if self.at_end() || self.peek() == Some(')') {
+                break;
+            }
+            let before = self.pos;
+            if self.keyword("OR") {
+                self.pos = before;
+                break;
+            }
+            self.keyword("AND");
+            let right = self.parse_unary()?;
+            left = FilterExpr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, FilterError> {
+        self.skip_ws();
+        if self.at_end() {
+            return Err(self.error("expected a condition"));
+        }
+        let negated = self.keyword("NOT") || {
+            let mut chars = self.rest().chars();
+            let dash = chars.next() == Some('-')
+                && chars.next().is_some_and(|c| c.is_alphabetic() || c == '(');
+            if dash {
+                self.pos += 1;
+            }
+            dash
+        };
+        if negated {
+            self.enter()?;
+            let inner = self.parse_unary()?;
+            self.depth -= 1;
+            return Ok(FilterExpr::Not(Box::new(inner)));
+        }
+        if self.peek() == Some('(') {
+            let open = self.pos;
+            self.pos += 1;
+            self.enter()?;
+            let inner = self.parse_or()?;
+            self.depth -= 1;
+            self.skip_ws();
+            if self.peek() != Some(')') {
+                return Err(self.error_at(open, "unclosed `(`"));
+            }
+            self.pos += 1;
+            return Ok(inner);
+        }
+        self.parse_condition()
+    }
+
+    fn parse_condition(&mut self) -> Result<FilterExpr, FilterError> {
+        let start = self.pos;
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        let name = &self.src[start..start + len];
+        if name.is_empty() {
+            return Err(self.error("expected a field name"));
+        }
+        self.pos += len;
+        let field = FIELDS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, f)| *f);
+
+        let (op, op_text) = match self.rest() {
+            r if r.starts_with(">=") => (Cmp::Ge, ">="),
+            r if r.starts_with("<=") => (Cmp::Le, "<="),
+            r if r.starts_with("!=") => (Cmp::Ne, "!="),
+            r if r.starts_with(':') => (Cmp::Eq, ":"),
+            r if r.starts_with('=') => (Cmp::Eq, "="),
+            r if r.starts_with('<') => (Cmp::Lt, "<"),
+            r if r.starts_with('>') => (Cmp::Gt, ">"),
+            _ if field.is_none() => {
+                return Err(self.error_at(
+                    start,
+                    format!("unknown field `{name}` (use title:{name} to search titles)"),
+                ));
+            }
+            _ => {
+                return Err(self.error(format!(
+                    "expected an operator (: = != < <= > >=) after `{name}`"
+                )));
+            }
+        };
+        let Some(field) = field else {
+            let known: Vec<&str> = FIELDS.iter().map(|(n, _)| *n).collect();
+            return Err(self.error_at(
+                start,
+                format!(
+                    "unknown field `{name}` (expected one of {})",
+                    known.join(", ")
+                ),
+            ));
+        };
+        self.pos += op_text.len();
+
+        let value_pos = self.pos;
+        let value = self.parse_value(name, op_text)?;
+        self.test(field, op, op_text == ":", &value, value_pos)
+    }
+
+    fn parse_value(&mut self, name: &str, op: &str) -> Result<String, FilterError> {
+        if self.peek() == Some('"') {
+            let open = self.pos;
+            let body = &self.rest()[1..];
+            let Some(end) = body.find('"') else {
+                return Err(self.error_at(open, "unterminated quoted value"));
+            };
+            let value = body[..end].to_string();
+            self.pos += end + 2;
+            return Ok(value);
+        }
+        let len = self
+            .rest()
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error(format!("expected a value after `{name}{op}`")));
+        }
+        let value = self.rest()[..len].to_string();
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// Build the condition for `field op value`, validating the value.
+    fn test(
+        &self,
+        field: Field,
+        op: Cmp,
+        colon: bool,
+        value: &str,
+        pos: usize,
+    ) -> Result<FilterExpr, FilterError> {
+        let cond = |test| FilterExpr::Cond(field, test);
+        let name = field.name();
+        let equality_only = || {
+            if matches!(op, Cmp::Eq | Cmp::Ne) {
+                Ok(())
+            } else {
+                Err(self.error_at(pos, format!("`{name}` only supports :, = and !=")))
+            }
+        };
+        let none = value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("null");
+        if none && field != Field::Pinned && field != Field::Priority {
+            equality_only()?;
+            return Ok(cond(Test::Unset(op == Cmp::Eq)));
+        }
+        Ok(match field {
+            Field::Status | Field::Category | Field::Project => {
+                equality_only()?;
+                cond(Test::Text(op, value.to_string()))
+            }
+            Field::Title | Field::Note => {
+                equality_only()?;
+                match (colon, op) {
+                    (true, _) => cond(Test::Contains(value.to_string())),
+                    _ => cond(Test::Text(op, value.to_string())),
+                }
+            }
+            Field::Tag => {
+                equality_only()?;
+                let has = cond(Test::HasTag(value.to_string()));
+                match op {
+                    Cmp::Ne => FilterExpr::Not(Box::new(has)),
+                    _ => has,
+                }
+            }
+            Field::Priority | Field::Estimate => {
+                let n = value.parse::<i64>().map_err(|_| {
+                    self.error_at(pos, format!("`{name}` needs a whole number, got `{value}`"))
+                })?;
+                cond(Test::Int(op, n))
+            }
+            Field::Pinned => {
+                equality_only()?;
+                let v = match value.to_ascii_lowercase().as_str() {
+                    "true" | "yes" | "1" => true,
+                    "false" | "no" | "0" => false,
+                    _ => {
+                        return Err(self.error_at(
+                            pos,
+                            format!("`pinned` needs true or false, got `{value}`"),
+                        ));
+                    }
+                };
+                cond(Test::Bool(if op == Cmp::Ne { !v } else { v }))
+            }
+            Field::Due | Field::Start | Field::Completed | Field::Created | Field::Updated => {
+                let when = self.when(value).ok_or_else(|| {
+                    self.error_at(
+                        pos,
+                        format!(
+                            "`{name}` needs a date (YYYY-MM-DD, RFC 3339, today, now, +7d, -12h or none), got `{value}`"
+                        ),
+                    )
+                })?;
+                let at = |cmp, t| cond(Test::Time(cmp, t));
+                let both = |a, b| FilterExpr::And(Box::new(a), Box::new(b));
+                let either = |a, b| FilterExpr::Or(Box::new(a), Box::new(b));
+                match when {
+                    When::At(t) => at(op, t),
+                    When::Day(start, end) => match op {
+                        Cmp::Eq => both(at(Cmp::Ge, start), at(Cmp::Lt, end)),
+                        Cmp::Ne => either(at(Cmp::Lt, start), at(Cmp::Ge, end)),
+                        Cmp::Lt => at(Cmp::Lt, start),
+                        Cmp::Le => at(Cmp::Lt, end),
+                        Cmp::Gt => at(Cmp::Ge, end),
+                        Cmp::Ge => at(Cmp::Ge, start),
+                    },
+                }
+            }
+        })
+    }
+
+    fn when(&self, value: &str) -> Option<When> {
+        let lower = value.to_ascii_lowercase();
+        let today = self.now.with_timezone(&self.tz).date_naive();
+        let day = match lower.as_str() {
+            "now" => return Some(When::At(self.now)),
+            "today" => Some(today),
+            "tomorrow" => today.succ_opt(),
+            "yesterday" => today.pred_opt(),
+            _ => None,
+        };
+        if let Some(day) = day.or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()) {
+            return local_day(self.tz, day);
+        }
+        if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+            return Some(When::At(t.with_timezone(&Utc)));
+        }
+        // Relative: [+|-]N(h|d|w)
+        let (sign, body) = match lower.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, lower.strip_prefix('+').unwrap_or(&lower)),
+        };
+        let unit = body.chars().last()?;
+        let n: i64 = body[..body.len() - unit.len_utf8()].parse().ok()?;
+        let delta = match unit {
+            'h' => Duration::try_hours(n)?,
+            'd' => Duration::try_days(n)?,
+            'w' => Duration::try_weeks(n)?,
+            _ => return None,
+        };
+        self.now.checked_add_signed(delta * sign).map(When::At)
+    }
+}
+
+/// A calendar day in `tz` as a UTC range.
+fn local_day(tz: Tz, day: NaiveDate) -> Option<When> {
+    Some(When::Day(
+        timezone::midnight(tz, day),
+        timezone::midnight(tz, day.succ_opt()?),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono_tz::Europe::Berlin;
+
+    use super::*;
+    use crate::{
+        db::init_pool_with_size,
+        repository::{SqliteTodoRepository, TodoRepository},
+    };
+
+    /// 2026-03-10 23:30 UTC is already 00:30 on the 11th in Berlin (UTC+1).
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 10, 23, 30, 0).unwrap()
+    }
+
+    fn at(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, d, h, m, 0).unwrap()
+    }
+
+    fn parse(src: &str) -> FilterExpr {
+        FilterExpr::parse(src, now(), Berlin).unwrap_or_else(|e| panic!("{src}: {e}"))
+    }
+
+    fn parse_err(src: &str) -> FilterError {
+        FilterExpr::parse(src, now(), Berlin)
+            .err()
+            .unwrap_or_else(|| panic!("{src}: parsed"))
+    }
+
+    /// The tree as a string of field names, to check grouping.
+    fn shape(e: &FilterExpr) -> String {
+        match e {
+            FilterExpr::And(a, b) => format!("({} AND {})", shape(a), shape(b)),
+            FilterExpr::Or(a, b) => format!("({} OR {})", shape(a), shape(b)),
+            FilterExpr::Not(e) => format!("NOT {}", shape(e)),
+            FilterExpr::Cond(f, _) => f.name().to_string(),
+        }
+    }
+
+    fn todo(id: &str, title: &str, edit: impl FnOnce(&mut Todo)) -> Todo {
+        let mut t = Todo {
+            id: id.into(),
+            title: title.into(),
+            note: None,
+            status: "todo".into(),
+            priority: 0,
+            pinned: false,
+            due_at: None,
+            start_at: None,
+            completed_at: None,
+            tags: None,
+            category_id: None,
+            project_id: None,
+            assignee_id: None,
+            latitude: None,
+            longitude: None,
+            location_name: None,
+            checklist_done: 0,
+            checklist_total: 0,
+            snooze_count: 0,
+            timer_started_at: None,
+            tracked_secs: 0,
+            estimate_minutes: None,
+            sort_order: 0,
+            rank: 0.0,
+            created_at: at(1, 9, 0),
+            updated_at: at(1, 9, 0),
+            version: 1,
+            deleted: 0,
+        };
+        edit(&mut t);
+        t
+    }
+
+    fn fixtures() -> Vec<Todo> {
+        vec![
+            todo("1", "Buy milk", |t| {
+                t.tags = Some("urgent, home".into());
+                t.priority = 3;
+                t.pinned = true;
+                t.estimate_minutes = Some(30);
+                t.due_at = Some(at(10, 22, 59)); // 23:59 yesterday in Berlin
+            }),
+            todo("2", "Call plumber", |t| {
+                t.status = "doing".into();
+                t.note = Some("Ask about the boiler".into());
+                t.tags = Some("Home".into());
+                t.priority = 1;
+                t.due_at = Some(at(10, 23, 0)); // Midnight today in Berlin
+                t.updated_at = at(10, 12, 0);
+            }),
+            todo("3", "File taxes", |t| {
+                t.status = "done".into();
+                t.note = Some(String::new());
+                t.due_at = Some(at(11, 22, 59)); // 23:59 today in Berlin
+                t.completed_at = Some(at(10, 23, 15));
+            }),
+            todo("4", "Water plants", |t| {
+                t.tags = Some(String::new());
+                t.priority = 2;
+                t.estimate_minutes = Some(5);
+                t.due_at = Some(at(11, 23, 0)); // Midnight tomorrow in Berlin
+                t.start_at = Some(at(11, 6, 0));
+            }),
+            todo("5", "Read book", |t| {
+                t.tags = Some("later".into());
+                t.estimate_minutes = Some(90);
+                t.created_at = at(9, 8, 0);
+            }),
+        ]
+    }
+
+    fn matching(src: &str) -> Vec<String> {
+        let expr = parse(src);
+        fixtures()
+            .into_iter()
+            .filter(|t| expr.matches(t))
+            .map(|t| t.id)
+            .collect()
+    }
+
+    #[test]
+    fn precedence() {
+        let cases = [
+            ("status:todo tag:x", "(status AND tag)"),
+            (
+                "status:todo OR tag:x priority>1",
+                "(status OR (tag AND priority))",
+            ),
+            (
+                "status:todo AND tag:x OR priority>1",
+                "((status AND tag) OR priority)",
+            ),
+            ("NOT status:done tag:x", "(NOT status AND tag)"),
+            (
+                "-tag:x OR -(status:a OR status:b)",
+                "(NOT tag OR NOT (status OR status))",
+            ),
+            ("not not pinned:true", "NOT NOT pinned"),
+            ("(title:a OR title:b) note:c", "((title OR title) AND note)"),
+            // `-` only negates before a field or `(`: `-12h` stays a value
+            ("due<-12h", "due"),
+        ];
+        for (src, want) in cases {
+            assert_eq!(shape(&parse(src)), want, "{src}");
+        }
+    }
+
+    #[test]
+    fn error_columns() {
+        let cases = [
+            ("", 1, "empty filter"),
+            ("status:todo AND bogus:x", 17, "unknown field `bogus`"),
+            ("status:todo bogus", 13, "unknown field `bogus`"),
+            ("title:é AND bad:1", 13, "unknown field `bad`"),
+            ("status", 7, "expected an operator"),
+            ("tag:x (status:todo", 7, "unclosed `(`"),
+            ("status:todo )", 13, "unmatched `)`"),
+            ("priority>high", 10, "needs a whole number"),
+            ("due:soon", 5, "needs a date"),
+            ("pinned:maybe", 8, "needs true or false"),
+            ("status<todo", 8, "only supports"),
+            ("title:\"milk", 7, "unterminated quoted value"),
+            ("status:todo AND", 16, "expected a condition"),
+        ];
+        for (src, column, message) in cases {
+            let err = parse_err(src);
+            assert_eq!(err.column, column, "{src}: {err}");
+            assert!(err.message.contains(message), "{src}: {err}");
+        }
+    }
+
+    #[test]
+    fn calendar_days_use_the_zone() {
+        // Today in Berlin is 2026-03-11: [03-10 23:00 UTC, 03-11 23:00 UTC)
+        let FilterExpr::And(a, b) = parse("due:today") else {
+            panic!("due:today is not a range");
+        };
+        assert!(
+            matches!(*a, FilterExpr::Cond(Field::Due, Test::Time(Cmp::Ge, t)) if t == at(10, 23, 0))
+        );
+        assert!(
+            matches!(*b, FilterExpr::Cond(Field::Due, Test::Time(Cmp::Lt, t)) if t == at(11, 23, 0))
+        );
+        assert!(matches!(
+            parse("due<today"),
+            FilterExpr::Cond(Field::Due, Test::Time(Cmp::Lt, t)) if t == at(10, 23, 0)
+        ));
+
+        assert_eq!(matching("due:today"), ["2", "3"]);
+        assert_eq!(matching("due<today"), ["1"]);
+        assert_eq!(matching("due<=today"), ["1", "2", "3"]);
+        assert_eq!(matching("due>today"), ["4"]);
+        assert_eq!(matching("due>=today"), ["2", "3", "4"]);
+        assert_eq!(matching("due!=today"), ["1", "4"]);
+        assert_eq!(matching("due:yesterday"), ["1"]);
+        assert_eq!(matching("due:tomorrow"), ["4"]);
+        assert_eq!(matching("due:2026-03-11"), ["2", "3"]);
+        // Instants are not widened to a day
+        assert_eq!(matching("due<now"), ["1", "2"]);
+        assert_eq!(matching("due<2026-03-10T23:00:00Z"), ["1"]);
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(matching("tag:home"), ["1", "2"]);
+        assert_eq!(matching("tag:URGENT"), ["1"]);
+        assert_eq!(matching("tag!=home"), ["3", "4", "5"]);
+        assert_eq!(matching("tag:none"), ["3", "4"]);
+        assert_eq!(matching("tag!=none"), ["1", "2", "5"]);
+        // Whole tags only
+        assert!(matching("tag:hom").is_empty());
+    }
+
+    /// Every fixture expression selects the same todos in SQLite as in memory.
+    #[tokio::test]
+    async fn sql_agrees_with_matches() {
+        let pool = init_pool_with_size("sqlite::memory:", 1).await.unwrap();
+        let repo = SqliteTodoRepository::new(pool.clone());
+        for t in fixtures() {
+            repo.insert(&t, &[]).await.unwrap();
+        }
+        let exprs = [
+            "status:todo",
+            "status!=done",
+            "title:MILK",
+            "title=\"Buy milk\"",
+            "title!=\"Buy milk\"",
+            "note:boiler",
+            "note:none",
+            "note!=none",
+            "tag:home",
+            "tag!=home",
+            "tag:none",
+            "tag!=none",
+            "priority>=2",
+            "priority<2 OR pinned:true",
+            "estimate>10",
+            "estimate<=30",
+            "estimate:none",
+            "NOT estimate>10",
+            "pinned:false",
+            "pinned!=true",
+            "category:none",
+            "project!=none",
+            "due:today",
+            "due<today",
+            "due<=today",
+            "due>today",
+            "due!=today",
+            "due:none",
+            "NOT due<today",
+            "-due:today",
+            "start>=today",
+            "start:none",
+            "completed:today",
+            "completed!=none",
+            "created<2026-03-01T12:00:00Z",
+            "updated:2026-03-10",
+            "status:todo (tag:home OR priority>1) -due:none",
+            "status!=done -(tag:home OR priority>2)",
+            "NOT (title:a OR note:a)",
+        ];
+        for src in exprs {
+            let expr = parse(src);
+            let (cond, binds) = expr.to_sql(Dialect::Sqlite, 1);
+            let sql = format!("SELECT id FROM todos WHERE {cond} ORDER BY id");
+            let mut query = sqlx::query_scalar::<_, String>(&sql);
+            for value in binds {
+                query = match value {
+                    SqlValue::Text(v) => query.bind(v),
+                    SqlValue::Int(v) => query.bind(v),
+                    SqlValue::Bool(v) => query.bind(v),
+                    SqlValue::Time(v) => query.bind(v),
+                };
+            }
+            let from_sql = query.fetch_all(&pool).await.unwrap();
+            assert_eq!(from_sql, matching(src), "{src}: {cond}");
+        }
+    }
+}
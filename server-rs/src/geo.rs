@@ -0,0 +1,160 @@
+/**
+ * Location-aware todo endpoints
+ *
+ * Todos may carry an optional WGS84 point (latitude/longitude) plus a
+ * free-text place label. The GeoJSON endpoint feeds the map view on the
+ * dashboard; only open, non-deleted todos with coordinates are included.
+ *
+ * The errand route orders the same set into a walking/driving sequence
+ * from a start point using a nearest-neighbor heuristic. Distances are
+ * great-circle (haversine) kilometres, not road distances.
+ */
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    error::{ApiError, ApiResult},
+    model::Todo,
+    routes::AppState,
+};
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/todos/geojson", get(geojson))
+        .route("/api/errands/route", get(errand_route))
+}
+
+/// GeoJSON FeatureCollection (RFC 7946) of located open todos.
+async fn geojson(State(st): State<AppState>) -> ApiResult<Json<Value>> {
+    let features: Vec<Value> = st
+        .todos
+        .open_with_location()
+        .await?
+        .iter()
+        .map(|t| {
+            json!({
+                "type": "Feature",
+                "id": t.id,
+                // GeoJSON positions are [longitude, latitude]
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [t.longitude, t.latitude],
+                },
+                "properties": {
+                    "title": t.title,
+                    "status": t.status,
+                    "priority": t.priority,
+                    "due_at": t.due_at,
+                    "category_id": t.category_id,
+                    "location_name": t.location_name,
+                },
+            })
+        })
+        .collect();
+    Ok(Json(json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })))
+}
+
+#[derive(Deserialize)]
+struct RouteParams {
+    lat: f64,                    // Start latitude
+    lon: f64,                    // Start longitude
+    category_id: Option<String>, // Only route todos in this category
+    round_trip: Option<bool>,    // Add the leg back to the start
+}
+
+/**
+ * One stop on the errand route
+ */
+#[derive(Debug, Serialize)]
+struct RouteStop {
+    todo: Todo,
+    leg_km: f64,        // Distance from the previous stop (or the start)
+    cumulative_km: f64, // Distance travelled so far
+}
+
+/**
+ * Ordered errand route
+ */
+#[derive(Debug, Serialize)]
+struct ErrandRoute {
+    start: [f64; 2], // [latitude, longitude]
+    stops: Vec<RouteStop>,
+    return_km: Option<f64>, // Last stop back to the start, for round trips
+    total_distance_km: f64,
+}
+
+/// Order located open todos into a route starting at `lat`/`lon`.
+async fn errand_route(
+    State(st): State<AppState>,
+    Query(p): Query<RouteParams>,
+) -> ApiResult<Json<ErrandRoute>> {
+    if !(-90.0..=90.0).contains(&p.lat) || !(-180.0..=180.0).contains(&p.lon) {
+        return Err(ApiError::BadRequest("start point out of range".into()));
+    }
+    let mut remaining: Vec<Todo> = st
+        .todos
+        .open_with_location()
+        .await?
+        .into_iter()
+        .filter(|t| p.category_id.is_none() || t.category_id == p.category_id)
+        .collect();
+
+    // Nearest neighbor: always walk to the closest unvisited errand
+    let mut here = (p.lat, p.lon);
+    let mut total = 0.0;
+    let mut stops = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let (index, leg_km) = remaining
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (i, distance_km(here, position(t))))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("remaining is not empty");
+        let todo = remaining.swap_remove(index);
+        here = position(&todo);
+        total += leg_km;
+        stops.push(RouteStop {
+            todo,
+            leg_km,
+            cumulative_km: total,
+        });
+    }
+
+    let return_km = (p.round_trip.unwrap_or(false) && !stops.is_empty())
+        .then(|| distance_km(here, (p.lat, p.lon)));
+    total += return_km.unwrap_or(0.0);
+
+    Ok(Json(ErrandRoute {
+        start: [p.lat, p.lon],
+        stops,
+        return_km,
+        total_distance_km: total,
+    }))
+}
+
+/// (latitude, longitude) of a located todo.
+fn position(t: &Todo) -> (f64, f64) {
+    (
+        t.latitude.unwrap_or_default(),
+        t.longitude.unwrap_or_default(),
+    )
+}
+
+/// Great-circle distance between two (latitude, longitude) points.
+fn distance_km(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
@@ -0,0 +1,148 @@
+/**
+ * Idempotency-Key support for retried writes
+ *
+ * Clients on flaky Wi-Fi retry a POST when the response got lost. With an
+ * `Idempotency-Key` header the first request is executed and its response
+ * stored for 24 hours; a retry with the same key gets that response again
+ * (marked `Idempotent-Replayed: true`) instead of creating a duplicate.
+ *
+ * - same key while the first request still runs  -> 409
+ * - same key with a different body or endpoint    -> 400
+ * - failed requests release the key, so a retry executes again
+ */
+use axum::{
+    Json,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+
+use crate::{
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+};
+
+pub const HEADER: &str = "idempotency-key";
+const REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_KEY_LEN: usize = 255;
+
+/// How long a stored response can be replayed.
+fn retention() -> Duration {
+    Duration::hours(24)
+}
+
+/// The request's Idempotency-Key, if it sent one.
+pub fn key(headers: &HeaderMap) -> ApiResult<Option<String>> {
+    let Some(value) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .map(str::trim)
+        .map_err(|_| ApiError::BadRequest("Idempotency-Key must be ASCII".into()))?;
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "Idempotency-Key must be 1-{MAX_KEY_LEN} characters"
+        )));
+    }
+    Ok(Some(key.to_string()))
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredKey {
+    scope: String,
+    request: String,
+    status: Option<i64>,
+    response: Option<String>,
+}
+
+/**
+ * Claim `key` for this request
+ *
+ * Returns the stored response when the key was already completed; None
+ * means the caller should execute the request and then call complete()
+ * (or release() when it fails).
+ */
+pub async fn begin(
+    pool: &SqlitePool,
+    key: &str,
+    scope: &str,
+    request: &str,
+) -> ApiResult<Option<Response>> {
+    let now = Utc::now();
+    sqlx::query("DELETE FROM idempotency_keys WHERE created_at < ?1")
+        .bind(now - retention())
+        .execute(pool)
+        .await?;
+    let claimed = sqlx::query(
+        "INSERT OR IGNORE INTO idempotency_keys (key,scope,request,created_at) VALUES (?1,?2,?3,?4)",
+    )
+    .bind(key)
+    .bind(scope)
+    .bind(request)
+    .bind(now)
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1;
+    if claimed {
+        return Ok(None);
+    }
+
+    let stored = sqlx::query_as::<_, StoredKey>(
+        "SELECT scope, request, status, response FROM idempotency_keys WHERE key=?1",
+    )
+    .bind(key)
+    .fetch_one(pool)
+    .await?;
+    if stored.scope != scope || stored.request != request {
+        return Err(ApiError::BadRequest(
+            "Idempotency-Key was already used for a different request".into(),
+        ));
+    }
+    let (Some(status), Some(body)) = (stored.status, stored.response) else {
+        return Err(ApiError::Conflict(
+            "a request with this Idempotency-Key is still in progress".into(),
+        ));
+    };
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|s| StatusCode::from_u16(s).ok())
+        .unwrap_or(StatusCode::OK);
+    let body: serde_json::Value =
+        serde_json::from_str(&body).map_err(|e| ApiError::Anyhow(e.into()))?;
+    let mut response = (status, Json(body)).into_response();
+    response
+        .headers_mut()
+        .insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    Ok(Some(response))
+}
+
+/// Store the successful response for replays.
+pub async fn complete<T: Serialize>(
+    pool: &SqlitePool,
+    key: &str,
+    status: StatusCode,
+    body: &T,
+) -> ApiResult<()> {
+    let body = serde_json::to_string(body).map_err(|e| ApiError::Anyhow(e.into()))?;
+    sqlx::query("UPDATE idempotency_keys SET status=?2, response=?3 WHERE key=?1")
+        .bind(key)
+        .bind(i64::from(status.as_u16()))
+        .bind(body)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Forget a claimed key after the request failed, so a retry runs again.
+pub async fn release(pool: &SqlitePool, key: &str) {
+    let result = sqlx::query("DELETE FROM idempotency_keys WHERE key=?1 AND response IS NULL")
+        .bind(key)
+        .execute(pool)
+        .await;
+    if let Err(e) = result {
+        tracing::warn!(error = %e, "failed to release idempotency key");
+    }
+}
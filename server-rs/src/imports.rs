@@ -0,0 +1,354 @@
+/**
+ * Retry-safe bulk imports
+ *
+ * POST /api/imports stores every row up front, each with a pre-assigned
+ * todo id, and returns the job right away; a background task then imports
+ * the rows in chunks. Because a row's todo id is fixed before it is
+ * written, re-running a chunk after a crash or restart never creates
+ * duplicates.
+ *
+ * - POST /api/imports               {"id"?, "source"?, "category_id"?, "items": [...]}
+ *   re-posting a known client `id` returns the existing job instead
+ * - GET  /api/imports               recent jobs
+ * - GET  /api/imports/{id}          progress (total/processed/failed/status)
+ * - POST /api/imports/{id}/resume   continue an interrupted job
+ *
+ * Sources: "json" (TodoCreate objects, default) and "todoist" (REST API
+ * task objects: content, description, priority 1-4, due, labels).
+ *
+ * Individual todos are inserted without todo.created events; clients get
+ * `import.progress` after every chunk and `import.completed` at the end.
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{ImportCreate, ImportJob, Todo, TodoCreate},
+    routes::AppState,
+    services::{TodoService, client_id, emit},
+    ws::WsHub,
+};
+
+const CHUNK_SIZE: i64 = 200;
+const MAX_ITEMS: usize = 50_000;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/imports", get(list_imports).post(create_import))
+        .route("/api/imports/{id}", get(get_import))
+        .route("/api/imports/{id}/resume", post(resume_import))
+}
+
+/**
+ * Todoist REST API task (the fields we map)
+ */
+#[derive(Debug, Deserialize)]
+struct TodoistTask {
+    content: String,
+    #[serde(default)]
+    description: String,
+    priority: Option<i64>, // 1 (normal) .. 4 (urgent)
+    due: Option<TodoistDue>,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TodoistDue {
+    date: NaiveDate,
+    datetime: Option<DateTime<Utc>>,
+}
+
+impl From<TodoistTask> for TodoCreate {
+    fn from(t: TodoistTask) -> Self {
+        TodoCreate {
+            title: t.content,
+            note: (!t.description.is_empty()).then_some(t.description),
+            priority: t.priority.map(|p| (p - 1).clamp(0, 3)),
+            due_at: t.due.and_then(|d| d.datetime.or_else(|| date_due(d.date))),
+            tags: (!t.labels.is_empty()).then(|| t.labels.join(",")),
+            ..Default::default()
+        }
+    }
+}
+
+/// All-day Todoist dates become 18:00 local time, like dates given in words elsewhere.
+fn date_due(date: NaiveDate) -> Option<DateTime<Utc>> {
+    date.and_time(NaiveTime::from_hms_opt(18, 0, 0)?)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// Convert one source row; errors name the row so the whole upload can be fixed.
+fn convert(source: &str, index: usize, item: serde_json::Value) -> ApiResult<TodoCreate> {
+    let parsed = match source {
+        "json" => serde_json::from_value::<TodoCreate>(item),
+        "todoist" => serde_json::from_value::<TodoistTask>(item).map(TodoCreate::from),
+        other => {
+            return Err(ApiError::BadRequest(format!(
+                "unknown import source `{other}`"
+            )));
+        }
+    };
+    let create = parsed.map_err(|e| ApiError::BadRequest(format!("item {index}: {e}")))?;
+    if create.title.trim().is_empty() {
+        return Err(ApiError::BadRequest(format!(
+            "item {index}: title must not be empty"
+        )));
+    }
+    Ok(create)
+}
+
+async fn fetch_job(pool: &SqlitePool, id: &str) -> ApiResult<ImportJob> {
+    sqlx::query_as::<_, ImportJob>("SELECT * FROM imports WHERE id=?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+/// Jobs left running by a previous process are marked interrupted at startup.
+pub async fn mark_interrupted(pool: &SqlitePool) -> anyhow::Result<()> {
+    let res = sqlx::query("UPDATE imports SET status='interrupted' WHERE status='running'")
+        .execute(pool)
+        .await?;
+    if res.rows_affected() > 0 {
+        tracing::warn!(
+            count = res.rows_affected(),
+            "imports interrupted by restart; POST /api/imports/{{id}}/resume to continue"
+        );
+    }
+    Ok(())
+}
+
+/// Claim the job (one runner at a time) and process it in the background.
+async fn start(st: &AppState, id: &str) -> ApiResult<bool> {
+    let claimed = sqlx::query(
+        "UPDATE imports SET status='running' WHERE id=?1 AND status IN ('pending','interrupted')",
+    )
+    .bind(id)
+    .execute(&st.pool)
+    .await?
+    .rows_affected()
+        == 1;
+    if claimed {
+        let (pool, todos, hub, id) = (
+            st.pool.clone(),
+            st.todos.as_actor(Actor::new(format!("import:{id}"))),
+            st.hub.clone(),
+            id.to_string(),
+        );
+        tokio::spawn(async move {
+            if let Err(e) = run(&pool, &todos, &hub, &id).await {
+                tracing::warn!(import = %id, error = %e, "import stopped");
+                let _ =
+                    sqlx::query("UPDATE imports SET status='interrupted', error=?2 WHERE id=?1")
+                        .bind(&id)
+                        .bind(e.to_string())
+                        .execute(&pool)
+                        .await;
+                if let Ok(job) = fetch_job(&pool, &id).await {
+                    emit(&hub, "import.progress", &job);
+                }
+            }
+        });
+    }
+    Ok(claimed)
+}
+
+#[derive(sqlx::FromRow)]
+struct ImportRow {
+    idx: i64,
+    todo_id: String,
+    item: String,
+}
+
+/// Derive progress from the rows themselves, so counters survive a crash mid-chunk.
+async fn refresh_counts(pool: &SqlitePool, id: &str) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE imports SET
+            processed = (SELECT COUNT(*) FROM import_rows WHERE import_id=?1 AND done=1),
+            failed = (SELECT COUNT(*) FROM import_rows WHERE import_id=?1 AND error IS NOT NULL),
+            error = (SELECT error FROM import_rows WHERE import_id=?1 AND error IS NOT NULL
+                     ORDER BY idx DESC LIMIT 1),
+            updated_at = ?2
+        WHERE id=?1
+    "#,
+    )
+    .bind(id)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn run(pool: &SqlitePool, todos: &TodoService, hub: &WsHub, id: &str) -> ApiResult<()> {
+    loop {
+        let rows = sqlx::query_as::<_, ImportRow>(
+            "SELECT idx, todo_id, item FROM import_rows WHERE import_id=?1 AND done=0 ORDER BY idx LIMIT ?2",
+        )
+        .bind(id)
+        .bind(CHUNK_SIZE)
+        .fetch_all(pool)
+        .await?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in rows {
+            // Already written by an earlier attempt that died before marking the row
+            let exists = todos.get(&row.todo_id).await.is_ok();
+            let error = if exists {
+                None
+            } else {
+                let create: TodoCreate =
+                    serde_json::from_str(&row.item).map_err(|e| ApiError::Anyhow(e.into()))?;
+                let mut todo = Todo::new_from_create(create);
+                todo.id = row.todo_id.clone();
+                match todos.insert_quiet(&todo).await {
+                    Ok(()) => None,
+                    Err(ApiError::BadRequest(msg)) => Some(msg),
+                    Err(e) => return Err(e),
+                }
+            };
+            sqlx::query("UPDATE import_rows SET done=1, error=?3 WHERE import_id=?1 AND idx=?2")
+                .bind(id)
+                .bind(row.idx)
+                .bind(&error)
+                .execute(pool)
+                .await?;
+        }
+
+        refresh_counts(pool, id).await?;
+        emit(hub, "import.progress", &fetch_job(pool, id).await?);
+        tokio::task::yield_now().await;
+    }
+
+    refresh_counts(pool, id).await?;
+    sqlx::query("UPDATE imports SET status='completed', updated_at=?2 WHERE id=?1")
+        .bind(id)
+        .bind(Utc::now())
+        .execute(pool)
+        .await?;
+    let job = fetch_job(pool, id).await?;
+    tracing::info!(import = %id, total = job.total, failed = job.failed, "import completed");
+    emit(hub, "import.completed", &job);
+    Ok(())
+}
+
+async fn create_import(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<ImportCreate>,
+) -> ApiResult<Json<ImportJob>> {
+    // Retried upload of a job we already have
+    if let Some(id) = &body.id {
+        match fetch_job(&st.pool, id).await {
+            Ok(job) => return Ok(Json(job)),
+            Err(ApiError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if body.items.is_empty() {
+        return Err(ApiError::BadRequest("items must not be empty".into()));
+    }
+    if body.items.len() > MAX_ITEMS {
+        return Err(ApiError::BadRequest(format!(
+            "at most {MAX_ITEMS} items per import"
+        )));
+    }
+
+    let source = body.source.unwrap_or_else(|| "json".into());
+    let mut rows = Vec::with_capacity(body.items.len());
+    for (index, item) in body.items.into_iter().enumerate() {
+        let mut create = convert(&source, index, item)?;
+        if create.category_id.is_none() {
+            create.category_id = body.category_id.clone();
+        }
+        // Client ids are kept, so re-importing the same items skips them
+        let todo_id = client_id(&create)
+            .map_err(|e| ApiError::BadRequest(format!("item {index}: {e}")))?
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let item = serde_json::to_string(&create).map_err(|e| ApiError::Anyhow(e.into()))?;
+        rows.push((todo_id, item));
+    }
+
+    let now = Utc::now();
+    let job = ImportJob {
+        id: body.id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+        source,
+        status: "pending".into(),
+        total: rows.len() as i64,
+        processed: 0,
+        failed: 0,
+        error: None,
+        created_at: now,
+        updated_at: now,
+    };
+
+    // Job and rows land together, so a dropped upload leaves nothing behind
+    let mut tx = st.pool.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO imports (id,source,status,total,processed,failed,error,created_at,updated_at)
+        VALUES (?1,?2,?3,?4,0,0,NULL,?5,?5)
+    "#,
+    )
+    .bind(&job.id)
+    .bind(&job.source)
+    .bind(&job.status)
+    .bind(job.total)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+    for (idx, (todo_id, item)) in rows.iter().enumerate() {
+        sqlx::query("INSERT INTO import_rows (import_id,idx,todo_id,item) VALUES (?1,?2,?3,?4)")
+            .bind(&job.id)
+            .bind(idx as i64)
+            .bind(todo_id)
+            .bind(item)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    start(&st, &job.id).await?;
+    Ok(Json(fetch_job(&st.pool, &job.id).await?))
+}
+
+async fn list_imports(State(st): State<AppState>) -> ApiResult<Json<Vec<ImportJob>>> {
+    let rows =
+        sqlx::query_as::<_, ImportJob>("SELECT * FROM imports ORDER BY created_at DESC LIMIT 50")
+            .fetch_all(&st.pool)
+            .await?;
+    Ok(Json(rows))
+}
+
+async fn get_import(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<ImportJob>> {
+    Ok(Json(fetch_job(&st.pool, &id).await?))
+}
+
+async fn resume_import(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<ImportJob>> {
+    let job = fetch_job(&st.pool, &id).await?;
+    if job.status == "running" {
+        return Err(ApiError::BadRequest("import is already running".into()));
+    }
+    start(&st, &id).await?;
+    Ok(Json(fetch_job(&st.pool, &id).await?))
+}
@@ -0,0 +1,168 @@
+/**
+ * Inbound webhook endpoint for quick capture
+ *
+ * POST /api/inbound/{token} turns a minimal payload into a todo so that
+ * email-to-todo bridges and IFTTT rules can drop items into the board.
+ * Each token carries its own default category and tags.
+ *
+ * Accepted bodies:
+ * - JSON: {"title": "...", "note": "...", "priority": 2, "due_at": "...", "tags": "..."}
+ *   (`text`/`subject` are accepted for the title, `body` for the note)
+ * - anything else is treated as plain text: first line = title, rest = note
+ */
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, header::CONTENT_TYPE},
+    routing::{delete, get, post},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    audit::Actor,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{InboundToken, InboundTokenCreate, Todo, TodoCreate},
+    routes::AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/inbound/{token}", post(receive))
+        .route("/api/inbound-tokens", get(list_tokens).post(create_token))
+        .route("/api/inbound-tokens/{id}", delete(delete_token))
+}
+
+#[derive(Deserialize)]
+struct InboundPayload {
+    #[serde(alias = "text", alias = "subject")]
+    title: String,
+    #[serde(alias = "body")]
+    note: Option<String>,
+    priority: Option<i64>,
+    due_at: Option<DateTime<Utc>>,
+    tags: Option<String>,
+}
+
+impl InboundPayload {
+    fn from_text(raw: &str) -> Option<Self> {
+        let mut lines = raw.trim().lines();
+        let title = lines.next()?.trim().to_string();
+        let note = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+        Some(Self {
+            title,
+            note: (!note.is_empty()).then_some(note),
+            priority: None,
+            due_at: None,
+            tags: None,
+        })
+    }
+}
+
+/// Combine the token's default tags with the payload tags, dropping duplicates.
+fn merge_tags(defaults: Option<&str>, extra: Option<&str>) -> Option<String> {
+    let mut tags: Vec<&str> = Vec::new();
+    for tag in defaults
+        .into_iter()
+        .chain(extra)
+        .flat_map(|s| s.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    (!tags.is_empty()).then(|| tags.join(","))
+}
+
+async fn receive(
+    State(st): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Json<Todo>> {
+    let inbound: InboundToken = sqlx::query_as("SELECT * FROM inbound_tokens WHERE token=?1")
+        .bind(&token)
+        .fetch_optional(&st.pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let payload = if is_json {
+        serde_json::from_slice::<InboundPayload>(&body)
+            .map_err(|e| ApiError::BadRequest(e.to_string()))?
+    } else {
+        let text = std::str::from_utf8(&body)
+            .map_err(|_| ApiError::BadRequest("body is not valid UTF-8".into()))?;
+        InboundPayload::from_text(text).ok_or_else(|| ApiError::BadRequest("empty body".into()))?
+    };
+    if payload.title.trim().is_empty() {
+        return Err(ApiError::BadRequest("title must not be empty".into()));
+    }
+
+    let todo = st
+        .todos
+        .as_actor(Actor::new(format!("inbound:{}", inbound.name)))
+        .create(TodoCreate {
+            title: payload.title.trim().to_string(),
+            note: payload.note,
+            priority: payload.priority,
+            due_at: payload.due_at,
+            tags: merge_tags(inbound.tags.as_deref(), payload.tags.as_deref()),
+            category_id: inbound.category_id.clone(),
+            ..Default::default()
+        })
+        .await?;
+    tracing::info!(source = %inbound.name, id = %todo.id, "todo captured via inbound webhook");
+    Ok(Json(todo))
+}
+
+async fn list_tokens(State(st): State<AppState>) -> ApiResult<Json<Vec<InboundToken>>> {
+    let rows =
+        sqlx::query_as::<_, InboundToken>("SELECT * FROM inbound_tokens ORDER BY created_at ASC")
+            .fetch_all(&st.pool)
+            .await?;
+    Ok(Json(rows))
+}
+
+async fn create_token(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<InboundTokenCreate>,
+) -> ApiResult<Json<InboundToken>> {
+    let token = InboundToken::new_from_create(body);
+    sqlx::query(
+        r#"
+        INSERT INTO inbound_tokens (id,token,name,category_id,tags,created_at)
+        VALUES (?1,?2,?3,?4,?5,?6)
+    "#,
+    )
+    .bind(&token.id)
+    .bind(&token.token)
+    .bind(&token.name)
+    .bind(&token.category_id)
+    .bind(&token.tags)
+    .bind(token.created_at)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(token))
+}
+
+async fn delete_token(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let res = sqlx::query("DELETE FROM inbound_tokens WHERE id=?1")
+        .bind(&id)
+        .execute(&st.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(json!({"ok": true})))
+}
@@ -0,0 +1,312 @@
+/**
+ * Heavy background job scheduler
+ *
+ * The Pi often shares duty with other services (media server, Home
+ * Assistant), so maintenance work like VACUUM or report generation should
+ * not compete with them. Jobs registered here only run:
+ * - inside the quiet-hours window (JOBS_QUIET_HOURS, e.g. "01:00-06:00",
+ *   local time; unset = any time), and
+ * - while the CPU temperature is below JOBS_MAX_CPU_TEMP (default 75 C).
+ *
+ * A job that is due but not allowed to run is marked deferred (with the
+ * reason) and picked up on the first check where the policy allows it.
+ * Jobs run one at a time. State is exposed via GET /api/admin/jobs.
+ */
+use std::{
+    env,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use crate::{config::ServerConfig, db::SqlitePool, services::TodoService, system};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/**
+ * A deferrable unit of heavy work
+ */
+pub trait Job: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// How often the job should run.
+    fn interval(&self) -> Duration;
+
+    fn run(&self) -> BoxFuture<'_, anyhow::Result<()>>;
+}
+
+/**
+ * When heavy jobs may run, read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct JobPolicy {
+    pub quiet_hours: Option<(NaiveTime, NaiveTime)>, // JOBS_QUIET_HOURS, start-end local time
+    pub max_cpu_temp: f64,                           // JOBS_MAX_CPU_TEMP in C, default 75
+    pub thermal_path: PathBuf,                       // JOBS_THERMAL_PATH, default thermal_zone0
+}
+
+impl JobPolicy {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let quiet_hours = match env::var("JOBS_QUIET_HOURS") {
+            Ok(s) => Some(parse_window(&s).context("JOBS_QUIET_HOURS must look like 01:00-06:00")?),
+            Err(_) => None,
+        };
+        Ok(Self {
+            quiet_hours,
+            max_cpu_temp: env::var("JOBS_MAX_CPU_TEMP")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(75.0),
+            thermal_path: system::thermal_path(),
+        })
+    }
+
+    /// Whether `now` falls into the quiet-hours window (windows may wrap midnight).
+    fn in_window(&self, now: NaiveTime) -> bool {
+        match self.quiet_hours {
+            None => true,
+            Some((start, end)) if start <= end => now >= start && now < end,
+            Some((start, end)) => now >= start || now < end,
+        }
+    }
+
+    /// Current CPU temperature in C, if the platform exposes one.
+    pub fn cpu_temp(&self) -> Option<f64> {
+        system::cpu_temp(&self.thermal_path)
+    }
+
+    /// None when jobs may run now, otherwise the reason they are deferred.
+    pub fn deferral_reason(&self) -> Option<String> {
+        if !self.in_window(Local::now().time()) {
+            return Some("outside quiet hours".into());
+        }
+        match self.cpu_temp() {
+            Some(temp) if temp >= self.max_cpu_temp => Some(format!(
+                "CPU temperature {temp:.1} C >= {:.1} C",
+                self.max_cpu_temp
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn parse_window(s: &str) -> anyhow::Result<(NaiveTime, NaiveTime)> {
+    let (start, end) = s.split_once('-').ok_or_else(|| anyhow!("missing `-`"))?;
+    Ok((
+        NaiveTime::parse_from_str(start.trim(), "%H:%M")?,
+        NaiveTime::parse_from_str(end.trim(), "%H:%M")?,
+    ))
+}
+
+/**
+ * Per-job state (serialized into /api/admin/jobs)
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub interval_secs: u64,
+    pub next_due: DateTime<Utc>,
+    pub running: bool,
+    pub deferred: Option<String>, // Why a due job is waiting
+    pub runs: u64,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/**
+ * Scheduler overview for the admin page
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct JobsOverview {
+    pub quiet_hours: Option<String>,
+    pub in_quiet_hours: bool,
+    pub cpu_temp: Option<f64>,
+    pub max_cpu_temp: f64,
+    pub jobs: Vec<JobStatus>,
+}
+
+/**
+ * Owns the registered jobs and runs them according to the policy
+ */
+pub struct JobScheduler {
+    policy: JobPolicy,
+    jobs: Vec<Arc<dyn Job>>,
+    status: RwLock<Vec<JobStatus>>,
+}
+
+impl JobScheduler {
+    pub fn new(policy: JobPolicy) -> Self {
+        Self {
+            policy,
+            jobs: Vec::new(),
+            status: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Register a job; its first run is due one interval from now.
+    pub fn register(&mut self, job: Arc<dyn Job>) {
+        let interval = job.interval();
+        self.status.write().unwrap().push(JobStatus {
+            name: job.name().to_string(),
+            interval_secs: interval.as_secs(),
+            next_due: Utc::now() + interval,
+            running: false,
+            deferred: None,
+            runs: 0,
+            last_run: None,
+            last_duration_ms: None,
+            last_error: None,
+        });
+        self.jobs.push(job);
+    }
+
+    pub fn overview(&self) -> JobsOverview {
+        JobsOverview {
+            quiet_hours: self
+                .policy
+                .quiet_hours
+                .map(|(s, e)| format!("{}-{}", s.format("%H:%M"), e.format("%H:%M"))),
+            in_quiet_hours: self.policy.in_window(Local::now().time()),
+            cpu_temp: self.policy.cpu_temp(),
+            max_cpu_temp: self.policy.max_cpu_temp,
+            jobs: self.status.read().unwrap().clone(),
+        }
+    }
+
+    /// Check for due jobs every minute, running them one at a time.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            for (index, job) in self.jobs.iter().enumerate() {
+                if self.status.read().unwrap()[index].next_due > Utc::now() {
+                    continue;
+                }
+                // Re-evaluated per job: a long job may heat up the CPU or overrun the window
+                if let Some(reason) = self.policy.deferral_reason() {
+                    let mut status = self.status.write().unwrap();
+                    if status[index].deferred.is_none() {
+                        tracing::info!(job = job.name(), %reason, "job deferred");
+                    }
+                    status[index].deferred = Some(reason);
+                    continue;
+                }
+                self.run_job(index, job.as_ref()).await;
+            }
+        }
+    }
+
+    async fn run_job(&self, index: usize, job: &dyn Job) {
+        {
+            let mut status = self.status.write().unwrap();
+            status[index].running = true;
+            status[index].deferred = None;
+        }
+        tracing::info!(job = job.name(), "job started");
+        let started = std::time::Instant::now();
+        let result = job.run().await;
+        let elapsed = started.elapsed();
+
+        let mut status = self.status.write().unwrap();
+        let s = &mut status[index];
+        s.running = false;
+        s.runs += 1;
+        s.last_run = Some(Utc::now());
+        s.last_duration_ms = Some(elapsed.as_millis() as u64);
+        s.next_due = Utc::now() + job.interval();
+        match result {
+            Ok(()) => {
+                tracing::info!(job = job.name(), ?elapsed, "job finished");
+                s.last_error = None;
+            }
+            Err(e) => {
+                tracing::warn!(job = job.name(), error = %e, "job failed");
+                s.last_error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+/**
+ * Reclaims free pages and refreshes query planner statistics
+ */
+pub struct VacuumJob {
+    pool: SqlitePool,
+    interval: Duration,
+}
+
+impl VacuumJob {
+    /// Interval from `vacuum_interval_hours`, default weekly.
+    pub fn from_config(pool: SqlitePool, config: &ServerConfig) -> Self {
+        Self {
+            pool,
+            interval: Duration::from_secs(config.vacuum_interval_hours * 3600),
+        }
+    }
+}
+
+impl Job for VacuumJob {
+    fn name(&self) -> &'static str {
+        "vacuum"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn run(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            sqlx::query("VACUUM").execute(&self.pool).await?;
+            sqlx::query("PRAGMA optimize").execute(&self.pool).await?;
+            Ok(())
+        })
+    }
+}
+
+/**
+ * Archives todos that were completed more than `auto_archive_days` ago
+ *
+ * Keeps the active board short without deleting history; archived todos
+ * stay searchable and can be reopened.
+ */
+pub struct AutoArchiveJob {
+    todos: TodoService,
+    days: u64,
+}
+
+impl AutoArchiveJob {
+    /// None when `auto_archive_days` is 0 (disabled).
+    pub fn from_config(todos: TodoService, config: &ServerConfig) -> Option<Self> {
+        (config.auto_archive_days > 0).then(|| Self {
+            todos,
+            days: config.auto_archive_days,
+        })
+    }
+}
+
+impl Job for AutoArchiveJob {
+    fn name(&self) -> &'static str {
+        "auto-archive"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(24 * 3600)
+    }
+
+    fn run(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let before = Utc::now() - chrono::Duration::days(self.days as i64);
+            let archived = self.todos.archive_done(Some(before)).await?;
+            if !archived.is_empty() {
+                tracing::info!(count = archived.len(), "auto-archived finished todos");
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,241 @@
+/**
+ * Wall display (kiosk) rotation
+ *
+ * A central, server-driven sequence of views that every wall display
+ * cycles through in sync, instead of per-device JS timers.
+ *
+ * - GET  /api/kiosk/rotation        config plus the current position
+ * - PUT  /api/kiosk/rotation        replace the config, restarts at view 0
+ * - POST /api/kiosk/rotation/next   skip to the next view now
+ *
+ * WebSocket events:
+ * - kiosk.rotation.updated  the config changed (data = full rotation state)
+ * - kiosk.view              a new view became active (index, view, ends_at)
+ *
+ * Displays that connect mid-cycle read `current` from GET and wait for
+ * the next kiosk.view event. The config is persisted in the settings table.
+ */
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Notify;
+
+use crate::{
+    db::{self, SqlitePool},
+    error::{ApiError, ApiResult, JsonBody},
+    routes::AppState,
+    ws::WsHub,
+};
+
+const SETTINGS_KEY: &str = "kiosk_rotation";
+const MIN_DURATION_SECS: u64 = 5;
+
+/**
+ * One step of the rotation
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KioskView {
+    pub view: String,          // Display view: "board", "today", "category", "map", ...
+    pub title: Option<String>, // Optional heading shown on the display
+    pub category_id: Option<String>, // For category views
+    pub status: Option<String>, // Optional status filter for list views
+    pub duration_secs: u64,    // How long the view stays on screen
+}
+
+/**
+ * Rotation config, as stored and as accepted by PUT
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KioskRotation {
+    pub enabled: bool,
+    pub views: Vec<KioskView>,
+}
+
+/**
+ * Currently active view
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct KioskCurrent {
+    pub index: usize,
+    pub view: KioskView,
+    pub started_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+/**
+ * Response for GET /api/kiosk/rotation
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct KioskState {
+    #[serde(flatten)]
+    pub rotation: KioskRotation,
+    pub current: Option<KioskCurrent>,
+}
+
+struct Position {
+    rotation: KioskRotation,
+    index: usize,
+    started_at: DateTime<Utc>,
+}
+
+impl Position {
+    fn current(&self) -> Option<KioskCurrent> {
+        if !self.rotation.enabled {
+            return None;
+        }
+        let view = self.rotation.views.get(self.index)?.clone();
+        Some(KioskCurrent {
+            index: self.index,
+            ends_at: self.started_at + chrono::Duration::seconds(view.duration_secs as i64),
+            view,
+            started_at: self.started_at,
+        })
+    }
+}
+
+/**
+ * Owns the rotation clock shared by all displays
+ */
+pub struct KioskRotator {
+    pool: SqlitePool,
+    hub: Arc<WsHub>,
+    position: RwLock<Position>,
+    changed: Notify, // Wakes the clock when the config changes or a view is skipped
+}
+
+impl KioskRotator {
+    pub fn new(pool: SqlitePool, hub: Arc<WsHub>) -> Self {
+        Self {
+            pool,
+            hub,
+            position: RwLock::new(Position {
+                rotation: KioskRotation::default(),
+                index: 0,
+                started_at: Utc::now(),
+            }),
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn state(&self) -> KioskState {
+        let position = self.position.read().unwrap();
+        KioskState {
+            rotation: position.rotation.clone(),
+            current: position.current(),
+        }
+    }
+
+    /// Load the stored config and advance views until shutdown.
+    pub async fn run(self: Arc<Self>) {
+        match db::get_setting::<KioskRotation>(&self.pool, SETTINGS_KEY).await {
+            Ok(Some(rotation)) => self.restart(rotation),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "failed to load kiosk rotation"),
+        }
+        loop {
+            let remaining = self
+                .position
+                .read()
+                .unwrap()
+                .current()
+                .map(|c| (c.ends_at - Utc::now()).to_std().unwrap_or_default());
+            match remaining {
+                Some(remaining) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(remaining) => self.advance(),
+                        _ = self.changed.notified() => {}
+                    }
+                }
+                None => self.changed.notified().await,
+            }
+        }
+    }
+
+    /// Validate, persist and activate a new config.
+    pub async fn replace(&self, rotation: KioskRotation) -> ApiResult<KioskState> {
+        if rotation.enabled && rotation.views.is_empty() {
+            return Err(ApiError::BadRequest(
+                "an enabled rotation needs at least one view".into(),
+            ));
+        }
+        if let Some(v) = rotation
+            .views
+            .iter()
+            .find(|v| v.duration_secs < MIN_DURATION_SECS || v.view.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest(format!(
+                "view `{}`: views need a name and at least {MIN_DURATION_SECS}s duration",
+                v.view
+            )));
+        }
+        db::put_setting(&self.pool, SETTINGS_KEY, &rotation).await?;
+        self.restart(rotation);
+
+        let state = self.state();
+        let event = json!({"type":"kiosk.rotation.updated","data": &state});
+        let _ = self.hub.tx.send(event.to_string());
+        self.announce();
+        self.changed.notify_one();
+        Ok(state)
+    }
+
+    /// Skip to the next view immediately.
+    pub fn skip(&self) -> KioskState {
+        self.advance();
+        self.changed.notify_one();
+        self.state()
+    }
+
+    fn restart(&self, rotation: KioskRotation) {
+        let mut position = self.position.write().unwrap();
+        position.rotation = rotation;
+        position.index = 0;
+        position.started_at = Utc::now();
+    }
+
+    fn advance(&self) {
+        {
+            let mut position = self.position.write().unwrap();
+            let len = position.rotation.views.len().max(1);
+            position.index = (position.index + 1) % len;
+            position.started_at = Utc::now();
+        }
+        self.announce();
+    }
+
+    /// Broadcast the active view to all displays.
+    fn announce(&self) {
+        if let Some(current) = self.position.read().unwrap().current() {
+            let event = json!({"type":"kiosk.view","data": current});
+            let _ = self.hub.tx.send(event.to_string());
+        }
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/kiosk/rotation", get(get_rotation).put(put_rotation))
+        .route("/api/kiosk/rotation/next", post(next_view))
+}
+
+async fn get_rotation(State(st): State<AppState>) -> Json<KioskState> {
+    Json(st.kiosk.state())
+}
+
+async fn put_rotation(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<KioskRotation>,
+) -> ApiResult<Json<KioskState>> {
+    Ok(Json(st.kiosk.replace(body).await?))
+}
+
+async fn next_view(State(st): State<AppState>) -> Json<KioskState> {
+    Json(st.kiosk.skip())
+}
@@ -0,0 +1,190 @@
+/**
+ * Cross-cutting HTTP layers
+ *
+ * Every request gets an `x-request-id` (kept when the client or a proxy
+ * already sent one) that is echoed in the response and recorded on the
+ * request's tracing span, so log lines and bug reports can be matched.
+ *
+ * A panicking handler is caught and answered with a JSON 500 instead of
+ * a dropped connection; the panic is logged inside the request's span.
+ *
+ * Handlers taking longer than `request_timeout_secs` are abandoned with a
+ * 504, and requests slower than `slow_request_ms` are logged with their
+ * route. Backups and integrity checks may legitimately take minutes on a
+ * large database and are exempt from the timeout.
+ *
+ * Security headers (SecurityHeaders) go on API and static responses
+ * alike; headers a handler set itself are left alone.
+ */
+use std::{any::Any, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode, header},
+    middleware::Next,
+    response::IntoResponse,
+};
+use serde_json::json;
+use tracing::Span;
+
+use crate::{config::ServerConfig, routes::AppState};
+
+/// Routes allowed to run past the request timeout.
+const NO_TIMEOUT: [&str; 3] = [
+    "/api/admin/backup",
+    "/api/admin/integrity-check",
+    crate::events::PATH, // Long polls wait on purpose
+];
+
+/// Tracing span for one request, tagged with its request ID.
+pub fn request_span<B>(req: &Request<B>) -> Span {
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        request_id,
+    )
+}
+
+/// JSON 500 for a handler that panicked (CatchPanicLayer::custom).
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response<Body> {
+    let message = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+    tracing::error!(panic = message, "handler panicked");
+    let body = json!({
+        "error": "internal",
+        "message": "internal server error",
+    });
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Middleware enforcing the request timeout (504) and logging slow requests.
+pub async fn time_requests(
+    State(st): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let method = req.method().clone();
+    let timeout = match st.config.request_timeout_secs {
+        0 => None,
+        _ if NO_TIMEOUT.contains(&route.as_str()) => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let started = tokio::time::Instant::now();
+    let response = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, next.run(req)).await {
+            Ok(response) => response,
+            Err(_) => {
+                tracing::warn!(%method, route, ?timeout, "request timed out");
+                let body = json!({
+                    "error": "timeout",
+                    "message": "the request took too long and was abandoned",
+                });
+                return (StatusCode::GATEWAY_TIMEOUT, axum::Json(body)).into_response();
+            }
+        },
+        None => next.run(req).await,
+    };
+    let elapsed = started.elapsed();
+    let slow = st.config.slow_request_ms;
+    if slow > 0 && elapsed >= Duration::from_millis(slow) && route != crate::events::PATH {
+        tracing::warn!(
+            %method,
+            route,
+            duration_ms = elapsed.as_millis() as u64,
+            status = response.status().as_u16(),
+            "slow request"
+        );
+    }
+    response
+}
+
+/**
+ * Response headers hardening browsers against sniffing, framing and leaks
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SecurityHeaders {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SecurityHeaders {
+    /// Headers from the config; Strict-Transport-Security only when `tls` is on.
+    pub fn from_config(config: &ServerConfig, tls: bool) -> anyhow::Result<Self> {
+        let mut headers = vec![(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        )];
+        let configured = [
+            (
+                header::X_FRAME_OPTIONS,
+                "frame_options",
+                &config.frame_options,
+            ),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                "content_security_policy",
+                &config.content_security_policy,
+            ),
+            (
+                header::REFERRER_POLICY,
+                "referrer_policy",
+                &config.referrer_policy,
+            ),
+        ];
+        for (name, field, value) in configured {
+            if !value.is_empty() {
+                let value = HeaderValue::from_str(value)
+                    .with_context(|| format!("config field `{field}`: invalid header value"))?;
+                headers.push((name, value));
+            }
+        }
+        if tls && config.hsts_max_age_secs > 0 {
+            let hsts = format!("max-age={}", config.hsts_max_age_secs);
+            headers.push((
+                header::STRICT_TRANSPORT_SECURITY,
+                HeaderValue::from_str(&hsts)?,
+            ));
+        }
+        Ok(Self { headers })
+    }
+
+    fn apply(&self, target: &mut HeaderMap) {
+        for (name, value) in &self.headers {
+            if !target.contains_key(name) {
+                target.insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Middleware adding the security headers to every response.
+pub async fn security_headers(
+    State(headers): State<Arc<SecurityHeaders>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let mut response = next.run(req).await;
+    headers.apply(response.headers_mut());
+    response
+}
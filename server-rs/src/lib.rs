@@ -0,0 +1,189 @@
+/**
+ * Raspi Todo engine
+ *
+ * Library half of the server: everything except process setup lives here,
+ * so the todo engine can be embedded in another binary or exercised
+ * without HTTP. The usual entry points are:
+ * - db::init_pool        open (and migrate) the SQLite database
+ * - services             TodoService / CategoryService business logic
+ * - repository           pluggable storage behind the services
+ * - AppState::new + app  the complete Axum application
+ * - outbox               publishes todo/category events; spawn `state.outbox.run()`
+ *
+ * main.rs is a thin binary that reads the environment, starts the optional
+ * background integrations and serves `app()`.
+ */
+// Module declarations - Similar to #include in C++, but with better dependency management
+pub mod account; // The caller's own account: export archive and erasure
+pub mod acme; // Optional Let's Encrypt certificate automation
+pub mod activity; // Per-todo activity feed (changes and comments)
+pub mod admin; // Admin/introspection endpoints
+pub mod attachments; // Files attached to todos (receipts, photos)
+pub mod audit; // Who changed what: audit log and history endpoints
+pub mod backups; // Timestamped database backups with retention
+pub mod capabilities; // Feature discovery and deprecation notices
+pub mod checklist; // Checklist entries inside a todo
+pub mod comments; // Comments on todos
+pub mod config; // config.toml + environment settings with validation
+pub mod crypto; // Optional AES-GCM encryption of notes and file names at rest
+pub mod db; // Database connection and initialization
+pub mod ddns; // Optional dynamic DNS updater
+pub mod email; // SMTP reminders and daily digest
+pub mod error; // Error handling and custom error types
+pub mod escalation; // Priority/tag escalation of overdue todos
+pub mod etag; // ETags and If-None-Match (304) for polling clients
+pub mod events; // Long-polling fallback for the WebSocket feed
+pub mod filter; // Compact ?filter= expressions (status:todo AND due<+7d)
+pub mod geo; // Todo locations and GeoJSON map data
+pub mod idempotency; // Idempotency-Key replay for retried POSTs
+pub mod imports; // Chunked, resumable bulk imports (JSON, Todoist)
+pub mod inbound; // Inbound webhook endpoint for quick capture
+pub mod jobs; // Quiet-hours/thermal aware heavy job scheduler
+pub mod kiosk; // Centrally managed wall display rotation
+pub mod layers; // Request IDs, panic recovery, timeouts, security headers
+pub mod links; // "Blocks" dependencies between todos
+pub mod logging; // Runtime log level changes
+pub mod maintenance; // Export, backup and seed tasks for the CLI
+pub mod members; // Category membership and todo assignment
+pub mod metrics; // Prometheus /metrics endpoint
+pub mod model; // Data models/structs (like C++ classes)
+pub mod mqtt; // Optional MQTT bridge (events out, commands in)
+pub mod notes; // Collaborative note editing with operational patches
+pub mod notifications; // Per-user notifications and @mentions
+pub mod notify; // Push notification channels (ntfy, Gotify)
+pub mod oidc; // Optional OpenID Connect sign-in
+pub mod outbox; // Transactional outbox publishing todo/category events
+pub mod pagination; // ?limit=&offset= paging with X-Total-Count and Link headers
+pub mod pomodoro; // Shared pomodoro clock bound to a todo
+pub mod portmap; // Optional UPnP/NAT-PMP router port mapping
+pub mod presence; // Who is connected over WebSocket
+pub mod projects; // Projects (independent boards)
+pub mod read_only; // Runtime read-only/maintenance mode
+pub mod reminders; // Due-soon/overdue reminder scheduler
+pub mod repository; // Storage traits with SQLite and in-memory backends
+pub mod routes; // HTTP route handlers (like controller classes in C++)
+pub mod services; // Business logic shared by HTTP, MQTT and bots
+pub mod sessions; // Device sessions with rotating refresh tokens
+pub mod settings; // Per-user preferences (timezone, theme, ...)
+pub mod shares; // Secret read-only links to a filtered list
+pub mod simple; // Server-rendered HTML fallback UI at /simple
+pub mod stats; // Completion statistics
+pub mod statuses; // Custom workflow statuses
+pub mod system; // Host health: CPU temperature, load, memory, free disk
+pub mod systemd; // sd_notify readiness and watchdog pings
+pub mod telegram; // Optional Telegram bot (add/list/complete todos)
+pub mod timer; // Time tracking (start/stop timers, reports)
+pub mod timezone; // Household and per-user time zones for calendar days
+pub mod tls; // HTTPS with a static certificate and HTTP redirect
+pub mod users; // Household members and notification preferences
+pub mod versions; // /api/v1 prefix; unversioned /api/... as a deprecated alias
+pub mod webhooks; // Outgoing webhooks with per-hook event filters
+pub mod ws; // WebSocket handling for real-time communication
+
+use axum::{
+    Router,
+    extract::{DefaultBodyLimit, Query, State, WebSocketUpgrade},
+    middleware,
+    response::Response,
+    routing::get,
+};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::CompressionLayer,
+    cors::CorsLayer,
+    limit::RequestBodyLimitLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+
+pub use routes::AppState;
+pub use services::{CategoryService, TodoService};
+
+/// Request body limit unless configured otherwise (bulk imports need a few MiB).
+pub const DEFAULT_BODY_LIMIT: usize = 8 * 1024 * 1024;
+
+/**
+ * WebSocket handler route wrapper
+ *
+ * This function adapts our WebSocket handler to work with Axum's routing system.
+ * It extracts the application state and passes it to the WebSocket handler.
+ * `?project_id=` limits project-specific events to one project; the
+ * connection opens with a snapshot unless `?snapshot=false`.
+ *
+ * Pattern: Adapter pattern - adapting incompatible interfaces
+ */
+async fn ws_handler_route(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(scope): Query<ws::WsScope>,
+) -> Response {
+    ws::ws_snapshot_handler(ws, state, scope).await
+}
+
+/**
+ * Build the application router (REST API + WebSocket)
+ *
+ * Static file serving is left to the caller.
+ */
+pub fn app(state: AppState) -> Router {
+    app_with_cors(state, CorsLayer::very_permissive())
+}
+
+/**
+ * Same as app(), with an explicit CORS policy (see ServerConfig::cors_layer)
+ */
+pub fn app_with_cors(state: AppState, cors: CorsLayer) -> Router {
+    app_with_options(state, cors, DEFAULT_BODY_LIMIT)
+}
+
+/**
+ * Same as app_with_cors(), with an explicit request body limit in bytes
+ */
+pub fn app_with_options(state: AppState, cors: CorsLayer, max_body_bytes: usize) -> Router {
+    let v1 = Router::new()
+        .merge(routes::api_router()) // Mount API routes (REST endpoints)
+        .route("/ws/updates", get(ws_handler_route)) // WebSocket endpoint
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            read_only::reject_writes,
+        )) // 503 for writes in read-only mode
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin::identify,
+        )) // Admin token or admin session, checked by admin::require
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            sessions::authenticate,
+        )) // Bearer access tokens act as their member (401 when invalid)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            layers::time_requests,
+        )) // 504 for stuck handlers, slow request warnings
+        .with_state(state.clone()); // Inject shared state
+    let mut app = Router::new()
+        .merge(versions::mount(1, v1.clone())) // /api/v1/...
+        .merge(v1); // Deprecated unversioned alias /api/..., and /ws/updates
+    if state.config.simple_ui {
+        app = app.merge(
+            simple::router()
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    read_only::reject_writes,
+                ))
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    layers::time_requests,
+                ))
+                .with_state(state),
+        ); // Server-rendered fallback UI at /simple
+    }
+    app.layer(CatchPanicLayer::custom(layers::panic_response)) // JSON 500 instead of a dropped connection
+        .layer(middleware::from_fn(capabilities::deprecation_headers)) // Deprecation/Sunset headers
+        .layer(DefaultBodyLimit::disable()) // Replaced by the configurable limit below
+        .layer(RequestBodyLimitLayer::new(max_body_bytes)) // 413 for oversized bodies
+        .layer(CompressionLayer::new()) // gzip/brotli JSON responses when the client accepts it
+        .layer(cors) // Enable CORS for web browsers
+        .layer(TraceLayer::new_for_http().make_span_with(layers::request_span)) // Add HTTP request logging
+        .layer(PropagateRequestIdLayer::x_request_id()) // Echo x-request-id in responses
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid)) // Assign one unless the client sent it
+}
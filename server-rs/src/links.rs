@@ -0,0 +1,171 @@
+/**
+ * Dependency links between todos ("A blocks B")
+ *
+ * A todo is blocked while at least one of its blockers is still open.
+ * Links that would form a cycle are rejected. When a blocker is completed
+ * (or deleted) and that was the last open blocker, TodoService broadcasts
+ * `todo.unblocked` with the freed todo.
+ *
+ * - GET    /api/todos/{id}/links          {"blocks": [todo], "blocked_by": [todo]}
+ * - POST   /api/todos/{id}/links          {"blocks": "<id>"} or {"blocked_by": "<id>"}
+ * - DELETE /api/todos/{id}/links/{other}  remove the link in either direction
+ * - GET    /api/todos?blocked=true|false  only (un)blocked todos
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{delete, get},
+};
+use chrono::Utc;
+use serde_json::json;
+
+use crate::{
+    audit::Actor,
+    db::{SqlitePool, retry_busy},
+    error::{ApiError, ApiResult, JsonBody},
+    model::TodoLinkCreate,
+    routes::AppState,
+};
+
+/**
+ * Storage for the todo_links table
+ */
+#[derive(Clone)]
+pub struct TodoLinks {
+    pool: SqlitePool,
+}
+
+impl TodoLinks {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Ids of the todos blocking `id`.
+    pub async fn blockers(&self, id: &str) -> ApiResult<Vec<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT blocker_id FROM todo_links WHERE blocked_id=?1")
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Ids of the todos `id` blocks.
+    pub async fn dependents(&self, id: &str) -> ApiResult<Vec<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT blocked_id FROM todo_links WHERE blocker_id=?1")
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Every (blocker, blocked) pair.
+    pub async fn all(&self) -> ApiResult<Vec<(String, String)>> {
+        Ok(
+            sqlx::query_as("SELECT blocker_id, blocked_id FROM todo_links")
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Add `blocker` -> `blocked`; adding an existing link is a no-op.
+    pub async fn add(&self, blocker: &str, blocked: &str) -> ApiResult<()> {
+        if blocker == blocked {
+            return Err(ApiError::BadRequest("a todo cannot block itself".into()));
+        }
+        // Check and insert under the write lock (IMMEDIATE), so two
+        // concurrent links cannot each pass the check and close a cycle
+        retry_busy(|| async {
+            let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+            // Cycle if `blocker` is already reachable from `blocked`
+            let cycle: Option<i64> = sqlx::query_scalar(
+                r#"
+                WITH RECURSIVE reach(id) AS (
+                    SELECT blocked_id FROM todo_links WHERE blocker_id = ?1
+                    UNION
+                    SELECT l.blocked_id FROM todo_links l JOIN reach r ON l.blocker_id = r.id
+                )
+                SELECT 1 FROM reach WHERE id = ?2 LIMIT 1
+            "#,
+            )
+            .bind(blocked)
+            .bind(blocker)
+            .fetch_optional(&mut *tx)
+            .await?;
+            if cycle.is_some() {
+                return Err(ApiError::BadRequest(format!(
+                    "`{blocker}` already depends on `{blocked}`; the link would form a cycle"
+                )));
+            }
+            sqlx::query(
+                "INSERT OR IGNORE INTO todo_links (blocker_id, blocked_id, created_at) VALUES (?1,?2,?3)",
+            )
+            .bind(blocker)
+            .bind(blocked)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove the link between `a` and `b` in either direction.
+    pub async fn remove(&self, a: &str, b: &str) -> ApiResult<bool> {
+        let res = sqlx::query(
+            r#"
+            DELETE FROM todo_links
+            WHERE (blocker_id=?1 AND blocked_id=?2) OR (blocker_id=?2 AND blocked_id=?1)
+        "#,
+        )
+        .bind(a)
+        .bind(b)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/todos/{id}/links", get(list_links).post(add_link))
+        .route("/api/todos/{id}/links/{other}", delete(remove_link))
+}
+
+async fn list_links(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let (blocks, blocked_by) = st.todos.links_of(&id).await?;
+    Ok(Json(json!({"blocks": blocks, "blocked_by": blocked_by})))
+}
+
+async fn add_link(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<TodoLinkCreate>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let (blocker, blocked) = match (body.blocks, body.blocked_by) {
+        (Some(other), None) => (id, other),
+        (None, Some(other)) => (other, id),
+        _ => {
+            return Err(ApiError::BadRequest(
+                "give exactly one of `blocks` or `blocked_by`".into(),
+            ));
+        }
+    };
+    st.todos.as_actor(actor).link(&blocker, &blocked).await?;
+    Ok(Json(json!({"blocker_id": blocker, "blocked_id": blocked})))
+}
+
+async fn remove_link(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((id, other)): Path<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    st.todos.as_actor(actor).unlink(&id, &other).await?;
+    Ok(Json(json!({"ok": true})))
+}
@@ -0,0 +1,93 @@
+/**
+ * Runtime log level
+ *
+ * The binary installs its EnvFilter behind a reload layer (see
+ * LogControl::new), so the filter can be swapped while running:
+ *
+ * - GET /api/admin/log-level  current directives
+ * - PUT /api/admin/log-level  {"level": "debug,tower_http=debug"}
+ *
+ * Both need admin access (see admin.rs). Directives use the RUST_LOG
+ * syntax. Changes last until the next change or restart, when RUST_LOG
+ * applies again.
+ */
+use std::sync::RwLock;
+
+use anyhow::anyhow;
+use axum::{Json, Router, extract::State, middleware, routing::get};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+use crate::{
+    admin,
+    error::{ApiError, ApiResult, JsonBody},
+    routes::AppState,
+};
+
+/**
+ * Handle to the live log filter
+ */
+#[derive(Default)]
+pub struct LogControl {
+    handle: Option<reload::Handle<EnvFilter, Registry>>, // None when the embedder owns logging
+    current: RwLock<String>,                             // Directives in effect
+}
+
+impl LogControl {
+    /// Reloadable filter layer for the registry, and the control for it.
+    pub fn new(directives: &str) -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new(directives));
+        let control = Self {
+            handle: Some(handle),
+            current: RwLock::new(directives.to_string()),
+        };
+        (layer, control)
+    }
+
+    pub fn current(&self) -> String {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replace the filter; invalid directives leave the old one in place.
+    pub fn set(&self, directives: &str) -> anyhow::Result<()> {
+        let handle = self
+            .handle
+            .as_ref()
+            .ok_or_else(|| anyhow!("logging is not managed by this server"))?;
+        let filter = EnvFilter::try_new(directives)?;
+        handle.reload(filter)?;
+        *self.current.write().unwrap() = directives.to_string();
+        Ok(())
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/log-level", get(get_level).put(put_level))
+        .route_layer(middleware::from_fn(admin::require))
+}
+
+#[derive(Serialize, Deserialize)]
+struct LogLevel {
+    level: String, // RUST_LOG style directives
+}
+
+async fn get_level(State(st): State<AppState>) -> Json<LogLevel> {
+    Json(LogLevel {
+        level: st.log.current(),
+    })
+}
+
+async fn put_level(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<LogLevel>,
+) -> ApiResult<Json<LogLevel>> {
+    let level = body.level.trim();
+    st.log
+        .set(level)
+        .map_err(|e| ApiError::BadRequest(format!("invalid log level `{level}`: {e}")))?;
+    tracing::info!(level, "log level changed");
+    Ok(Json(LogLevel {
+        level: st.log.current(),
+    }))
+}
@@ -1,6 +1,9 @@
 /**
  * Main entry point for the Rust Todo Server
  *
+ * Thin binary around the server_rs library (see lib.rs): reads the
+ * environment, starts background integrations and serves the app.
+ *
  * This file sets up a modern async web server using:
  * - Axum: High-performance web framework (similar to Express.js but faster)
  * - Tokio: Async runtime (like async/await in modern C++)
@@ -14,50 +17,304 @@
  * - Data Access Layer (Database)
  * - Cross-cutting concerns (Logging, CORS, WebSocket)
  */
-// Module declarations - Similar to #include in C++, but with better dependency management
-mod db; // Database connection and initialization
-mod error; // Error handling and custom error types
-mod model; // Data models/structs (like C++ classes)
-mod routes; // HTTP route handlers (like controller classes in C++)
-mod ws; // WebSocket handling for real-time communication
+use std::{env, io::Write, path::PathBuf, sync::Arc};
 
-use std::{env, path::PathBuf, sync::Arc};
+// Command line parsing
+use clap::{Parser, Subcommand, ValueEnum};
 
 // Axum framework imports - Web server components
 use axum::{
-    Router,                             // Application router (like URL dispatcher)
-    extract::{State, WebSocketUpgrade}, // Dependency injection and WebSocket upgrade
-    response::Response,                 // HTTP response type
-    routing::get,                       // HTTP GET route helper
-};
+    Router,
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::{self, Next},
+    response::Response,
+}; // Application router (like URL dispatcher)
 
 // Tower HTTP middleware - Similar to middleware in Express.js
 use tower_http::{
-    cors::CorsLayer,                 // Cross-Origin Resource Sharing
+    compression::CompressionLayer,   // On-the-fly gzip/brotli fallback
     services::{ServeDir, ServeFile}, // Static file serving
-    trace::TraceLayer,               // HTTP request/response logging
 };
 
 // Structured logging - Better than printf debugging
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt,
+};
 
-// Internal module imports
-use crate::{
-    db::init_pool,                  // Database connection pool
-    routes::{AppState, api_router}, // API routes and shared application state
-    ws::{WsHub, ws_handler},        // WebSocket handling
+// Library imports
+use server_rs::{
+    acme::{self, AcmeConfig, AcmeManager}, // ACME certificate automation
+    attachments::{AttachmentCleanupJob, AttachmentStore}, // Upload storage and cleanup
+    backups::BackupStore,                  // Timestamped backups with retention
+    config::{Listen, ServerConfig},        // config.toml + env settings
+    crypto::{self, Cipher},                // Field-level encryption at rest
+    db::{self, Backend, DatabaseCorrupt, DatabaseKey, IntegrityCheck, init_pool_with_options}, // Database connection pool
+    ddns::{DdnsConfig, DdnsUpdater}, // Dynamic DNS background task
+    email::{self, EmailConfig, EmailNotifier, Mailer}, // SMTP notifications
+    escalation,                      // Overdue priority/tag escalation
+    imports,                         // Bulk import jobs
+    jobs::{AutoArchiveJob, JobPolicy, JobScheduler, VacuumJob}, // Heavy background jobs
+    layers::{self, SecurityHeaders}, // Security response headers
+    logging::LogControl,             // Reloadable log filter
+    maintenance,                     // Export, backup and seed commands
+    mqtt::{self, MqttConfig},        // MQTT bridge settings
+    notify,                          // Push notification channels
+    oidc::{OidcClient, OidcConfig},  // OpenID Connect sign-in
+    portmap::{PortMapConfig, PortMapper}, // Router port mapping
+    reminders::{self, ReminderConfig}, // Reminder scheduler settings
+    repository::{MemoryCategoryRepository, MemoryTodoRepository}, // Demo-mode storage
+    routes::AppState,                // Shared application state
+    services::Workflow,              // Allowed status transitions
+    systemd,                         // sd_notify readiness/watchdog
+    telegram::{self, TelegramConfig}, // Telegram bot settings
+    users,                           // Privacy export/anonymize
+    webhooks,                        // Outgoing webhook dispatcher
+    ws::WsHub,                       // WebSocket broadcast hub
 };
 
 /**
- * WebSocket handler route wrapper
+ * Resolves when the process receives Ctrl+C or SIGTERM (systemd stop)
  *
- * This function adapts our WebSocket handler to work with Axum's routing system.
- * It extracts the application state and passes it to the WebSocket handler.
+ * Used for graceful shutdown so cleanup (e.g. removing router port
+ * mappings) runs before the process exits.
+ */
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("shutdown signal received");
+    systemd::stopping();
+}
+
+/// shutdown_signal, then close the WebSocket connections: they never end on
+/// their own and would keep the graceful shutdown waiting.
+async fn shutdown(hub: Arc<WsHub>) {
+    shutdown_signal().await;
+    hub.close_all();
+}
+
+/**
+ * Serve the application over HTTPS with an ACME-managed certificate
  *
- * Pattern: Adapter pattern - adapting incompatible interfaces
+ * For http-01 a plain HTTP listener is started first so the ACME server can
+ * reach the challenge tokens. It serves nothing else: every other request
+ * is redirected to HTTPS, so the API never travels in plaintext over the
+ * port that has to be open to the internet.
+ * The certificate is renewed in the background and hot reloaded.
  */
-async fn ws_handler_route(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws_handler(ws, state.hub).await
+async fn serve_acme(
+    config: AcmeConfig,
+    app: Router,
+    addr: std::net::SocketAddr,
+    hub: Arc<WsHub>,
+) -> anyhow::Result<()> {
+    // axum-server is built without a default crypto provider; use ring
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let (resolver, challenge_router) = acme::build_resolver(&config)?;
+    if let Some(challenges) = challenge_router {
+        let http_addr = std::net::SocketAddr::from(([0, 0, 0, 0], config.http_port));
+        let listener = tokio::net::TcpListener::bind(http_addr).await?;
+        let http_app = challenges.merge(server_rs::tls::redirect_router(addr.port()));
+        tracing::info!(?http_addr, "ACME http-01 listener started");
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, http_app).await {
+                tracing::error!(error = %e, "ACME http listener stopped");
+            }
+        });
+    }
+
+    let manager = Arc::new(AcmeManager::new(config, resolver));
+    let tls = manager.load_or_issue().await?;
+    tokio::spawn(manager.renewal_loop(tls.clone()));
+
+    serve_rustls(app, addr, tls, hub).await
+}
+
+/**
+ * Serve the application over HTTPS with certificate files from the config
+ *
+ * Optionally starts a plain HTTP listener that redirects to HTTPS.
+ */
+async fn serve_tls(
+    cert: String,
+    key: String,
+    redirect_port: Option<u16>,
+    app: Router,
+    addr: std::net::SocketAddr,
+    hub: Arc<WsHub>,
+) -> anyhow::Result<()> {
+    // axum-server is built without a default crypto provider; use ring
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let tls = server_rs::tls::load(&cert, &key).await?;
+    tokio::spawn(server_rs::tls::reload_loop(tls.clone(), cert, key));
+
+    if let Some(http_port) = redirect_port {
+        let http_addr = std::net::SocketAddr::from(([0, 0, 0, 0], http_port));
+        let listener = tokio::net::TcpListener::bind(http_addr).await?;
+        let redirect = server_rs::tls::redirect_router(addr.port());
+        tracing::info!(?http_addr, "HTTP -> HTTPS redirect listener started");
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, redirect).await {
+                tracing::error!(error = %e, "redirect listener stopped");
+            }
+        });
+    }
+
+    serve_rustls(app, addr, tls, hub).await
+}
+
+/**
+ * Serve plain HTTP on a Unix domain socket (for reverse proxies)
+ *
+ * A stale socket file from an unclean exit is replaced; the socket is
+ * removed again on shutdown.
+ */
+async fn serve_unix(
+    app: Router,
+    path: &std::path::Path,
+    mode: u32,
+    hub: Arc<WsHub>,
+) -> anyhow::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    tracing::info!(socket = %path.display(), mode = format!("{mode:o}"), "server listening (unix)");
+    systemd::ready();
+
+    let result = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown(hub))
+        .await;
+    let _ = std::fs::remove_file(path);
+    Ok(result?)
+}
+
+/**
+ * HTTPS listener with graceful shutdown, shared by ACME and static TLS
+ */
+async fn serve_rustls(
+    app: Router,
+    addr: std::net::SocketAddr,
+    tls: axum_server::tls_rustls::RustlsConfig,
+    hub: Arc<WsHub>,
+) -> anyhow::Result<()> {
+    let handle = axum_server::Handle::new();
+    let draining = handle.clone();
+    tokio::spawn(async move {
+        shutdown(hub).await;
+        draining.graceful_shutdown(Some(std::time::Duration::from_secs(10)));
+    });
+
+    // axum-server binds inside serve(); report readiness once it has
+    let listening = handle.clone();
+    tokio::spawn(async move {
+        if listening.listening().await.is_some() {
+            systemd::ready();
+        }
+    });
+
+    tracing::info!(?addr, "server listening (https)");
+    axum_server::bind_rustls(addr, tls)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .await?;
+    Ok(())
+}
+
+/**
+ * Command line interface
+ *
+ * Without a subcommand the binary serves, so existing systemd units and
+ * Docker images keep working unchanged.
+ */
+#[derive(Parser)]
+#[command(
+    name = "todo-server",
+    version,
+    about = "Raspi Todo server and admin tool",
+    long_about = None
+)]
+struct Cli {
+    /// Config file (default: ./config.toml when present)
+    #[arg(long, global = true, env = "CONFIG_FILE")]
+    config: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Create or upgrade the database schema, then exit
+    Migrate,
+    /// Export todos (and categories) to stdout or a file
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Output file (default: stdout)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Include soft-deleted todos
+        #[arg(long)]
+        include_deleted: bool,
+    },
+    /// Copy the SQLite database to a new file, safe while the server runs
+    Backup {
+        /// Output file (default: a timestamped file in backups_dir, with retention)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Insert a few sample todos (demos, screenshots)
+    Seed,
+    /// Encrypt notes, attachment names and history stored before ENCRYPTION_KEY was set
+    Encrypt,
+    /// Change the SQLCipher key of the SQLite database (server stopped; new key
+    /// from DATABASE_NEW_KEY or asked for)
+    Rekey {
+        /// Write an encrypted copy here instead, e.g. to encrypt a plain database
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Export a user's data, then scrub it (housemate moving out)
+    Anonymize {
+        #[arg(long)]
+        user: String,
+        /// Hand the user's webhooks to this user instead of deleting them
+        #[arg(long)]
+        reassign_to: Option<String>,
+        /// Where to write the export (default: stdout)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Json,
+    Csv,
 }
 
 /**
@@ -71,69 +328,487 @@ async fn ws_handler_route(ws: WebSocketUpgrade, State(state): State<AppState>) -
  */
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+
     // Initialize structured logging subsystem
     // This is more sophisticated than std::cout - provides leveled, filterable logs
+    // Admin commands log to stderr so `export` output on stdout stays clean
+    let writer = match command {
+        Command::Serve => BoxMakeWriter::new(std::io::stdout),
+        _ => BoxMakeWriter::new(std::io::stderr),
+    };
+    // Use RUST_LOG environment variable, default to "info" level;
+    // reloadable so PUT /api/admin/log-level can change it at runtime
+    let (filter, log) =
+        LogControl::new(&env::var("RUST_LOG").unwrap_or_else(|_| "info,tower_http=info".into()));
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            // Use RUST_LOG environment variable, default to "info" level
-            env::var("RUST_LOG").unwrap_or_else(|_| "info,tower_http=info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer()) // Human-readable console output
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(writer)) // Human-readable console output
         .init();
 
-    // Configuration from environment variables (12-factor app methodology)
+    // Configuration from config.toml with environment overrides (12-factor app methodology)
     // Similar to reading from config files, but more deployment-friendly
-    let port: u16 = env::var("PORT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(8000);
-    let db_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://./data/todos.db".into());
-    let static_dir = env::var("STATIC_DIR").unwrap_or_else(|_| "../server/static".into());
+    let config = ServerConfig::load_from(cli.config)?;
+
+    match command {
+        Command::Serve => serve(config, Arc::new(log)).await,
+        Command::Migrate => {
+            // Opening the databases runs the idempotent migrations
+            open_state(&config).await?;
+            tracing::info!("database schema is up to date");
+            Ok(())
+        }
+        Command::Export {
+            format,
+            out,
+            include_deleted,
+        } => {
+            let state = open_state(&config).await?;
+            let mut writer: Box<dyn Write> = match &out {
+                Some(path) => Box::new(std::fs::File::create(path)?),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            match format {
+                ExportFormat::Json => {
+                    let doc = maintenance::export_json(&state, include_deleted).await?;
+                    serde_json::to_writer_pretty(&mut writer, &doc)?;
+                    writeln!(writer)?;
+                }
+                ExportFormat::Csv => {
+                    maintenance::export_csv(&state, include_deleted, &mut writer).await?
+                }
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        Command::Backup { out } => {
+            if Backend::from_url(&config.database_url)? != Backend::Sqlite {
+                anyhow::bail!("backup only covers SQLite databases; use pg_dump for Postgres");
+            }
+            let state = open_state(&config).await?;
+            match out {
+                Some(out) => {
+                    maintenance::backup_sqlite(&state.pool, &out).await?;
+                    tracing::info!(out = %out.display(), "backup written");
+                }
+                None => {
+                    let backup = state.backups.create(&state.pool).await?;
+                    tracing::info!(backup = %backup.name, size = backup.size, "backup written");
+                }
+            }
+            Ok(())
+        }
+        Command::Anonymize {
+            user,
+            reassign_to,
+            out,
+        } => {
+            let state = open_state(&config).await?;
+            // Export is returned before anything is scrubbed; write it out first
+            let export = users::export_user(&state.pool, &user).await?;
+            let json = serde_json::to_string_pretty(&export)?;
+            match &out {
+                Some(path) => std::fs::write(path, json + "\n")?,
+                None => println!("{json}"),
+            }
+            users::anonymize_user(&state.pool, &user, reassign_to.as_deref()).await?;
+            Ok(())
+        }
+        Command::Encrypt => {
+            let state = open_state(&config).await?;
+            let done = maintenance::encrypt_existing(&state).await?;
+            tracing::info!(
+                todos = done.todos,
+                attachments = done.attachments,
+                audit_entries = done.audit_entries,
+                "existing rows encrypted"
+            );
+            Ok(())
+        }
+        Command::Rekey { out } => {
+            let url = match Backend::from_url(&config.database_url)? {
+                Backend::Sqlite => config.database_url.as_str(),
+                #[cfg(feature = "postgres")]
+                Backend::Postgres => config.local_database_url.as_str(),
+            };
+            let key = DatabaseKey::resolve(url)?;
+            let new_key = DatabaseKey::replacement()?;
+            // One connection: after PRAGMA rekey others would still use the old key
+            let check = IntegrityCheck::parse(&config.integrity_check).unwrap_or_default();
+            let pool = init_pool_with_options(url, 1, false, check, key.as_ref()).await?;
+            match (out, key) {
+                (Some(out), _) => {
+                    db::export_encrypted(&pool, &out, &new_key).await?;
+                    tracing::info!(out = %out.display(), "encrypted copy written");
+                }
+                (None, Some(_)) => {
+                    db::rekey(&pool, &new_key).await?;
+                    tracing::info!("database key changed; update DATABASE_KEY");
+                }
+                (None, None) => anyhow::bail!(
+                    "the database is not encrypted; write an encrypted copy with --out"
+                ),
+            }
+            pool.close().await;
+            Ok(())
+        }
+        Command::Seed => {
+            let state = open_state(&config).await?;
+            let created = maintenance::seed_demo(&state).await?;
+            tracing::info!(count = created.len(), "sample todos created");
+            Ok(())
+        }
+    }
+}
+
+/**
+ * Open the configured databases (running migrations) and build the state
+ */
+async fn open_state(config: &ServerConfig) -> anyhow::Result<AppState> {
+    let db_url = config.database_url.as_str();
+
+    // Field-level encryption (ENCRYPTION_KEY / ENCRYPTION_KEY_FILE), before anything is read
+    if let Some(cipher) = Cipher::from_env()? {
+        crypto::install(cipher);
+        tracing::info!("encrypting todo notes and attachment names at rest");
+    }
 
     // Ensure data directory exists (similar to mkdir -p)
     std::fs::create_dir_all("./data").ok();
 
     // Initialize database connection pool
     // Connection pooling is crucial for performance - reuses connections
-    let pool = init_pool(&db_url).await?;
+    // With a Postgres DATABASE_URL only todos/categories live there; server-local
+    // state (users, tokens, reminder log, settings) stays in LOCAL_DATABASE_URL
+    let backend = Backend::from_url(db_url)?;
+    let (pool, read_only) = match backend {
+        Backend::Sqlite => open_sqlite(db_url, config).await?,
+        #[cfg(feature = "postgres")]
+        Backend::Postgres => open_sqlite(&config.local_database_url, config).await?,
+    };
 
     // Create WebSocket broadcast hub wrapped in Arc (Atomic Reference Counting)
     // Arc is similar to std::shared_ptr in C++ - allows safe sharing between threads
-    let hub = Arc::new(WsHub::new());
+    let hub = Arc::new(
+        WsHub::with_capacity(config.ws_buffer_size)
+            .with_limits(config.ws_max_connections, config.ws_send_queue),
+    );
 
     // Application state - shared across all request handlers
     // This is dependency injection pattern - all handlers get access to DB and WebSocket
-    let state = AppState {
-        pool,
-        hub: hub.clone(),
+    // STORAGE=memory keeps todos/categories in RAM (demo mode, nothing persisted)
+    let mut state = if env::var("STORAGE").is_ok_and(|s| s == "memory") {
+        tracing::warn!("STORAGE=memory: todos and categories are not persisted");
+        let todos = MemoryTodoRepository::new();
+        let categories = MemoryCategoryRepository::with_defaults().with_outbox_of(&todos);
+        let mut state = AppState::with_repositories(
+            pool.clone(),
+            hub.clone(),
+            Arc::new(todos),
+            Arc::new(categories),
+        );
+        state.integrations.storage = "memory";
+        state
+    } else {
+        match backend {
+            Backend::Sqlite => AppState::new(pool.clone(), hub.clone()),
+            #[cfg(feature = "postgres")]
+            Backend::Postgres => {
+                let pg = server_rs::db::init_pg_pool(db_url).await?;
+                tracing::info!("storing todos and categories in Postgres");
+                let mut state = AppState::with_repositories(
+                    pool.clone(),
+                    hub.clone(),
+                    Arc::new(server_rs::repository::PgTodoRepository::new(pg.clone())),
+                    Arc::new(server_rs::repository::PgCategoryRepository::new(pg)),
+                );
+                state.integrations.storage = "postgres";
+                state
+            }
+        }
     };
+    state.config = Arc::new(config.clone());
+    state.integrations.read_only = read_only;
+    if read_only {
+        state
+            .read_only
+            .enable(Some("database integrity check failed".into()));
+    } else if config.read_only {
+        state.read_only.enable(Some("read-only deployment".into()));
+    }
+    state.attachments = Arc::new(AttachmentStore::from_config(config));
+    state.backups = Arc::new(BackupStore::from_config(config)?);
+    state.todos = state.todos.with_workflow(Workflow::from_config(config));
+    Ok(state)
+}
+
+/// Open (and migrate) a SQLite database after the configured integrity check;
+/// with on_corruption = "read-only" a corrupt database is opened read-only
+/// instead (returned flag).
+async fn open_sqlite(url: &str, config: &ServerConfig) -> anyhow::Result<(db::SqlitePool, bool)> {
+    let check = IntegrityCheck::parse(&config.integrity_check).unwrap_or_default();
+    // SQLCipher key: DATABASE_KEY(_FILE), or asked for when the file is encrypted
+    let key = DatabaseKey::resolve(url)?;
+    let pool = init_pool_with_options(
+        url,
+        config.db_pool_size,
+        config.low_write_mode,
+        check,
+        key.as_ref(),
+    )
+    .await;
+    match pool {
+        Err(e) if e.is::<DatabaseCorrupt>() && config.on_corruption == "read-only" => {
+            tracing::error!(error = %e, "database is corrupt, serving it read-only");
+            let pool = db::open_read_only(url, config.db_pool_size, key.as_ref()).await?;
+            Ok((pool, true))
+        }
+        result => Ok((result?, false)),
+    }
+}
+
+/**
+ * Router serving the built frontend
+ *
+ * Unknown paths fall back to index.html for SPA routing. Precompressed
+ * `.br`/`.gz` siblings (created at build time) are served when the client
+ * accepts them; other files are compressed on the fly.
+ */
+fn static_files(dir: PathBuf) -> Router {
+    let index = ServeFile::new(dir.join("index.html"))
+        .precompressed_br()
+        .precompressed_gzip();
+    let svc = ServeDir::new(dir)
+        .precompressed_br()
+        .precompressed_gzip()
+        .not_found_service(index);
+    Router::new()
+        .fallback_service(svc)
+        .layer(CompressionLayer::new())
+        .layer(middleware::from_fn(static_cache_control))
+}
+
+/**
+ * Cache-Control for static files
+ *
+ * Vite puts content-hashed files under /assets/, so they can be cached
+ * for a year; everything else (index.html, icons, manifest) is revalidated
+ * on every load so new deployments show up immediately.
+ */
+async fn static_cache_control(req: Request, next: Next) -> Response {
+    let hashed = req.uri().path().starts_with("/assets/");
+    let mut response = next.run(req).await;
+    // Missing assets must not be cached as errors for a year
+    let value = if hashed && response.status().is_success() {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static(value));
+    response
+}
+
+/**
+ * Run the server with all background integrations until shutdown
+ */
+async fn serve(config: ServerConfig, log: Arc<LogControl>) -> anyhow::Result<()> {
+    let port = config.port;
+    let static_dir = config.static_dir.clone();
+    let mut state = open_state(&config).await?;
+    state.log = log;
+    let pool = state.pool.clone();
+    let hub = state.hub.clone(); // Its connections are closed on shutdown
+
+    // Imports cut short by the last shutdown wait for an explicit resume
+    if !state.integrations.read_only {
+        imports::mark_interrupted(&pool).await?;
+    }
+
+    // Optional dynamic DNS updater running as a background task
+    let ddns = DdnsConfig::from_env()?.map(|config| Arc::new(DdnsUpdater::new(config)));
+    if let Some(updater) = &ddns {
+        tokio::spawn(updater.clone().run());
+    }
+
+    // Reminder scheduler - alerts land in the notification center and the push channels
+    let mut notifiers = notify::notifiers_from_env();
+    state.integrations.push = !notifiers.is_empty();
+    state.notifiers = notifiers.clone(); // Mentions go to the push channels only
+    if let Some(email_config) = EmailConfig::from_env()? {
+        state.integrations.email = true;
+        let mailer = Arc::new(Mailer::new(&email_config)?);
+        notifiers.push(Arc::new(EmailNotifier::new(mailer.clone(), pool.clone())));
+        if let Some(hour) = email_config.digest_hour {
+            email::spawn_digest(
+                mailer,
+                pool.clone(),
+                state.todos.clone(),
+                hour,
+                config.zone(),
+            );
+        }
+    }
+    reminders::spawn(
+        pool.clone(),
+        state.todos.clone(),
+        state.notifications.clone(),
+        notifiers,
+        ReminderConfig::from_config(&config),
+    );
+
+    // Overdue escalation - bumps priority/tags of todos left past their due date
+    escalation::spawn(
+        pool.clone(),
+        state.todos.clone(),
+        config.escalation_rules.clone(),
+    );
+
+    // Optional router port mapping (UPnP/NAT-PMP), renewed in the background
+    let port_mapper =
+        PortMapConfig::from_env(port)?.map(|config| Arc::new(PortMapper::new(config)));
+    if let Some(mapper) = &port_mapper {
+        tokio::spawn(mapper.clone().run());
+    }
+
+    // Periodic WAL checkpoints (cheap, so not deferred like the heavy jobs below)
+    if config.wal_checkpoint_minutes > 0 {
+        db::spawn_wal_checkpoints(
+            pool.clone(),
+            std::time::Duration::from_secs(config.wal_checkpoint_minutes * 60),
+        );
+    }
+
+    // Heavy maintenance jobs, deferred to quiet hours and a cool CPU
+    let mut scheduler = JobScheduler::new(JobPolicy::from_env()?);
+    scheduler.register(Arc::new(VacuumJob::from_config(pool.clone(), &config)));
+    scheduler.register(Arc::new(AttachmentCleanupJob::new(
+        pool.clone(),
+        state.todos.clone(),
+        state.attachments.clone(),
+    )));
+    if let Some(job) = AutoArchiveJob::from_config(state.todos.clone(), &config) {
+        scheduler.register(Arc::new(job));
+    }
+    let scheduler = Arc::new(scheduler);
+    tokio::spawn(scheduler.clone().run());
+
+    state.ddns = ddns;
+    state.port_mapper = port_mapper.clone();
+    state.jobs = Some(scheduler);
+
+    // Scheduled backups (and uploads), status in /api/health and /metrics
+    if state.backups.schedule.is_some() {
+        if state.integrations.storage == "sqlite" {
+            tokio::spawn(state.backups.clone().run(pool.clone()));
+        } else {
+            tracing::warn!("backup_schedule only applies to SQLite storage; ignoring it");
+        }
+    }
+
+    // Todo/category events: publishes what the last run left pending, then follows writes
+    tokio::spawn(state.outbox.clone().run());
+
+    // Wall display rotation clock
+    tokio::spawn(state.kiosk.clone().run());
+
+    // Pomodoro clock (resumes a session left running)
+    tokio::spawn(state.pomodoro.clone().run());
+
+    // Optional MQTT bridge - mirrors hub events and accepts commands
+    if let Some(mqtt_config) = MqttConfig::from_env() {
+        state.integrations.mqtt = true;
+        mqtt::spawn(mqtt_config, state.clone());
+    }
+
+    // systemd watchdog pings while the health self-check passes (WatchdogSec=)
+    systemd::spawn_watchdog(state.clone());
+
+    // Outgoing webhooks (configured at runtime via /api/webhooks)
+    webhooks::spawn(state.clone());
+
+    // Optional OIDC sign-in (members authenticate at an identity provider)
+    if let Some(oidc_config) = OidcConfig::from_env()? {
+        state.integrations.oidc = true;
+        state.oidc = Some(Arc::new(OidcClient::new(oidc_config)));
+    }
+    if !config.admin_users.is_empty() && state.oidc.is_none() {
+        anyhow::bail!(
+            "admin_users needs OIDC sign-in (OIDC_ISSUER): without it anyone can open a \
+             session as any member; use admin_token instead"
+        );
+    }
+
+    // Optional Telegram bot (long polling, no public endpoint needed)
+    if let Some(telegram_config) = TelegramConfig::from_env() {
+        state.integrations.telegram = true;
+        telegram::spawn(telegram_config, state.clone());
+    }
+
+    let acme = AcmeConfig::from_env();
+    if acme.is_some() && config.tls_cert.is_some() {
+        anyhow::bail!("ACME_DOMAINS and tls_cert/TLS_CERT are mutually exclusive");
+    }
+    let tls = acme.is_some() || config.tls_cert.is_some();
+    state.integrations.tls = tls;
 
     // Build the application router
     // This is the main HTTP request dispatcher
-    let mut app = Router::new()
-        .merge(api_router()) // Mount API routes (REST endpoints)
-        .route("/ws/updates", get(ws_handler_route)) // WebSocket endpoint
-        .with_state(state) // Inject shared state
-        .layer(CorsLayer::very_permissive()) // Enable CORS for web browsers
-        .layer(TraceLayer::new_for_http()); // Add HTTP request logging
+    let mut app = server_rs::app_with_options(state, config.cors_layer(), config.max_body_bytes);
 
     // Static file serving (for React frontend)
     // This serves the built React application
     let static_path = PathBuf::from(&static_dir);
     if static_path.exists() {
-        let index = static_path.join("index.html");
-        // Serve static files, fallback to index.html for SPA routing
-        let svc = ServeDir::new(static_path).not_found_service(ServeFile::new(index));
-        app = app.fallback_service(svc);
+        app = app.merge(static_files(static_path));
     }
+    // nosniff, framing, CSP, referrer policy (and HSTS over TLS) on API and static responses
+    let security = SecurityHeaders::from_config(&config, tls)?;
+    app = app.layer(middleware::from_fn_with_state(
+        Arc::new(security),
+        layers::security_headers,
+    ));
 
     // Bind to network address and start the server
     // 0.0.0.0 means listen on all network interfaces
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
-    tracing::info!(?addr, "server listening");
+    let addr = match config.listen() {
+        Listen::Tcp(addr) => addr,
+        Listen::Unix(path) => {
+            if acme.is_some() {
+                anyhow::bail!("ACME needs a TCP listener, not a Unix socket");
+            }
+            serve_unix(app, &path, config.socket_mode(), hub).await?;
+            if let Some(mapper) = &port_mapper {
+                mapper.unmap().await;
+            }
+            return Ok(());
+        }
+    };
+    if let Some(acme_config) = acme {
+        serve_acme(acme_config, app, addr, hub).await?;
+    } else if let (Some(cert), Some(key)) = (config.tls_cert.clone(), config.tls_key.clone()) {
+        serve_tls(cert, key, config.tls_redirect_port, app, addr, hub).await?;
+    } else {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        tracing::info!(?addr, "server listening");
+        systemd::ready();
 
-    // Start the async HTTP server
-    // This is the event loop - similar to io_context.run() in Boost.Asio
-    axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await?;
+        // Start the async HTTP server
+        // This is the event loop - similar to io_context.run() in Boost.Asio
+        // Peer addresses show up in the session device list
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown(hub))
+        .await?;
+    }
+
+    // Server has stopped - give the router its port back
+    if let Some(mapper) = &port_mapper {
+        mapper.unmap().await;
+    }
     Ok(())
 }
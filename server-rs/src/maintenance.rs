@@ -0,0 +1,240 @@
+/**
+ * Offline maintenance tasks
+ *
+ * Used by the `export`, `backup`, `seed` and `encrypt` CLI subcommands so that
+ * routine administration no longer needs hand-written SQL against the
+ * database file. Everything goes through the services, so the same code
+ * works for the SQLite, Postgres and in-memory backends.
+ */
+use std::{io::Write, path::Path};
+
+use anyhow::{Context, anyhow};
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+use crate::{
+    crypto,
+    db::SqlitePool,
+    model::{AuditEntry, Todo, TodoCreate},
+    routes::AppState,
+    services::TodoFilter,
+};
+
+/// All categories and todos as one JSON document.
+pub async fn export_json(
+    state: &AppState,
+    include_deleted: bool,
+) -> anyhow::Result<serde_json::Value> {
+    let filter = TodoFilter {
+        include_deleted,
+        ..Default::default()
+    };
+    Ok(json!({
+        "exported_at": Utc::now(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "categories": state.categories.list().await?,
+        "todos": state.todos.list(&filter).await?,
+    }))
+}
+
+/// Todos as CSV (one row per todo, header first).
+pub async fn export_csv(
+    state: &AppState,
+    include_deleted: bool,
+    out: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let filter = TodoFilter {
+        include_deleted,
+        ..Default::default()
+    };
+    writeln!(
+        out,
+        "id,title,note,status,priority,due_at,tags,category_id,location_name,created_at,updated_at,deleted"
+    )?;
+    for t in state.todos.list(&filter).await? {
+        let fields = [
+            t.id,
+            t.title,
+            t.note.unwrap_or_default(),
+            t.status,
+            t.priority.to_string(),
+            t.due_at.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            t.tags.unwrap_or_default(),
+            t.category_id.unwrap_or_default(),
+            t.location_name.unwrap_or_default(),
+            t.created_at.to_rfc3339(),
+            t.updated_at.to_rfc3339(),
+            t.deleted.to_string(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(out, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field when it contains separators, quotes or newlines.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Consistent copy of a live SQLite database (VACUUM INTO, safe while serving).
+pub async fn backup_sqlite(pool: &SqlitePool, out: &Path) -> anyhow::Result<()> {
+    if out.exists() {
+        return Err(anyhow!("{} already exists", out.display()));
+    }
+    let target = out
+        .to_str()
+        .ok_or_else(|| anyhow!("backup path must be valid UTF-8"))?;
+    sqlx::query("VACUUM INTO ?1")
+        .bind(target)
+        .execute(pool)
+        .await
+        .with_context(|| format!("backup to {} failed", out.display()))?;
+    Ok(())
+}
+
+/// A handful of sample todos spread over the default categories.
+pub async fn seed_demo(state: &AppState) -> anyhow::Result<Vec<Todo>> {
+    let categories = state.categories.list().await?;
+    let category = |name: &str| {
+        categories
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.id.clone())
+    };
+    let now = Utc::now();
+    let samples = [
+        ("Welcome to Raspi Todo", "General", 1, None),
+        (
+            "Prepare weekly report",
+            "Work",
+            2,
+            Some(now + Duration::days(2)),
+        ),
+        (
+            "Call the dentist",
+            "Health",
+            1,
+            Some(now + Duration::days(1)),
+        ),
+        (
+            "Buy milk and bread",
+            "Shopping",
+            0,
+            Some(now + Duration::hours(6)),
+        ),
+        (
+            "Water the plants",
+            "Personal",
+            0,
+            Some(now - Duration::hours(3)),
+        ),
+    ];
+    let mut created = Vec::new();
+    for (title, category_name, priority, due_at) in samples {
+        let todo = state
+            .todos
+            .create(TodoCreate {
+                title: title.into(),
+                priority: Some(priority),
+                due_at,
+                category_id: category(category_name),
+                ..Default::default()
+            })
+            .await?;
+        created.push(todo);
+    }
+    Ok(created)
+}
+
+/**
+ * Rows `encrypt_existing` rewrote
+ */
+#[derive(Debug, Default)]
+pub struct Encrypted {
+    pub todos: u64,         // Notes
+    pub attachments: u64,   // File names
+    pub audit_entries: u64, // History entries mentioning a note
+}
+
+/// Encrypt notes, file names and history stored before a key was set.
+///
+/// Works on the SQLite database; Postgres todos are encrypted as they are
+/// next written. Already encrypted values are left alone, so it can be
+/// run again at any time.
+pub async fn encrypt_existing(state: &AppState) -> anyhow::Result<Encrypted> {
+    if !crypto::enabled() {
+        return Err(anyhow!(
+            "no encryption key; set ENCRYPTION_KEY or ENCRYPTION_KEY_FILE"
+        ));
+    }
+    let pool = &state.pool;
+    let mut done = Encrypted::default();
+    let mut tx = pool.begin().await?;
+    if state.integrations.storage == "sqlite" {
+        let notes: Vec<(String, String)> = sqlx::query_as(
+            "SELECT id, note FROM todos WHERE note IS NOT NULL AND note NOT LIKE 'enc:v1:%'",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for (id, note) in notes {
+            sqlx::query("UPDATE todos SET note=?2 WHERE id=?1")
+                .bind(id)
+                .bind(crypto::seal(&note))
+                .execute(&mut *tx)
+                .await?;
+            done.todos += 1;
+        }
+    }
+
+    let names: Vec<(String, String)> =
+        sqlx::query_as("SELECT id, filename FROM attachments WHERE filename NOT LIKE 'enc:v1:%'")
+            .fetch_all(&mut *tx)
+            .await?;
+    for (id, name) in names {
+        sqlx::query("UPDATE attachments SET filename=?2 WHERE id=?1")
+            .bind(id)
+            .bind(crypto::seal(&name))
+            .execute(&mut *tx)
+            .await?;
+        done.attachments += 1;
+    }
+
+    // Only todo history carries notes
+    let entries: Vec<(i64, String, Option<String>, Option<String>)> =
+        sqlx::query_as("SELECT id, changes, before, after FROM audit_log WHERE entity='todo'")
+            .fetch_all(&mut *tx)
+            .await?;
+    for (id, changes, before, after) in entries {
+        let seal = |doc: &str| -> Option<String> {
+            let mut value: serde_json::Value = serde_json::from_str(doc).ok()?;
+            AuditEntry::seal_snapshot(&mut value);
+            let sealed = value.to_string();
+            (sealed != doc).then_some(sealed)
+        };
+        let (c, b, a) = (
+            seal(&changes),
+            before.as_deref().and_then(seal),
+            after.as_deref().and_then(seal),
+        );
+        if c.is_none() && b.is_none() && a.is_none() {
+            continue;
+        }
+        sqlx::query(
+            "UPDATE audit_log SET changes=COALESCE(?2, changes), before=COALESCE(?3, before), after=COALESCE(?4, after) WHERE id=?1",
+        )
+        .bind(id)
+        .bind(c)
+        .bind(b)
+        .bind(a)
+        .execute(&mut *tx)
+        .await?;
+        done.audit_entries += 1;
+    }
+    tx.commit().await?;
+    Ok(done)
+}
@@ -0,0 +1,216 @@
+/**
+ * Category membership and todo assignment
+ *
+ * Todos can be assigned to a household member (`assignee_id`, a user id;
+ * `""` unassigns), and `GET /api/todos?assignee=me` lists the caller's
+ * (the `X-User-Id` header; `none` lists unassigned todos). Every change of
+ * assignee is broadcast as `todo.assigned`:
+ *
+ * ```text
+ * {"todo_id": "...", "project_id": null, "from": null, "to": "<user id>", "by": "user:<id>"}
+ * ```
+ *
+ * A category can be shared between some members only. As long as it has
+ * no members anyone may change its todos, as before; once it has, changes
+ * to its todos made as a user (`X-User-Id`) are limited to its members
+ * (403 otherwise). Anonymous calls, integrations and background jobs are
+ * not restricted. Owners manage the member list; editors only change todos.
+ *
+ * - GET    /api/categories/{id}/members
+ * - PUT    /api/categories/{id}/members/{user_id}    {"role": "owner" | "editor"}
+ * - DELETE /api/categories/{id}/members/{user_id}
+ *
+ * The first member added becomes the owner whatever the role asked for;
+ * the last owner cannot leave or step down while other members remain.
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, put},
+};
+use chrono::Utc;
+use serde_json::json;
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{CategoryMember, CategoryMemberSet},
+    routes::AppState,
+};
+
+/// Members that may change todos and manage the member list.
+pub const OWNER: &str = "owner";
+/// Members that may change todos.
+pub const EDITOR: &str = "editor";
+
+/**
+ * Storage for the category_members table
+ */
+#[derive(Clone)]
+pub struct Members {
+    pool: SqlitePool,
+}
+
+impl Members {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Members of a category, owners first.
+    pub async fn list(&self, category_id: &str) -> ApiResult<Vec<CategoryMember>> {
+        Ok(sqlx::query_as::<_, CategoryMember>(
+            "SELECT * FROM category_members WHERE category_id=?1 ORDER BY role DESC, created_at ASC",
+        )
+        .bind(category_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Forbidden unless `actor` may change todos in `category_id`.
+    pub async fn check(&self, actor: &Actor, category_id: &str) -> ApiResult<()> {
+        let Some(user) = actor.user_id() else {
+            return Ok(());
+        };
+        let members = self.list(category_id).await?;
+        if members.is_empty() || members.iter().any(|m| m.user_id == user) {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(format!(
+            "only members of category `{category_id}` can change its todos"
+        )))
+    }
+
+    /// Bad request unless `user_id` is an existing user.
+    pub async fn check_user(&self, user_id: &str) -> ApiResult<()> {
+        let found: Option<String> =
+            sqlx::query_scalar("SELECT id FROM users WHERE id=?1 AND deleted=0")
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        match found {
+            Some(_) => Ok(()),
+            None => Err(ApiError::BadRequest(format!("unknown user `{user_id}`"))),
+        }
+    }
+
+    /// Forbidden unless `actor` may manage the members (an owner, or anyone
+    /// while the category has no owner).
+    async fn check_owner(&self, actor: &Actor, members: &[CategoryMember]) -> ApiResult<()> {
+        let Some(user) = actor.user_id() else {
+            return Ok(());
+        };
+        let owners = || members.iter().filter(|m| m.role == OWNER);
+        if owners().next().is_none() || owners().any(|m| m.user_id == user) {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(
+            "only owners can change the members".into(),
+        ))
+    }
+}
+
+/// Conflict when `members` (after a change) has members but no owner left.
+fn keep_owner(members: &[CategoryMember]) -> ApiResult<()> {
+    if !members.is_empty() && !members.iter().any(|m| m.role == OWNER) {
+        return Err(ApiError::Conflict(
+            "the last owner cannot leave while other members remain".into(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/categories/{id}/members", get(list_members))
+        .route(
+            "/api/categories/{id}/members/{user_id}",
+            put(set_member).delete(remove_member),
+        )
+}
+
+async fn list_members(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Vec<CategoryMember>>> {
+    st.categories.get(&id).await?;
+    Ok(Json(Members::new(st.pool.clone()).list(&id).await?))
+}
+
+async fn set_member(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((id, user_id)): Path<(String, String)>,
+    JsonBody(body): JsonBody<CategoryMemberSet>,
+) -> ApiResult<Json<CategoryMember>> {
+    if body.role != OWNER && body.role != EDITOR {
+        return Err(ApiError::BadRequest(
+            "role must be `owner` or `editor`".into(),
+        ));
+    }
+    st.categories.get(&id).await?;
+    let members = Members::new(st.pool.clone());
+    members.check_user(&user_id).await?;
+    let mut current = members.list(&id).await?;
+    members.check_owner(&actor, &current).await?;
+
+    let created_at = current
+        .iter()
+        .find(|m| m.user_id == user_id)
+        .map_or_else(Utc::now, |m| m.created_at);
+    let member = CategoryMember {
+        category_id: id,
+        user_id,
+        role: body.role,
+        created_at,
+    };
+    current.retain(|m| m.user_id != member.user_id);
+    current.push(member.clone());
+    // The first member of a category becomes its owner
+    let member = if current.len() == 1 {
+        CategoryMember {
+            role: OWNER.into(),
+            ..member
+        }
+    } else {
+        keep_owner(&current)?;
+        member
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO category_members (category_id,user_id,role,created_at) VALUES (?1,?2,?3,?4)
+        ON CONFLICT(category_id,user_id) DO UPDATE SET role=excluded.role
+    "#,
+    )
+    .bind(&member.category_id)
+    .bind(&member.user_id)
+    .bind(&member.role)
+    .bind(member.created_at)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(member))
+}
+
+async fn remove_member(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((id, user_id)): Path<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let members = Members::new(st.pool.clone());
+    let mut current = members.list(&id).await?;
+    if !current.iter().any(|m| m.user_id == user_id) {
+        return Err(ApiError::NotFound);
+    }
+    // Leaving is always allowed, removing others takes an owner
+    if actor.user_id() != Some(user_id.as_str()) {
+        members.check_owner(&actor, &current).await?;
+    }
+    current.retain(|m| m.user_id != user_id);
+    keep_owner(&current)?;
+    sqlx::query("DELETE FROM category_members WHERE category_id=?1 AND user_id=?2")
+        .bind(&id)
+        .bind(&user_id)
+        .execute(&st.pool)
+        .await?;
+    Ok(Json(json!({"ok": true})))
+}
@@ -0,0 +1,110 @@
+/**
+ * Prometheus metrics
+ *
+ * GET /metrics in the Prometheus text format, for scraping by a home
+ * Prometheus/VictoriaMetrics or the Grafana agent. Only cheap values are
+ * exported (in-memory state, a few /proc and /sys reads for the host, see
+ * system.rs) so frequent scrapes cost nothing. Host gauges the platform
+ * does not provide are left out.
+ */
+use std::fmt::Write;
+
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+
+use crate::{
+    routes::AppState,
+    system::{self, SystemHealth},
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/metrics", get(metrics))
+}
+
+async fn metrics(State(st): State<AppState>) -> impl IntoResponse {
+    let mut out = String::new();
+    gauge(
+        &mut out,
+        "todo_uptime_seconds",
+        "Seconds since the server started",
+        st.started_at.elapsed().as_secs_f64(),
+    );
+    let host = SystemHealth::read(&system::data_dir(&st.config));
+    let bytes = |v: Option<u64>| v.map(|b| b as f64);
+    for (name, help, value) in [
+        (
+            "todo_cpu_temperature_celsius",
+            "CPU (SoC) temperature",
+            host.cpu_temp_c,
+        ),
+        ("todo_load1", "Load average over 1 minute", host.load_1m),
+        ("todo_load5", "Load average over 5 minutes", host.load_5m),
+        ("todo_load15", "Load average over 15 minutes", host.load_15m),
+        (
+            "todo_memory_total_bytes",
+            "Installed memory",
+            bytes(host.memory_total_bytes),
+        ),
+        (
+            "todo_memory_available_bytes",
+            "Memory available without swapping",
+            bytes(host.memory_available_bytes),
+        ),
+        (
+            "todo_disk_total_bytes",
+            "Size of the partition holding the database",
+            bytes(host.disk_total_bytes),
+        ),
+        (
+            "todo_disk_available_bytes",
+            "Free space on the partition holding the database",
+            bytes(host.disk_available_bytes),
+        ),
+    ] {
+        if let Some(value) = value {
+            gauge(&mut out, name, help, value);
+        }
+    }
+    if let Some(backup) = st.backups.status() {
+        gauge(
+            &mut out,
+            "todo_backup_ok",
+            "1 unless the last scheduled backup failed",
+            if backup.ok { 1.0 } else { 0.0 },
+        );
+        let timestamp =
+            |t: Option<chrono::DateTime<chrono::Utc>>| t.map_or(0.0, |t| t.timestamp() as f64);
+        gauge(
+            &mut out,
+            "todo_backup_last_success_timestamp_seconds",
+            "Unix time of the last successful scheduled backup, 0 = none yet",
+            timestamp(backup.last_success_at),
+        );
+        gauge(
+            &mut out,
+            "todo_backup_last_failure_timestamp_seconds",
+            "Unix time of the last failed scheduled backup, 0 = none",
+            timestamp(backup.last_failure_at),
+        );
+        gauge(
+            &mut out,
+            "todo_backup_last_size_bytes",
+            "Size of the last scheduled backup",
+            backup.last_backup.map_or(0.0, |b| b.size as f64),
+        );
+        let _ = writeln!(
+            out,
+            "# HELP todo_backup_failures_total Failed scheduled backups since startup\n\
+             # TYPE todo_backup_failures_total counter\n\
+             todo_backup_failures_total {}",
+            backup.failures
+        );
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}"
+    );
+}
@@ -12,9 +12,11 @@
  */
 use chrono::{DateTime, Utc}; // Date/time handling (like std::chrono in C++)
 use serde::{Deserialize, Serialize}; // JSON serialization (like nlohmann/json)
-use sqlx::FromRow; // Database row mapping
+use sqlx::{FromRow, types::Json}; // Database row mapping
 use uuid::Uuid; // UUID generation
 
+use crate::{crypto, ddns::DdnsStatus, portmap::PortMapStatus};
+
 /**
  * Main Todo entity - represents a todo item in the database
  *
@@ -28,19 +30,35 @@ use uuid::Uuid; // UUID generation
  */
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Todo {
-    pub id: String,                    // UUIDv4 string - Primary key
-    pub title: String,                 // Todo title - Required field
-    pub note: Option<String>,          // Optional note (like std::optional)
-    pub status: String,                // Workflow state: todo/doing/done/archived
-    pub priority: i64,                 // Priority level: 0 (low) to 3 (high)
-    pub due_at: Option<DateTime<Utc>>, // Optional due date with timezone
-    pub tags: Option<String>,          // Optional tags (MVP implementation)
-    pub category_id: Option<String>,   // Optional category ID (foreign key to categories table)
-    pub sort_order: i64,               // Manual sorting order
-    pub created_at: DateTime<Utc>,     // Creation timestamp
-    pub updated_at: DateTime<Utc>,     // Last modification timestamp
-    pub deleted: i64,                  // Soft delete flag: 0=active, 1=deleted
-                                       // Note: Using i64 instead of bool for SQLite compatibility
+    pub id: String,                              // UUIDv4 string - Primary key
+    pub title: String,                           // Todo title - Required field
+    pub note: Option<String>,                    // Optional note (like std::optional)
+    pub status: String,                          // Workflow state, a name from the statuses table
+    pub priority: i64,                           // Priority level: 0 (low) to 3 (high)
+    pub pinned: bool,                            // Listed before everything else
+    pub due_at: Option<DateTime<Utc>>,           // Optional due date with timezone
+    pub start_at: Option<DateTime<Utc>>,         // Not relevant before this (hidden from "today")
+    pub completed_at: Option<DateTime<Utc>>,     // When it was finished; cleared when reopened
+    pub tags: Option<String>,                    // Optional tags (MVP implementation)
+    pub category_id: Option<String>,             // Optional category ID (foreign key to categories)
+    pub project_id: Option<String>,              // Owning project (board); None = default board
+    pub assignee_id: Option<String>, // Household member (users.id) doing it; None = anyone
+    pub latitude: Option<f64>,       // Optional location (WGS84)
+    pub longitude: Option<f64>,      // Optional location (WGS84)
+    pub location_name: Option<String>, // Optional place label ("Hardware store")
+    pub checklist_done: i64,         // Checklist progress: items done ...
+    pub checklist_total: i64,        // ... out of all items ("2/5")
+    pub snooze_count: i64,           // How often the due date was pushed back (snooze)
+    pub timer_started_at: Option<DateTime<Utc>>, // Running time tracking timer, if any ...
+    pub tracked_secs: i64,           // ... and seconds of finished time entries
+    pub estimate_minutes: Option<i64>, // Expected effort, for the workload report
+    pub sort_order: i64,             // Manual sorting order (bulk reorder)
+    pub rank: f64,                   // Fractional position; one drag = one row
+    pub created_at: DateTime<Utc>,   // Creation timestamp
+    pub updated_at: DateTime<Utc>,   // Last modification timestamp
+    pub version: i64,                // Bumped on every write (ETags, conflict checks)
+    pub deleted: i64,                // Soft delete flag: 0=active, 1=deleted
+                                     // Note: Using i64 instead of bool for SQLite compatibility
 }
 
 /**
@@ -52,12 +70,31 @@ pub struct Category {
     pub name: String,                // Category name - Required field
     pub color: Option<String>,       // Optional color for UI display (hex color)
     pub description: Option<String>, // Optional description
+    pub project_id: Option<String>,  // Owning project (board); None = default board
+    pub parent_id: Option<String>,   // Parent category; None = top level
+    pub is_default: bool,            // New todos without a category land here
+    pub archived: bool,              // Hidden from the category list, todos kept
     pub sort_order: i64,             // Manual sorting order
     pub created_at: DateTime<Utc>,   // Creation timestamp
     pub updated_at: DateTime<Utc>,   // Last modification timestamp
     pub deleted: i64,                // Soft delete flag: 0=active, 1=deleted
 }
 
+/**
+ * User entity - a household member who can receive notifications
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct User {
+    pub id: String,                // UUIDv4 string - Primary key
+    pub name: String,              // Display name
+    pub email: Option<String>,     // Address for reminder/digest mails
+    pub email_reminders: i64,      // 1 = send immediate due-soon/overdue mails
+    pub email_digest: i64,         // 1 = send the daily digest
+    pub created_at: DateTime<Utc>, // Creation timestamp
+    pub updated_at: DateTime<Utc>, // Last modification timestamp
+    pub deleted: i64,              // Soft delete flag: 0=active, 1=deleted
+}
+
 /**
  * Data Transfer Object for creating new todos
  *
@@ -68,14 +105,32 @@ pub struct Category {
  *
  * Similar to a C++ struct used for function parameters
  */
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TodoCreate {
-    pub title: String,                 // Required: what needs to be done
-    pub note: Option<String>,          // Optional: additional details
-    pub priority: Option<i64>,         // Optional: defaults to 0 if not specified
-    pub due_at: Option<DateTime<Utc>>, // Optional: when it should be completed
-    pub tags: Option<String>,          // Optional: categorization
-    pub category_id: Option<String>,   // Optional: category assignment
+    pub id: Option<String>,              // Optional: client UUID (offline sync)
+    pub title: String,                   // Required: what needs to be done
+    pub note: Option<String>,            // Optional: additional details
+    pub priority: Option<i64>,           // Optional: defaults to 0 if not specified
+    pub due_at: Option<DateTime<Utc>>,   // Optional: when it should be completed
+    pub start_at: Option<DateTime<Utc>>, // Optional: when it becomes relevant
+    pub estimate_minutes: Option<i64>,   // Optional: expected effort
+    pub tags: Option<String>,            // Optional: categorization
+    pub category_id: Option<String>,     // Optional: category assignment
+    pub project_id: Option<String>,      // Optional: project (board)
+    pub assignee_id: Option<String>,     // Optional: responsible user
+    pub latitude: Option<f64>,           // Optional: location
+    pub longitude: Option<f64>,          // Optional: location
+    pub location_name: Option<String>,   // Optional: place label
+}
+
+/**
+ * Category with its subcategories, for GET /api/categories/tree
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryNode {
+    #[serde(flatten)]
+    pub category: Category,
+    pub children: Vec<CategoryNode>,
 }
 
 /**
@@ -86,6 +141,31 @@ pub struct CategoryCreate {
     pub name: String,                // Required: category name
     pub color: Option<String>,       // Optional: color for UI display
     pub description: Option<String>, // Optional: category description
+    pub project_id: Option<String>,  // Optional: project (board)
+    pub parent_id: Option<String>,   // Optional: parent category
+}
+
+/**
+ * Data Transfer Object for creating users
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserCreate {
+    pub name: String,                 // Required: display name
+    pub email: Option<String>,        // Optional: notification address
+    pub email_reminders: Option<i64>, // Optional: defaults to 1 when an email is given
+    pub email_digest: Option<i64>,    // Optional: defaults to 0
+}
+
+/**
+ * Data Transfer Object for updating users
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserUpdate {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub email_reminders: Option<i64>,
+    pub email_digest: Option<i64>,
+    pub deleted: Option<i64>,
 }
 
 /**
@@ -96,17 +176,25 @@ pub struct CategoryCreate {
  *
  * Pattern: Builder pattern variation - allows incremental construction
  */
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct TodoUpdate {
-    pub title: Option<String>,         // Update title
-    pub note: Option<String>,          // Update or clear note
-    pub status: Option<String>,        // Change workflow status
-    pub priority: Option<i64>,         // Change priority level
-    pub due_at: Option<DateTime<Utc>>, // Update or clear due date
-    pub tags: Option<String>,          // Update or clear tags
-    pub category_id: Option<String>,   // Update or clear category
-    pub sort_order: Option<i64>,       // Change sort position
-    pub deleted: Option<i64>,          // Soft delete/undelete
+    pub title: Option<String>,           // Update title
+    pub note: Option<String>,            // Update or clear note
+    pub status: Option<String>,          // Change workflow status
+    pub priority: Option<i64>,           // Change priority level
+    pub pinned: Option<bool>,            // Pin/unpin
+    pub due_at: Option<DateTime<Utc>>,   // Update or clear due date
+    pub start_at: Option<DateTime<Utc>>, // Update start date
+    pub estimate_minutes: Option<i64>,   // Update effort estimate
+    pub tags: Option<String>,            // Update or clear tags
+    pub category_id: Option<String>,     // Update or clear category
+    pub project_id: Option<String>,      // Move to another project
+    pub assignee_id: Option<String>,     // Assign to a user ("" = unassign)
+    pub sort_order: Option<i64>,         // Change sort position
+    pub deleted: Option<i64>,            // Soft delete/undelete
+    pub latitude: Option<f64>,           // Update location
+    pub longitude: Option<f64>,          // Update location
+    pub location_name: Option<String>,   // Update place label
 }
 
 /**
@@ -117,6 +205,8 @@ pub struct CategoryUpdate {
     pub name: Option<String>,        // Update category name
     pub color: Option<String>,       // Update or clear color
     pub description: Option<String>, // Update or clear description
+    pub project_id: Option<String>,  // Move to another project
+    pub parent_id: Option<String>,   // Move under another category; "" = top level
     pub sort_order: Option<i64>,     // Change sort position
     pub deleted: Option<i64>,        // Soft delete/undelete
 }
@@ -135,6 +225,425 @@ pub struct ReorderItem {
     pub sort_order: i64, // New position in the list
 }
 
+/**
+ * Inbound webhook token - lets external services (IFTTT, email bridges)
+ * create todos via POST /api/inbound/{token} with preset defaults
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct InboundToken {
+    pub id: String,                  // UUIDv4 string - Primary key
+    pub token: String,               // Secret used in the webhook URL
+    pub name: String,                // Human-readable label ("IFTTT", "Email")
+    pub category_id: Option<String>, // Default category for created todos
+    pub tags: Option<String>,        // Default tags merged into created todos
+    pub created_at: DateTime<Utc>,   // Creation timestamp
+}
+
+/**
+ * Data Transfer Object for creating inbound webhook tokens
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct InboundTokenCreate {
+    pub name: String,                // Required: label for the integration
+    pub category_id: Option<String>, // Optional: default category
+    pub tags: Option<String>,        // Optional: default tags
+}
+
+/**
+ * Category member - a user allowed to change the category's todos
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct CategoryMember {
+    pub category_id: String,       // Shared category
+    pub user_id: String,           // Member (users.id)
+    pub role: String,              // "owner" (also manages members) or "editor"
+    pub created_at: DateTime<Utc>, // When the user was added
+}
+
+/**
+ * Data Transfer Object for adding a member or changing their role
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct CategoryMemberSet {
+    pub role: String, // "owner" or "editor"
+}
+
+/**
+ * Share - secret read-only link to a filtered todo list for guests
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Share {
+    pub id: String,                        // UUIDv4 string - Primary key
+    pub token: String,                     // Secret used in the share URL
+    pub name: String,                      // Title shown to the guest ("Shopping")
+    pub project_id: Option<String>,        // Only todos of this project
+    pub category_id: Option<String>,       // Only todos in this category
+    pub filter: Option<String>,            // Filter expression (see filter.rs)
+    pub expires_at: Option<DateTime<Utc>>, // Link stops working after this; None = never
+    pub created_at: DateTime<Utc>,         // Creation timestamp
+}
+
+/**
+ * Data Transfer Object for creating shares
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShareCreate {
+    pub name: String,                      // Required
+    pub project_id: Option<String>,        // Optional
+    pub category_id: Option<String>,       // Optional
+    pub filter: Option<String>,            // Optional, e.g. "status:todo"
+    pub expires_at: Option<DateTime<Utc>>, // Optional; default: never
+}
+
+/**
+ * Outgoing webhook - receives hub events (todo.created, ...) as JSON POSTs.
+ * Filters are evaluated server-side; unset filters match everything.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: String,                   // UUIDv4 string - Primary key
+    pub name: String,                 // Human-readable label ("Slack #work")
+    pub url: String,                  // Delivery endpoint
+    pub user_id: Option<String>,      // Owner; None = household-wide hook
+    pub event_types: Option<String>,  // Comma separated, "todo.*" style prefixes allowed
+    pub category_ids: Option<String>, // Comma separated category ids
+    pub min_priority: Option<i64>,    // Only todos with priority >= this
+    pub assignee_id: Option<String>,  // Only todos assigned to this user
+    pub enabled: i64,                 // 1 = deliver, 0 = paused
+    pub created_at: DateTime<Utc>,    // Creation timestamp
+    pub updated_at: DateTime<Utc>,    // Last modification timestamp
+}
+
+/**
+ * Data Transfer Object for creating outgoing webhooks
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookCreate {
+    pub name: String,                // Required: label
+    pub url: String,                 // Required: http(s) endpoint
+    pub user_id: Option<String>,     // Optional: owning user
+    pub event_types: Option<String>, // Optional filters, see Webhook
+    pub category_ids: Option<String>,
+    pub min_priority: Option<i64>,
+    pub assignee_id: Option<String>,
+}
+
+/**
+ * Data Transfer Object for updating outgoing webhooks
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookUpdate {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub event_types: Option<String>,
+    pub category_ids: Option<String>,
+    pub min_priority: Option<i64>,
+    pub assignee_id: Option<String>,
+    pub enabled: Option<i64>,
+}
+
+/**
+ * Bulk import job - rows are stored up front and processed in chunks,
+ * so an interrupted import can be resumed without duplicates
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ImportJob {
+    pub id: String,                // UUIDv4 string (or client-chosen id) - Primary key
+    pub source: String,            // "json" (TodoCreate items) or "todoist" (REST tasks)
+    pub status: String,            // pending, running, interrupted, completed
+    pub total: i64,                // Number of rows in the import
+    pub processed: i64,            // Rows handled so far (imported or failed)
+    pub failed: i64,               // Rows that could not be imported
+    pub error: Option<String>,     // Last row error, for display
+    pub created_at: DateTime<Utc>, // Upload timestamp
+    pub updated_at: DateTime<Utc>, // Last progress timestamp
+}
+
+/**
+ * Data Transfer Object for snoozing a todo - a duration or an absolute time
+ *
+ * Durations count from the current due date, or from now when the todo is
+ * undated or already overdue.
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TodoSnooze {
+    pub minutes: Option<i64>,         // Push back by minutes ...
+    pub hours: Option<i64>,           // ... hours ...
+    pub days: Option<i64>,            // ... and/or days (summed)
+    pub until: Option<DateTime<Utc>>, // Or: new due date (must be in the future)
+}
+
+/**
+ * Drag-and-drop target: put the todo directly before or after a sibling
+ * (exactly one field is set)
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TodoPlace {
+    pub before: Option<String>, // Id of the todo to go in front of ...
+    pub after: Option<String>,  // ... or to follow
+}
+
+/**
+ * Kanban drop: new column (category and/or status) and position in one write
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TodoMove {
+    pub category_id: Option<String>, // New category; "" = uncategorized
+    pub status: Option<String>,      // New status (workflow rules apply)
+    pub before: Option<String>,      // Drop in front of this todo ...
+    pub after: Option<String>,       // ... or behind it; neither = keep the rank
+}
+
+/**
+ * Options for duplicating a todo
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TodoDuplicate {
+    pub checklist: Option<bool>, // Also copy checklist entries (unchecked); default false
+}
+
+/**
+ * Data Transfer Object for linking todos - exactly one field is set
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoLinkCreate {
+    pub blocks: Option<String>,     // This todo blocks that one
+    pub blocked_by: Option<String>, // That todo blocks this one
+}
+
+/**
+ * Checklist entry - a lightweight step inside a todo
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ChecklistItem {
+    pub id: String,                // UUIDv4 string - Primary key
+    pub todo_id: String,           // Owning todo
+    pub text: String,              // What to do
+    pub done: i64,                 // 0=open, 1=done
+    pub sort_order: i64,           // Position within the checklist
+    pub created_at: DateTime<Utc>, // Creation timestamp
+    pub updated_at: DateTime<Utc>, // Last modification timestamp
+}
+
+/**
+ * Time entry - one tracked stretch of work on a todo
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TimeEntry {
+    pub id: String,                      // UUIDv4 string - Primary key
+    pub todo_id: String,                 // Todo the time was spent on
+    pub started_at: DateTime<Utc>,       // Timer start
+    pub ended_at: Option<DateTime<Utc>>, // Timer stop; None while running
+    pub actor: String,                   // Who tracked it
+}
+
+/**
+ * Data Transfer Object for adding a checklist entry
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChecklistItemCreate {
+    pub text: String,            // Required
+    pub done: Option<i64>,       // Default 0
+    pub sort_order: Option<i64>, // Default: after the last entry
+}
+
+/**
+ * Data Transfer Object for editing a checklist entry
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChecklistItemUpdate {
+    pub text: Option<String>,
+    pub done: Option<i64>,
+    pub sort_order: Option<i64>,
+}
+
+/**
+ * Project - an independent board ("Household", "Work", "Garden")
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Project {
+    pub id: String,                // UUIDv4 string - Primary key
+    pub name: String,              // Display name
+    pub color: Option<String>,     // Optional color for UI display (hex color)
+    pub sort_order: i64,           // Position in the project switcher
+    pub created_at: DateTime<Utc>, // Creation timestamp
+    pub updated_at: DateTime<Utc>, // Last modification timestamp
+}
+
+/**
+ * Data Transfer Object for creating projects
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectCreate {
+    pub name: String,            // Required
+    pub color: Option<String>,   // Optional
+    pub sort_order: Option<i64>, // Default: after the last project
+}
+
+/**
+ * Data Transfer Object for editing projects
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectUpdate {
+    pub name: Option<String>,
+    pub color: Option<String>,
+    pub sort_order: Option<i64>,
+}
+
+/**
+ * Workflow status - one board column
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Status {
+    pub name: String,              // Primary key, stored in todos.status
+    pub color: Option<String>,     // Optional color for UI display (hex color)
+    pub sort_order: i64,           // Column position
+    pub is_done: i64,              // 1 = finished: not open, sets completed_at
+    pub created_at: DateTime<Utc>, // Creation timestamp
+    pub updated_at: DateTime<Utc>, // Last modification timestamp
+}
+
+/**
+ * Data Transfer Object for adding a status
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusCreate {
+    pub name: String,            // Required, 1-32 characters
+    pub color: Option<String>,   // Optional
+    pub sort_order: Option<i64>, // Default: after the last column
+    pub is_done: Option<i64>,    // Default 0
+}
+
+/**
+ * Data Transfer Object for editing a status (the name is fixed)
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusUpdate {
+    pub color: Option<String>,
+    pub sort_order: Option<i64>,
+    pub is_done: Option<i64>,
+}
+
+/**
+ * File attached to a todo; the bytes live under attachments_dir/<id>
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Attachment {
+    pub id: String,                // UUIDv4, also the file name on disk
+    pub todo_id: String,           // Owning todo
+    pub filename: String,          // Original name from the upload
+    pub content_type: String,      // MIME type from the upload
+    pub size: i64,                 // Bytes
+    pub created_at: DateTime<Utc>, // Upload timestamp
+}
+
+/**
+ * Comment on a todo
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Comment {
+    pub id: String,                // UUIDv4 string - Primary key
+    pub todo_id: String,           // Commented todo
+    pub actor: String,             // Who wrote it ("user:<id>", "api", ...)
+    pub body: String,              // Text, 1-10000 characters
+    pub created_at: DateTime<Utc>, // When it was written
+}
+
+/**
+ * Data Transfer Object for adding a comment
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommentCreate {
+    pub body: String, // Required
+}
+
+/**
+ * Notification for one user, shown in the app (see notifications.rs)
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserNotification {
+    pub id: String,                     // UUIDv4 string - Primary key
+    pub user_id: String,                // Recipient
+    pub kind: String,                   // "mention", "assigned", "reminder" or "overdue"
+    pub todo_id: Option<String>,        // Todo it is about, if any
+    pub actor: String,                  // Who caused it ("user:<id>", "api", ...)
+    pub title: String,                  // Short headline
+    pub message: String,                // Body text (excerpt)
+    pub read_at: Option<DateTime<Utc>>, // When it was marked read; None = unread
+    pub created_at: DateTime<Utc>,      // Creation timestamp
+}
+
+/**
+ * Signed-in device (see sessions.rs); token hashes are never serialized
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Session {
+    pub id: String,      // UUIDv4 string - Primary key
+    pub user_id: String, // Signed-in household member
+    #[serde(skip)]
+    pub access_hash: String, // SHA-256 of the current access token
+    #[serde(skip)]
+    pub refresh_hash: String, // SHA-256 of the current refresh token
+    #[serde(skip)]
+    pub prev_refresh_hash: Option<String>, // The refresh token it replaced (reuse detection)
+    pub user_agent: Option<String>, // User-Agent at the last request
+    pub ip: Option<String>, // Client address at the last request
+    pub created_at: DateTime<Utc>, // Sign-in time
+    pub last_seen_at: DateTime<Utc>, // Last authenticated request (to the minute)
+    pub access_expires_at: DateTime<Utc>, // Current access token stops working
+    pub refresh_expires_at: DateTime<Utc>, // Session ends unless refreshed before
+    pub revoked_at: Option<DateTime<Utc>>, // Signed out; None = active
+}
+
+/**
+ * Data Transfer Object for signing a device in
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionCreate {
+    pub user_id: String, // Required: the household member
+}
+
+/**
+ * Data Transfer Object for rotating a session's tokens
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionRefresh {
+    pub refresh_token: String, // Required: the latest refresh token
+}
+
+/**
+ * Audit log entry - one change to a todo or category
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditEntry {
+    pub id: i64,                                 // Sequence number (ascending)
+    pub entity: String,                          // "todo" or "category"
+    pub entity_id: String,                       // Id of the changed record
+    pub action: String,                          // created, updated, deleted, undo, ...
+    pub actor: String,                           // Who: "user:<id>", "api", "mqtt", ...
+    pub changes: Json<serde_json::Value>,        // {"field": {"from": old, "to": new}}
+    pub before: Option<Json<serde_json::Value>>, // Snapshot before (None for creates)
+    pub after: Option<Json<serde_json::Value>>,  // Snapshot after the change
+    pub version: Option<i64>,                    // Todo version after the change
+    pub batch: Option<String>,                   // Shared by changes from one request
+    pub undone_at: Option<DateTime<Utc>>,        // Set once undone via /api/undo
+    pub created_at: DateTime<Utc>,               // When
+}
+
+/**
+ * Data Transfer Object for starting an import
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportCreate {
+    pub id: Option<String>,     // Optional: client id, makes the upload retry-safe
+    pub source: Option<String>, // Optional: "json" (default) or "todoist"
+    pub category_id: Option<String>, // Optional: category for items without one
+    pub items: Vec<serde_json::Value>, // Required: rows in the source format
+}
+
 /**
  * Health check response
  *
@@ -147,6 +656,56 @@ pub struct ReorderItem {
 pub struct Health {
     pub ok: bool,   // Overall system status
     pub db: String, // Database status message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<crate::backups::BackupStatus>, // Scheduled backups, when configured
+    pub system: crate::system::SystemHealth, // CPU temperature, load, memory, free disk
+}
+
+/**
+ * Admin overview response
+ *
+ * One-stop summary of the instance for the admin page: data volume,
+ * connected clients and the state of optional background integrations.
+ */
+#[derive(Debug, Serialize)]
+pub struct AdminOverview {
+    pub version: String,                     // Crate version
+    pub uptime_secs: u64,                    // Seconds since the server started
+    pub todos_active: i64,                   // Non-deleted todos
+    pub todos_deleted: i64,                  // Soft-deleted todos
+    pub categories: i64,                     // Non-deleted categories
+    pub ws_clients: usize,                   // Currently connected WebSocket clients
+    pub ddns: Option<DdnsStatus>,            // Dynamic DNS updater (None when disabled)
+    pub port_mapping: Option<PortMapStatus>, // Router port mapping (None when disabled)
+}
+
+/**
+ * Admin info response
+ *
+ * What is actually running: effective settings (secrets masked), the
+ * exact build, live connections and database size.
+ */
+#[derive(Debug, Serialize)]
+pub struct AdminInfo {
+    pub version: String,           // Crate version
+    pub git_hash: String,          // Commit the binary was built from
+    pub uptime_secs: u64,          // Seconds since the server started
+    pub ws_clients: usize,         // Currently connected WebSocket clients
+    pub config: serde_json::Value, // Effective ServerConfig, redacted
+    pub db: DbStats,
+}
+
+/**
+ * SQLite statistics for the admin info
+ */
+#[derive(Debug, Serialize)]
+pub struct DbStats {
+    pub sqlite_version: String,
+    pub journal_mode: String,
+    pub size_bytes: i64,       // page_count * page_size
+    pub free_bytes: i64,       // Unused pages VACUUM would reclaim
+    pub pool_connections: u32, // Open pool connections
+    pub pool_idle: usize,      // Of which idle
 }
 
 /**
@@ -169,21 +728,90 @@ impl Todo {
      */
     pub fn new_from_create(c: TodoCreate) -> Self {
         let now = Utc::now(); // Current timestamp
+        let id = c.id.unwrap_or_else(|| Uuid::new_v4().to_string()); // Client id or a new one
         Self {
-            id: Uuid::new_v4().to_string(),    // Generate unique identifier
+            id,                                // Unique identifier
             title: c.title,                    // User-provided title
             note: c.note,                      // Optional note
             status: "todo".to_string(),        // Default to "todo" status
             priority: c.priority.unwrap_or(1), // Default priority = 1 (medium)
+            pinned: false,                     // Not pinned
             due_at: c.due_at,                  // Optional due date
+            start_at: c.start_at,              // Optional start date
+            completed_at: None,                // Not done yet
             tags: c.tags,                      // Optional tags
             category_id: c.category_id,        // Optional category
-            sort_order: 0,                     // Default sort order
-            created_at: now,                   // Set creation time
-            updated_at: now,                   // Set update time (same as creation)
-            deleted: 0,                        // Default to not deleted
+            project_id: c.project_id,          // Optional project
+            assignee_id: c.assignee_id,        // Optional assignee
+            latitude: c.latitude,              // Optional location
+            longitude: c.longitude,
+            location_name: c.location_name,
+            checklist_done: 0,
+            checklist_total: 0,
+            snooze_count: 0,
+            timer_started_at: None,
+            tracked_secs: 0,
+            estimate_minutes: c.estimate_minutes,
+            sort_order: 0,   // Default sort order
+            rank: 0.0,       // Same position as sort_order 0
+            created_at: now, // Set creation time
+            updated_at: now, // Set update time (same as creation)
+            deleted: 0,      // Default to not deleted
+            version: 1,      // First revision
         }
     }
+
+    /// The note as it is stored (encrypted when a key is set, see crypto.rs).
+    pub fn stored_note(&self) -> Option<String> {
+        crypto::seal_opt(self.note.as_deref())
+    }
+
+    /// A row as read from the database, note decrypted.
+    pub fn opened(mut self) -> Self {
+        self.note = crypto::open_opt(self.note);
+        self
+    }
+}
+
+/**
+ * Implementation block for Attachment struct
+ */
+impl Attachment {
+    /// The file name as it is stored (encrypted when a key is set).
+    pub fn stored_filename(&self) -> String {
+        crypto::seal(&self.filename)
+    }
+
+    /// A row as read from the database, file name decrypted.
+    pub fn opened(mut self) -> Self {
+        self.filename = crypto::open(&self.filename);
+        self
+    }
+}
+
+/**
+ * Implementation block for AuditEntry struct
+ */
+impl AuditEntry {
+    /// Encrypt the todo note inside a snapshot or change set before storing it.
+    pub fn seal_snapshot(doc: &mut serde_json::Value) {
+        crypto::map_field(doc, "note", crypto::seal);
+    }
+
+    /// A row as read from the database, notes in snapshots and changes decrypted.
+    pub fn opened(mut self) -> Self {
+        for doc in [
+            Some(&mut self.changes),
+            self.before.as_mut(),
+            self.after.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            crypto::map_field(&mut doc.0, "note", crypto::open);
+        }
+        self
+    }
 }
 
 /**
@@ -200,6 +828,10 @@ impl Category {
             name: c.name,
             color: c.color,
             description: c.description,
+            project_id: c.project_id,
+            parent_id: c.parent_id,
+            is_default: false,
+            archived: false,
             sort_order: 0,
             created_at: now,
             updated_at: now,
@@ -207,3 +839,91 @@ impl Category {
         }
     }
 }
+
+/**
+ * Implementation block for InboundToken struct
+ */
+impl InboundToken {
+    /**
+     * Factory method - generates a fresh random webhook secret
+     */
+    pub fn new_from_create(c: InboundTokenCreate) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            token: Uuid::new_v4().simple().to_string(),
+            name: c.name,
+            category_id: c.category_id,
+            tags: c.tags,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/**
+ * Implementation block for Share struct
+ */
+impl Share {
+    /**
+     * Factory method - generates a fresh random share token
+     */
+    pub fn new_from_create(c: ShareCreate) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            token: Uuid::new_v4().simple().to_string(),
+            name: c.name,
+            project_id: c.project_id,
+            category_id: c.category_id,
+            filter: c.filter,
+            expires_at: c.expires_at,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/**
+ * Implementation block for Webhook struct
+ */
+impl Webhook {
+    /**
+     * Factory method to create a new Webhook from WebhookCreate request
+     */
+    pub fn new_from_create(c: WebhookCreate) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: c.name,
+            url: c.url,
+            user_id: c.user_id,
+            event_types: c.event_types,
+            category_ids: c.category_ids,
+            min_priority: c.min_priority,
+            assignee_id: c.assignee_id,
+            enabled: 1,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/**
+ * Implementation block for User struct
+ */
+impl User {
+    /**
+     * Factory method to create a new User from UserCreate request
+     */
+    pub fn new_from_create(c: UserCreate) -> Self {
+        let now = Utc::now();
+        let has_email = c.email.is_some();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: c.name,
+            email: c.email,
+            email_reminders: c.email_reminders.unwrap_or(has_email as i64),
+            email_digest: c.email_digest.unwrap_or(0),
+            created_at: now,
+            updated_at: now,
+            deleted: 0,
+        }
+    }
+}
@@ -0,0 +1,329 @@
+/**
+ * MQTT bridge
+ *
+ * Optional client that mirrors the WebSocket event stream onto an MQTT
+ * broker and accepts commands back, so Home Assistant, Node-RED and
+ * microcontrollers can talk to the board in their native protocol.
+ *
+ * Topics (prefix = MQTT_TOPIC_PREFIX, default "raspi-todo"):
+ * - {prefix}/events/{type}       every WsHub event, e.g. .../events/todo.created
+ * - {prefix}/state/open_count    retained counters for dashboards
+ * - {prefix}/state/overdue_count
+ * - {prefix}/command             JSON commands, {"action":"add","title":...}
+ *   or {"action":"complete","id":...}
+ * - {prefix}/command/add         plain-text title, creates a todo
+ *
+ * With MQTT_HA_DISCOVERY enabled (default) Home Assistant discovery configs
+ * are published on connect, exposing the counters as sensors and the add
+ * topic as a text entity.
+ */
+use std::{env, time::Duration};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    audit::Actor,
+    model::{Todo, TodoCreate},
+    routes::AppState,
+};
+
+/**
+ * MQTT settings read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,             // MQTT_HOST - enables the bridge when set
+    pub port: u16,                // MQTT_PORT, default 1883
+    pub client_id: String,        // MQTT_CLIENT_ID, default raspi-todo
+    pub username: Option<String>, // MQTT_USERNAME
+    pub password: Option<String>, // MQTT_PASSWORD
+    pub prefix: String,           // MQTT_TOPIC_PREFIX, default raspi-todo
+    pub event_topic: String,      // MQTT_EVENT_TOPIC template, `{type}` = event type
+    pub ha_discovery: bool,       // MQTT_HA_DISCOVERY, default true
+    pub discovery_prefix: String, // MQTT_DISCOVERY_PREFIX, default homeassistant
+}
+
+impl MqttConfig {
+    /// Returns None when MQTT_HOST is not set.
+    pub fn from_env() -> Option<Self> {
+        let host = env::var("MQTT_HOST").ok()?;
+        let prefix = env::var("MQTT_TOPIC_PREFIX").unwrap_or_else(|_| "raspi-todo".into());
+        Some(Self {
+            host,
+            port: env::var("MQTT_PORT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1883),
+            client_id: env::var("MQTT_CLIENT_ID").unwrap_or_else(|_| "raspi-todo".into()),
+            username: env::var("MQTT_USERNAME").ok(),
+            password: env::var("MQTT_PASSWORD").ok(),
+            event_topic: env::var("MQTT_EVENT_TOPIC")
+                .unwrap_or_else(|_| format!("{prefix}/events/{{type}}")),
+            prefix,
+            ha_discovery: env::var("MQTT_HA_DISCOVERY")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            discovery_prefix: env::var("MQTT_DISCOVERY_PREFIX")
+                .unwrap_or_else(|_| "homeassistant".into()),
+        })
+    }
+
+    fn command_topic(&self) -> String {
+        format!("{}/command", self.prefix)
+    }
+
+    fn add_topic(&self) -> String {
+        format!("{}/command/add", self.prefix)
+    }
+
+    fn state_topic(&self, name: &str) -> String {
+        format!("{}/state/{name}", self.prefix)
+    }
+}
+
+/**
+ * Commands accepted on {prefix}/command
+ */
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum Command {
+    Add {
+        title: String,
+        note: Option<String>,
+        priority: Option<i64>,
+        due_at: Option<DateTime<Utc>>,
+        tags: Option<String>,
+        category_id: Option<String>,
+    },
+    Complete {
+        id: String,
+    },
+}
+
+/**
+ * Start the bridge: one task drives the MQTT connection and handles
+ * incoming commands, another forwards hub events to the broker.
+ */
+pub fn spawn(config: MqttConfig, state: AppState) {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+        options.set_credentials(user, pass);
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+
+    // Forward hub events; the client queues publishes while disconnected
+    let mut rx = state.hub.tx.subscribe();
+    let forward_client = client.clone();
+    let forward_config = config.clone();
+    let forward_state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            let msg = match rx.recv().await {
+                Ok(msg) => msg,
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "MQTT bridge lagged, events dropped");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let event_type = serde_json::from_str::<serde_json::Value>(&msg)
+                .ok()
+                .and_then(|v| v["type"].as_str().map(str::to_string))
+                .unwrap_or_else(|| "unknown".into());
+            let topic = forward_config.event_topic.replace("{type}", &event_type);
+            if let Err(e) = forward_client
+                .publish(topic, QoS::AtLeastOnce, false, msg)
+                .await
+            {
+                tracing::warn!(error = %e, "MQTT publish failed");
+            }
+            if event_type.starts_with("todo") {
+                publish_counts(&forward_client, &forward_config, &forward_state).await;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    tracing::info!(host = %config.host, "MQTT connected");
+                    // Never await client requests inside the poll loop - spawn instead
+                    let (client, config, state) = (client.clone(), config.clone(), state.clone());
+                    tokio::spawn(async move { on_connect(&client, &config, &state).await });
+                }
+                Ok(Event::Incoming(Packet::Publish(p))) => {
+                    let (client, config, state) = (client.clone(), config.clone(), state.clone());
+                    tokio::spawn(async move {
+                        let result = handle_message(&state, &config, &p.topic, &p.payload).await;
+                        match result {
+                            Ok(todo) => {
+                                tracing::info!(id = %todo.id, "MQTT command applied");
+                                publish_counts(&client, &config, &state).await;
+                            }
+                            Err(e) => {
+                                tracing::warn!(error = %e, topic = %p.topic, "MQTT command rejected")
+                            }
+                        }
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(error = %e, "MQTT connection error, retrying");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    });
+}
+
+async fn on_connect(client: &AsyncClient, config: &MqttConfig, state: &AppState) {
+    for topic in [config.command_topic(), config.add_topic()] {
+        if let Err(e) = client.subscribe(topic, QoS::AtLeastOnce).await {
+            tracing::warn!(error = %e, "MQTT subscribe failed");
+        }
+    }
+    if config.ha_discovery {
+        publish_discovery(client, config).await;
+    }
+    publish_counts(client, config, state).await;
+}
+
+async fn handle_message(
+    state: &AppState,
+    config: &MqttConfig,
+    topic: &str,
+    payload: &[u8],
+) -> anyhow::Result<Todo> {
+    let command = if topic == config.add_topic() {
+        let title = std::str::from_utf8(payload)?.trim().to_string();
+        Command::Add {
+            title,
+            note: None,
+            priority: None,
+            due_at: None,
+            tags: None,
+            category_id: None,
+        }
+    } else {
+        serde_json::from_slice(payload)?
+    };
+
+    match command {
+        Command::Add {
+            title,
+            note,
+            priority,
+            due_at,
+            tags,
+            category_id,
+        } => {
+            if title.is_empty() {
+                return Err(anyhow!("title must not be empty"));
+            }
+            let todo = state
+                .todos
+                .as_actor(Actor::new("mqtt"))
+                .create(TodoCreate {
+                    title,
+                    note,
+                    priority,
+                    due_at,
+                    tags,
+                    category_id,
+                    ..Default::default()
+                })
+                .await?;
+            Ok(todo)
+        }
+        Command::Complete { id } => Ok(state
+            .todos
+            .as_actor(Actor::new("mqtt"))
+            .set_status(&id, "done".into())
+            .await?),
+    }
+}
+
+/// Publish retained open/overdue counters for dashboards and HA sensors.
+async fn publish_counts(client: &AsyncClient, config: &MqttConfig, state: &AppState) {
+    let Ok(counts) = state.todos.counts().await else {
+        return;
+    };
+    for (name, value) in [
+        ("open_count", counts.open),
+        ("overdue_count", counts.overdue),
+    ] {
+        let _ = client
+            .publish(
+                config.state_topic(name),
+                QoS::AtLeastOnce,
+                true,
+                value.to_string(),
+            )
+            .await;
+    }
+}
+
+/// Announce sensors and the quick-add text entity to Home Assistant.
+async fn publish_discovery(client: &AsyncClient, config: &MqttConfig) {
+    let node = &config.client_id;
+    let device = json!({
+        "identifiers": [node],
+        "name": "Raspi Todo",
+        "manufacturer": "raspi-todo",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let entities = [
+        (
+            "sensor",
+            "open_todos",
+            json!({
+                "name": "Open todos",
+                "unique_id": format!("{node}_open_todos"),
+                "state_topic": config.state_topic("open_count"),
+                "icon": "mdi:format-list-checks",
+                "device": device,
+            }),
+        ),
+        (
+            "sensor",
+            "overdue_todos",
+            json!({
+                "name": "Overdue todos",
+                "unique_id": format!("{node}_overdue_todos"),
+                "state_topic": config.state_topic("overdue_count"),
+                "icon": "mdi:alarm-note",
+                "device": device,
+            }),
+        ),
+        (
+            "text",
+            "add_todo",
+            json!({
+                "name": "Add todo",
+                "unique_id": format!("{node}_add_todo"),
+                "command_topic": config.add_topic(),
+                "icon": "mdi:playlist-plus",
+                "device": device,
+            }),
+        ),
+    ];
+    for (component, object_id, payload) in entities {
+        let topic = format!(
+            "{}/{component}/{node}/{object_id}/config",
+            config.discovery_prefix
+        );
+        if let Err(e) = client
+            .publish(topic, QoS::AtLeastOnce, true, payload.to_string())
+            .await
+        {
+            tracing::warn!(error = %e, "MQTT discovery publish failed");
+        }
+    }
+}
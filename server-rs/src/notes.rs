@@ -0,0 +1,648 @@
+/**
+ * Collaborative note editing
+ *
+ * Instead of PUTting the whole note (last write wins), clients that edit a
+ * note together send operational patches against a revision, the way
+ * ot.js does it. An operation walks the note from the start; it is a JSON
+ * array whose items are
+ *
+ * ```text
+ * 5        retain (skip) 5 characters
+ * "abc"    insert "abc"
+ * -2       delete 2 characters
+ * ```
+ *
+ * Lengths are counted in Unicode characters and must cover the whole note
+ * (so `[5, "!"]` on a 5 character note appends "!").
+ *
+ * - GET  /api/todos/{id}/note            {"note", "revision"}
+ * - POST /api/todos/{id}/note/patches    {"revision", "ops", "client_id"?}
+ *
+ * A patch based on an older revision is transformed against the patches
+ * applied since, so concurrent edits from two devices both survive; on
+ * a tie the incoming insert goes first. The response carries the new
+ * revision and the ops as applied, and every WebSocket client gets them as
+ * a `note.patch` event (with the sender's `client_id`, so it can recognize
+ * its own). Clients keep at most one patch in flight and transform their
+ * pending edits against incoming `note.patch` events, as in ot.js.
+ *
+ * Sessions live in memory. Only the last HISTORY_LIMIT patches of a note
+ * are kept; a patch based on an older revision, or on a note that was
+ * changed with a plain PUT meanwhile, gets 409 and the client reloads the
+ * note. Revisions start from the clock, so they do not repeat after a
+ * restart. Patches are saved through the todo service like any other
+ * update (audit, read-only mode and `todo.updated` events apply).
+ */
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+
+use crate::{
+    audit::Actor,
+    error::{ApiError, ApiResult, JsonBody},
+    model::TodoUpdate,
+    notifications,
+    routes::AppState,
+    services::emit,
+};
+
+/// Patches kept per note for transforming late ones.
+const HISTORY_LIMIT: usize = 200;
+/// Notes with an open session; the least recently edited is dropped first.
+const MAX_SESSIONS: usize = 256;
+/// Longest note a patch may produce (characters).
+const MAX_NOTE_CHARS: usize = 100_000;
+
+/**
+ * One component of a text operation
+ */
+#[derive(Debug, Clone, PartialEq)]
+enum Component {
+    Retain(usize),
+    Insert(String),
+    Delete(usize),
+}
+
+impl Component {
+    /// Characters of the input this component covers.
+    fn len(&self) -> usize {
+        match self {
+            Component::Retain(n) | Component::Delete(n) => *n,
+            Component::Insert(s) => s.chars().count(),
+        }
+    }
+
+    /// The rest of a retain/delete after consuming `n` characters.
+    fn shorten(self, n: usize) -> Option<Component> {
+        match self {
+            Component::Retain(m) if m > n => Some(Component::Retain(m - n)),
+            Component::Delete(m) if m > n => Some(Component::Delete(m - n)),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Text operation: retains, inserts and deletes covering a whole note
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextOp {
+    components: Vec<Component>,
+    base_len: usize,   // Length of the note it applies to
+    target_len: usize, // Length of the note it produces
+}
+
+impl TextOp {
+    fn retain(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.base_len += n;
+        self.target_len += n;
+        match self.components.last_mut() {
+            Some(Component::Retain(m)) => *m += n,
+            _ => self.components.push(Component::Retain(n)),
+        }
+    }
+
+    /// Inserts are kept in front of an adjacent delete, so equal edits
+    /// always end up with the same components.
+    fn insert(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        self.target_len += s.chars().count();
+        let len = self.components.len();
+        let at = match self.components.last() {
+            Some(Component::Delete(_)) => len - 1,
+            _ => len,
+        };
+        match at.checked_sub(1).map(|i| &mut self.components[i]) {
+            Some(Component::Insert(prev)) => prev.push_str(s),
+            _ => self.components.insert(at, Component::Insert(s.to_string())),
+        }
+    }
+
+    fn delete(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.base_len += n;
+        match self.components.last_mut() {
+            Some(Component::Delete(m)) => *m += n,
+            _ => self.components.push(Component::Delete(n)),
+        }
+    }
+
+    /// Parse the JSON array form (positive = retain, string = insert,
+    /// negative = delete).
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let items = value.as_array().ok_or("ops must be an array")?;
+        let mut op = TextOp::default();
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                Value::String(s) if !s.is_empty() => op.insert(s),
+                Value::Number(n) => {
+                    let n = n
+                        .as_i64()
+                        .filter(|n| *n != 0)
+                        .ok_or_else(|| format!("ops[{i}]: expected a non-zero integer"))?;
+                    let len = usize::try_from(n.unsigned_abs())
+                        .ok()
+                        .filter(|len| op.base_len.checked_add(*len).is_some())
+                        .filter(|len| n < 0 || op.target_len.checked_add(*len).is_some())
+                        .ok_or_else(|| format!("ops[{i}]: operation is too long"))?;
+                    if n > 0 {
+                        op.retain(len);
+                    } else {
+                        op.delete(len);
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "ops[{i}]: expected an integer or a non-empty string"
+                    ));
+                }
+            }
+        }
+        Ok(op)
+    }
+
+    /// JSON array form, as accepted by from_json.
+    pub fn to_json(&self) -> Value {
+        self.components
+            .iter()
+            .map(|c| match c {
+                Component::Retain(n) => json!(n),
+                Component::Insert(s) => json!(s),
+                Component::Delete(n) => json!(-(*n as i64)),
+            })
+            .collect()
+    }
+
+    /// Whether applying the operation changes nothing.
+    pub fn is_noop(&self) -> bool {
+        self.components
+            .iter()
+            .all(|c| matches!(c, Component::Retain(_)))
+    }
+
+    /// Apply to `text`; None if the operation does not cover it exactly.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        let mut chars = text.chars();
+        if chars.clone().count() != self.base_len {
+            return None;
+        }
+        let mut out = String::with_capacity(text.len());
+        for c in &self.components {
+            match c {
+                Component::Retain(n) => out.extend(chars.by_ref().take(*n)),
+                Component::Insert(s) => out.push_str(s),
+                Component::Delete(n) => {
+                    chars.by_ref().take(*n).for_each(drop);
+                }
+            }
+        }
+        Some(out)
+    }
+
+    /**
+     * Transform two concurrent operations on the same note
+     *
+     * Returns `(a', b')` such that applying `a` then `b'` gives the same
+     * note as `b` then `a'`. Inserts at the same position put `a`'s first.
+     * None if the operations do not apply to notes of the same length.
+     */
+    pub fn transform(a: &TextOp, b: &TextOp) -> Option<(TextOp, TextOp)> {
+        if a.base_len != b.base_len {
+            return None;
+        }
+        let (mut a_prime, mut b_prime) = (TextOp::default(), TextOp::default());
+        let mut a_iter = a.components.iter().cloned();
+        let mut b_iter = b.components.iter().cloned();
+        let (mut x, mut y) = (a_iter.next(), b_iter.next());
+        loop {
+            match (x.take(), y.take()) {
+                (None, None) => break,
+                (Some(Component::Insert(s)), rest) => {
+                    b_prime.retain(s.chars().count());
+                    a_prime.insert(&s);
+                    (x, y) = (a_iter.next(), rest);
+                }
+                (rest, Some(Component::Insert(s))) => {
+                    a_prime.retain(s.chars().count());
+                    b_prime.insert(&s);
+                    (x, y) = (rest, b_iter.next());
+                }
+                (None, Some(_)) | (Some(_), None) => return None,
+                (Some(p), Some(q)) => {
+                    let n = p.len().min(q.len());
+                    match (&p, &q) {
+                        (Component::Retain(_), Component::Retain(_)) => {
+                            a_prime.retain(n);
+                            b_prime.retain(n);
+                        }
+                        (Component::Delete(_), Component::Retain(_)) => a_prime.delete(n),
+                        (Component::Retain(_), Component::Delete(_)) => b_prime.delete(n),
+                        _ => {} // Both deleted the same characters
+                    }
+                    x = p.shorten(n).or_else(|| a_iter.next());
+                    y = q.shorten(n).or_else(|| b_iter.next());
+                }
+            }
+        }
+        Some((a_prime, b_prime))
+    }
+}
+
+/**
+ * Editing session of one note
+ */
+struct NoteDoc {
+    text: String,
+    revision: i64,             // Revision `text` is at
+    history: VecDeque<TextOp>, // Last patches; the newest produced `revision`
+    touched: Instant,          // For dropping idle sessions
+}
+
+impl NoteDoc {
+    fn new(text: String, revision: i64) -> Self {
+        Self {
+            text,
+            revision,
+            history: VecDeque::new(),
+            touched: Instant::now(),
+        }
+    }
+}
+
+/**
+ * Open note sessions, keyed by todo id
+ */
+#[derive(Default)]
+pub struct NoteSessions {
+    docs: Mutex<HashMap<String, NoteDoc>>,
+}
+
+/// First revision of a new session: the clock, so revisions handed out
+/// before a restart or a reset are never valid again.
+fn fresh_revision(after: i64) -> i64 {
+    Utc::now().timestamp_millis().max(after + 1)
+}
+
+impl NoteSessions {
+    /// Session for `todo_id`, (re)started if the stored note differs.
+    fn doc<'a>(
+        docs: &'a mut HashMap<String, NoteDoc>,
+        todo_id: &str,
+        stored: &str,
+    ) -> &'a mut NoteDoc {
+        if !docs.contains_key(todo_id) && docs.len() >= MAX_SESSIONS {
+            let idle = docs
+                .iter()
+                .min_by_key(|(_, d)| d.touched)
+                .map(|(id, _)| id.clone());
+            if let Some(idle) = idle {
+                docs.remove(&idle);
+            }
+        }
+        let doc = docs
+            .entry(todo_id.to_string())
+            .or_insert_with(|| NoteDoc::new(stored.to_string(), fresh_revision(0)));
+        if doc.text != stored {
+            *doc = NoteDoc::new(stored.to_string(), fresh_revision(doc.revision));
+        }
+        doc.touched = Instant::now();
+        doc
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/todos/{id}/note", get(get_note))
+        .route("/api/todos/{id}/note/patches", post(patch_note))
+}
+
+/**
+ * Response for GET /api/todos/{id}/note
+ */
+#[derive(Debug, Serialize)]
+pub struct NoteState {
+    pub todo_id: String,
+    pub note: String,  // Current note ("" when there is none)
+    pub revision: i64, // Base revision for the next patch
+}
+
+/**
+ * Request body for POST /api/todos/{id}/note/patches
+ */
+#[derive(Debug, Deserialize)]
+pub struct NotePatch {
+    pub revision: i64,             // Revision the ops were made against
+    pub ops: Value,                // Operation in JSON array form
+    pub client_id: Option<String>, // Echoed in the note.patch event
+}
+
+/**
+ * Response for a patch, also the data of the `note.patch` event
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct NotePatched {
+    pub todo_id: String,
+    pub project_id: Option<String>,
+    pub revision: i64, // Revision after the patch
+    pub ops: Value,    // Ops as applied (transformed against concurrent patches)
+    pub client_id: Option<String>,
+}
+
+async fn get_note(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<NoteState>> {
+    let todo = st.todos.get(&id).await?;
+    let note = todo.note.unwrap_or_default();
+    let mut docs = st.notes.docs.lock().await;
+    let doc = NoteSessions::doc(&mut docs, &id, &note);
+    Ok(Json(NoteState {
+        todo_id: id,
+        note,
+        revision: doc.revision,
+    }))
+}
+
+async fn patch_note(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<NotePatch>,
+) -> ApiResult<Json<NotePatched>> {
+    let op = TextOp::from_json(&body.ops).map_err(ApiError::BadRequest)?;
+
+    // One patch at a time, so the history matches what was saved
+    let mut docs = st.notes.docs.lock().await;
+    let todo = st.todos.get(&id).await?;
+    let stored = todo.note.unwrap_or_default();
+    let doc = NoteSessions::doc(&mut docs, &id, &stored);
+
+    let behind = doc
+        .revision
+        .checked_sub(body.revision)
+        .and_then(|behind| usize::try_from(behind).ok())
+        .filter(|behind| *behind <= doc.history.len())
+        .ok_or_else(|| {
+            ApiError::Conflict(format!(
+                "revision {} is not available, reload the note (now at {})",
+                body.revision, doc.revision
+            ))
+        })?;
+    let mut op = op;
+    for applied in doc.history.iter().skip(doc.history.len() - behind) {
+        op = TextOp::transform(&op, applied)
+            .ok_or_else(|| ApiError::BadRequest("ops do not match the note's length".into()))?
+            .0;
+    }
+    if op.target_len > MAX_NOTE_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "note must be at most {MAX_NOTE_CHARS} characters"
+        )));
+    }
+    let text = op
+        .apply(&doc.text)
+        .ok_or_else(|| ApiError::BadRequest("ops do not match the note's length".into()))?;
+
+    if !op.is_noop() {
+        let update = TodoUpdate {
+            note: Some(text.clone()),
+            ..Default::default()
+        };
+        let saved = st.todos.as_actor(actor.clone()).update(&id, update).await?;
+        notifications::note_mentions(&st, &actor, &saved, Some(&doc.text)).await;
+        doc.text = text;
+        doc.revision += 1;
+        doc.history.push_back(op.clone());
+        if doc.history.len() > HISTORY_LIMIT {
+            doc.history.pop_front();
+        }
+    }
+
+    let patched = NotePatched {
+        todo_id: id,
+        project_id: todo.project_id,
+        revision: doc.revision,
+        ops: op.to_json(),
+        client_id: body.client_id,
+    };
+    if !op.is_noop() {
+        emit(&st.hub, "note.patch", &patched);
+    }
+    Ok(Json(patched))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{db::init_pool_with_size, model::TodoCreate, ws::WsHub};
+
+    fn op(ops: Value) -> TextOp {
+        TextOp::from_json(&ops).unwrap()
+    }
+
+    /// Both orders of applying `a` and `b` to `s`, which must agree (TP1).
+    fn converge(s: &str, a: &TextOp, b: &TextOp) -> String {
+        let (a_prime, b_prime) = TextOp::transform(a, b).unwrap();
+        let ab = b_prime.apply(&a.apply(s).unwrap()).unwrap();
+        let ba = a_prime.apply(&b.apply(s).unwrap()).unwrap();
+        assert_eq!(ab, ba, "{:?} / {:?}", a.to_json(), b.to_json());
+        ab
+    }
+
+    #[test]
+    fn concurrent_edits_converge() {
+        let s = "abcdef";
+        let ops = [
+            json!([6]),
+            json!(["X", 6]),
+            json!([3, "X", 3]),
+            json!([3, "Y", 3]),
+            json!([6, "Z"]),
+            json!([2, -2, 2]),
+            json!([1, -3, 2]),
+            json!([3, -3]),
+            json!([-6]),
+            json!([2, -2, "Q", 2]),
+            json!([3, "W", -1, 2]),
+        ];
+        for a in &ops {
+            for b in &ops {
+                converge(s, &op(a.clone()), &op(b.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn transform_cases() {
+        let s = "abcdef";
+        let cases = [
+            // Inserts at the same position: the first operation's goes first
+            (json!([3, "X", 3]), json!([3, "Y", 3]), "abcXYdef"),
+            (json!([3, "Y", 3]), json!([3, "X", 3]), "abcYXdef"),
+            (json!(["X", 6]), json!(["Y", 6]), "XYabcdef"),
+            (json!([6, "X"]), json!([6, "Y"]), "abcdefXY"),
+            // An insert inside a deleted range survives
+            (json!([1, -3, 2]), json!([2, "X", 4]), "aXef"),
+            (json!([2, "X", 4]), json!([1, -3, 2]), "aXef"),
+            // Deletes: same range once, overlapping ranges as their union
+            (json!([2, -2, 2]), json!([2, -2, 2]), "abef"),
+            (json!([1, -3, 2]), json!([2, -3, 1]), "af"),
+            (json!([-6]), json!([2, -2, 2]), ""),
+            // Replacing the same range keeps both replacements
+            (json!([2, -2, "Q", 2]), json!([2, -2, "R", 2]), "abQRef"),
+        ];
+        for (a, b, want) in cases {
+            assert_eq!(
+                converge(s, &op(a.clone()), &op(b.clone())),
+                want,
+                "{a} / {b}"
+            );
+        }
+    }
+
+    #[test]
+    fn lengths_count_characters() {
+        let s = "héllo 🌍"; // 7 characters, 11 bytes
+        assert_eq!(op(json!([7])).apply(s).as_deref(), Some(s));
+        assert_eq!(op(json!([11])).apply(s), None);
+        assert_eq!(
+            op(json!([1, -1, "e", 5])).apply(s).as_deref(),
+            Some("hello 🌍")
+        );
+        assert_eq!(
+            op(json!([6, -1, "🌱"])).apply(s).as_deref(),
+            Some("héllo 🌱")
+        );
+        // Inserts are kept in front of an adjacent delete
+        assert_eq!(op(json!([6, -1, "🌱"])).to_json(), json!([6, "🌱", -1]));
+
+        let a = op(json!([1, -1, "e", 5]));
+        let b = op(json!([6, "🙂", 1]));
+        assert_eq!(converge(s, &a, &b), "hello 🙂🌍");
+        let (_, b_prime) = TextOp::transform(&a, &b).unwrap();
+        assert_eq!(b_prime.to_json(), json!([6, "🙂", 1]));
+    }
+
+    #[test]
+    fn mismatched_lengths_are_rejected() {
+        assert_eq!(op(json!([5])).apply("abcdef"), None);
+        assert_eq!(op(json!([3, -4])).apply("abcdef"), None);
+        assert_eq!(op(json!(["X", 7])).apply("abcdef"), None);
+        assert!(TextOp::transform(&op(json!([5])), &op(json!([6]))).is_none());
+        assert!(TextOp::transform(&op(json!([2, "X", 3])), &op(json!([-6]))).is_none());
+
+        for bad in [
+            json!({"retain": 5}),
+            json!([0]),
+            json!([""]),
+            json!([1.5]),
+            json!([null]),
+            json!([i64::MAX, i64::MAX, i64::MAX]),
+            json!([i64::MAX, i64::MAX, -i64::MAX]),
+        ] {
+            assert!(TextOp::from_json(&bad).is_err(), "{bad}");
+        }
+    }
+
+    fn patch(revision: i64, ops: Value) -> JsonBody<NotePatch> {
+        JsonBody(NotePatch {
+            revision,
+            ops,
+            client_id: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn patches_are_rebased_or_rejected() {
+        let pool = init_pool_with_size("sqlite::memory:", 1).await.unwrap();
+        let st = AppState::new(pool, Arc::new(WsHub::new()));
+        let todo = st
+            .todos
+            .create(TodoCreate {
+                title: "Shopping".into(),
+                note: Some("milk".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let id = || Path(todo.id.clone());
+        let send = |body| patch_note(State(st.clone()), Actor::system(), id(), body);
+
+        let Json(start) = get_note(State(st.clone()), id()).await.unwrap();
+        assert_eq!(start.note, "milk");
+        let rev = start.revision;
+
+        // Current revision: applied as sent
+        let Json(first) = send(patch(rev, json!([4, ", eggs"]))).await.unwrap();
+        assert_eq!(first.revision, rev + 1);
+        assert_eq!(first.ops, json!([4, ", eggs"]));
+
+        // One revision behind: transformed against the patch in between
+        let Json(second) = send(patch(rev, json!(["- ", 4]))).await.unwrap();
+        assert_eq!(second.revision, rev + 2);
+        assert_eq!(second.ops, json!(["- ", 10]));
+        let saved = st.todos.get(&todo.id).await.unwrap();
+        assert_eq!(saved.note.as_deref(), Some("- milk, eggs"));
+
+        // A no-op keeps the revision
+        let Json(noop) = send(patch(rev + 2, json!([12]))).await.unwrap();
+        assert_eq!(noop.revision, rev + 2);
+
+        // Revisions from the future, before the session or far off: reload
+        for revision in [rev + 3, rev - 1, i64::MIN, i64::MAX] {
+            let err = send(patch(revision, json!([12]))).await.unwrap_err();
+            assert!(matches!(err, ApiError::Conflict(_)), "{revision}: {err:?}");
+        }
+
+        // Ops that do not cover the note
+        for ops in [
+            json!([5]),
+            json!([13]),
+            json!([i64::MAX, i64::MAX, i64::MAX]),
+        ] {
+            let err = send(patch(rev + 2, ops.clone())).await.unwrap_err();
+            assert!(matches!(err, ApiError::BadRequest(_)), "{ops}: {err:?}");
+        }
+
+        // A plain update restarts the session: earlier revisions are gone
+        st.todos
+            .update(
+                &todo.id,
+                TodoUpdate {
+                    note: Some("bread".into()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let err = send(patch(rev + 2, json!([5, "!"]))).await.unwrap_err();
+        assert!(matches!(err, ApiError::Conflict(_)), "{err:?}");
+        let Json(reloaded) = get_note(State(st.clone()), id()).await.unwrap();
+        assert_eq!(reloaded.note, "bread");
+        assert!(reloaded.revision > rev + 2);
+        let Json(after) = send(patch(reloaded.revision, json!([5, "!"])))
+            .await
+            .unwrap();
+        assert_eq!(after.revision, reloaded.revision + 1);
+    }
+}
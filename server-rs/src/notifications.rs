@@ -0,0 +1,477 @@
+/**
+ * Notification center and @mentions
+ *
+ * Every household member has an in-app inbox, independent of the push
+ * channels, so the web UI's bell works without ntfy or SMTP. Kinds:
+ *
+ * - "mention"   someone wrote `@name` in a comment or a todo's note
+ * - "assigned"  someone else made the member the assignee of a todo
+ * - "reminder"  a todo the member is responsible for is due soon
+ * - "overdue"   ... is past its due date
+ *
+ * Reminders go to the todo's assignee, else the members of its category,
+ * else everyone. New notifications are broadcast over the WebSocket as
+ * `notification.created`, and every change of a member's unread count as
+ * `notification.unread` {"user_id", "unread"}; clients keep the ones for
+ * their own `user_id`.
+ *
+ * Mentions: a handle matches a user whose name, without spaces and
+ * ignoring case, is the same ("@MaryAnn" reaches "Mary Ann"); unknown
+ * handles and e-mail addresses are ignored, and nobody is notified about
+ * their own mentions. A note only notifies members it did not mention
+ * before the edit, so retyping the rest of the note stays quiet. Mentions
+ * are also sent to the push channels (ntfy, Gotify) when configured.
+ *
+ * Requests act for the X-User-Id caller:
+ * - GET    /api/notifications              newest first; `?unread=true`, `?kind=`
+ * - GET    /api/notifications/unread       {"unread": n}
+ * - POST   /api/notifications/{id}/read    mark one as read
+ * - POST   /api/notifications/read         mark all as read
+ * - DELETE /api/notifications/{id}         clear one
+ * - DELETE /api/notifications              clear all (`?read=true`: only read ones)
+ */
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    routing::{delete, get, post},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+    model::{Todo, User, UserNotification},
+    notify::{self, Notification},
+    routes::AppState,
+    services::emit,
+    settings,
+    ws::WsHub,
+};
+
+/// Most notifications returned by one list request.
+const LIST_LIMIT: i64 = 200;
+/// Longest excerpt kept in a notification (characters).
+const EXCERPT_CHARS: usize = 200;
+
+/**
+ * Storage for the notifications table; announces changes on the hub
+ */
+#[derive(Clone)]
+pub struct Notifications {
+    pool: SqlitePool,
+    hub: Arc<WsHub>,
+}
+
+impl Notifications {
+    pub fn new(pool: SqlitePool, hub: Arc<WsHub>) -> Self {
+        Self { pool, hub }
+    }
+
+    /// Store a notification for `user_id` and announce it.
+    pub async fn add(
+        &self,
+        user_id: &str,
+        kind: &str,
+        todo_id: Option<&str>,
+        actor: &Actor,
+        title: String,
+        message: String,
+    ) -> ApiResult<UserNotification> {
+        let n = UserNotification {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            kind: kind.to_string(),
+            todo_id: todo_id.map(String::from),
+            actor: actor.as_str().to_string(),
+            title,
+            message: excerpt(&message),
+            read_at: None,
+            created_at: Utc::now(),
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO notifications (id,user_id,kind,todo_id,actor,title,message,read_at,created_at)
+            VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)
+        "#,
+        )
+        .bind(&n.id)
+        .bind(&n.user_id)
+        .bind(&n.kind)
+        .bind(&n.todo_id)
+        .bind(&n.actor)
+        .bind(&n.title)
+        .bind(&n.message)
+        .bind(n.read_at)
+        .bind(n.created_at)
+        .execute(&self.pool)
+        .await?;
+        emit(&self.hub, "notification.created", &n);
+        self.announce_unread(user_id).await?;
+        Ok(n)
+    }
+
+    /// Notifications of `user_id` not marked read.
+    pub async fn unread(&self, user_id: &str) -> ApiResult<i64> {
+        Ok(sqlx::query_scalar(
+            "SELECT COUNT(*) FROM notifications WHERE user_id=?1 AND read_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Broadcast the current unread count of `user_id`.
+    async fn announce_unread(&self, user_id: &str) -> ApiResult<()> {
+        let unread = self.unread(user_id).await?;
+        emit(
+            &self.hub,
+            "notification.unread",
+            &json!({"user_id": user_id, "unread": unread}),
+        );
+        Ok(())
+    }
+
+    /// Who hears about a todo: its assignee, else the members of its
+    /// category, else every active user.
+    pub async fn recipients(&self, todo: &Todo) -> ApiResult<Vec<String>> {
+        if let Some(assignee) = &todo.assignee_id {
+            return Ok(vec![assignee.clone()]);
+        }
+        if let Some(category) = &todo.category_id {
+            let members: Vec<String> =
+                sqlx::query_scalar("SELECT user_id FROM category_members WHERE category_id=?1")
+                    .bind(category)
+                    .fetch_all(&self.pool)
+                    .await?;
+            if !members.is_empty() {
+                return Ok(members);
+            }
+        }
+        Ok(sqlx::query_scalar("SELECT id FROM users WHERE deleted=0")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    /// Tell the new assignee (unless they assigned themselves).
+    pub async fn assigned(&self, actor: &Actor, todo: &Todo) -> ApiResult<()> {
+        let Some(assignee) = todo.assignee_id.as_deref() else {
+            return Ok(());
+        };
+        if actor.user_id() == Some(assignee) {
+            return Ok(());
+        }
+        let by = who(&self.pool, actor).await;
+        self.add(
+            assignee,
+            "assigned",
+            Some(&todo.id),
+            actor,
+            format!("{by} assigned \"{}\" to you", todo.title),
+            todo.note.clone().unwrap_or_default(),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/notifications",
+            get(list_notifications).delete(clear_all),
+        )
+        .route("/api/notifications/unread", get(unread_count))
+        .route("/api/notifications/read", post(mark_all_read))
+        .route("/api/notifications/{id}", delete(clear_one))
+        .route("/api/notifications/{id}/read", post(mark_read))
+}
+
+/// The `@handles` in a text, lowercased, without duplicates.
+fn handles(text: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut prev: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        // An `@` inside a word is an e-mail address, not a mention
+        if c == '@' && !prev.is_some_and(|p| p.is_alphanumeric() || p == '_') {
+            let rest = &text[i + 1..];
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '-')))
+                .unwrap_or(rest.len());
+            // Sentence punctuation after a mention is not part of it
+            let handle = rest[..end].trim_end_matches(['.', '-']).to_lowercase();
+            if !handle.is_empty() && !found.contains(&handle) {
+                found.push(handle);
+            }
+        }
+        prev = Some(c);
+    }
+    found
+}
+
+/// The handle that mentions a user: the name without spaces, lowercased.
+fn handle_of(user: &User) -> String {
+    user.name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Shortened text for a notification body.
+fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/**
+ * Notify the members mentioned in a comment
+ *
+ * Failures are logged; the comment itself is already saved.
+ */
+pub async fn comment_mentions(st: &AppState, actor: &Actor, todo: &Todo, body: &str) {
+    let title = format!(
+        "{} mentioned you on \"{}\"",
+        who(&st.pool, actor).await,
+        todo.title
+    );
+    mentions(st, actor, todo, &handles(body), &title, body).await;
+}
+
+/**
+ * Notify the members newly mentioned in a todo's note
+ *
+ * `before` is the note as it was before the change.
+ */
+pub async fn note_mentions(st: &AppState, actor: &Actor, todo: &Todo, before: Option<&str>) {
+    let Some(note) = todo.note.as_deref() else {
+        return;
+    };
+    let old: HashSet<String> = before
+        .map(handles)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let new: Vec<String> = handles(note)
+        .into_iter()
+        .filter(|h| !old.contains(h))
+        .collect();
+    if new.is_empty() {
+        return;
+    }
+    let title = format!(
+        "{} mentioned you in the note of \"{}\"",
+        who(&st.pool, actor).await,
+        todo.title
+    );
+    mentions(st, actor, todo, &new, &title, note).await;
+}
+
+/// Display name for the actor in a notification title.
+async fn who(pool: &SqlitePool, actor: &Actor) -> String {
+    let Some(id) = actor.user_id() else {
+        return "Someone".into();
+    };
+    sqlx::query_scalar("SELECT name FROM users WHERE id=?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "Someone".into())
+}
+
+async fn mentions(
+    st: &AppState,
+    actor: &Actor,
+    todo: &Todo,
+    handles: &[String],
+    title: &str,
+    text: &str,
+) {
+    if handles.is_empty() {
+        return;
+    }
+    let users = match sqlx::query_as::<_, User>("SELECT * FROM users WHERE deleted=0")
+        .fetch_all(&st.pool)
+        .await
+    {
+        Ok(users) => users,
+        Err(e) => {
+            tracing::warn!(error = %e, "loading users for mentions failed");
+            return;
+        }
+    };
+    for user in users {
+        if !handles.contains(&handle_of(&user)) || actor.user_id() == Some(user.id.as_str()) {
+            continue;
+        }
+        let added = st
+            .notifications
+            .add(
+                &user.id,
+                "mention",
+                Some(&todo.id),
+                actor,
+                title.to_string(),
+                text.to_string(),
+            )
+            .await;
+        let push = !st.notifiers.is_empty()
+            && settings::wants_channel(&st.pool, &user.id, "push")
+                .await
+                .unwrap_or(true);
+        match added {
+            Ok(n) if push => {
+                let notifiers = st.notifiers.clone();
+                let push = Notification {
+                    title: n.title,
+                    message: n.message,
+                    urgent: false,
+                    tags: vec!["speech_balloon".into()],
+                };
+                tokio::spawn(async move { notify::broadcast(&notifiers, &push).await });
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!(user = %user.id, error = %e, "mention notification failed"),
+        }
+    }
+}
+
+/// The caller's user id; notifications need an X-User-Id header.
+fn recipient(actor: &Actor) -> ApiResult<&str> {
+    actor
+        .user_id()
+        .ok_or_else(|| ApiError::BadRequest("notifications need an X-User-Id header".into()))
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    unread: Option<bool>, // true = only notifications not marked read
+    kind: Option<String>, // Only this kind ("mention", "assigned", ...)
+}
+
+async fn list_notifications(
+    State(st): State<AppState>,
+    actor: Actor,
+    Query(p): Query<ListParams>,
+) -> ApiResult<Json<Vec<UserNotification>>> {
+    let user_id = recipient(&actor)?;
+    let unread = p.unread.unwrap_or(false);
+    let rows = sqlx::query_as::<_, UserNotification>(
+        r#"
+        SELECT * FROM notifications
+        WHERE user_id=?1 AND (?2=0 OR read_at IS NULL) AND (?3 IS NULL OR kind=?3)
+        ORDER BY created_at DESC LIMIT ?4
+    "#,
+    )
+    .bind(user_id)
+    .bind(unread)
+    .bind(p.kind)
+    .bind(LIST_LIMIT)
+    .fetch_all(&st.pool)
+    .await?;
+    Ok(Json(rows))
+}
+
+/**
+ * Response for GET /api/notifications/unread and the mark-all/clear endpoints
+ */
+#[derive(Debug, Serialize)]
+pub struct UnreadCount {
+    pub unread: i64,
+}
+
+async fn unread_count(State(st): State<AppState>, actor: Actor) -> ApiResult<Json<UnreadCount>> {
+    let user_id = recipient(&actor)?;
+    Ok(Json(UnreadCount {
+        unread: st.notifications.unread(user_id).await?,
+    }))
+}
+
+async fn mark_read(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> ApiResult<Json<UserNotification>> {
+    let user_id = recipient(&actor)?;
+    let res = sqlx::query(
+        "UPDATE notifications SET read_at=?1 WHERE id=?2 AND user_id=?3 AND read_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(&id)
+    .bind(user_id)
+    .execute(&st.pool)
+    .await?;
+    let n = sqlx::query_as::<_, UserNotification>(
+        "SELECT * FROM notifications WHERE id=?1 AND user_id=?2",
+    )
+    .bind(&id)
+    .bind(user_id)
+    .fetch_optional(&st.pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    if res.rows_affected() > 0 {
+        st.notifications.announce_unread(user_id).await?;
+    }
+    Ok(Json(n))
+}
+
+async fn mark_all_read(State(st): State<AppState>, actor: Actor) -> ApiResult<Json<UnreadCount>> {
+    let user_id = recipient(&actor)?;
+    sqlx::query("UPDATE notifications SET read_at=?1 WHERE user_id=?2 AND read_at IS NULL")
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&st.pool)
+        .await?;
+    st.notifications.announce_unread(user_id).await?;
+    Ok(Json(UnreadCount { unread: 0 }))
+}
+
+async fn clear_one(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let user_id = recipient(&actor)?;
+    let res = sqlx::query("DELETE FROM notifications WHERE id=?1 AND user_id=?2")
+        .bind(&id)
+        .bind(user_id)
+        .execute(&st.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    st.notifications.announce_unread(user_id).await?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+struct ClearParams {
+    read: Option<bool>, // true = keep unread notifications
+}
+
+async fn clear_all(
+    State(st): State<AppState>,
+    actor: Actor,
+    Query(p): Query<ClearParams>,
+) -> ApiResult<Json<UnreadCount>> {
+    let user_id = recipient(&actor)?;
+    sqlx::query("DELETE FROM notifications WHERE user_id=?1 AND (?2=0 OR read_at IS NOT NULL)")
+        .bind(user_id)
+        .bind(p.read.unwrap_or(false))
+        .execute(&st.pool)
+        .await?;
+    st.notifications.announce_unread(user_id).await?;
+    Ok(Json(UnreadCount {
+        unread: st.notifications.unread(user_id).await?,
+    }))
+}
@@ -0,0 +1,133 @@
+/**
+ * Push notification channels
+ *
+ * A Notifier delivers a short alert to some external service. The reminder
+ * scheduler fans every alert out to all configured notifiers, so adding a
+ * new channel means implementing the trait and registering it in
+ * notifiers_from_env().
+ *
+ * Channels:
+ * - ntfy   (NTFY_URL=https://ntfy.sh/<topic>, optional NTFY_TOKEN)
+ * - Gotify (GOTIFY_URL=https://gotify.example.com, GOTIFY_TOKEN=<app token>)
+ */
+use std::{env, sync::Arc};
+
+use futures::future::BoxFuture;
+use serde_json::json;
+
+/**
+ * A single alert, independent of the delivery channel
+ */
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub title: String,     // Short headline ("Overdue: pay rent")
+    pub message: String,   // Body text
+    pub urgent: bool,      // Raises the priority on channels that support it
+    pub tags: Vec<String>, // Channel hints (ntfy renders known tags as emoji)
+}
+
+/**
+ * Delivery channel abstraction
+ */
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+/**
+ * ntfy.sh (or self-hosted ntfy) topic publisher
+ */
+pub struct NtfyNotifier {
+    client: reqwest::Client,
+    url: String,           // Full topic URL
+    token: Option<String>, // Access token for protected topics
+}
+
+impl Notifier for NtfyNotifier {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let mut req = self
+                .client
+                .post(&self.url)
+                .header("Title", &n.title)
+                .header("Priority", if n.urgent { "high" } else { "default" })
+                .body(n.message.clone());
+            if !n.tags.is_empty() {
+                req = req.header("Tags", n.tags.join(","));
+            }
+            if let Some(token) = &self.token {
+                req = req.bearer_auth(token);
+            }
+            req.send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/**
+ * Gotify server message publisher
+ */
+pub struct GotifyNotifier {
+    client: reqwest::Client,
+    url: String,   // Server base URL
+    token: String, // Application token
+}
+
+impl Notifier for GotifyNotifier {
+    fn name(&self) -> &'static str {
+        "gotify"
+    }
+
+    fn send<'a>(&'a self, n: &'a Notification) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.client
+                .post(format!("{}/message", self.url.trim_end_matches('/')))
+                .header("X-Gotify-Key", &self.token)
+                .json(&json!({
+                    "title": n.title,
+                    "message": n.message,
+                    "priority": if n.urgent { 8 } else { 5 },
+                }))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Build every notifier whose environment variables are present.
+pub fn notifiers_from_env() -> Vec<Arc<dyn Notifier>> {
+    let client = reqwest::Client::new();
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+
+    if let Ok(url) = env::var("NTFY_URL") {
+        notifiers.push(Arc::new(NtfyNotifier {
+            client: client.clone(),
+            url,
+            token: env::var("NTFY_TOKEN").ok(),
+        }));
+    }
+    if let (Ok(url), Ok(token)) = (env::var("GOTIFY_URL"), env::var("GOTIFY_TOKEN")) {
+        notifiers.push(Arc::new(GotifyNotifier {
+            client: client.clone(),
+            url,
+            token,
+        }));
+    }
+    notifiers
+}
+
+/// Deliver a notification on every channel; failures are logged, not fatal.
+pub async fn broadcast(notifiers: &[Arc<dyn Notifier>], notification: &Notification) {
+    for notifier in notifiers {
+        if let Err(e) = notifier.send(notification).await {
+            tracing::warn!(channel = notifier.name(), error = %e, "notification failed");
+        }
+    }
+}
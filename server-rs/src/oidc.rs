@@ -0,0 +1,419 @@
+/**
+ * OpenID Connect sign-in (authorization code flow)
+ *
+ * Optional: with OIDC_ISSUER set, members sign in at an identity provider
+ * (Authelia, Keycloak, Google, ...) instead of picking their name, and no
+ * passwords are kept on the Pi. A successful sign-in opens a device
+ * session (see sessions.rs) and POST /api/sessions is disabled.
+ *
+ * - GET /api/auth/oidc/login?return_to=/     redirect to the provider
+ * - GET /api/auth/oidc/callback              provider redirects back here
+ *
+ * The callback redirects to `return_to` (a path on this server) with the
+ * session tokens in the URL fragment, which browsers do not send on:
+ *
+ * ```text
+ * /#session_id=...&access_token=...&refresh_token=...&access_expires_at=...
+ * ```
+ *
+ * Identities are mapped to members by issuer and subject (`sub`). A
+ * subject seen for the first time is linked to the member with the same
+ * verified e-mail address; with OIDC_CREATE_USERS=true an unknown person
+ * becomes a new member, otherwise the sign-in is refused (anyone with a
+ * Google account could sign in otherwise).
+ *
+ * Settings: OIDC_ISSUER, OIDC_CLIENT_ID, OIDC_CLIENT_SECRET,
+ * OIDC_REDIRECT_URL (the callback's public URL), optional OIDC_SCOPES
+ * (default "openid profile email") and OIDC_CREATE_USERS.
+ *
+ * The ID token comes straight from the token endpoint over TLS, so its
+ * issuer, audience, expiry and nonce are checked but not its signature
+ * (OpenID Connect Core 3.1.3.7).
+ */
+use std::{collections::HashMap, env, sync::Arc};
+
+use anyhow::{Context, anyhow};
+use axum::{
+    Router,
+    extract::{Query, State},
+    response::Redirect,
+    routing::get,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ApiResult},
+    model::{User, UserCreate},
+    routes::AppState,
+    sessions::{self, Device},
+};
+
+/// How long a started sign-in may take at the provider.
+const LOGIN_TTL: Duration = Duration::minutes(10);
+/// Sign-ins in progress kept at most (older ones are dropped first).
+const MAX_PENDING: usize = 256;
+
+/**
+ * OIDC settings read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,        // OIDC_ISSUER - enables OIDC when set
+    pub client_id: String,     // OIDC_CLIENT_ID
+    pub client_secret: String, // OIDC_CLIENT_SECRET
+    pub redirect_url: String,  // OIDC_REDIRECT_URL: public URL of /api/auth/oidc/callback
+    pub scopes: String,        // OIDC_SCOPES, default "openid profile email"
+    pub create_users: bool,    // OIDC_CREATE_USERS: unknown people become members
+}
+
+impl OidcConfig {
+    /// Returns None when OIDC_ISSUER is not set; errors on incomplete settings.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(issuer) = env::var("OIDC_ISSUER") else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: env::var("OIDC_CLIENT_ID").context("OIDC_ISSUER requires OIDC_CLIENT_ID")?,
+            client_secret: env::var("OIDC_CLIENT_SECRET")
+                .context("OIDC_ISSUER requires OIDC_CLIENT_SECRET")?,
+            redirect_url: env::var("OIDC_REDIRECT_URL")
+                .context("OIDC_ISSUER requires OIDC_REDIRECT_URL")?,
+            scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid profile email".into()),
+            create_users: env::var("OIDC_CREATE_USERS").is_ok_and(|v| v == "true" || v == "1"),
+        }))
+    }
+}
+
+/**
+ * Provider endpoints from its discovery document
+ */
+#[derive(Debug, Clone, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+/**
+ * A sign-in waiting for the provider's redirect
+ */
+#[derive(Debug)]
+struct Pending {
+    nonce: String,
+    return_to: String,
+    started_at: DateTime<Utc>,
+}
+
+/**
+ * Client for one identity provider
+ */
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    discovery: Mutex<Option<Discovery>>, // Fetched on the first sign-in
+    pending: Mutex<HashMap<String, Pending>>, // By `state` parameter
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            discovery: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The provider's endpoints, fetched once and then cached.
+    async fn discovery(&self) -> anyhow::Result<Discovery> {
+        let mut cached = self.discovery.lock().await;
+        if let Some(d) = &*cached {
+            return Ok(d.clone());
+        }
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+        let d: Discovery = self
+            .http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("invalid discovery document at {url}"))?;
+        *cached = Some(d.clone());
+        Ok(d)
+    }
+
+    /// Start a sign-in: the provider URL to send the browser to.
+    async fn login_url(&self, return_to: String) -> anyhow::Result<String> {
+        let d = self.discovery().await?;
+        let state = Uuid::new_v4().simple().to_string();
+        let nonce = Uuid::new_v4().simple().to_string();
+        let mut url = reqwest::Url::parse(&d.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce);
+
+        let now = Utc::now();
+        let mut pending = self.pending.lock().await;
+        pending.retain(|_, p| p.started_at + LOGIN_TTL > now);
+        if pending.len() >= MAX_PENDING
+            && let Some(oldest) = pending
+                .iter()
+                .min_by_key(|(_, p)| p.started_at)
+                .map(|(k, _)| k.clone())
+        {
+            pending.remove(&oldest);
+        }
+        pending.insert(
+            state,
+            Pending {
+                nonce,
+                return_to,
+                started_at: now,
+            },
+        );
+        Ok(url.into())
+    }
+
+    /// Finish a sign-in: exchange the code and return the ID token claims.
+    async fn claims(&self, code: &str, pending: &Pending) -> anyhow::Result<Value> {
+        let d = self.discovery().await?;
+        let response: Value = self
+            .http
+            .post(&d.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let id_token = response["id_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("token response has no id_token"))?;
+        let payload = id_token
+            .split('.')
+            .nth(1)
+            .and_then(base64url_decode)
+            .ok_or_else(|| anyhow!("malformed id_token"))?;
+        let claims: Value = serde_json::from_slice(&payload)?;
+
+        let audience_ok = match &claims["aud"] {
+            Value::String(aud) => *aud == self.config.client_id,
+            Value::Array(auds) => auds.iter().any(|a| *a == *self.config.client_id),
+            _ => false,
+        };
+        if claims["iss"].as_str() != Some(d.issuer.as_str()) || !audience_ok {
+            return Err(anyhow!("id_token was issued for someone else"));
+        }
+        if claims["exp"]
+            .as_i64()
+            .is_none_or(|exp| exp <= Utc::now().timestamp())
+        {
+            return Err(anyhow!("id_token has expired"));
+        }
+        if claims["nonce"].as_str() != Some(pending.nonce.as_str()) {
+            return Err(anyhow!("id_token nonce does not match"));
+        }
+        Ok(claims)
+    }
+}
+
+/// Decode unpadded base64url (JWT segments).
+fn base64url_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let (mut buf, mut bits) = (0u32, 0);
+    for c in input.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        buf = (buf << 6) | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buf >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/auth/oidc/login", get(login))
+        .route("/api/auth/oidc/callback", get(callback))
+}
+
+fn client(st: &AppState) -> ApiResult<&Arc<OidcClient>> {
+    st.oidc.as_ref().ok_or(ApiError::NotFound)
+}
+
+#[derive(Deserialize)]
+struct LoginParams {
+    return_to: Option<String>, // Path to come back to (default "/")
+}
+
+async fn login(State(st): State<AppState>, Query(p): Query<LoginParams>) -> ApiResult<Redirect> {
+    let oidc = client(&st)?;
+    let return_to = p.return_to.unwrap_or_else(|| "/".into());
+    // Only paths on this server, so the tokens cannot be sent elsewhere
+    if !return_to.starts_with('/') || return_to.starts_with("//") || return_to.contains('\\') {
+        return Err(ApiError::BadRequest(
+            "return_to must be a path on this server".into(),
+        ));
+    }
+    Ok(Redirect::to(&oidc.login_url(return_to).await?))
+}
+
+#[derive(Deserialize)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>, // Set by the provider when sign-in failed
+    error_description: Option<String>,
+}
+
+async fn callback(
+    State(st): State<AppState>,
+    device: Device,
+    Query(p): Query<CallbackParams>,
+) -> ApiResult<Redirect> {
+    let oidc = client(&st)?;
+    if let Some(error) = p.error {
+        let detail = p.error_description.unwrap_or_default();
+        return Err(ApiError::Unauthorized(format!(
+            "sign-in failed: {error} {detail}"
+        )));
+    }
+    let (Some(code), Some(state)) = (p.code, p.state) else {
+        return Err(ApiError::BadRequest("missing code or state".into()));
+    };
+    let pending = oidc
+        .pending
+        .lock()
+        .await
+        .remove(&state)
+        .filter(|p| p.started_at + LOGIN_TTL > Utc::now())
+        .ok_or_else(|| ApiError::Unauthorized("sign-in expired, start again".into()))?;
+    let claims = oidc.claims(&code, &pending).await.map_err(|e| {
+        tracing::warn!(error = %e, "OIDC sign-in failed");
+        ApiError::Unauthorized("the identity provider's answer was not accepted".into())
+    })?;
+
+    let user_id = member_for(&st, &oidc.config, &claims).await?;
+    let tokens = sessions::sign_in(&st, &user_id, device).await?;
+    tracing::info!(user = %user_id, "signed in with OIDC");
+    Ok(Redirect::to(&format!(
+        "{}#session_id={}&user_id={}&access_token={}&access_expires_at={}&refresh_token={}&refresh_expires_at={}",
+        pending.return_to,
+        tokens.session_id,
+        tokens.user_id,
+        tokens.access_token,
+        tokens.access_expires_at.timestamp(),
+        tokens.refresh_token,
+        tokens.refresh_expires_at.timestamp(),
+    )))
+}
+
+/// The member an identity belongs to, linking or creating one on first sign-in.
+async fn member_for(st: &AppState, config: &OidcConfig, claims: &Value) -> ApiResult<String> {
+    let issuer = claims["iss"].as_str().unwrap_or_default();
+    let subject = claims["sub"]
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| ApiError::Unauthorized("id_token has no subject".into()))?;
+    let linked: Option<String> = sqlx::query_scalar(
+        r#"
+        SELECT i.user_id FROM user_identities i JOIN users u ON u.id = i.user_id
+        WHERE i.issuer=?1 AND i.subject=?2 AND u.deleted=0
+    "#,
+    )
+    .bind(issuer)
+    .bind(subject)
+    .fetch_optional(&st.pool)
+    .await?;
+    if let Some(user_id) = linked {
+        return Ok(user_id);
+    }
+
+    let email = claims["email"]
+        .as_str()
+        .filter(|_| claims["email_verified"].as_bool() == Some(true));
+    let by_email: Option<String> = match email {
+        Some(email) => {
+            sqlx::query_scalar(
+                "SELECT id FROM users WHERE lower(email)=lower(?1) AND deleted=0 LIMIT 1",
+            )
+            .bind(email)
+            .fetch_optional(&st.pool)
+            .await?
+        }
+        None => None,
+    };
+    let user_id = match by_email {
+        Some(id) => id,
+        None if config.create_users => {
+            let name = ["name", "preferred_username", "email"]
+                .iter()
+                .find_map(|k| claims[*k].as_str().filter(|v| !v.trim().is_empty()))
+                .unwrap_or(subject);
+            let user = User::new_from_create(UserCreate {
+                name: name.trim().to_string(),
+                email: email.map(String::from),
+                email_reminders: None,
+                email_digest: None,
+            });
+            sqlx::query(
+                r#"
+                INSERT INTO users (id,name,email,email_reminders,email_digest,created_at,updated_at,deleted)
+                VALUES (?1,?2,?3,?4,?5,?6,?7,?8)
+            "#,
+            )
+            .bind(&user.id)
+            .bind(&user.name)
+            .bind(&user.email)
+            .bind(user.email_reminders)
+            .bind(user.email_digest)
+            .bind(user.created_at)
+            .bind(user.updated_at)
+            .bind(user.deleted)
+            .execute(&st.pool)
+            .await?;
+            user.id
+        }
+        None => {
+            return Err(ApiError::Forbidden(
+                "no member matches this account; ask to be added with your e-mail address".into(),
+            ));
+        }
+    };
+    sqlx::query(
+        "INSERT INTO user_identities (issuer,subject,user_id,created_at) VALUES (?1,?2,?3,?4)",
+    )
+    .bind(issuer)
+    .bind(subject)
+    .bind(&user_id)
+    .bind(Utc::now())
+    .execute(&st.pool)
+    .await?;
+    Ok(user_id)
+}
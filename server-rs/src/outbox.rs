@@ -0,0 +1,169 @@
+/**
+ * Transactional outbox for WebSocket events
+ *
+ * Todo and category writes store the events describing them in an
+ * `outbox` table in the same transaction as the change (see repository),
+ * so a failed write announces nothing and a committed one is never lost.
+ * The dispatcher publishes pending events to the hub in `seq` order and
+ * marks them afterwards: after a crash between the two an event goes out
+ * again (at-least-once), never out of order. Published messages carry
+ * their `seq`, so clients can drop duplicates and line events up with the
+ * WebSocket snapshot (see ws.rs).
+ *
+ * Services wake the dispatcher after every write; a slow poll catches up
+ * on events left by a restart or a missed wake-up. Published events are
+ * kept for a day, then pruned; until then long-polling clients can fetch
+ * them by seq (see wait_events and events.rs).
+ */
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::sync::{Notify, watch};
+
+use crate::{
+    error::ApiResult,
+    repository::{OutboxEvent, TodoRepository},
+    ws::{WsHub, WsScope},
+};
+
+/// Fallback poll for events nobody woke the dispatcher for.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Events fetched per round trip.
+const BATCH_SIZE: i64 = 100;
+/// How long published events stay in the table.
+const RETENTION_HOURS: i64 = 24;
+/// Time between prunes.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Serialize a `{"type": ..., "data": ...}` event for the outbox.
+pub fn event<T: Serialize + ?Sized>(event_type: &str, data: &T) -> String {
+    json!({"type": event_type, "data": data}).to_string()
+}
+
+/// The stored `{"type": ..., "data": ...}` message with its `"seq"` added.
+pub fn sequenced(e: &OutboxEvent) -> Value {
+    let mut message: Value = serde_json::from_str(&e.message).unwrap_or(Value::Null);
+    if let Value::Object(fields) = &mut message {
+        fields.insert("seq".into(), e.seq.into());
+    }
+    message
+}
+
+/**
+ * Publishes stored events to the WebSocket hub
+ */
+pub struct Outbox {
+    repo: Arc<dyn TodoRepository>, // Owner of the outbox table
+    hub: Arc<WsHub>,
+    wake: Notify,                  // Signalled after every write that stored events
+    published: watch::Sender<i64>, // Seq of the newest event published by this process
+}
+
+impl Outbox {
+    pub fn new(repo: Arc<dyn TodoRepository>, hub: Arc<WsHub>) -> Self {
+        Self {
+            repo,
+            hub,
+            wake: Notify::new(),
+            published: watch::Sender::new(0),
+        }
+    }
+
+    /// Store events that are not part of a write and wake the dispatcher.
+    pub async fn enqueue(&self, events: &[String]) -> ApiResult<()> {
+        self.repo.enqueue_events(events).await?;
+        self.notify();
+        Ok(())
+    }
+
+    /// Publish promptly instead of at the next poll.
+    pub fn notify(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Publish every pending event; returns how many went out.
+    pub async fn publish_pending(&self) -> ApiResult<usize> {
+        let mut published = 0;
+        loop {
+            let batch = self.repo.pending_events(BATCH_SIZE).await?;
+            if batch.is_empty() {
+                return Ok(published);
+            }
+            for e in batch {
+                let _ = self.hub.tx.send(sequenced(&e).to_string()); // No clients is fine
+                self.repo.mark_published(e.seq).await?;
+                self.published.send_replace(e.seq);
+                published += 1;
+            }
+        }
+    }
+
+    /// Seq of the newest published event, the starting point for new pollers.
+    pub async fn last_seq(&self) -> ApiResult<i64> {
+        self.repo.last_published_seq().await
+    }
+
+    /**
+     * Published events after `since` that `scope` wants, waiting up to
+     * `wait` for the first one
+     *
+     * Returns the events (at most one batch) and the seq to continue from,
+     * which moves past events skipped by the scope as well.
+     */
+    pub async fn wait_events(
+        &self,
+        mut since: i64,
+        wait: Duration,
+        scope: &WsScope,
+    ) -> ApiResult<(Vec<OutboxEvent>, i64)> {
+        let deadline = tokio::time::Instant::now() + wait;
+        // Subscribe before querying so a publish in between still wakes us
+        let mut published = self.published.subscribe();
+        loop {
+            let batch = self.repo.published_events(since, BATCH_SIZE).await?;
+            let full = batch.len() as i64 == BATCH_SIZE;
+            if let Some(last) = batch.last() {
+                since = last.seq;
+            }
+            let events: Vec<OutboxEvent> = batch
+                .into_iter()
+                .filter(|e| scope.wants(&e.message))
+                .collect();
+            if !events.is_empty() {
+                return Ok((events, since));
+            }
+            if full {
+                continue; // A whole batch of other projects' events; look further
+            }
+            match tokio::time::timeout_at(deadline, published.changed()).await {
+                Ok(Ok(())) => {}
+                _ => return Ok((events, since)), // Timed out (or shutting down)
+            }
+        }
+    }
+
+    /// Publish stored events (including any left from before a restart) until shutdown.
+    pub async fn run(self: Arc<Self>) {
+        let mut pruned_at = tokio::time::Instant::now();
+        loop {
+            if let Err(e) = self.publish_pending().await {
+                tracing::warn!(error = %e, "failed to publish outbox events");
+            }
+            if pruned_at.elapsed() >= PRUNE_INTERVAL {
+                pruned_at = tokio::time::Instant::now();
+                let before = Utc::now() - chrono::Duration::hours(RETENTION_HOURS);
+                match self.repo.prune_events(before).await {
+                    Ok(n) if n > 0 => tracing::debug!(pruned = n, "pruned outbox events"),
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "failed to prune outbox events"),
+                }
+            }
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+/**
+ * Paging collections: X-Total-Count and Link headers
+ *
+ * GET /api/todos and /api/categories take `?limit=` and `?offset=`, as the
+ * activity feed does. Without `limit` the whole collection is returned, as
+ * before. Either way the response says how many items there are, and with
+ * a limit where the neighbouring pages are (RFC 8288, formerly 5988), so
+ * generic admin UIs can page without knowing this API:
+ *
+ * ```text
+ * X-Total-Count: 137
+ * Link: </api/v1/todos?status=todo&limit=50&offset=50>; rel="next",
+ *       </api/v1/todos?status=todo&limit=50&offset=0>; rel="prev",
+ *       </api/v1/todos?status=todo&limit=50&offset=0>; rel="first",
+ *       </api/v1/todos?status=todo&limit=50&offset=100>; rel="last"
+ * ```
+ *
+ * Links repeat the request's path (including its /api/v{n} prefix) and
+ * other query parameters; `prev` is left out on the first page and `next`
+ * on the last. Filters apply before paging, so the count is that of the
+ * filtered collection.
+ *
+ * A page's ETag covers only that page: its items, where it starts, its
+ * size and the collection's count, so a cached first page never answers
+ * for the second. Todo pages are read with LIMIT/OFFSET unless a filter
+ * only applied in memory (blocked, category, pinned, assignee, started)
+ * is in use; categories are few and always paged in memory.
+ */
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    http::{HeaderName, HeaderValue, Uri, header},
+    response::Response,
+};
+use serde::Deserialize;
+
+use crate::error::{ApiError, ApiResult};
+
+/// Largest accepted `?limit=`.
+const MAX_LIMIT: usize = 500;
+
+/// Header with the number of items in the whole (filtered) collection.
+pub const TOTAL_COUNT: &str = "x-total-count";
+
+/**
+ * `?limit=&offset=` of a collection request
+ */
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct PageParams {
+    pub limit: Option<usize>,  // Items per page (max 500); None = everything
+    pub offset: Option<usize>, // Items to skip
+}
+
+/**
+ * Where one page sits in its collection, for the response headers
+ */
+#[derive(Debug, Clone)]
+pub struct Page {
+    total: usize,
+    limit: Option<usize>,
+    offset: usize,
+    uri: Uri, // As requested, before the version prefix was stripped
+}
+
+impl Page {
+    /// Check the parameters; the total is set by `cut` or `set_total`.
+    pub fn new(params: PageParams, uri: Uri) -> ApiResult<Self> {
+        let limit = match params.limit {
+            Some(0) => return Err(ApiError::BadRequest("limit must be at least 1".into())),
+            Some(l) => Some(l.min(MAX_LIMIT)),
+            None => None,
+        };
+        Ok(Self {
+            total: 0,
+            limit,
+            offset: params.offset.unwrap_or(0),
+            uri,
+        })
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Size of the whole collection, when storage returned just the page.
+    pub fn set_total(&mut self, total: usize) {
+        self.total = total;
+    }
+
+    /// Count the whole collection in `items` and cut it down to the page.
+    pub fn cut<T>(&mut self, items: &mut Vec<T>) {
+        self.total = items.len();
+        items.drain(..self.offset.min(self.total));
+        if let Some(limit) = self.limit {
+            items.truncate(limit);
+        }
+    }
+
+    /**
+     * ETag of the page from `tag`, the validator of the items on it
+     *
+     * Adds the page's position, size and total and a hash of its `ids`, so
+     * changes elsewhere that shift items into or out of it change it too.
+     * Without paging `tag` is returned as is.
+     */
+    pub fn etag<'a>(&self, tag: String, ids: impl IntoIterator<Item = &'a str>) -> String {
+        if self.limit.is_none() && self.offset == 0 {
+            return tag;
+        }
+        let limit = self.limit.unwrap_or(0); // 0: the rest of the collection
+        let mut hasher = DefaultHasher::new();
+        ids.into_iter().for_each(|id| id.hash(&mut hasher));
+        let opaque = tag.trim_start_matches("W/").trim_matches('"');
+        format!(
+            "W/\"{opaque}-{}-{}-{limit}-{:x}\"",
+            self.total,
+            self.offset,
+            hasher.finish()
+        )
+    }
+
+    /// Add X-Total-Count and, when paging, the Link header to `response`.
+    pub fn apply(&self, response: &mut Response) {
+        let headers = response.headers_mut();
+        headers.insert(HeaderName::from_static(TOTAL_COUNT), self.total.into());
+        if let Some(links) = self.links()
+            && let Ok(value) = HeaderValue::from_str(&links)
+        {
+            headers.insert(header::LINK, value);
+        }
+    }
+
+    /// `<url>; rel="..."` for next, prev, first and last; None without a limit.
+    fn links(&self) -> Option<String> {
+        let limit = self.limit?;
+        let last = self.total.saturating_sub(1) / limit * limit;
+        let mut rels = Vec::with_capacity(4);
+        if self.offset.saturating_add(limit) < self.total {
+            rels.push(("next", self.offset + limit));
+        }
+        if self.offset > 0 {
+            rels.push(("prev", self.offset.saturating_sub(limit).min(last)));
+        }
+        rels.push(("first", 0));
+        rels.push(("last", last));
+        let links: Vec<String> = rels
+            .into_iter()
+            .map(|(rel, offset)| format!("<{}>; rel=\"{rel}\"", self.url(limit, offset)))
+            .collect();
+        Some(links.join(", "))
+    }
+
+    /// The request URL with `limit` and `offset` replaced.
+    fn url(&self, limit: usize, offset: usize) -> String {
+        let mut query: Vec<(String, String)> = self
+            .uri
+            .query()
+            .and_then(|q| serde_urlencoded::from_str(q).ok())
+            .unwrap_or_default();
+        query.retain(|(k, _)| k != "limit" && k != "offset");
+        query.push(("limit".into(), limit.to_string()));
+        query.push(("offset".into(), offset.to_string()));
+        let query = serde_urlencoded::to_string(&query).unwrap_or_default();
+        format!("{}?{query}", self.uri.path())
+    }
+}
@@ -0,0 +1,287 @@
+/**
+ * Pomodoro sessions
+ *
+ * One server-side pomodoro clock (work, then break) bound to a todo, so
+ * the e-ink display, browsers and phones all count down the same timer.
+ * Sessions are persisted; a session still running when the server stops
+ * is picked up again on start.
+ *
+ * - GET  /api/pomodoro                    the running session, or null
+ * - POST /api/todos/{id}/pomodoro         start {"work_minutes"?: 25, "break_minutes"?: 5}
+ * - POST /api/pomodoro/stop               cancel the running session
+ * - GET  /api/todos/{id}/pomodoros        past sessions of a todo
+ *
+ * WebSocket events:
+ * - pomodoro.started    a session began (data = session state)
+ * - pomodoro.tick       every second while running (phase, remaining_secs, ends_at)
+ * - pomodoro.break      the work phase ended, the break began
+ * - pomodoro.finished   the break ended (or the session was cancelled)
+ */
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+    routes::AppState,
+    services::emit,
+    ws::WsHub,
+};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_WORK_MINUTES: i64 = 25;
+const DEFAULT_BREAK_MINUTES: i64 = 5;
+
+/**
+ * A stored pomodoro session
+ */
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PomodoroSession {
+    pub id: String,                         // UUIDv4 string - Primary key
+    pub todo_id: String,                    // Todo being worked on
+    pub work_secs: i64,                     // Length of the work phase
+    pub break_secs: i64,                    // Length of the break that follows
+    pub started_at: DateTime<Utc>,          // Start of the work phase
+    pub status: String,                     // "running", "finished" or "cancelled"
+    pub finished_at: Option<DateTime<Utc>>, // When it finished or was cancelled
+    pub actor: String,                      // Who started it
+}
+
+impl PomodoroSession {
+    fn work_ends_at(&self) -> DateTime<Utc> {
+        self.started_at + chrono::Duration::seconds(self.work_secs)
+    }
+
+    fn ends_at(&self) -> DateTime<Utc> {
+        self.work_ends_at() + chrono::Duration::seconds(self.break_secs)
+    }
+
+    /// Live view of the session at `now`.
+    fn state(&self, now: DateTime<Utc>) -> PomodoroState {
+        let (phase, ends_at) = if now < self.work_ends_at() {
+            ("work", self.work_ends_at())
+        } else {
+            ("break", self.ends_at())
+        };
+        PomodoroState {
+            session: self.clone(),
+            phase,
+            ends_at,
+            remaining_secs: (ends_at - now).num_seconds().max(0),
+        }
+    }
+}
+
+/**
+ * Data Transfer Object for starting a session
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PomodoroStart {
+    pub work_minutes: Option<i64>,  // Default 25
+    pub break_minutes: Option<i64>, // Default 5; 0 = no break
+}
+
+/**
+ * Running session plus its current phase, as sent to clients
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct PomodoroState {
+    #[serde(flatten)]
+    pub session: PomodoroSession,
+    pub phase: &'static str,    // "work" or "break"
+    pub ends_at: DateTime<Utc>, // End of the current phase
+    pub remaining_secs: i64,    // Seconds left in the current phase
+}
+
+/**
+ * Owns the pomodoro clock shared by all clients
+ */
+pub struct PomodoroTimer {
+    pool: SqlitePool,
+    hub: Arc<WsHub>,
+    current: RwLock<Option<PomodoroSession>>,
+    changed: Notify, // Wakes the clock when a session starts or stops
+}
+
+impl PomodoroTimer {
+    pub fn new(pool: SqlitePool, hub: Arc<WsHub>) -> Self {
+        Self {
+            pool,
+            hub,
+            current: RwLock::new(None),
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn state(&self) -> Option<PomodoroState> {
+        self.current
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|s| s.state(Utc::now()))
+    }
+
+    /// Resume a session left running and tick until shutdown.
+    pub async fn run(self: Arc<Self>) {
+        match sqlx::query_as::<_, PomodoroSession>(
+            "SELECT * FROM pomodoro_sessions WHERE status='running' ORDER BY started_at DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(session) => *self.current.write().unwrap() = session,
+            Err(e) => tracing::warn!(error = %e, "failed to load pomodoro session"),
+        }
+        loop {
+            let Some(before) = self.state() else {
+                self.changed.notified().await;
+                continue;
+            };
+            let remaining = (before.ends_at - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(remaining.min(TICK_INTERVAL)) => {}
+                _ = self.changed.notified() => continue,
+            }
+            let Some(after) = self.state() else { continue };
+            if after.session.id != before.session.id {
+                continue;
+            }
+            if Utc::now() >= after.session.ends_at() {
+                if let Err(e) = self.finish("finished").await {
+                    tracing::warn!(error = %e, "failed to finish pomodoro session");
+                }
+            } else if before.phase != after.phase {
+                emit(&self.hub, "pomodoro.break", &after);
+            } else {
+                emit(&self.hub, "pomodoro.tick", &after);
+            }
+        }
+    }
+
+    /// Start a session on `todo_id`; conflict while another one runs.
+    pub async fn start(
+        &self,
+        todo_id: String,
+        body: PomodoroStart,
+        actor: &Actor,
+    ) -> ApiResult<PomodoroState> {
+        if self.current.read().unwrap().is_some() {
+            return Err(ApiError::Conflict(
+                "a pomodoro is already running; stop it first".into(),
+            ));
+        }
+        let work = body.work_minutes.unwrap_or(DEFAULT_WORK_MINUTES);
+        let rest = body.break_minutes.unwrap_or(DEFAULT_BREAK_MINUTES);
+        if !(1..=180).contains(&work) || !(0..=60).contains(&rest) {
+            return Err(ApiError::BadRequest(
+                "work_minutes must be 1-180 and break_minutes 0-60".into(),
+            ));
+        }
+        let session = PomodoroSession {
+            id: Uuid::new_v4().to_string(),
+            todo_id,
+            work_secs: work * 60,
+            break_secs: rest * 60,
+            started_at: Utc::now(),
+            status: "running".into(),
+            finished_at: None,
+            actor: actor.as_str().to_string(),
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO pomodoro_sessions (id,todo_id,work_secs,break_secs,started_at,status,finished_at,actor)
+            VALUES (?1,?2,?3,?4,?5,?6,NULL,?7)
+        "#,
+        )
+        .bind(&session.id)
+        .bind(&session.todo_id)
+        .bind(session.work_secs)
+        .bind(session.break_secs)
+        .bind(session.started_at)
+        .bind(&session.status)
+        .bind(&session.actor)
+        .execute(&self.pool)
+        .await?;
+
+        let state = session.state(Utc::now());
+        *self.current.write().unwrap() = Some(session);
+        emit(&self.hub, "pomodoro.started", &state);
+        self.changed.notify_one();
+        Ok(state)
+    }
+
+    /// End the running session with `status` and broadcast `pomodoro.finished`.
+    async fn finish(&self, status: &str) -> ApiResult<PomodoroSession> {
+        let Some(mut session) = self.current.write().unwrap().take() else {
+            return Err(ApiError::Conflict("no pomodoro is running".into()));
+        };
+        session.status = status.to_string();
+        session.finished_at = Some(Utc::now());
+        sqlx::query("UPDATE pomodoro_sessions SET status=?2, finished_at=?3 WHERE id=?1")
+            .bind(&session.id)
+            .bind(&session.status)
+            .bind(session.finished_at)
+            .execute(&self.pool)
+            .await?;
+        emit(&self.hub, "pomodoro.finished", &session);
+        self.changed.notify_one();
+        Ok(session)
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/pomodoro", get(current))
+        .route("/api/pomodoro/stop", post(stop))
+        .route("/api/todos/{id}/pomodoro", post(start))
+        .route("/api/todos/{id}/pomodoros", get(history))
+}
+
+async fn current(State(st): State<AppState>) -> Json<Option<PomodoroState>> {
+    Json(st.pomodoro.state())
+}
+
+async fn start(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(todo_id): Path<String>,
+    body: Option<Json<PomodoroStart>>,
+) -> ApiResult<Json<PomodoroState>> {
+    st.todos.get(&todo_id).await?;
+    let body = body.map(|Json(b)| b).unwrap_or_default();
+    Ok(Json(st.pomodoro.start(todo_id, body, &actor).await?))
+}
+
+async fn stop(State(st): State<AppState>) -> ApiResult<Json<PomodoroSession>> {
+    Ok(Json(st.pomodoro.finish("cancelled").await?))
+}
+
+async fn history(
+    State(st): State<AppState>,
+    Path(todo_id): Path<String>,
+) -> ApiResult<Json<Vec<PomodoroSession>>> {
+    st.todos.get(&todo_id).await?;
+    Ok(Json(
+        sqlx::query_as::<_, PomodoroSession>(
+            "SELECT * FROM pomodoro_sessions WHERE todo_id=?1 ORDER BY started_at DESC",
+        )
+        .bind(&todo_id)
+        .fetch_all(&st.pool)
+        .await?,
+    ))
+}
@@ -0,0 +1,274 @@
+/**
+ * Router port mapping (UPnP IGD / NAT-PMP)
+ *
+ * Opt-in helper for remote access without touching the router UI:
+ * on startup the server asks the gateway to forward an external port to
+ * the local listener, keeps the lease alive, and deletes the mapping again
+ * on shutdown. Status is logged and shown in /api/admin/overview.
+ *
+ * PORT_MAPPING=upnp   - UPnP Internet Gateway Device (most consumer routers)
+ * PORT_MAPPING=natpmp - NAT-PMP (Apple routers, OpenWrt with miniupnpd)
+ */
+use std::{
+    env,
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Utc};
+use igd_next::{PortMappingProtocol, SearchOptions, aio::tokio::search_gateway};
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+const NATPMP_PORT: u16 = 5351;
+
+/**
+ * Mapping protocol selected by PORT_MAPPING
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MappingProtocol {
+    Upnp,
+    NatPmp,
+}
+
+/**
+ * Port mapping settings read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct PortMapConfig {
+    pub protocol: MappingProtocol, // PORT_MAPPING: upnp or natpmp
+    pub internal_port: u16,        // The port the server listens on
+    pub external_port: u16,        // PORT_MAPPING_EXTERNAL_PORT, defaults to internal port
+    pub lease: Duration,           // PORT_MAPPING_LEASE_SECS, renewed at half-life
+}
+
+impl PortMapConfig {
+    /// Returns None when PORT_MAPPING is unset.
+    pub fn from_env(internal_port: u16) -> anyhow::Result<Option<Self>> {
+        let Ok(protocol) = env::var("PORT_MAPPING") else {
+            return Ok(None);
+        };
+        let protocol = match protocol.as_str() {
+            "upnp" => MappingProtocol::Upnp,
+            "natpmp" => MappingProtocol::NatPmp,
+            other => return Err(anyhow!("unsupported PORT_MAPPING `{other}`")),
+        };
+        Ok(Some(Self {
+            protocol,
+            internal_port,
+            external_port: env::var("PORT_MAPPING_EXTERNAL_PORT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(internal_port),
+            lease: Duration::from_secs(
+                env::var("PORT_MAPPING_LEASE_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(3600),
+            ),
+        }))
+    }
+}
+
+/**
+ * Current mapping state (serialized into the admin overview)
+ */
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PortMapStatus {
+    pub protocol: String,
+    pub gateway: Option<String>,
+    pub local_addr: Option<String>,
+    pub external_port: u16,
+    pub external_ip: Option<String>,
+    pub active: bool,
+    pub renewed_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/**
+ * Owns the mapping lifecycle: map, renew, unmap
+ */
+pub struct PortMapper {
+    config: PortMapConfig,
+    status: RwLock<PortMapStatus>,
+}
+
+impl PortMapper {
+    pub fn new(config: PortMapConfig) -> Self {
+        let status = PortMapStatus {
+            protocol: match config.protocol {
+                MappingProtocol::Upnp => "upnp".into(),
+                MappingProtocol::NatPmp => "natpmp".into(),
+            },
+            external_port: config.external_port,
+            ..Default::default()
+        };
+        Self {
+            config,
+            status: RwLock::new(status),
+        }
+    }
+
+    pub fn status(&self) -> PortMapStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Create the mapping and renew it at half the lease time.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            match self.map().await {
+                Ok(()) => {
+                    let mut status = self.status.write().unwrap();
+                    if !status.active {
+                        tracing::info!(
+                            gateway = ?status.gateway,
+                            external_port = self.config.external_port,
+                            "router port mapping established"
+                        );
+                    }
+                    status.active = true;
+                    status.renewed_at = Some(Utc::now());
+                    status.last_error = None;
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "router port mapping failed");
+                    let mut status = self.status.write().unwrap();
+                    status.active = false;
+                    status.last_error = Some(e.to_string());
+                }
+            }
+            tokio::time::sleep(self.config.lease / 2).await;
+        }
+    }
+
+    /// Remove the mapping (called on shutdown).
+    pub async fn unmap(&self) {
+        if !self.status.read().unwrap().active {
+            return;
+        }
+        let result = match self.config.protocol {
+            MappingProtocol::Upnp => self.upnp_unmap().await,
+            MappingProtocol::NatPmp => self.natpmp_request(0).await.map(|_| ()),
+        };
+        match result {
+            Ok(()) => tracing::info!("router port mapping removed"),
+            Err(e) => tracing::warn!(error = %e, "failed to remove router port mapping"),
+        }
+        self.status.write().unwrap().active = false;
+    }
+
+    async fn map(&self) -> anyhow::Result<()> {
+        match self.config.protocol {
+            MappingProtocol::Upnp => self.upnp_map().await,
+            MappingProtocol::NatPmp => {
+                let lease = self.config.lease.as_secs() as u32;
+                let external_port = self.natpmp_request(lease).await?;
+                self.status.write().unwrap().external_port = external_port;
+                Ok(())
+            }
+        }
+    }
+
+    async fn upnp_map(&self) -> anyhow::Result<()> {
+        let gateway = search_gateway(SearchOptions::default()).await?;
+        let local_ip = local_ip_towards(gateway.addr).await?;
+        let local_addr = SocketAddr::new(local_ip, self.config.internal_port);
+        gateway
+            .add_port(
+                PortMappingProtocol::TCP,
+                self.config.external_port,
+                local_addr,
+                self.config.lease.as_secs() as u32,
+                "raspi-todo",
+            )
+            .await?;
+        let external_ip = gateway.get_external_ip().await.ok();
+
+        let mut status = self.status.write().unwrap();
+        status.gateway = Some(gateway.addr.to_string());
+        status.local_addr = Some(local_addr.to_string());
+        status.external_ip = external_ip.map(|ip| ip.to_string());
+        Ok(())
+    }
+
+    async fn upnp_unmap(&self) -> anyhow::Result<()> {
+        let gateway = search_gateway(SearchOptions::default()).await?;
+        gateway
+            .remove_port(PortMappingProtocol::TCP, self.config.external_port)
+            .await?;
+        Ok(())
+    }
+
+    /**
+     * Send a NAT-PMP TCP mapping request (RFC 6886 section 3.3)
+     *
+     * A lifetime of 0 deletes the mapping. Returns the external port the
+     * gateway actually assigned.
+     */
+    async fn natpmp_request(&self, lifetime: u32) -> anyhow::Result<u16> {
+        let gateway = default_gateway().context("no default IPv4 gateway found")?;
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        socket.connect((gateway, NATPMP_PORT)).await?;
+
+        let external_port = if lifetime == 0 {
+            0
+        } else {
+            self.config.external_port
+        };
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&[0, 2, 0, 0]); // version 0, opcode 2 (TCP), reserved
+        request.extend_from_slice(&self.config.internal_port.to_be_bytes());
+        request.extend_from_slice(&external_port.to_be_bytes());
+        request.extend_from_slice(&lifetime.to_be_bytes());
+
+        // Retransmit with doubling timeouts, as the RFC suggests
+        let mut response = [0u8; 16];
+        let mut timeout = Duration::from_millis(250);
+        let mut received = None;
+        for _ in 0..5 {
+            socket.send(&request).await?;
+            if let Ok(n) = tokio::time::timeout(timeout, socket.recv(&mut response)).await {
+                received = Some(n?);
+                break;
+            }
+            timeout *= 2;
+        }
+        let n = received.ok_or_else(|| anyhow!("NAT-PMP gateway {gateway} did not respond"))?;
+        if n < 16 || response[1] != 130 {
+            return Err(anyhow!("malformed NAT-PMP response"));
+        }
+        let result_code = u16::from_be_bytes([response[2], response[3]]);
+        if result_code != 0 {
+            return Err(anyhow!(
+                "NAT-PMP request refused (result code {result_code})"
+            ));
+        }
+
+        let mut status = self.status.write().unwrap();
+        status.gateway = Some(gateway.to_string());
+        Ok(u16::from_be_bytes([response[10], response[11]]))
+    }
+}
+
+/// Local interface address used to reach the given gateway.
+async fn local_ip_towards(gateway: SocketAddr) -> anyhow::Result<std::net::IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect(gateway).await?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Default IPv4 gateway from the Linux routing table.
+fn default_gateway() -> Option<Ipv4Addr> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        // Gateway is stored as little-endian hex
+        let raw = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        Some(Ipv4Addr::from(raw.to_le_bytes()))
+    })
+}
@@ -0,0 +1,177 @@
+/**
+ * Presence: who is looking at the board right now
+ *
+ * Every WebSocket connection is tracked while it is open. A client
+ * introduces itself with a hello message
+ *
+ * ```text
+ * {"type": "hello", "name": "Alice"}
+ * ```
+ *
+ * and gets `{"type": "hello", "data": {"id": ...}}` back with its own id.
+ * The first hello is announced to everyone as `presence.join` (a later one
+ * renames the client and is announced the same way), closing the socket
+ * as `presence.leave`. Clients that never say hello are listed without a
+ * name but not announced.
+ *
+ * Once introduced, a client can say what it is editing, so two people do
+ * not rewrite the same note at once:
+ *
+ * ```text
+ * {"type": "editing", "todo_id": "...", "field": "note", "active": true}
+ * ```
+ *
+ * Every other client gets it as an `editing` event with the sender's id and
+ * name. Nothing is stored: clients drop an indicator on `"active": false`,
+ * on the sender's `presence.leave`, or after not hearing from it for a while.
+ * Relays are throttled per client (EDITING_INTERVAL; stops always pass).
+ *
+ * GET /api/presence lists the current connections, oldest first.
+ */
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{Json, Router, extract::State, routing::get};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::routes::AppState;
+
+/// Longest accepted display name (characters).
+const MAX_NAME_CHARS: usize = 64;
+/// Longest accepted todo id / field name in an editing message.
+const MAX_ID_CHARS: usize = 64;
+/// Minimum time between two relayed editing messages of one client.
+const EDITING_INTERVAL: Duration = Duration::from_millis(250);
+
+/**
+ * One connected WebSocket client
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Presence {
+    pub id: String,                 // Connection id (UUIDv4), sent back in the hello reply
+    pub name: Option<String>,       // From the client's hello; None until it says hello
+    pub project_id: Option<String>, // Board the client subscribed to (`?project_id=`)
+    pub connected_at: DateTime<Utc>,
+    #[serde(skip)]
+    last_editing: Option<Instant>, // Last relayed editing message (throttle)
+}
+
+/**
+ * Relayed `editing` message: what a client is changing right now
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Editing {
+    pub client_id: String, // Sender's presence id
+    pub name: Option<String>,
+    pub project_id: Option<String>, // Sender's board, so scoped clients only see their own
+    pub todo_id: String,
+    pub field: Option<String>, // "title", "note", ... (None = the todo in general)
+    pub active: bool,          // false = stopped editing
+}
+
+/**
+ * Open connections, shared by the hub and GET /api/presence
+ */
+#[derive(Clone, Default)]
+pub struct Presences {
+    clients: Arc<Mutex<HashMap<String, Presence>>>,
+}
+
+impl Presences {
+    /// Track a new connection; returns its id.
+    pub fn connect(&self, project_id: Option<String>) -> String {
+        let id = Uuid::new_v4().to_string();
+        let presence = Presence {
+            id: id.clone(),
+            name: None,
+            project_id,
+            connected_at: Utc::now(),
+            last_editing: None,
+        };
+        self.clients.lock().unwrap().insert(id.clone(), presence);
+        id
+    }
+
+    /// Name a connection from its hello; None if the name is unusable.
+    pub fn hello(&self, id: &str, name: &str) -> Option<Presence> {
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let name: String = name.chars().take(MAX_NAME_CHARS).collect();
+        let mut clients = self.clients.lock().unwrap();
+        let presence = clients.get_mut(id)?;
+        presence.name = Some(name);
+        Some(presence.clone())
+    }
+
+    /**
+     * Relay for an `editing` message from a client
+     *
+     * None if the client has not said hello yet, the message lacks a
+     * usable todo_id/field, or it comes faster than EDITING_INTERVAL.
+     */
+    pub fn editing(&self, id: &str, message: &Value) -> Option<Editing> {
+        let short = |v: &Value| {
+            v.as_str()
+                .map(str::trim)
+                .filter(|s| !s.is_empty() && s.chars().count() <= MAX_ID_CHARS)
+                .map(str::to_string)
+        };
+        let todo_id = short(&message["todo_id"])?;
+        let field = match &message["field"] {
+            Value::Null => None,
+            v => Some(short(v)?),
+        };
+        let active = message["active"].as_bool().unwrap_or(true);
+
+        let mut clients = self.clients.lock().unwrap();
+        let presence = clients.get_mut(id).filter(|p| p.name.is_some())?;
+        let now = Instant::now();
+        let too_soon = presence
+            .last_editing
+            .is_some_and(|at| now.duration_since(at) < EDITING_INTERVAL);
+        if active && too_soon {
+            return None;
+        }
+        presence.last_editing = Some(now);
+        Some(Editing {
+            client_id: presence.id.clone(),
+            name: presence.name.clone(),
+            project_id: presence.project_id.clone(),
+            todo_id,
+            field,
+            active,
+        })
+    }
+
+    /// Forget a closed connection; returns it if it had said hello.
+    pub fn disconnect(&self, id: &str) -> Option<Presence> {
+        self.clients
+            .lock()
+            .unwrap()
+            .remove(id)
+            .filter(|p| p.name.is_some())
+    }
+
+    /// Current connections, oldest first.
+    pub fn list(&self) -> Vec<Presence> {
+        let mut clients: Vec<Presence> = self.clients.lock().unwrap().values().cloned().collect();
+        clients.sort_by_key(|p| p.connected_at);
+        clients
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/presence", get(list_presence))
+}
+
+async fn list_presence(State(st): State<AppState>) -> Json<Vec<Presence>> {
+    Json(st.hub.presence.list())
+}
@@ -0,0 +1,212 @@
+/**
+ * Projects - independent boards on one server
+ *
+ * A project groups todos and categories ("Household", "Work", "Garden").
+ * Todos and categories without a project form the default board, so
+ * existing data keeps working unchanged. Lists are scoped with
+ * `?project_id=`, and WebSocket clients can subscribe to one project with
+ * `/ws/updates?project_id=<id>`.
+ *
+ * - GET    /api/projects
+ * - POST   /api/projects          {"name", "color"?, "sort_order"?}
+ * - GET    /api/projects/{id}
+ * - PUT    /api/projects/{id}     {"name"?, "color"?, "sort_order"?}
+ * - DELETE /api/projects/{id}     refused while active todos or categories use it
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::get,
+};
+use chrono::Utc;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    db::SqlitePool,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{Project, ProjectCreate, ProjectUpdate},
+    routes::AppState,
+    services::{TodoFilter, emit},
+};
+
+/**
+ * Storage for the projects table
+ */
+#[derive(Clone)]
+pub struct Projects {
+    pool: SqlitePool,
+}
+
+impl Projects {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Every project in switcher order.
+    pub async fn list(&self) -> ApiResult<Vec<Project>> {
+        Ok(
+            sqlx::query_as::<_, Project>(
+                "SELECT * FROM projects ORDER BY sort_order ASC, name ASC",
+            )
+            .fetch_all(&self.pool)
+            .await?,
+        )
+    }
+
+    pub async fn get(&self, id: &str) -> ApiResult<Option<Project>> {
+        Ok(
+            sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE id=?1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Bad request unless `id` is an existing project.
+    pub async fn check(&self, id: &str) -> ApiResult<()> {
+        match self.get(id).await? {
+            Some(_) => Ok(()),
+            None => Err(ApiError::BadRequest(format!("unknown project `{id}`"))),
+        }
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/projects", get(list_projects).post(create_project))
+        .route(
+            "/api/projects/{id}",
+            get(get_project).put(update_project).delete(delete_project),
+        )
+}
+
+fn validate(project: &Project) -> ApiResult<()> {
+    if project.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    Ok(())
+}
+
+async fn list_projects(State(st): State<AppState>) -> ApiResult<Json<Vec<Project>>> {
+    Ok(Json(Projects::new(st.pool.clone()).list().await?))
+}
+
+async fn get_project(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Project>> {
+    Projects::new(st.pool.clone())
+        .get(&id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
+
+async fn create_project(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<ProjectCreate>,
+) -> ApiResult<Json<Project>> {
+    let sort_order = match body.sort_order {
+        Some(v) => v,
+        None => {
+            sqlx::query_scalar::<_, i64>("SELECT COALESCE(MAX(sort_order) + 1, 0) FROM projects")
+                .fetch_one(&st.pool)
+                .await?
+        }
+    };
+    let now = Utc::now();
+    let project = Project {
+        id: Uuid::new_v4().to_string(),
+        name: body.name.trim().to_string(),
+        color: body.color,
+        sort_order,
+        created_at: now,
+        updated_at: now,
+    };
+    validate(&project)?;
+    sqlx::query(
+        r#"
+        INSERT INTO projects (id, name, color, sort_order, created_at, updated_at)
+        VALUES (?1,?2,?3,?4,?5,?6)
+    "#,
+    )
+    .bind(&project.id)
+    .bind(&project.name)
+    .bind(&project.color)
+    .bind(project.sort_order)
+    .bind(project.created_at)
+    .bind(project.updated_at)
+    .execute(&st.pool)
+    .await?;
+    emit(&st.hub, "project.created", &project);
+    Ok(Json(project))
+}
+
+async fn update_project(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<ProjectUpdate>,
+) -> ApiResult<Json<Project>> {
+    let mut project = Projects::new(st.pool.clone())
+        .get(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if let Some(v) = body.name {
+        project.name = v.trim().to_string();
+    }
+    if let Some(v) = body.color {
+        project.color = Some(v);
+    }
+    if let Some(v) = body.sort_order {
+        project.sort_order = v;
+    }
+    validate(&project)?;
+    project.updated_at = Utc::now();
+    sqlx::query("UPDATE projects SET name=?2, color=?3, sort_order=?4, updated_at=?5 WHERE id=?1")
+        .bind(&project.id)
+        .bind(&project.name)
+        .bind(&project.color)
+        .bind(project.sort_order)
+        .bind(project.updated_at)
+        .execute(&st.pool)
+        .await?;
+    emit(&st.hub, "project.updated", &project);
+    Ok(Json(project))
+}
+
+async fn delete_project(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    Projects::new(st.pool.clone())
+        .get(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let todos = st
+        .todos
+        .list(&TodoFilter {
+            project_id: Some(id.clone()),
+            ..Default::default()
+        })
+        .await?
+        .len();
+    let categories = st
+        .categories
+        .list()
+        .await?
+        .iter()
+        .filter(|c| c.project_id.as_deref() == Some(id.as_str()))
+        .count();
+    if todos + categories > 0 {
+        return Err(ApiError::Conflict(format!(
+            "project still has todos ({todos}) or categories ({categories}); move or delete them first"
+        )));
+    }
+    sqlx::query("DELETE FROM projects WHERE id=?1")
+        .bind(&id)
+        .execute(&st.pool)
+        .await?;
+    emit(&st.hub, "project.deleted", &json!({"id": id}));
+    Ok(Json(json!({"ok": true})))
+}
@@ -0,0 +1,145 @@
+/**
+ * Read-only (maintenance) mode
+ *
+ * While enabled, every mutating API request (anything but GET, HEAD and
+ * OPTIONS) is answered with 503 and a JSON explanation; reads and the
+ * WebSocket feed keep working. Useful while copying the database or
+ * running a migration by hand, and for kiosk-only deployments.
+ *
+ * - READ_ONLY=true (config `read_only`) enables it at startup
+ * - a database that failed its integrity check starts in it
+ * - GET/PUT /api/admin/read-only shows or toggles it at runtime (admin
+ *   access, see admin.rs)
+ *
+ * The toggle itself, backups, the integrity check and the log level stay
+ * available. Read-only mode forced by a failed integrity check cannot be
+ * switched off over HTTP: the database is opened read-only then, and only
+ * a repair or restore followed by a restart ends it.
+ * Background jobs (reminders, auto-archive, ...) are not paused.
+ */
+use std::sync::RwLock;
+
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    admin,
+    error::{ApiError, ApiResult, JsonBody},
+    routes::AppState,
+};
+
+/// POST/PUT endpoints that do not change data and stay open in read-only mode.
+const ALLOWED: [&str; 4] = [
+    "/api/admin/read-only",
+    "/api/admin/log-level",
+    "/api/admin/backup",
+    "/api/admin/integrity-check",
+];
+
+/**
+ * Current mode (GET /api/admin/read-only, body of rejected requests)
+ */
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReadOnlyStatus {
+    pub enabled: bool,
+    pub reason: Option<String>, // Shown to clients whose writes are rejected
+    pub since: Option<DateTime<Utc>>,
+}
+
+/**
+ * Runtime read-only switch shared by all requests
+ */
+#[derive(Debug, Default)]
+pub struct ReadOnlyMode {
+    status: RwLock<ReadOnlyStatus>,
+}
+
+impl ReadOnlyMode {
+    pub fn status(&self) -> ReadOnlyStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.status.read().unwrap().enabled
+    }
+
+    /// Reject writes from now on; `reason` replaces an earlier one.
+    pub fn enable(&self, reason: Option<String>) {
+        let mut status = self.status.write().unwrap();
+        if !status.enabled {
+            status.since = Some(Utc::now());
+        }
+        status.enabled = true;
+        status.reason = reason;
+        tracing::warn!(reason = status.reason.as_deref(), "read-only mode enabled");
+    }
+
+    pub fn disable(&self) {
+        *self.status.write().unwrap() = ReadOnlyStatus::default();
+        tracing::info!("read-only mode disabled");
+    }
+}
+
+/// Middleware answering mutating requests with 503 while read-only mode is on.
+pub async fn reject_writes(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    let reads = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if reads || !st.read_only.is_enabled() || ALLOWED.contains(&req.uri().path()) {
+        return next.run(req).await;
+    }
+    let status = st.read_only.status();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({
+            "error": "read_only",
+            "message": "the server is in read-only mode; changes are not accepted right now",
+            "reason": status.reason,
+            "since": status.since,
+        })),
+    )
+        .into_response()
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/admin/read-only",
+            get(get_read_only).put(put_read_only),
+        )
+        .route_layer(middleware::from_fn(admin::require))
+}
+
+#[derive(Deserialize)]
+struct ReadOnlyUpdate {
+    enabled: bool,
+    reason: Option<String>,
+}
+
+async fn get_read_only(State(st): State<AppState>) -> Json<ReadOnlyStatus> {
+    Json(st.read_only.status())
+}
+
+async fn put_read_only(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<ReadOnlyUpdate>,
+) -> ApiResult<Json<ReadOnlyStatus>> {
+    if body.enabled {
+        st.read_only.enable(body.reason);
+    } else if st.integrations.read_only {
+        return Err(ApiError::Conflict(
+            "read-only after a failed integrity check; repair or restore the database and restart"
+                .into(),
+        ));
+    } else {
+        st.read_only.disable();
+    }
+    Ok(Json(st.read_only.status()))
+}
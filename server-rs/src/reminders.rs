@@ -0,0 +1,179 @@
+/**
+ * Reminder scheduler
+ *
+ * Background task that periodically looks for open todos that are due soon
+ * or already overdue, puts an alert in the notification center of the
+ * members responsible for them (see notifications.rs) and hands it to
+ * every configured notifier.
+ *
+ * Due times are written in each member's time zone (their `timezone`
+ * setting), and in the household zone for the shared push/e-mail
+ * channels (see timezone.rs).
+ *
+ * Each (todo, kind, due_at) combination is recorded in `reminder_log`, so a
+ * todo is announced once as "due soon" and once as "overdue" - and again if
+ * its due date is moved.
+ */
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use chrono_tz::Tz;
+
+use crate::{
+    audit::Actor,
+    config::ServerConfig,
+    db::SqlitePool,
+    model::Todo,
+    notifications::Notifications,
+    notify::{self, Notification, Notifier},
+    services::TodoService,
+    timezone,
+};
+
+/**
+ * Scheduler settings (see ServerConfig)
+ */
+#[derive(Debug, Clone)]
+pub struct ReminderConfig {
+    pub interval: Duration,     // REMINDER_INTERVAL_SECS, default 60
+    pub lead: chrono::Duration, // REMINDER_LEAD_MINUTES before due_at, default 60
+    pub zone: Tz,               // TIMEZONE, household zone for shared channels
+}
+
+impl ReminderConfig {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            interval: Duration::from_secs(config.reminder_interval_secs),
+            lead: chrono::Duration::minutes(config.reminder_lead_minutes),
+            zone: config.zone(),
+        }
+    }
+}
+
+/**
+ * Reminder kinds, stored in reminder_log.kind
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReminderKind {
+    DueSoon,
+    Overdue,
+}
+
+impl ReminderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReminderKind::DueSoon => "due_soon",
+            ReminderKind::Overdue => "overdue",
+        }
+    }
+
+    /// Kind of the in-app notification.
+    pub fn notification_kind(self) -> &'static str {
+        match self {
+            ReminderKind::DueSoon => "reminder",
+            ReminderKind::Overdue => "overdue",
+        }
+    }
+}
+
+/// Start the scheduler loop. `pool` holds the reminder log.
+pub fn spawn(
+    pool: SqlitePool,
+    todos: TodoService,
+    inbox: Notifications,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    config: ReminderConfig,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = check_once(&pool, &todos, &inbox, &notifiers, &config).await {
+                tracing::warn!(error = %e, "reminder check failed");
+            }
+        }
+    });
+}
+
+/// Find todos that need a reminder, notify, and record the delivery.
+async fn check_once(
+    pool: &SqlitePool,
+    todos: &TodoService,
+    inbox: &Notifications,
+    notifiers: &[Arc<dyn Notifier>],
+    config: &ReminderConfig,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    for todo in todos.open_due_before(now + config.lead).await? {
+        let Some(due_at) = todo.due_at else { continue };
+        let kind = if due_at < now {
+            ReminderKind::Overdue
+        } else {
+            ReminderKind::DueSoon
+        };
+        let sent: Option<i64> = sqlx::query_scalar(
+            "SELECT 1 FROM reminder_log WHERE todo_id = ?1 AND kind = ?2 AND due_at = ?3",
+        )
+        .bind(&todo.id)
+        .bind(kind.as_str())
+        .bind(due_at)
+        .fetch_optional(pool)
+        .await?;
+        if sent.is_some() {
+            continue;
+        }
+        for user_id in inbox.recipients(&todo).await? {
+            let zone = timezone::of_user(pool, &user_id, config.zone).await?;
+            let alert = reminder_notification(&todo, kind, zone);
+            inbox
+                .add(
+                    &user_id,
+                    kind.notification_kind(),
+                    Some(&todo.id),
+                    &Actor::system(),
+                    alert.title,
+                    alert.message,
+                )
+                .await?;
+        }
+        notify::broadcast(notifiers, &reminder_notification(&todo, kind, config.zone)).await;
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO reminder_log (todo_id, kind, due_at, sent_at) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(&todo.id)
+        .bind(kind.as_str())
+        .bind(due_at)
+        .bind(Utc::now())
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Render the alert text for a todo, with the due time in `zone`.
+pub fn reminder_notification(todo: &Todo, kind: ReminderKind, zone: Tz) -> Notification {
+    let due = todo
+        .due_at
+        .map(|d| {
+            d.with_timezone(&zone)
+                .format("%Y-%m-%d %H:%M %Z")
+                .to_string()
+        })
+        .unwrap_or_default();
+    let (prefix, tag) = match kind {
+        ReminderKind::DueSoon => ("Due soon", "hourglass"),
+        ReminderKind::Overdue => ("Overdue", "warning"),
+    };
+    let mut message = format!("Due {due}");
+    if let Some(note) = todo.note.as_deref().filter(|n| !n.is_empty()) {
+        message.push_str("\n\n");
+        message.push_str(note);
+    }
+    Notification {
+        title: format!("{prefix}: {}", todo.title),
+        message,
+        urgent: kind == ReminderKind::Overdue || todo.priority >= 3,
+        tags: vec![tag.to_string()],
+    }
+}
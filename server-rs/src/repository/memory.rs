@@ -0,0 +1,527 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+
+use super::{
+    CategoryRepository, CategoryTodoCounts, OutboxEvent, TodoCounts, TodoRepository, is_open,
+};
+use crate::{
+    db::DEFAULT_CATEGORIES,
+    error::ApiResult,
+    model::{Category, CategoryCreate, ReorderItem, Todo},
+    services::TodoFilter,
+};
+
+/// Stored events with the time each was published.
+type OutboxLog = Vec<(OutboxEvent, Option<DateTime<Utc>>)>;
+
+/**
+ * Outbox shared by the memory todo and category repositories
+ */
+#[derive(Clone, Default)]
+struct MemoryOutbox {
+    events: Arc<Mutex<OutboxLog>>,
+}
+
+impl MemoryOutbox {
+    fn store(&self, messages: &[String]) {
+        let mut events = self.events.lock().unwrap();
+        for message in messages {
+            let seq = events.last().map_or(1, |(e, _)| e.seq + 1);
+            let event = OutboxEvent {
+                seq,
+                message: message.clone(),
+            };
+            events.push((event, None));
+        }
+    }
+}
+
+/**
+ * Todos kept in a process-local map (lost on restart)
+ */
+#[derive(Default)]
+pub struct MemoryTodoRepository {
+    todos: RwLock<HashMap<String, Todo>>,
+    outbox: MemoryOutbox,
+}
+
+impl MemoryTodoRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clone the todos matching `keep`, for read queries.
+    fn select(&self, keep: impl Fn(&Todo) -> bool) -> Vec<Todo> {
+        self.todos
+            .read()
+            .unwrap()
+            .values()
+            .filter(|t| keep(t))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Whether `t` passes `filter` (its page aside).
+fn matches(filter: &TodoFilter, t: &Todo) -> bool {
+    filter.status.as_ref().is_none_or(|s| &t.status == s)
+        && (filter.include_deleted || t.deleted == 0)
+        && filter
+            .project_id
+            .as_ref()
+            .is_none_or(|p| t.project_id.as_ref() == Some(p))
+        && filter.expr.as_ref().is_none_or(|e| e.matches(t))
+}
+
+impl TodoRepository for MemoryTodoRepository {
+    fn list<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let mut rows = self.select(|t| matches(filter, t));
+            // Same order as the SQL backend: undated todos after dated ones
+            rows.sort_by(|a, b| {
+                b.pinned
+                    .cmp(&a.pinned)
+                    .then_with(|| b.priority.cmp(&a.priority))
+                    .then_with(|| a.due_at.is_none().cmp(&b.due_at.is_none()))
+                    .then_with(|| a.due_at.cmp(&b.due_at))
+                    .then_with(|| a.rank.total_cmp(&b.rank))
+                    .then_with(|| a.created_at.cmp(&b.created_at))
+            });
+            rows.drain(..filter.offset.min(rows.len()));
+            if let Some(limit) = filter.limit {
+                rows.truncate(limit);
+            }
+            Ok(rows)
+        })
+    }
+
+    fn count_matching<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<i64>> {
+        Box::pin(async move { Ok(self.select(|t| matches(filter, t)).len() as i64) })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Todo>>> {
+        Box::pin(async move { Ok(self.todos.read().unwrap().get(id).cloned()) })
+    }
+
+    fn insert<'a>(&'a self, todo: &'a Todo, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            self.todos
+                .write()
+                .unwrap()
+                .insert(todo.id.clone(), todo.clone());
+            self.outbox.store(events);
+            Ok(())
+        })
+    }
+
+    fn update<'a>(
+        &'a self,
+        todo: &'a Todo,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            match self.todos.write().unwrap().get_mut(&todo.id) {
+                Some(existing) if existing.version == todo.version - 1 => {
+                    *existing = todo.clone();
+                    self.outbox.store(events);
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+    }
+
+    fn set_status<'a>(
+        &'a self,
+        todo: &'a Todo,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            match self.todos.write().unwrap().get_mut(&todo.id) {
+                Some(t) if t.version == todo.version - 1 => {
+                    t.status = todo.status.clone();
+                    t.completed_at = todo.completed_at;
+                    t.updated_at = todo.updated_at;
+                    t.version = todo.version;
+                    self.outbox.store(events);
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+    }
+
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            Ok(match self.todos.write().unwrap().get_mut(id) {
+                Some(t) => {
+                    t.deleted = 1;
+                    t.updated_at = Utc::now();
+                    t.version += 1;
+                    self.outbox.store(events);
+                    true
+                }
+                None => false,
+            })
+        })
+    }
+
+    fn reorder<'a>(
+        &'a self,
+        items: &'a [ReorderItem],
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut todos = self.todos.write().unwrap();
+            let now = Utc::now();
+            for it in items {
+                if let Some(t) = todos.get_mut(&it.id) {
+                    t.sort_order = it.sort_order;
+                    t.rank = it.sort_order as f64;
+                    t.updated_at = now;
+                    t.version += 1;
+                }
+            }
+            self.outbox.store(events);
+            Ok(())
+        })
+    }
+
+    fn set_ranks<'a>(&'a self, ranks: &'a [(String, f64)]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut todos = self.todos.write().unwrap();
+            for (id, rank) in ranks {
+                if let Some(t) = todos.get_mut(id) {
+                    t.rank = *rank;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn open_due_before<'a>(
+        &'a self,
+        before: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let mut rows =
+                self.select(|t| is_open(t, done) && t.due_at.is_some_and(|d| d < before));
+            rows.sort_by(|a, b| {
+                a.due_at
+                    .cmp(&b.due_at)
+                    .then_with(|| b.priority.cmp(&a.priority))
+            });
+            Ok(rows)
+        })
+    }
+
+    fn find_open_by_title<'a>(
+        &'a self,
+        title: &'a str,
+        category_id: Option<&'a str>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Option<Todo>>> {
+        Box::pin(async move {
+            let title = title.to_lowercase();
+            let rows = self.select(|t| {
+                is_open(t, done)
+                    && t.category_id.as_deref() == category_id
+                    && t.title.to_lowercase() == title
+            });
+            Ok(rows.into_iter().min_by_key(|t| t.created_at))
+        })
+    }
+
+    fn open_with_location<'a>(&'a self, done: &'a [String]) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let mut rows =
+                self.select(|t| is_open(t, done) && t.latitude.is_some() && t.longitude.is_some());
+            rows.sort_by(|a, b| {
+                (a.due_at.is_none(), a.due_at)
+                    .cmp(&(b.due_at.is_none(), b.due_at))
+                    .then_with(|| b.priority.cmp(&a.priority))
+            });
+            Ok(rows)
+        })
+    }
+
+    fn move_category<'a>(
+        &'a self,
+        from: &'a str,
+        to: Option<&'a str>,
+    ) -> BoxFuture<'a, ApiResult<Vec<String>>> {
+        Box::pin(async move {
+            let mut todos = self.todos.write().unwrap();
+            let now = Utc::now();
+            let mut ids = Vec::new();
+            for t in todos.values_mut() {
+                if t.deleted == 0 && t.category_id.as_deref() == Some(from) {
+                    t.category_id = to.map(str::to_string);
+                    t.updated_at = now;
+                    t.version += 1;
+                    ids.push(t.id.clone());
+                }
+            }
+            Ok(ids)
+        })
+    }
+
+    fn count_in_category<'a>(&'a self, category_id: &'a str) -> BoxFuture<'a, ApiResult<i64>> {
+        Box::pin(async move {
+            let todos = self.todos.read().unwrap();
+            Ok(todos
+                .values()
+                .filter(|t| t.deleted == 0 && t.category_id.as_deref() == Some(category_id))
+                .count() as i64)
+        })
+    }
+
+    fn counts_by_category<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<CategoryTodoCounts>>> {
+        Box::pin(async move {
+            let mut by_category: HashMap<Option<String>, CategoryTodoCounts> = HashMap::new();
+            for t in self
+                .todos
+                .read()
+                .unwrap()
+                .values()
+                .filter(|t| t.deleted == 0)
+            {
+                let counts = by_category.entry(t.category_id.clone()).or_insert_with(|| {
+                    CategoryTodoCounts {
+                        category_id: t.category_id.clone(),
+                        ..Default::default()
+                    }
+                });
+                counts.active += 1;
+                if is_open(t, done) {
+                    counts.open += 1;
+                    if t.due_at.is_some_and(|d| d < now) {
+                        counts.overdue += 1;
+                    }
+                } else {
+                    counts.done += 1;
+                }
+            }
+            Ok(by_category.into_values().collect())
+        })
+    }
+
+    fn counts<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<TodoCounts>> {
+        Box::pin(async move {
+            let todos = self.todos.read().unwrap();
+            let mut counts = TodoCounts::default();
+            for t in todos.values() {
+                if t.deleted != 0 {
+                    counts.deleted += 1;
+                    continue;
+                }
+                counts.active += 1;
+                if is_open(t, done) {
+                    counts.open += 1;
+                    if t.due_at.is_some_and(|d| d < now) {
+                        counts.overdue += 1;
+                    }
+                }
+            }
+            Ok(counts)
+        })
+    }
+
+    fn enqueue_events<'a>(&'a self, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            self.outbox.store(events);
+            Ok(())
+        })
+    }
+
+    fn pending_events(&self, limit: i64) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>> {
+        Box::pin(async move {
+            Ok(self
+                .outbox
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, published)| published.is_none())
+                .take(limit.max(0) as usize)
+                .map(|(event, _)| event.clone())
+                .collect())
+        })
+    }
+
+    fn mark_published(&self, seq: i64) -> BoxFuture<'_, ApiResult<()>> {
+        Box::pin(async move {
+            let mut events = self.outbox.events.lock().unwrap();
+            if let Some((_, published)) = events.iter_mut().find(|(e, _)| e.seq == seq) {
+                *published = Some(Utc::now());
+            }
+            Ok(())
+        })
+    }
+
+    fn published_events(
+        &self,
+        after: i64,
+        limit: i64,
+    ) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>> {
+        Box::pin(async move {
+            Ok(self
+                .outbox
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(e, published)| published.is_some() && e.seq > after)
+                .take(limit.max(0) as usize)
+                .map(|(event, _)| event.clone())
+                .collect())
+        })
+    }
+
+    fn last_published_seq(&self) -> BoxFuture<'_, ApiResult<i64>> {
+        Box::pin(async move {
+            let events = self.outbox.events.lock().unwrap();
+            let newest = events.iter().filter(|(_, published)| published.is_some());
+            Ok(newest.map(|(e, _)| e.seq).max().unwrap_or(0))
+        })
+    }
+
+    fn prune_events(&self, before: DateTime<Utc>) -> BoxFuture<'_, ApiResult<u64>> {
+        Box::pin(async move {
+            let mut events = self.outbox.events.lock().unwrap();
+            let len = events.len();
+            events.retain(|(_, published)| published.is_none_or(|p| p >= before));
+            Ok((len - events.len()) as u64)
+        })
+    }
+}
+
+/**
+ * Categories kept in a process-local map (lost on restart)
+ */
+#[derive(Default)]
+pub struct MemoryCategoryRepository {
+    categories: RwLock<HashMap<String, Category>>,
+    outbox: MemoryOutbox,
+}
+
+impl MemoryCategoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pre-populated with the same default categories as a fresh database.
+    pub fn with_defaults() -> Self {
+        let repo = Self::new();
+        {
+            let mut categories = repo.categories.write().unwrap();
+            for (name, color, description) in DEFAULT_CATEGORIES {
+                let c = Category::new_from_create(CategoryCreate {
+                    name: name.to_string(),
+                    color: Some(color.to_string()),
+                    description: Some(description.to_string()),
+                    project_id: None,
+                    parent_id: None,
+                });
+                categories.insert(c.id.clone(), c);
+            }
+        }
+        repo
+    }
+
+    /// Store change events in the outbox of `todos`, whose dispatcher publishes them.
+    pub fn with_outbox_of(mut self, todos: &MemoryTodoRepository) -> Self {
+        self.outbox = todos.outbox.clone();
+        self
+    }
+}
+
+impl CategoryRepository for MemoryCategoryRepository {
+    fn list(&self) -> BoxFuture<'_, ApiResult<Vec<Category>>> {
+        Box::pin(async move {
+            let mut rows: Vec<Category> = self
+                .categories
+                .read()
+                .unwrap()
+                .values()
+                .filter(|c| c.deleted == 0)
+                .cloned()
+                .collect();
+            rows.sort_by(|a, b| {
+                a.sort_order
+                    .cmp(&b.sort_order)
+                    .then_with(|| a.name.cmp(&b.name))
+            });
+            Ok(rows)
+        })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Category>>> {
+        Box::pin(async move { Ok(self.categories.read().unwrap().get(id).cloned()) })
+    }
+
+    fn insert<'a>(
+        &'a self,
+        category: &'a Category,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            self.categories
+                .write()
+                .unwrap()
+                .insert(category.id.clone(), category.clone());
+            self.outbox.store(events);
+            Ok(())
+        })
+    }
+
+    fn update<'a>(
+        &'a self,
+        category: &'a Category,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            if let Some(existing) = self.categories.write().unwrap().get_mut(&category.id) {
+                *existing = category.clone();
+            }
+            self.outbox.store(events);
+            Ok(())
+        })
+    }
+
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            Ok(match self.categories.write().unwrap().get_mut(id) {
+                Some(c) => {
+                    c.deleted = 1;
+                    c.updated_at = Utc::now();
+                    self.outbox.store(events);
+                    true
+                }
+                None => false,
+            })
+        })
+    }
+}
@@ -0,0 +1,221 @@
+/**
+ * Storage abstraction for todos and categories
+ *
+ * Services talk to these traits instead of issuing sqlx calls directly, so
+ * the storage backend can be swapped:
+ * - sqlite: the default, persistent backend
+ * - memory: process-local maps for tests and demo mode (STORAGE=memory)
+ * - postgres: larger deployments, behind the `postgres` cargo feature
+ *
+ * Repositories are plain storage: validation and WebSocket broadcasts live
+ * in the service layer. Methods return BoxFuture so the traits stay
+ * object safe (`Arc<dyn TodoRepository>`).
+ *
+ * Writes take the WebSocket events describing them (`events`, already
+ * serialized) and store them in the backend's outbox in the same
+ * transaction, so an event exists exactly when its change was committed.
+ * The outbox dispatcher (crate::outbox) publishes them in order.
+ */
+mod memory;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod sqlite;
+
+pub use memory::{MemoryCategoryRepository, MemoryTodoRepository};
+#[cfg(feature = "postgres")]
+pub use postgres::{PgCategoryRepository, PgTodoRepository};
+pub use sqlite::{SqliteCategoryRepository, SqliteTodoRepository};
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use crate::{
+    error::ApiResult,
+    model::{Category, ReorderItem, Todo},
+    services::TodoFilter,
+};
+
+/**
+ * Todo storage
+ */
+pub trait TodoRepository: Send + Sync {
+    /// Todos matching the filter, in board order, limited to its page.
+    fn list<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<Vec<Todo>>>;
+
+    /// Number of todos matching the filter, whatever its page.
+    fn count_matching<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<i64>>;
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Todo>>>;
+
+    fn insert<'a>(&'a self, todo: &'a Todo, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>>;
+
+    /// Overwrite every mutable column of an existing todo, provided it is still
+    /// at the version `todo` was built from (`todo.version - 1`); returns false
+    /// (storing no events) when another write got there first.
+    fn update<'a>(&'a self, todo: &'a Todo, events: &'a [String])
+    -> BoxFuture<'a, ApiResult<bool>>;
+
+    /// Write `todo`'s status, completion and change time, provided it is still
+    /// at the version `todo` was built from (as `update`); returns false
+    /// (storing no events) when another write got there first.
+    fn set_status<'a>(
+        &'a self,
+        todo: &'a Todo,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>>;
+
+    /// Mark as deleted; returns false (storing no events) when the todo does not exist.
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>>;
+
+    /// Apply all new sort positions atomically.
+    fn reorder<'a>(
+        &'a self,
+        items: &'a [ReorderItem],
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>>;
+
+    /// Renumber ranks atomically, leaving version and updated_at alone
+    /// (the visible order does not change).
+    fn set_ranks<'a>(&'a self, ranks: &'a [(String, f64)]) -> BoxFuture<'a, ApiResult<()>>;
+
+    /// Open todos (status not in `done`) with a due date before `before`, earliest first.
+    fn open_due_before<'a>(
+        &'a self,
+        before: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<Todo>>>;
+
+    /// Oldest open todo (status not in `done`) in `category_id` (None = uncategorized)
+    /// whose title equals `title` ignoring case.
+    fn find_open_by_title<'a>(
+        &'a self,
+        title: &'a str,
+        category_id: Option<&'a str>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Option<Todo>>>;
+
+    /// Open todos (status not in `done`) that have coordinates, nearest-due first.
+    fn open_with_location<'a>(&'a self, done: &'a [String]) -> BoxFuture<'a, ApiResult<Vec<Todo>>>;
+
+    /// Move every non-deleted todo of category `from` to `to` (None = uncategorized)
+    /// atomically; returns the ids of the moved todos.
+    fn move_category<'a>(
+        &'a self,
+        from: &'a str,
+        to: Option<&'a str>,
+    ) -> BoxFuture<'a, ApiResult<Vec<String>>>;
+
+    /// Number of non-deleted todos assigned to a category.
+    fn count_in_category<'a>(&'a self, category_id: &'a str) -> BoxFuture<'a, ApiResult<i64>>;
+
+    /// Counters per category (one GROUP BY); categories without todos are left out.
+    fn counts_by_category<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<CategoryTodoCounts>>>;
+
+    /// Board-wide counters; `now` decides what is overdue, `done` what is open.
+    fn counts<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<TodoCounts>>;
+
+    // Outbox (shared with the category repository of the same backend)
+
+    /// Store events that do not belong to a write.
+    fn enqueue_events<'a>(&'a self, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>>;
+
+    /// Oldest unpublished events, in publish order.
+    fn pending_events(&self, limit: i64) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>>;
+
+    /// Mark one event as published.
+    fn mark_published(&self, seq: i64) -> BoxFuture<'_, ApiResult<()>>;
+
+    /// Published events with a seq above `after`, in publish order.
+    fn published_events(
+        &self,
+        after: i64,
+        limit: i64,
+    ) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>>;
+
+    /// Seq of the newest published event (0 if there is none).
+    fn last_published_seq(&self) -> BoxFuture<'_, ApiResult<i64>>;
+
+    /// Forget events published before `before`.
+    fn prune_events(&self, before: DateTime<Utc>) -> BoxFuture<'_, ApiResult<u64>>;
+}
+
+/**
+ * WebSocket event waiting in the outbox
+ */
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct OutboxEvent {
+    pub seq: i64,        // Publish order
+    pub message: String, // `{"type": ..., "data": ...}` as sent to clients
+}
+
+/**
+ * Board-wide todo counters (admin overview, MQTT sensors)
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TodoCounts {
+    pub active: i64,  // Not deleted
+    pub deleted: i64, // Soft-deleted
+    pub open: i64,    // Active and not in a done status
+    pub overdue: i64, // Open with due_at in the past
+}
+
+/**
+ * Todo counters of one category (sidebar badges); deleted todos are not counted
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CategoryTodoCounts {
+    #[serde(skip)]
+    pub category_id: Option<String>, // None = uncategorized
+    pub active: i64,  // Not deleted
+    pub open: i64,    // Not in a done status
+    pub done: i64,    // In a done status
+    pub overdue: i64, // Open with due_at in the past
+}
+
+/**
+ * Category storage
+ */
+pub trait CategoryRepository: Send + Sync {
+    /// Active categories in display order.
+    fn list(&self) -> BoxFuture<'_, ApiResult<Vec<Category>>>;
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Category>>>;
+
+    fn insert<'a>(
+        &'a self,
+        category: &'a Category,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>>;
+
+    /// Overwrite every mutable column of an existing category.
+    fn update<'a>(
+        &'a self,
+        category: &'a Category,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>>;
+
+    /// Mark as deleted; returns false (storing no events) when the category does not exist.
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>>;
+}
+
+/// Open = not deleted and not in one of the `done` statuses.
+pub(crate) fn is_open(todo: &Todo, done: &[String]) -> bool {
+    todo.deleted == 0 && !done.contains(&todo.status)
+}
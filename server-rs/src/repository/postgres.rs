@@ -0,0 +1,667 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use sqlx::PgPool;
+
+use super::{CategoryRepository, CategoryTodoCounts, OutboxEvent, TodoCounts, TodoRepository};
+use crate::{
+    error::ApiResult,
+    filter::{Dialect, SqlValue},
+    model::{Category, ReorderItem, Todo},
+    services::TodoFilter,
+};
+
+// `deleted` is a real BOOLEAN in Postgres; the models keep SQLite's 0/1 integer
+const TODO_COLUMNS: &str = "id, title, note, status, priority, pinned, due_at, start_at, \
+    completed_at, tags, category_id, project_id, assignee_id, latitude, longitude, location_name, \
+    sort_order, created_at, updated_at, \
+    checklist_done, checklist_total, snooze_count, timer_started_at, tracked_secs, \
+    estimate_minutes, rank, deleted::INT::BIGINT AS deleted, version";
+const CATEGORY_COLUMNS: &str = "id, name, color, description, project_id, parent_id, \
+    is_default, archived, sort_order, created_at, updated_at, deleted::INT::BIGINT AS deleted";
+
+/// WHERE of TodoRepository::list: $1 status, $2 include_deleted, $3 project,
+/// then the parameters of the filter expression.
+fn list_conditions(filter: &TodoFilter) -> (String, Vec<SqlValue>) {
+    let (expr, binds) = filter
+        .expr
+        .as_ref()
+        .map(|e| e.to_sql(Dialect::Postgres, 4))
+        .unwrap_or_else(|| ("TRUE".into(), Vec::new()));
+    let conditions = format!(
+        "($1::TEXT IS NULL OR status = $1) AND ($2 OR NOT deleted) \
+         AND ($3::TEXT IS NULL OR project_id = $3) AND {expr}"
+    );
+    (conditions, binds)
+}
+
+/// Bind the parameters of list_conditions in order.
+fn bind_filter<'q, O>(
+    query: sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments>,
+    filter: &'q TodoFilter,
+    binds: Vec<SqlValue>,
+) -> sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments> {
+    let mut query = query
+        .bind(&filter.status)
+        .bind(filter.include_deleted)
+        .bind(&filter.project_id);
+    for value in binds {
+        query = match value {
+            SqlValue::Text(v) => query.bind(v),
+            SqlValue::Int(v) => query.bind(v),
+            SqlValue::Bool(v) => query.bind(v),
+            SqlValue::Time(v) => query.bind(v),
+        };
+    }
+    query
+}
+
+/**
+ * Todos stored in the Postgres `todos` table
+ */
+#[derive(Clone)]
+pub struct PgTodoRepository {
+    pool: PgPool,
+}
+
+impl PgTodoRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl TodoRepository for PgTodoRepository {
+    fn list<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let (conditions, binds) = list_conditions(filter);
+            let limit = 4 + binds.len(); // $n of LIMIT, OFFSET follows
+            let sql = format!(
+                r#"
+                SELECT {TODO_COLUMNS} FROM todos
+                WHERE {conditions}
+                ORDER BY pinned DESC, priority DESC, due_at ASC NULLS LAST, rank ASC, created_at ASC
+                LIMIT ${limit} OFFSET ${}
+            "#,
+                limit + 1
+            );
+            let rows = bind_filter(sqlx::query_as::<_, Todo>(&sql), filter, binds)
+                .bind(filter.limit.map(|l| l as i64)) // NULL: no limit
+                .bind(filter.offset as i64)
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(rows.into_iter().map(Todo::opened).collect())
+        })
+    }
+
+    fn count_matching<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<i64>> {
+        Box::pin(async move {
+            let (conditions, binds) = list_conditions(filter);
+            let sql = format!("SELECT COUNT(*) FROM todos WHERE {conditions}");
+            let (count,) = bind_filter(sqlx::query_as::<_, (i64,)>(&sql), filter, binds)
+                .fetch_one(&self.pool)
+                .await?;
+            Ok(count)
+        })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Todo>>> {
+        Box::pin(async move {
+            let sql = format!("SELECT {TODO_COLUMNS} FROM todos WHERE id = $1");
+            let row: Option<Todo> = sqlx::query_as(&sql)
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+            Ok(row.map(Todo::opened))
+        })
+    }
+
+    fn insert<'a>(&'a self, todo: &'a Todo, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(r#"
+                INSERT INTO todos (id,title,note,status,priority,due_at,tags,category_id,sort_order,created_at,updated_at,deleted,latitude,longitude,location_name,version,checklist_done,checklist_total,completed_at,project_id,snooze_count,start_at,pinned,timer_started_at,tracked_secs,estimate_minutes,rank,assignee_id)
+                VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17,$18,$19,$20,$21,$22,$23,$24,$25,$26,$27,$28)
+            "#)
+                .bind(&todo.id)
+                .bind(&todo.title)
+                .bind(todo.stored_note())
+                .bind(&todo.status)
+                .bind(todo.priority)
+                .bind(todo.due_at)
+                .bind(&todo.tags)
+                .bind(&todo.category_id)
+                .bind(todo.sort_order)
+                .bind(todo.created_at)
+                .bind(todo.updated_at)
+                .bind(todo.deleted != 0)
+                .bind(todo.latitude)
+                .bind(todo.longitude)
+                .bind(&todo.location_name)
+                .bind(todo.version)
+                .bind(todo.checklist_done)
+                .bind(todo.checklist_total)
+                .bind(todo.completed_at)
+                .bind(&todo.project_id)
+                .bind(todo.snooze_count)
+                .bind(todo.start_at)
+                .bind(todo.pinned)
+                .bind(todo.timer_started_at)
+                .bind(todo.tracked_secs)
+                .bind(todo.estimate_minutes)
+                .bind(todo.rank)
+                .bind(&todo.assignee_id)
+                .execute(&mut *tx)
+                .await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn update<'a>(&'a self, t: &'a Todo, events: &'a [String]) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let result = sqlx::query(
+                r#"
+                UPDATE todos SET
+                title=$2, note=$3, status=$4, priority=$5, due_at=$6, tags=$7,
+                category_id=$8, sort_order=$9, updated_at=$10, deleted=$11,
+                latitude=$12, longitude=$13, location_name=$14, version=$15,
+                checklist_done=$16, checklist_total=$17, completed_at=$18, project_id=$19,
+                snooze_count=$20, start_at=$21, pinned=$22,
+                timer_started_at=$23, tracked_secs=$24,
+                estimate_minutes=$25, rank=$26, assignee_id=$27
+                WHERE id=$1 AND version=$15-1
+            "#,
+            )
+            .bind(&t.id)
+            .bind(&t.title)
+            .bind(t.stored_note())
+            .bind(&t.status)
+            .bind(t.priority)
+            .bind(t.due_at)
+            .bind(&t.tags)
+            .bind(&t.category_id)
+            .bind(t.sort_order)
+            .bind(t.updated_at)
+            .bind(t.deleted != 0)
+            .bind(t.latitude)
+            .bind(t.longitude)
+            .bind(&t.location_name)
+            .bind(t.version)
+            .bind(t.checklist_done)
+            .bind(t.checklist_total)
+            .bind(t.completed_at)
+            .bind(&t.project_id)
+            .bind(t.snooze_count)
+            .bind(t.start_at)
+            .bind(t.pinned)
+            .bind(t.timer_started_at)
+            .bind(t.tracked_secs)
+            .bind(t.estimate_minutes)
+            .bind(t.rank)
+            .bind(&t.assignee_id)
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Ok(false); // Changed meanwhile (or gone); the transaction rolls back
+            }
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(true)
+        })
+    }
+
+    fn set_status<'a>(
+        &'a self,
+        todo: &'a Todo,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let result = sqlx::query(
+                "UPDATE todos SET status=$2, completed_at=$3, updated_at=$4, version=$5 WHERE id=$1 AND version=$5-1",
+            )
+            .bind(&todo.id)
+            .bind(&todo.status)
+            .bind(todo.completed_at)
+            .bind(todo.updated_at)
+            .bind(todo.version)
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Ok(false); // Changed meanwhile (or gone); the transaction rolls back
+            }
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(true)
+        })
+    }
+
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let result = sqlx::query(
+                "UPDATE todos SET deleted=TRUE, updated_at=NOW(), version=version+1 WHERE id=$1",
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            let deleted = result.rows_affected() > 0;
+            if deleted {
+                store_events(&mut tx, events).await?;
+            }
+            tx.commit().await?;
+            Ok(deleted)
+        })
+    }
+
+    fn reorder<'a>(
+        &'a self,
+        items: &'a [ReorderItem],
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            for it in items.iter() {
+                sqlx::query("UPDATE todos SET sort_order=$2, rank=$3, updated_at=NOW(), version=version+1 WHERE id=$1")
+                    .bind(&it.id)
+                    .bind(it.sort_order)
+                    .bind(it.sort_order as f64)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn set_ranks<'a>(&'a self, ranks: &'a [(String, f64)]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            for (id, rank) in ranks {
+                sqlx::query("UPDATE todos SET rank=$2 WHERE id=$1")
+                    .bind(id)
+                    .bind(rank)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn open_due_before<'a>(
+        &'a self,
+        before: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let sql = format!(
+                r#"
+                SELECT {TODO_COLUMNS} FROM todos
+                WHERE NOT deleted AND status <> ALL($2)
+                  AND due_at IS NOT NULL AND due_at < $1
+                ORDER BY due_at ASC, priority DESC
+            "#
+            );
+            let rows: Vec<Todo> = sqlx::query_as(&sql)
+                .bind(before)
+                .bind(done)
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(rows.into_iter().map(Todo::opened).collect())
+        })
+    }
+
+    fn find_open_by_title<'a>(
+        &'a self,
+        title: &'a str,
+        category_id: Option<&'a str>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Option<Todo>>> {
+        Box::pin(async move {
+            let sql = format!(
+                r#"
+                SELECT {TODO_COLUMNS} FROM todos
+                WHERE NOT deleted AND status <> ALL($3)
+                  AND lower(title) = lower($1) AND category_id IS NOT DISTINCT FROM $2
+                ORDER BY created_at ASC
+                LIMIT 1
+            "#
+            );
+            let row: Option<Todo> = sqlx::query_as(&sql)
+                .bind(title)
+                .bind(category_id)
+                .bind(done)
+                .fetch_optional(&self.pool)
+                .await?;
+            Ok(row.map(Todo::opened))
+        })
+    }
+
+    fn open_with_location<'a>(&'a self, done: &'a [String]) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let sql = format!(
+                r#"
+                SELECT {TODO_COLUMNS} FROM todos
+                WHERE NOT deleted AND status <> ALL($1)
+                  AND latitude IS NOT NULL AND longitude IS NOT NULL
+                ORDER BY due_at ASC NULLS LAST, priority DESC
+            "#
+            );
+            let rows: Vec<Todo> = sqlx::query_as(&sql)
+                .bind(done)
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(rows.into_iter().map(Todo::opened).collect())
+        })
+    }
+
+    fn move_category<'a>(
+        &'a self,
+        from: &'a str,
+        to: Option<&'a str>,
+    ) -> BoxFuture<'a, ApiResult<Vec<String>>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let ids: Vec<String> =
+                sqlx::query_scalar("SELECT id FROM todos WHERE category_id=$1 AND NOT deleted")
+                    .bind(from)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            sqlx::query(
+                "UPDATE todos SET category_id=$2, updated_at=NOW(), version=version+1 WHERE category_id=$1 AND NOT deleted",
+            )
+            .bind(from)
+            .bind(to)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            Ok(ids)
+        })
+    }
+
+    fn count_in_category<'a>(&'a self, category_id: &'a str) -> BoxFuture<'a, ApiResult<i64>> {
+        Box::pin(async move {
+            Ok(sqlx::query_scalar(
+                "SELECT COUNT(*) FROM todos WHERE category_id=$1 AND NOT deleted",
+            )
+            .bind(category_id)
+            .fetch_one(&self.pool)
+            .await?)
+        })
+    }
+
+    fn counts_by_category<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<CategoryTodoCounts>>> {
+        Box::pin(async move {
+            let rows: Vec<(Option<String>, i64, i64, i64)> = sqlx::query_as(
+                r#"
+                SELECT
+                    category_id,
+                    COUNT(*),
+                    COUNT(*) FILTER (WHERE status <> ALL($2)),
+                    COUNT(*) FILTER (WHERE status <> ALL($2) AND due_at < $1)
+                FROM todos
+                WHERE NOT deleted
+                GROUP BY category_id
+            "#,
+            )
+            .bind(now)
+            .bind(done)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(rows
+                .into_iter()
+                .map(|(category_id, active, open, overdue)| CategoryTodoCounts {
+                    category_id,
+                    active,
+                    open,
+                    done: active - open,
+                    overdue,
+                })
+                .collect())
+        })
+    }
+
+    fn counts<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<TodoCounts>> {
+        Box::pin(async move {
+            let (active, deleted, open, overdue): (i64, i64, i64, i64) = sqlx::query_as(
+                r#"
+                SELECT
+                    COUNT(*) FILTER (WHERE NOT deleted),
+                    COUNT(*) FILTER (WHERE deleted),
+                    COUNT(*) FILTER (WHERE NOT deleted AND status <> ALL($2)),
+                    COUNT(*) FILTER (WHERE NOT deleted AND status <> ALL($2)
+                        AND due_at < $1)
+                FROM todos
+            "#,
+            )
+            .bind(now)
+            .bind(done)
+            .fetch_one(&self.pool)
+            .await?;
+            Ok(TodoCounts {
+                active,
+                deleted,
+                open,
+                overdue,
+            })
+        })
+    }
+
+    fn enqueue_events<'a>(&'a self, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn pending_events(&self, limit: i64) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>> {
+        Box::pin(async move {
+            Ok(sqlx::query_as::<_, OutboxEvent>(
+                "SELECT seq, message FROM outbox WHERE published_at IS NULL ORDER BY seq ASC LIMIT $1",
+            )
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+        })
+    }
+
+    fn mark_published(&self, seq: i64) -> BoxFuture<'_, ApiResult<()>> {
+        Box::pin(async move {
+            sqlx::query("UPDATE outbox SET published_at=$2 WHERE seq=$1")
+                .bind(seq)
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn published_events(
+        &self,
+        after: i64,
+        limit: i64,
+    ) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>> {
+        Box::pin(async move {
+            Ok(sqlx::query_as::<_, OutboxEvent>(
+                "SELECT seq, message FROM outbox WHERE published_at IS NOT NULL AND seq > $1 ORDER BY seq ASC LIMIT $2",
+            )
+            .bind(after)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+        })
+    }
+
+    fn last_published_seq(&self) -> BoxFuture<'_, ApiResult<i64>> {
+        Box::pin(async move {
+            Ok(sqlx::query_scalar(
+                "SELECT COALESCE(MAX(seq), 0)::BIGINT FROM outbox WHERE published_at IS NOT NULL",
+            )
+            .fetch_one(&self.pool)
+            .await?)
+        })
+    }
+
+    fn prune_events(&self, before: DateTime<Utc>) -> BoxFuture<'_, ApiResult<u64>> {
+        Box::pin(async move {
+            Ok(sqlx::query("DELETE FROM outbox WHERE published_at < $1")
+                .bind(before)
+                .execute(&self.pool)
+                .await?
+                .rows_affected())
+        })
+    }
+}
+
+/**
+ * Categories stored in the Postgres `categories` table
+ */
+#[derive(Clone)]
+pub struct PgCategoryRepository {
+    pool: PgPool,
+}
+
+impl PgCategoryRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl CategoryRepository for PgCategoryRepository {
+    fn list(&self) -> BoxFuture<'_, ApiResult<Vec<Category>>> {
+        Box::pin(async move {
+            let sql = format!(
+                "SELECT {CATEGORY_COLUMNS} FROM categories WHERE NOT deleted ORDER BY sort_order ASC, name ASC"
+            );
+            Ok(sqlx::query_as::<_, Category>(&sql)
+                .fetch_all(&self.pool)
+                .await?)
+        })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Category>>> {
+        Box::pin(async move {
+            let sql = format!("SELECT {CATEGORY_COLUMNS} FROM categories WHERE id=$1");
+            Ok(sqlx::query_as::<_, Category>(&sql)
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?)
+        })
+    }
+
+    fn insert<'a>(
+        &'a self,
+        category: &'a Category,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(
+                r#"
+                INSERT INTO categories (id,name,color,description,sort_order,created_at,updated_at,deleted,project_id,parent_id,is_default,archived)
+                VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12)
+            "#,
+            )
+            .bind(&category.id)
+            .bind(&category.name)
+            .bind(&category.color)
+            .bind(&category.description)
+            .bind(category.sort_order)
+            .bind(category.created_at)
+            .bind(category.updated_at)
+            .bind(category.deleted != 0)
+            .bind(&category.project_id)
+            .bind(&category.parent_id)
+            .bind(category.is_default)
+            .bind(category.archived)
+            .execute(&mut *tx)
+            .await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn update<'a>(&'a self, c: &'a Category, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(
+                r#"
+                UPDATE categories SET
+                name=$2, color=$3, description=$4, sort_order=$5, updated_at=$6, deleted=$7,
+                project_id=$8, parent_id=$9, is_default=$10, archived=$11
+                WHERE id=$1
+            "#,
+            )
+            .bind(&c.id)
+            .bind(&c.name)
+            .bind(&c.color)
+            .bind(&c.description)
+            .bind(c.sort_order)
+            .bind(c.updated_at)
+            .bind(c.deleted != 0)
+            .bind(&c.project_id)
+            .bind(&c.parent_id)
+            .bind(c.is_default)
+            .bind(c.archived)
+            .execute(&mut *tx)
+            .await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let result =
+                sqlx::query("UPDATE categories SET deleted=TRUE, updated_at=NOW() WHERE id=$1")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            let deleted = result.rows_affected() > 0;
+            if deleted {
+                store_events(&mut tx, events).await?;
+            }
+            tx.commit().await?;
+            Ok(deleted)
+        })
+    }
+}
+
+/// Store `events` in the outbox as part of `tx`.
+async fn store_events(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    events: &[String],
+) -> sqlx::Result<()> {
+    for message in events {
+        sqlx::query("INSERT INTO outbox (message, created_at) VALUES ($1, $2)")
+            .bind(message)
+            .bind(Utc::now())
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,759 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use sqlx::types::Json;
+
+use super::{CategoryRepository, CategoryTodoCounts, OutboxEvent, TodoCounts, TodoRepository};
+use crate::{
+    db::{SqlitePool, retry_busy},
+    error::ApiResult,
+    filter::{Dialect, SqlValue},
+    model::{Category, ReorderItem, Todo},
+    services::TodoFilter,
+};
+
+/**
+ * WHERE of TodoRepository::list and its parameters
+ *
+ * Only the filters in use become conditions: SQLite plans a statement
+ * before seeing its parameters, and `?1 IS NULL OR status = ?1` would keep
+ * it from using idx_todos_list (see db.rs), whose columns the WHERE and
+ * ORDER BY of list_query follow.
+ */
+fn list_conditions(filter: &TodoFilter) -> (String, Vec<SqlValue>) {
+    let mut conditions = Vec::new();
+    let mut binds = Vec::new();
+    if !filter.include_deleted {
+        conditions.push("deleted = 0".to_string());
+    }
+    if let Some(status) = &filter.status {
+        binds.push(SqlValue::Text(status.clone()));
+        conditions.push(format!("status = ?{}", binds.len()));
+    }
+    if let Some(project_id) = &filter.project_id {
+        binds.push(SqlValue::Text(project_id.clone()));
+        conditions.push(format!("project_id = ?{}", binds.len()));
+    }
+    if let Some(expr) = &filter.expr {
+        let (expr, expr_binds) = expr.to_sql(Dialect::Sqlite, binds.len() + 1);
+        conditions.push(format!("({expr})"));
+        binds.extend(expr_binds);
+    }
+    if conditions.is_empty() {
+        ("1".into(), binds)
+    } else {
+        (conditions.join(" AND "), binds)
+    }
+}
+
+/// SELECT of TodoRepository::list, paged with LIMIT/OFFSET, and its parameters.
+fn list_query(filter: &TodoFilter) -> (String, Vec<SqlValue>) {
+    let (conditions, mut binds) = list_conditions(filter);
+    // LIMIT -1 is no limit; SQLite only takes OFFSET after a LIMIT
+    let limit = filter.limit.map_or(-1, |l| l as i64);
+    binds.push(SqlValue::Int(limit));
+    binds.push(SqlValue::Int(filter.offset as i64));
+    let (limit, offset) = (binds.len() - 1, binds.len());
+    let sql = format!(
+        r#"
+        SELECT * FROM todos
+        WHERE {conditions}
+        ORDER BY
+            pinned DESC,
+            priority DESC,
+            COALESCE(due_at, '9999-12-31T00:00:00Z') ASC,
+            rank ASC,
+            created_at ASC
+        LIMIT ?{limit} OFFSET ?{offset}
+    "#
+    );
+    (sql, binds)
+}
+
+/// Bind filter parameters in order.
+fn bind_values<'q, O>(
+    mut query: sqlx::query::QueryAs<'q, sqlx::Sqlite, O, sqlx::sqlite::SqliteArguments<'q>>,
+    binds: Vec<SqlValue>,
+) -> sqlx::query::QueryAs<'q, sqlx::Sqlite, O, sqlx::sqlite::SqliteArguments<'q>> {
+    for value in binds {
+        query = match value {
+            SqlValue::Text(v) => query.bind(v),
+            SqlValue::Int(v) => query.bind(v),
+            SqlValue::Bool(v) => query.bind(v),
+            SqlValue::Time(v) => query.bind(v),
+        };
+    }
+    query
+}
+
+/**
+ * Todos stored in the SQLite `todos` table
+ */
+#[derive(Clone)]
+pub struct SqliteTodoRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteTodoRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+impl TodoRepository for SqliteTodoRepository {
+    fn list<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let (sql, binds) = list_query(filter);
+            let rows = bind_values(sqlx::query_as::<_, Todo>(&sql), binds)
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(rows.into_iter().map(Todo::opened).collect())
+        })
+    }
+
+    fn count_matching<'a>(&'a self, filter: &'a TodoFilter) -> BoxFuture<'a, ApiResult<i64>> {
+        Box::pin(async move {
+            let (conditions, binds) = list_conditions(filter);
+            let sql = format!("SELECT COUNT(*) FROM todos WHERE {conditions}");
+            let (count,) = bind_values(sqlx::query_as::<_, (i64,)>(&sql), binds)
+                .fetch_one(&self.pool)
+                .await?;
+            Ok(count)
+        })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Todo>>> {
+        Box::pin(async move {
+            let row: Option<Todo> = sqlx::query_as("SELECT * FROM todos WHERE id=?1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+            Ok(row.map(Todo::opened))
+        })
+    }
+
+    fn insert<'a>(&'a self, todo: &'a Todo, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(r#"
+                INSERT INTO todos (id,title,note,status,priority,due_at,tags,category_id,sort_order,created_at,updated_at,deleted,latitude,longitude,location_name,version,checklist_done,checklist_total,completed_at,project_id,snooze_count,start_at,pinned,timer_started_at,tracked_secs,estimate_minutes,rank,assignee_id)
+                VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20,?21,?22,?23,?24,?25,?26,?27,?28)
+            "#)
+                .bind(&todo.id)
+                .bind(&todo.title)
+                .bind(todo.stored_note())
+                .bind(&todo.status)
+                .bind(todo.priority)
+                .bind(todo.due_at)
+                .bind(&todo.tags)
+                .bind(&todo.category_id)
+                .bind(todo.sort_order)
+                .bind(todo.created_at)
+                .bind(todo.updated_at)
+                .bind(todo.deleted)
+                .bind(todo.latitude)
+                .bind(todo.longitude)
+                .bind(&todo.location_name)
+                .bind(todo.version)
+                .bind(todo.checklist_done)
+                .bind(todo.checklist_total)
+                .bind(todo.completed_at)
+                .bind(&todo.project_id)
+                .bind(todo.snooze_count)
+                .bind(todo.start_at)
+                .bind(todo.pinned)
+                .bind(todo.timer_started_at)
+                .bind(todo.tracked_secs)
+                .bind(todo.estimate_minutes)
+                .bind(todo.rank)
+                .bind(&todo.assignee_id)
+                .execute(&mut *tx)
+                .await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        }))
+    }
+
+    fn update<'a>(&'a self, t: &'a Todo, events: &'a [String]) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            let result = sqlx::query(
+                r#"
+                UPDATE todos SET
+                title=?2, note=?3, status=?4, priority=?5, due_at=?6, tags=?7,
+                category_id=?8, sort_order=?9, updated_at=?10, deleted=?11,
+                latitude=?12, longitude=?13, location_name=?14, version=?15,
+                checklist_done=?16, checklist_total=?17, completed_at=?18, project_id=?19,
+                snooze_count=?20, start_at=?21, pinned=?22,
+                timer_started_at=?23, tracked_secs=?24,
+                estimate_minutes=?25, rank=?26, assignee_id=?27
+                WHERE id=?1 AND version=?15-1
+            "#,
+            )
+            .bind(&t.id)
+            .bind(&t.title)
+            .bind(t.stored_note())
+            .bind(&t.status)
+            .bind(t.priority)
+            .bind(t.due_at)
+            .bind(&t.tags)
+            .bind(&t.category_id)
+            .bind(t.sort_order)
+            .bind(t.updated_at)
+            .bind(t.deleted)
+            .bind(t.latitude)
+            .bind(t.longitude)
+            .bind(&t.location_name)
+            .bind(t.version)
+            .bind(t.checklist_done)
+            .bind(t.checklist_total)
+            .bind(t.completed_at)
+            .bind(&t.project_id)
+            .bind(t.snooze_count)
+            .bind(t.start_at)
+            .bind(t.pinned)
+            .bind(t.timer_started_at)
+            .bind(t.tracked_secs)
+            .bind(t.estimate_minutes)
+            .bind(t.rank)
+            .bind(&t.assignee_id)
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Ok(false); // Changed meanwhile (or gone); the transaction rolls back
+            }
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(true)
+        }))
+    }
+
+    fn set_status<'a>(
+        &'a self,
+        todo: &'a Todo,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            let result = sqlx::query(
+                "UPDATE todos SET status=?2, completed_at=?3, updated_at=?4, version=?5 WHERE id=?1 AND version=?5-1",
+            )
+            .bind(&todo.id)
+            .bind(&todo.status)
+            .bind(todo.completed_at)
+            .bind(todo.updated_at)
+            .bind(todo.version)
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Ok(false); // Changed meanwhile (or gone); the transaction rolls back
+            }
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(true)
+        }))
+    }
+
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            let result =
+                sqlx::query(
+                    "UPDATE todos SET deleted=1, updated_at=CURRENT_TIMESTAMP, version=version+1 WHERE id=?1",
+                )
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            let deleted = result.rows_affected() > 0;
+            if deleted {
+                store_events(&mut tx, events).await?;
+            }
+            tx.commit().await?;
+            Ok(deleted)
+        }))
+    }
+
+    fn reorder<'a>(
+        &'a self,
+        items: &'a [ReorderItem],
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            for it in items.iter() {
+                sqlx::query(
+                    "UPDATE todos SET sort_order=?2, rank=?3, updated_at=CURRENT_TIMESTAMP, version=version+1 WHERE id=?1",
+                )
+                .bind(&it.id)
+                .bind(it.sort_order)
+                .bind(it.sort_order as f64)
+                .execute(&mut *tx)
+                .await?;
+            }
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        }))
+    }
+
+    fn set_ranks<'a>(&'a self, ranks: &'a [(String, f64)]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            for (id, rank) in ranks {
+                sqlx::query("UPDATE todos SET rank=?2 WHERE id=?1")
+                    .bind(id)
+                    .bind(rank)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok(())
+        }))
+    }
+
+    fn open_due_before<'a>(
+        &'a self,
+        before: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let rows: Vec<Todo> = sqlx::query_as(
+                r#"
+                SELECT * FROM todos
+                WHERE deleted = 0 AND status NOT IN (SELECT value FROM json_each(?2))
+                  AND due_at IS NOT NULL AND due_at < ?1
+                ORDER BY due_at ASC, priority DESC
+            "#,
+            )
+            .bind(before)
+            .bind(Json(done))
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(rows.into_iter().map(Todo::opened).collect())
+        })
+    }
+
+    fn find_open_by_title<'a>(
+        &'a self,
+        title: &'a str,
+        category_id: Option<&'a str>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Option<Todo>>> {
+        Box::pin(async move {
+            // NOCASE folds ASCII only; good enough for spotting double entries
+            let row: Option<Todo> = sqlx::query_as(
+                r#"
+                SELECT * FROM todos
+                WHERE deleted = 0 AND status NOT IN (SELECT value FROM json_each(?3))
+                  AND title = ?1 COLLATE NOCASE AND category_id IS ?2
+                ORDER BY created_at ASC
+                LIMIT 1
+            "#,
+            )
+            .bind(title)
+            .bind(category_id)
+            .bind(Json(done))
+            .fetch_optional(&self.pool)
+            .await?;
+            Ok(row.map(Todo::opened))
+        })
+    }
+
+    fn open_with_location<'a>(&'a self, done: &'a [String]) -> BoxFuture<'a, ApiResult<Vec<Todo>>> {
+        Box::pin(async move {
+            let rows: Vec<Todo> = sqlx::query_as(
+                r#"
+                SELECT * FROM todos
+                WHERE deleted = 0
+                  AND status NOT IN (SELECT value FROM json_each(?1))
+                  AND latitude IS NOT NULL AND longitude IS NOT NULL
+                ORDER BY COALESCE(due_at, '9999-12-31T00:00:00Z') ASC, priority DESC
+            "#,
+            )
+            .bind(Json(done))
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(rows.into_iter().map(Todo::opened).collect())
+        })
+    }
+
+    fn move_category<'a>(
+        &'a self,
+        from: &'a str,
+        to: Option<&'a str>,
+    ) -> BoxFuture<'a, ApiResult<Vec<String>>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            let ids: Vec<String> =
+                sqlx::query_scalar("SELECT id FROM todos WHERE category_id=?1 AND deleted=0")
+                    .bind(from)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            sqlx::query(
+                "UPDATE todos SET category_id=?2, updated_at=?3, version=version+1 WHERE category_id=?1 AND deleted=0",
+            )
+            .bind(from)
+            .bind(to)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            Ok(ids)
+        }))
+    }
+
+    fn count_in_category<'a>(&'a self, category_id: &'a str) -> BoxFuture<'a, ApiResult<i64>> {
+        Box::pin(async move {
+            Ok(
+                sqlx::query_scalar("SELECT COUNT(*) FROM todos WHERE category_id=?1 AND deleted=0")
+                    .bind(category_id)
+                    .fetch_one(&self.pool)
+                    .await?,
+            )
+        })
+    }
+
+    fn counts_by_category<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<Vec<CategoryTodoCounts>>> {
+        Box::pin(async move {
+            let rows: Vec<(Option<String>, i64, i64, i64)> = sqlx::query_as(
+                r#"
+                WITH done(name) AS (SELECT value FROM json_each(?2))
+                SELECT
+                    category_id,
+                    COUNT(*),
+                    COALESCE(SUM(status NOT IN done), 0),
+                    COALESCE(SUM(status NOT IN done AND due_at IS NOT NULL AND due_at < ?1), 0)
+                FROM todos
+                WHERE deleted = 0
+                GROUP BY category_id
+            "#,
+            )
+            .bind(now)
+            .bind(Json(done))
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(rows
+                .into_iter()
+                .map(|(category_id, active, open, overdue)| CategoryTodoCounts {
+                    category_id,
+                    active,
+                    open,
+                    done: active - open,
+                    overdue,
+                })
+                .collect())
+        })
+    }
+
+    fn counts<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        done: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<TodoCounts>> {
+        Box::pin(async move {
+            let (active, deleted, open, overdue): (i64, i64, i64, i64) = sqlx::query_as(
+                r#"
+                WITH done(name) AS (SELECT value FROM json_each(?2))
+                SELECT
+                    COALESCE(SUM(deleted = 0), 0),
+                    COALESCE(SUM(deleted != 0), 0),
+                    COALESCE(SUM(deleted = 0 AND status NOT IN done), 0),
+                    COALESCE(SUM(deleted = 0 AND status NOT IN done
+                        AND due_at IS NOT NULL AND due_at < ?1), 0)
+                FROM todos
+            "#,
+            )
+            .bind(now)
+            .bind(Json(done))
+            .fetch_one(&self.pool)
+            .await?;
+            Ok(TodoCounts {
+                active,
+                deleted,
+                open,
+                overdue,
+            })
+        })
+    }
+
+    fn enqueue_events<'a>(&'a self, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        }))
+    }
+
+    fn pending_events(&self, limit: i64) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>> {
+        Box::pin(async move {
+            Ok(sqlx::query_as::<_, OutboxEvent>(
+                "SELECT seq, message FROM outbox WHERE published_at IS NULL ORDER BY seq ASC LIMIT ?1",
+            )
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+        })
+    }
+
+    fn mark_published(&self, seq: i64) -> BoxFuture<'_, ApiResult<()>> {
+        Box::pin(retry_busy(move || async move {
+            sqlx::query("UPDATE outbox SET published_at=?2 WHERE seq=?1")
+                .bind(seq)
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }))
+    }
+
+    fn published_events(
+        &self,
+        after: i64,
+        limit: i64,
+    ) -> BoxFuture<'_, ApiResult<Vec<OutboxEvent>>> {
+        Box::pin(async move {
+            Ok(sqlx::query_as::<_, OutboxEvent>(
+                "SELECT seq, message FROM outbox WHERE published_at IS NOT NULL AND seq > ?1 ORDER BY seq ASC LIMIT ?2",
+            )
+            .bind(after)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+        })
+    }
+
+    fn last_published_seq(&self) -> BoxFuture<'_, ApiResult<i64>> {
+        Box::pin(async move {
+            Ok(sqlx::query_scalar(
+                "SELECT COALESCE(MAX(seq), 0) FROM outbox WHERE published_at IS NOT NULL",
+            )
+            .fetch_one(&self.pool)
+            .await?)
+        })
+    }
+
+    fn prune_events(&self, before: DateTime<Utc>) -> BoxFuture<'_, ApiResult<u64>> {
+        Box::pin(retry_busy(move || async move {
+            Ok(sqlx::query("DELETE FROM outbox WHERE published_at < ?1")
+                .bind(before)
+                .execute(&self.pool)
+                .await?
+                .rows_affected())
+        }))
+    }
+}
+
+/**
+ * Categories stored in the SQLite `categories` table
+ */
+#[derive(Clone)]
+pub struct SqliteCategoryRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteCategoryRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+impl CategoryRepository for SqliteCategoryRepository {
+    fn list(&self) -> BoxFuture<'_, ApiResult<Vec<Category>>> {
+        Box::pin(async move {
+            Ok(sqlx::query_as::<_, Category>(
+                "SELECT * FROM categories WHERE deleted = 0 ORDER BY sort_order ASC, name ASC",
+            )
+            .fetch_all(&self.pool)
+            .await?)
+        })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, ApiResult<Option<Category>>> {
+        Box::pin(async move {
+            Ok(
+                sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id=?1")
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await?,
+            )
+        })
+    }
+
+    fn insert<'a>(
+        &'a self,
+        category: &'a Category,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(
+                r#"
+                INSERT INTO categories (id,name,color,description,sort_order,created_at,updated_at,deleted,project_id,parent_id,is_default,archived)
+                VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12)
+            "#,
+            )
+            .bind(&category.id)
+            .bind(&category.name)
+            .bind(&category.color)
+            .bind(&category.description)
+            .bind(category.sort_order)
+            .bind(category.created_at)
+            .bind(category.updated_at)
+            .bind(category.deleted)
+            .bind(&category.project_id)
+            .bind(&category.parent_id)
+            .bind(category.is_default)
+            .bind(category.archived)
+            .execute(&mut *tx)
+            .await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        }))
+    }
+
+    fn update<'a>(&'a self, c: &'a Category, events: &'a [String]) -> BoxFuture<'a, ApiResult<()>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(
+                r#"
+                UPDATE categories SET
+                name=?2, color=?3, description=?4, sort_order=?5, updated_at=?6, deleted=?7,
+                project_id=?8, parent_id=?9, is_default=?10, archived=?11
+                WHERE id=?1
+            "#,
+            )
+            .bind(&c.id)
+            .bind(&c.name)
+            .bind(&c.color)
+            .bind(&c.description)
+            .bind(c.sort_order)
+            .bind(c.updated_at)
+            .bind(c.deleted)
+            .bind(&c.project_id)
+            .bind(&c.parent_id)
+            .bind(c.is_default)
+            .bind(c.archived)
+            .execute(&mut *tx)
+            .await?;
+            store_events(&mut tx, events).await?;
+            tx.commit().await?;
+            Ok(())
+        }))
+    }
+
+    fn soft_delete<'a>(
+        &'a self,
+        id: &'a str,
+        events: &'a [String],
+    ) -> BoxFuture<'a, ApiResult<bool>> {
+        Box::pin(retry_busy(move || async move {
+            let mut tx = self.pool.begin().await?;
+            let result = sqlx::query(
+                "UPDATE categories SET deleted=1, updated_at=CURRENT_TIMESTAMP WHERE id=?1",
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            let deleted = result.rows_affected() > 0;
+            if deleted {
+                store_events(&mut tx, events).await?;
+            }
+            tx.commit().await?;
+            Ok(deleted)
+        }))
+    }
+}
+
+/// Store `events` in the outbox as part of `tx`.
+async fn store_events(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    events: &[String],
+) -> sqlx::Result<()> {
+    for message in events {
+        sqlx::query("INSERT INTO outbox (message, created_at) VALUES (?1, ?2)")
+            .bind(message)
+            .bind(Utc::now())
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{db::init_pool_with_size, filter::FilterExpr};
+
+    /// Detail lines of EXPLAIN QUERY PLAN for the list query of `filter`.
+    async fn list_plan(pool: &SqlitePool, filter: &TodoFilter) -> Vec<String> {
+        let (sql, binds) = list_query(filter);
+        let sql = format!("EXPLAIN QUERY PLAN {sql}");
+        // Columns: id, parent, notused, detail
+        let rows = bind_values(sqlx::query_as::<_, (i64, i64, i64, String)>(&sql), binds)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        rows.into_iter().map(|(_, _, _, detail)| detail).collect()
+    }
+
+    #[tokio::test]
+    async fn list_queries_use_the_list_index() {
+        // One connection: every in-memory connection is a database of its own
+        let pool = init_pool_with_size("sqlite::memory:", 1).await.unwrap();
+        let filter_expr =
+            |src: &str| Some(FilterExpr::parse(src, Utc::now(), chrono_tz::UTC).unwrap());
+
+        let board = TodoFilter::default();
+        let column = TodoFilter {
+            status: Some("todo".into()),
+            ..Default::default()
+        };
+        let filtered = TodoFilter {
+            expr: filter_expr("tag:urgent priority>=2 due<+7d"),
+            ..Default::default()
+        };
+        let filtered_column = TodoFilter {
+            status: Some("doing".into()),
+            expr: filter_expr("title:milk"),
+            ..Default::default()
+        };
+        let column_page = TodoFilter {
+            status: Some("todo".into()),
+            limit: Some(50),
+            offset: 100,
+            ..Default::default()
+        };
+        for filter in [&board, &column, &filtered, &filtered_column, &column_page] {
+            let plan = list_plan(&pool, filter).await;
+            assert!(
+                plan.iter()
+                    .any(|d| d.contains("USING INDEX idx_todos_list")),
+                "{filter:?}: {plan:?}"
+            );
+            assert!(
+                !plan.iter().any(|d| d.starts_with("SCAN todos")),
+                "{filter:?}: {plan:?}"
+            );
+        }
+        // With a status the index order is the list order: no sorting step
+        for filter in [&column, &filtered_column, &column_page] {
+            let plan = list_plan(&pool, filter).await;
+            assert!(
+                !plan.iter().any(|d| d.contains("TEMP B-TREE")),
+                "{filter:?}: {plan:?}"
+            );
+        }
+    }
+}
@@ -1,19 +1,62 @@
 use axum::{
     Json, Router,
-    extract::{Path, Query, State},
+    body::Bytes,
+    extract::{OriginalUri, Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
     routing::{get, post},
 };
-use serde::Deserialize;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use sqlx::types::chrono::Utc;
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use crate::{
+    account, activity, admin,
+    attachments::{self, AttachmentStore},
+    audit::{self, Actor, AuditLog},
+    backups::{self, BackupStore},
+    capabilities::{self, Integrations},
+    checklist, comments,
+    config::ServerConfig,
     db::SqlitePool,
-    error::{ApiError, ApiResult},
+    ddns::DdnsUpdater,
+    error::{ApiError, ApiResult, JsonBody},
+    etag, events,
+    filter::FilterExpr,
+    geo, idempotency, imports, inbound,
+    jobs::JobScheduler,
+    kiosk::{self, KioskRotator},
+    links::{self, TodoLinks},
+    logging::{self, LogControl},
+    members::{self, Members},
+    metrics,
     model::{
-        Category, CategoryCreate, CategoryUpdate, Health, ReorderItem, Todo, TodoCreate, TodoUpdate,
+        Category, CategoryCreate, CategoryNode, CategoryUpdate, Health, ReorderItem, Todo,
+        TodoCreate, TodoDuplicate, TodoMove, TodoPlace, TodoSnooze, TodoUpdate,
     },
+    notes::{self, NoteSessions},
+    notifications::{self, Notifications},
+    notify::Notifier,
+    oidc::{self, OidcClient},
+    outbox::Outbox,
+    pagination::{Page, PageParams},
+    pomodoro::{self, PomodoroTimer},
+    portmap::PortMapper,
+    presence,
+    projects::{self, Projects},
+    read_only::{self, ReadOnlyMode},
+    repository::{
+        CategoryRepository, CategoryTodoCounts, SqliteCategoryRepository, SqliteTodoRepository,
+        TodoRepository,
+    },
+    services::{CategoryService, Dedupe, ReorderScope, TodoFilter, TodoService},
+    sessions, settings, shares, stats,
+    statuses::{self, Statuses},
+    system::{self, SystemHealth},
+    timer,
+    timezone::Zone,
+    users, webhooks,
     ws::WsHub,
 };
 
@@ -21,6 +64,80 @@ use crate::{
 pub struct AppState {
     pub pool: SqlitePool,
     pub hub: Arc<WsHub>,
+    pub ddns: Option<Arc<DdnsUpdater>>, // Dynamic DNS updater, when configured
+    pub oidc: Option<Arc<OidcClient>>,  // OpenID Connect sign-in, when configured
+    pub port_mapper: Option<Arc<PortMapper>>, // Router port mapping, when configured
+    pub jobs: Option<Arc<JobScheduler>>, // Heavy background jobs, when started
+    pub kiosk: Arc<KioskRotator>,       // Wall display rotation clock
+    pub pomodoro: Arc<PomodoroTimer>,   // Shared pomodoro clock
+    pub notes: Arc<NoteSessions>,       // Collaborative note editing sessions
+    pub outbox: Arc<Outbox>,            // Publishes stored todo/category events
+    pub notifications: Notifications,   // In-app notification center
+    pub notifiers: Vec<Arc<dyn Notifier>>, // Push channels for mentions, when configured
+    pub started_at: Instant,            // For uptime reporting
+    pub todos: TodoService,             // Todo business logic
+    pub categories: CategoryService,    // Category business logic
+    pub attachments: Arc<AttachmentStore>, // Attachment directory and upload limits
+    pub backups: Arc<BackupStore>,      // Backup directory and retention
+    pub read_only: Arc<ReadOnlyMode>,   // Rejects writes while enabled
+    pub config: Arc<ServerConfig>,      // Effective settings, for /api/admin/info
+    pub log: Arc<LogControl>,           // Live log filter, when the binary installed one
+    pub integrations: Integrations,     // Optional integrations, for /api/capabilities
+}
+
+impl AppState {
+    /// State with the required parts; optional integrations start out disabled.
+    pub fn new(pool: SqlitePool, hub: Arc<WsHub>) -> Self {
+        let todos: Arc<dyn TodoRepository> = Arc::new(SqliteTodoRepository::new(pool.clone()));
+        let categories = Arc::new(SqliteCategoryRepository::new(pool.clone()));
+        let mut state = Self::with_repositories(pool, hub, todos, categories);
+        state.integrations.storage = "sqlite";
+        state
+    }
+
+    /// State whose services use the given storage backends.
+    pub fn with_repositories(
+        pool: SqlitePool,
+        hub: Arc<WsHub>,
+        todos: Arc<dyn TodoRepository>,
+        categories: Arc<dyn CategoryRepository>,
+    ) -> Self {
+        let audit = AuditLog::new(pool.clone());
+        let outbox = Arc::new(Outbox::new(todos.clone(), hub.clone()));
+        let notifications = Notifications::new(pool.clone(), hub.clone());
+        Self {
+            todos: TodoService::new(todos.clone(), outbox.clone())
+                .with_categories(categories.clone())
+                .with_audit(audit.clone())
+                .with_links(TodoLinks::new(pool.clone()))
+                .with_statuses(Statuses::new(pool.clone()))
+                .with_projects(Projects::new(pool.clone()))
+                .with_members(Members::new(pool.clone()))
+                .with_notifications(notifications.clone()),
+            categories: CategoryService::new(categories, todos, outbox.clone())
+                .with_audit(audit)
+                .with_projects(Projects::new(pool.clone())),
+            kiosk: Arc::new(KioskRotator::new(pool.clone(), hub.clone())),
+            pomodoro: Arc::new(PomodoroTimer::new(pool.clone(), hub.clone())),
+            notes: Arc::new(NoteSessions::default()),
+            outbox,
+            notifications,
+            notifiers: Vec::new(),
+            pool,
+            hub,
+            ddns: None,
+            oidc: None,
+            port_mapper: None,
+            jobs: None,
+            started_at: Instant::now(),
+            attachments: Arc::new(AttachmentStore::default()),
+            backups: Arc::new(BackupStore::default()),
+            read_only: Arc::new(ReadOnlyMode::default()),
+            config: Arc::new(ServerConfig::default()),
+            log: Arc::new(LogControl::default()),
+            integrations: Integrations::default(),
+        }
+    }
 }
 
 pub fn api_router() -> Router<AppState> {
@@ -29,29 +146,89 @@ pub fn api_router() -> Router<AppState> {
         .route("/api/todos", get(list_todos).post(create_todo))
         .route(
             "/api/todos/{id}",
-            get(get_todo).put(update_todo).delete(delete_todo),
+            get(get_todo)
+                .put(update_todo)
+                .patch(patch_todo)
+                .delete(delete_todo),
         )
         .route(
             "/api/todos/{id}/status",
             axum::routing::patch(update_status),
         )
+        .route("/api/todos/today", get(today))
+        .route("/api/todos/calendar", get(calendar))
         .route("/api/todos/reorder", post(reorder))
+        .route("/api/todos/archive-done", post(archive_done))
+        .route("/api/todos/{id}/duplicate", post(duplicate_todo))
+        .route("/api/todos/{id}/snooze", post(snooze_todo))
+        .route("/api/todos/{id}/pin", post(pin_todo))
+        .route("/api/todos/{id}/place", post(place_todo))
+        .route("/api/todos/{id}/move", post(move_todo))
         .route(
             "/api/categories",
             get(list_categories).post(create_category),
         )
+        .route("/api/categories/tree", get(category_tree))
+        .route("/api/categories/with-counts", get(categories_with_counts))
+        .route("/api/categories/palette", get(category_palette))
         .route(
             "/api/categories/{id}",
             get(get_category)
                 .put(update_category)
                 .delete(delete_category),
         )
+        .route(
+            "/api/categories/{id}/default",
+            post(set_default_category).delete(unset_default_category),
+        )
+        .route("/api/categories/{id}/archive", post(archive_category))
+        .route("/api/categories/{id}/unarchive", post(unarchive_category))
+        .merge(geo::router())
+        .merge(imports::router())
+        .merge(inbound::router())
+        .merge(kiosk::router())
+        .merge(links::router())
+        .merge(admin::router())
+        .merge(attachments::router())
+        .merge(backups::router())
+        .merge(metrics::router())
+        .merge(events::router())
+        .merge(presence::router())
+        .merge(notes::router())
+        .merge(comments::router())
+        .merge(notifications::router())
+        .merge(activity::router())
+        .merge(shares::router())
+        .merge(account::router())
+        .merge(members::router())
+        .merge(sessions::router())
+        .merge(settings::router())
+        .merge(oidc::router())
+        .merge(read_only::router())
+        .merge(logging::router())
+        .merge(audit::router())
+        .merge(capabilities::router())
+        .merge(checklist::router())
+        .merge(pomodoro::router())
+        .merge(projects::router())
+        .merge(stats::router())
+        .merge(statuses::router())
+        .merge(timer::router())
+        .merge(users::router())
+        .merge(webhooks::router())
 }
 
-async fn health() -> Json<Health> {
+async fn health(State(st): State<AppState>) -> Json<Health> {
+    let system = SystemHealth::read(&system::data_dir(&st.config));
     Json(Health {
-        ok: true,
-        db: "ok".into(),
+        ok: !system.disk_low(), // A full SD card fails every write
+        db: if st.integrations.read_only {
+            "read-only: integrity check failed".into()
+        } else {
+            "ok".into()
+        },
+        backup: st.backups.status(),
+        system,
     })
 }
 
@@ -59,347 +236,804 @@ async fn health() -> Json<Health> {
 struct ListParams {
     status: Option<String>,
     include_deleted: Option<bool>,
+    blocked: Option<bool>, // Only todos with (true) or without (false) an open blocker
+    project_id: Option<String>, // Only todos in this project
+    started: Option<bool>, // Only todos whose start_at has (true) or has not (false) passed
+    pinned: Option<bool>,  // Only pinned (true) or unpinned (false) todos
+    category_id: Option<String>, // Only todos in this category ...
+    recursive: Option<bool>, // ... or (true) in it and its subcategories
+    expand: Option<String>, // "category": embed each todo's category
+    fields: Option<String>, // Comma-separated keys to return, e.g. "id,title,status"
+    filter: Option<String>, // Filter expression, e.g. "tag:urgent OR priority>=2" (see filter.rs)
+    assignee: Option<String>, // Assigned to this user id, "me" (X-User-Id) or "none"
+}
+
+#[derive(Deserialize)]
+struct GetParams {
+    expand: Option<String>, // "category": embed the todo's category
+    fields: Option<String>, // Comma-separated keys to return
+}
+
+/// Name and color of a todo's category, embedded by `?expand=category`.
+#[derive(Serialize)]
+struct CategoryRef {
+    id: String,
+    name: String,
+    color: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TodoWithCategory {
+    #[serde(flatten)]
+    todo: Todo,
+    category: Option<CategoryRef>, // None when uncategorized (or the category is deleted)
+}
+
+/// Whether `?expand=` asks for the category; bad request for anything else.
+fn expand_category(expand: Option<&str>) -> ApiResult<bool> {
+    let mut category = false;
+    for part in expand.unwrap_or_default().split(',').map(str::trim) {
+        match part {
+            "" => {}
+            "category" => category = true,
+            other => {
+                return Err(ApiError::BadRequest(format!(
+                    "cannot expand `{other}` (supported: category)"
+                )));
+            }
+        }
+    }
+    Ok(category)
+}
+
+/// `etag::respond`, keeping only the `?fields=` keys of each object.
+///
+/// Lets small clients (microcontroller displays) skip notes, locations and
+/// timestamps they never show. Unknown keys are ignored.
+fn respond_fields(
+    headers: &HeaderMap,
+    tag: String,
+    body: impl Serialize,
+    fields: Option<&str>,
+) -> ApiResult<Response> {
+    let Some(fields) = fields else {
+        return Ok(etag::respond(headers, tag, body));
+    };
+    let keep: Vec<&str> = fields.split(',').map(str::trim).collect();
+    let pick = |object: &mut serde_json::Value| {
+        if let Some(map) = object.as_object_mut() {
+            map.retain(|key, _| keep.contains(&key.as_str()));
+        }
+    };
+    let mut value = serde_json::to_value(body).map_err(anyhow::Error::from)?;
+    match &mut value {
+        serde_json::Value::Array(items) => items.iter_mut().for_each(pick),
+        object => pick(object),
+    }
+    Ok(etag::respond(headers, tag, value))
+}
+
+/// Attach each todo's category from one category list lookup.
+fn with_categories(todos: Vec<Todo>, categories: &[Category]) -> Vec<TodoWithCategory> {
+    todos
+        .into_iter()
+        .map(|todo| {
+            let category = categories
+                .iter()
+                .find(|c| todo.category_id.as_ref() == Some(&c.id))
+                .map(|c| CategoryRef {
+                    id: c.id.clone(),
+                    name: c.name.clone(),
+                    color: c.color.clone(),
+                });
+            TodoWithCategory { todo, category }
+        })
+        .collect()
 }
 
 async fn list_todos(
     State(st): State<AppState>,
+    actor: Actor,
+    zone: Zone,
     Query(p): Query<ListParams>,
-) -> ApiResult<Json<Vec<Todo>>> {
-    let include_flag = if p.include_deleted.unwrap_or(false) {
-        1_i64
-    } else {
-        0_i64
+    Query(page): Query<PageParams>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let mut page = Page::new(page, uri)?;
+    // These filters run on the loaded list, which must then hold every match
+    let in_memory = p.blocked.is_some()
+        || p.category_id.is_some()
+        || p.pinned.is_some()
+        || p.assignee.is_some()
+        || p.started.is_some();
+    let filter = TodoFilter {
+        status: p.status,
+        include_deleted: p.include_deleted.unwrap_or(false),
+        project_id: p.project_id,
+        expr: p
+            .filter
+            .as_deref()
+            .map(|f| FilterExpr::parse(f, Utc::now(), zone.0))
+            .transpose()
+            .map_err(|e| ApiError::BadRequest(format!("invalid filter: {e}")))?,
+        limit: page.limit().filter(|_| !in_memory),
+        offset: if in_memory { 0 } else { page.offset() },
     };
-    let rows = sqlx::query_as::<_, Todo>(
-        r#"
-        SELECT * FROM todos
-        WHERE
-            (?1 IS NULL OR status = ?1)
-        AND
-            (?2 != 0 OR deleted = 0)
-        ORDER BY
-            priority DESC,
-            COALESCE(due_at, '9999-12-31T00:00:00Z') ASC,
-            sort_order ASC,
-            created_at ASC
-    "#,
-    )
-    .bind(p.status) // ?1
-    .bind(include_flag) // ?2
-    .fetch_all(&st.pool)
-    .await?;
-    Ok(Json(rows))
+    let expand = expand_category(p.expand.as_deref())?;
+    let mut todos = st.todos.list(&filter).await?;
+    if let Some(want) = p.blocked {
+        let blocked = st.todos.blocked_ids().await?;
+        todos.retain(|t| blocked.contains(&t.id) == want);
+    }
+    if let Some(category) = p.category_id {
+        let ids = if p.recursive.unwrap_or(false) {
+            st.categories.descendants(&category).await?
+        } else {
+            vec![category]
+        };
+        todos.retain(|t| t.category_id.as_ref().is_some_and(|c| ids.contains(c)));
+    }
+    if let Some(want) = p.pinned {
+        todos.retain(|t| t.pinned == want);
+    }
+    if let Some(assignee) = p.assignee {
+        let assignee = match assignee.as_str() {
+            "none" => None,
+            "me" => Some(actor.user_id().map(str::to_string).ok_or_else(|| {
+                ApiError::BadRequest("assignee=me needs an X-User-Id header".into())
+            })?),
+            _ => Some(assignee),
+        };
+        todos.retain(|t| t.assignee_id == assignee);
+    }
+    if let Some(want) = p.started {
+        let now = Utc::now();
+        todos.retain(|t| t.start_at.is_none_or(|s| s <= now) == want);
+    }
+    if in_memory {
+        page.cut(&mut todos);
+    } else if filter.limit.is_none() && filter.offset == 0 {
+        page.set_total(todos.len());
+    } else {
+        page.set_total(st.todos.count_matching(&filter).await?);
+    }
+    let ids = || todos.iter().map(|t| t.id.as_str());
+    let fields = p.fields.as_deref();
+    if expand {
+        // Renaming or recoloring a category must change the ETag too
+        let categories = st.categories.list().await?;
+        let updated = todos.iter().map(|t| &t.updated_at);
+        let tag = etag::collection(updated.chain(categories.iter().map(|c| &c.updated_at)));
+        let tag = page.etag(tag, ids());
+        let body = with_categories(todos, &categories);
+        let mut response = respond_fields(&headers, tag, body, fields)?;
+        page.apply(&mut response);
+        return Ok(response);
+    }
+    let tag = page.etag(etag::collection(todos.iter().map(|t| &t.updated_at)), ids());
+    let mut response = respond_fields(&headers, tag, todos, fields)?;
+    page.apply(&mut response);
+    Ok(response)
+}
+
+#[derive(Deserialize)]
+struct CreateParams {
+    dedupe: Option<String>, // true/return, conflict or false/off; default: config dedupe_todos
 }
 
 async fn create_todo(
     State(st): State<AppState>,
-    Json(body): Json<TodoCreate>,
-) -> ApiResult<Json<Todo>> {
-    let todo = Todo::new_from_create(body);
-    sqlx::query(r#"
-        INSERT INTO todos (id,title,note,status,priority,due_at,tags,category_id,sort_order,created_at,updated_at,deleted)
-        VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12)
-    "#)
-        .bind(&todo.id)
-        .bind(&todo.title)
-        .bind(&todo.note)
-        .bind(&todo.status)
-        .bind(todo.priority)
-        .bind(todo.due_at)
-        .bind(&todo.tags)
-        .bind(&todo.category_id)
-        .bind(todo.sort_order)
-        .bind(todo.created_at)
-        .bind(todo.updated_at)
-        .bind(todo.deleted)
-        .execute(&st.pool)
-        .await?;
-
-    let event = json!({"type":"todo.created","data": &todo});
-    let _ = st.hub.tx.send(event.to_string());
-    Ok(Json(todo))
+    actor: Actor,
+    headers: HeaderMap,
+    Query(params): Query<CreateParams>,
+    JsonBody(body): JsonBody<TodoCreate>,
+) -> ApiResult<Response> {
+    let dedupe = params.dedupe.as_deref().unwrap_or(&st.config.dedupe_todos);
+    let dedupe = Dedupe::parse(dedupe).ok_or_else(|| {
+        ApiError::BadRequest("dedupe must be true, false, return, conflict or off".into())
+    })?;
+    let todos = st.todos.as_actor(actor.clone());
+    let started = Utc::now();
+    // Retried creates with the same Idempotency-Key replay the first response
+    let Some(key) = idempotency::key(&headers)? else {
+        let todo = todos.create_deduped(body, dedupe).await?;
+        created_mentions(&st, &actor, &todo, started).await;
+        return Ok(Json(todo).into_response());
+    };
+    let request = serde_json::to_string(&body).map_err(|e| ApiError::Anyhow(e.into()))?;
+    if let Some(replay) = idempotency::begin(&st.pool, &key, "POST /api/todos", &request).await? {
+        return Ok(replay);
+    }
+    match todos.create_deduped(body, dedupe).await {
+        Ok(todo) => {
+            created_mentions(&st, &actor, &todo, started).await;
+            idempotency::complete(&st.pool, &key, StatusCode::OK, &todo).await?;
+            Ok(Json(todo).into_response())
+        }
+        Err(e) => {
+            idempotency::release(&st.pool, &key).await;
+            Err(e)
+        }
+    }
 }
 
-async fn get_todo(State(st): State<AppState>, Path(id): Path<String>) -> ApiResult<Json<Todo>> {
-    let row = sqlx::query_as::<_, Todo>("SELECT * FROM todos WHERE id=?1")
-        .bind(&id)
-        .fetch_optional(&st.pool)
-        .await?;
-    match row {
-        Some(t) => Ok(Json(t)),
-        None => Err(ApiError::NotFound),
+/// Mentions in the note of a todo created since `started` (not a dedupe match).
+async fn created_mentions(st: &AppState, actor: &Actor, todo: &Todo, started: DateTime<Utc>) {
+    if todo.created_at >= started {
+        notifications::note_mentions(st, actor, todo, None).await;
     }
 }
 
-async fn update_todo(
+#[derive(Deserialize)]
+struct TodayParams {
+    project_id: Option<String>, // Only todos in this project
+}
+
+/// Daily agenda: open todos that are overdue, due today (in the request's
+/// zone, see timezone.rs) or started.
+///
+/// Sections are ordered for display: overdue and due-today by due date,
+/// started-but-undated (or due later) by priority, then start date.
+async fn today(
     State(st): State<AppState>,
-    Path(id): Path<String>,
-    Json(body): Json<TodoUpdate>,
-) -> ApiResult<Json<Todo>> {
-    let mut t: Todo = sqlx::query_as("SELECT * FROM todos WHERE id=?1")
-        .bind(&id)
-        .fetch_optional(&st.pool)
+    zone: Zone,
+    Query(p): Query<TodayParams>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let now = Utc::now();
+    let today = zone.today();
+    let end_of_today = zone.midnight(today + chrono::Days::new(1));
+    let done = st.todos.done_statuses().await?;
+    let filter = TodoFilter {
+        project_id: p.project_id,
+        ..Default::default()
+    };
+    let open: Vec<Todo> = st
+        .todos
+        .list(&filter)
         .await?
-        .ok_or(ApiError::NotFound)?;
+        .into_iter()
+        .filter(|t| !done.contains(&t.status))
+        .collect();
 
-    if let Some(v) = body.title {
-        t.title = v;
+    let mut overdue: Vec<&Todo> = open
+        .iter()
+        .filter(|t| t.due_at.is_some_and(|d| d < now))
+        .collect();
+    let mut due_today: Vec<&Todo> = open
+        .iter()
+        .filter(|t| t.due_at >= Some(now) && t.due_at < Some(end_of_today))
+        .collect();
+    let mut started: Vec<&Todo> = open
+        .iter()
+        .filter(|t| t.due_at.is_none_or(|d| d >= end_of_today))
+        .filter(|t| t.start_at.is_some_and(|s| s <= now))
+        .collect();
+    for section in [&mut overdue, &mut due_today] {
+        section.sort_by(|a, b| a.due_at.cmp(&b.due_at).then(b.priority.cmp(&a.priority)));
     }
-    if let Some(v) = body.note {
-        t.note = Some(v);
-    }
-    if let Some(v) = body.status {
-        t.status = v;
+    started.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then(a.start_at.cmp(&b.start_at))
+    });
+    Ok(Json(json!({
+        "date": today,
+        "timezone": zone.0.name(),
+        "overdue": overdue,
+        "due_today": due_today,
+        "started": started,
+    })))
+}
+
+#[derive(Deserialize)]
+struct CalendarParams {
+    from: NaiveDate,            // First day (request zone), inclusive
+    to: NaiveDate,              // Last day (request zone), inclusive
+    status: Option<String>,     // Only todos in this status
+    project_id: Option<String>, // Only todos in this project
+}
+
+/// Longest range one calendar request may cover.
+const MAX_CALENDAR_DAYS: i64 = 366;
+
+/// Dated todos in `from..=to`, grouped by due date in the request's zone
+/// (days without todos are left out).
+///
+/// Todos have no recurrence rules yet, so every todo appears at most once.
+async fn calendar(
+    State(st): State<AppState>,
+    zone: Zone,
+    Query(p): Query<CalendarParams>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let days = (p.to - p.from).num_days() + 1;
+    if !(1..=MAX_CALENDAR_DAYS).contains(&days) {
+        return Err(ApiError::BadRequest(format!(
+            "`to` must be on or after `from` and at most {MAX_CALENDAR_DAYS} days later"
+        )));
     }
-    if let Some(v) = body.priority {
-        t.priority = v;
+    let filter = TodoFilter {
+        status: p.status,
+        project_id: p.project_id,
+        ..Default::default()
+    };
+    let mut by_day: std::collections::BTreeMap<NaiveDate, Vec<Todo>> = Default::default();
+    for t in st.todos.list(&filter).await? {
+        let Some(day) = t.due_at.map(|d| zone.date(d)) else {
+            continue;
+        };
+        if (p.from..=p.to).contains(&day) {
+            by_day.entry(day).or_default().push(t);
+        }
     }
-    if let Some(v) = body.due_at {
-        t.due_at = Some(v);
+    let days: Vec<serde_json::Value> = by_day
+        .into_iter()
+        .map(|(date, mut todos)| {
+            todos.sort_by(|a, b| a.due_at.cmp(&b.due_at).then(b.priority.cmp(&a.priority)));
+            json!({"date": date, "todos": todos})
+        })
+        .collect();
+    Ok(Json(
+        json!({"from": p.from, "to": p.to, "timezone": zone.0.name(), "days": days}),
+    ))
+}
+
+async fn get_todo(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+    Query(p): Query<GetParams>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let expand = expand_category(p.expand.as_deref())?;
+    let todo = st.todos.get(&id).await?;
+    if expand {
+        let categories = st.categories.list().await?;
+        let category = categories
+            .iter()
+            .find(|c| todo.category_id.as_ref() == Some(&c.id));
+        let tag = etag::collection(
+            [&todo.updated_at]
+                .into_iter()
+                .chain(category.map(|c| &c.updated_at)),
+        );
+        let body = with_categories(vec![todo], &categories).remove(0);
+        return respond_fields(&headers, tag, body, p.fields.as_deref());
     }
-    if let Some(v) = body.tags {
-        t.tags = Some(v);
+    let tag = etag::item(todo.version, todo.updated_at);
+    respond_fields(&headers, tag, todo, p.fields.as_deref())
+}
+
+/// Copy of the todo (optionally with its checklist); body is optional.
+async fn duplicate_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    body: Option<Json<TodoDuplicate>>,
+) -> ApiResult<Json<Todo>> {
+    let body = body.map(|Json(b)| b).unwrap_or_default();
+    let todos = st.todos.as_actor(actor.clone());
+    let copy = todos.duplicate(&id).await?;
+    if body.checklist.unwrap_or(false) {
+        checklist::copy(&st, actor, &id, &copy.id).await?;
+        return Ok(Json(todos.get(&copy.id).await?));
     }
-    if let Some(v) = body.category_id {
-        t.category_id = Some(v);
+    Ok(Json(copy))
+}
+
+async fn update_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<TodoUpdate>,
+) -> ApiResult<Json<Todo>> {
+    let before = match body.note {
+        Some(_) => st.todos.get(&id).await?.note,
+        None => None,
+    };
+    let todo = st.todos.as_actor(actor.clone()).update(&id, body).await?;
+    if before != todo.note {
+        notifications::note_mentions(&st, &actor, &todo, before.as_deref()).await;
     }
-    if let Some(v) = body.sort_order {
-        t.sort_order = v;
+    Ok(Json(todo))
+}
+
+/// Media type of PATCH /api/todos/{id} bodies.
+const JSON_PATCH: &str = "application/json-patch+json";
+
+/// JSON Patch (RFC 6902) of one todo: `application/json-patch+json` with
+/// add/remove/replace/test operations, applied atomically.
+async fn patch_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Response> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    if !mime.eq_ignore_ascii_case(JSON_PATCH) {
+        let mut response = ApiError::UnsupportedMediaType(format!(
+            "PATCH expects {JSON_PATCH}; use PUT for a partial JSON object"
+        ))
+        .into_response();
+        response
+            .headers_mut()
+            .insert("accept-patch", HeaderValue::from_static(JSON_PATCH));
+        return Ok(response);
     }
-    if let Some(v) = body.deleted {
-        t.deleted = v;
+    let patch: json_patch::Patch = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("invalid JSON Patch: {e}")))?;
+    let before = st.todos.get(&id).await?.note;
+    let todo = st
+        .todos
+        .as_actor(actor.clone())
+        .patch(&id, &patch.0)
+        .await?;
+    if before != todo.note {
+        notifications::note_mentions(&st, &actor, &todo, before.as_deref()).await;
     }
-    t.updated_at = Utc::now();
-
-    sqlx::query(
-        r#"
-        UPDATE todos SET
-        title=?2, note=?3, status=?4, priority=?5, due_at=?6, tags=?7,
-        category_id=?8, sort_order=?9, updated_at=?10, deleted=?11
-        WHERE id=?1
-    "#,
-    )
-    .bind(&t.id)
-    .bind(&t.title)
-    .bind(&t.note)
-    .bind(&t.status)
-    .bind(t.priority)
-    .bind(t.due_at)
-    .bind(&t.tags)
-    .bind(&t.category_id)
-    .bind(t.sort_order)
-    .bind(t.updated_at)
-    .bind(t.deleted)
-    .execute(&st.pool)
-    .await?;
-
-    let event = json!({"type":"todo.updated","data": &t});
-    let _ = st.hub.tx.send(event.to_string());
-    Ok(Json(t))
+    Ok(Json(todo).into_response())
 }
 
 async fn update_status(
     State(st): State<AppState>,
+    actor: Actor,
     Path(id): Path<String>,
     Query(mut q): Query<std::collections::HashMap<String, String>>,
 ) -> ApiResult<Json<Todo>> {
     let status = q
         .remove("status")
         .ok_or_else(|| ApiError::BadRequest("missing status".into()))?;
-    let mut t: Todo = sqlx::query_as("SELECT * FROM todos WHERE id=?1")
-        .bind(&id)
-        .fetch_optional(&st.pool)
-        .await?
-        .ok_or(ApiError::NotFound)?;
-    t.status = status;
-    t.updated_at = Utc::now();
-
-    sqlx::query("UPDATE todos SET status=?2, updated_at=?3 WHERE id=?1")
-        .bind(&t.id)
-        .bind(&t.status)
-        .bind(t.updated_at)
-        .execute(&st.pool)
-        .await?;
+    Ok(Json(
+        st.todos.as_actor(actor).set_status(&id, status).await?,
+    ))
+}
+
+/// Push the due date back ("remind me tomorrow"): a duration or `until`.
+async fn snooze_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<TodoSnooze>,
+) -> ApiResult<Json<Todo>> {
+    let todos = st.todos.as_actor(actor);
+    let duration = [
+        body.minutes,
+        body.hours.map(|h| h.saturating_mul(60)),
+        body.days.map(|d| d.saturating_mul(24 * 60)),
+    ];
+    let until = match (body.until, duration.iter().any(Option::is_some)) {
+        (Some(until), false) => until,
+        (None, true) => {
+            let minutes = duration
+                .into_iter()
+                .flatten()
+                .fold(0i64, i64::saturating_add);
+            if minutes <= 0 {
+                return Err(ApiError::BadRequest(
+                    "snooze duration must be positive".into(),
+                ));
+            }
+            let now = Utc::now();
+            let from = todos
+                .get(&id)
+                .await?
+                .due_at
+                .filter(|d| *d > now)
+                .unwrap_or(now);
+            chrono::Duration::try_minutes(minutes)
+                .and_then(|d| from.checked_add_signed(d))
+                .ok_or_else(|| ApiError::BadRequest("snooze duration too long".into()))?
+        }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "give either a duration (minutes/hours/days) or `until`".into(),
+            ));
+        }
+    };
+    Ok(Json(todos.snooze(&id, until).await?))
+}
+
+#[derive(Deserialize)]
+struct PinParams {
+    pinned: Option<bool>, // New state; toggles when omitted
+}
+
+/// Pin or unpin a todo; pinned todos are listed first.
+async fn pin_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    Query(p): Query<PinParams>,
+) -> ApiResult<Json<Todo>> {
+    let todos = st.todos.as_actor(actor);
+    let pinned = match p.pinned {
+        Some(v) => v,
+        None => !todos.get(&id).await?.pinned,
+    };
+    let body = TodoUpdate {
+        pinned: Some(pinned),
+        ..Default::default()
+    };
+    Ok(Json(todos.update(&id, body).await?))
+}
+
+/// Drop a todo before or after a sibling; writes only the moved todo.
+async fn place_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<TodoPlace>,
+) -> ApiResult<Json<Todo>> {
+    Ok(Json(st.todos.as_actor(actor).place(&id, body).await?))
+}
 
-    let event = json!({"type":"todo.updated","data": &t});
-    let _ = st.hub.tx.send(event.to_string());
-    Ok(Json(t))
+/// Drop a todo into another kanban column at a position, as one change.
+async fn move_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<TodoMove>,
+) -> ApiResult<Json<Todo>> {
+    Ok(Json(st.todos.as_actor(actor).move_to(&id, body).await?))
 }
 
 async fn delete_todo(
     State(st): State<AppState>,
+    actor: Actor,
     Path(id): Path<String>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM todos WHERE id=?1")
-        .bind(&id)
-        .fetch_optional(&st.pool)
-        .await?;
-    if exists.is_none() {
-        return Err(ApiError::NotFound);
-    }
+    st.todos.as_actor(actor).delete(&id).await?;
+    Ok(Json(json!({"ok": true})))
+}
 
-    sqlx::query("UPDATE todos SET deleted=1, updated_at=CURRENT_TIMESTAMP WHERE id=?1")
-        .bind(&id)
-        .execute(&st.pool)
-        .await?;
+#[derive(Deserialize)]
+struct ReorderParams {
+    category_id: Option<String>, // Column being reordered: a category id or "uncategorized" ...
+    status: Option<String>,      // ... and/or a status
+}
 
-    let event = json!({"type":"todo.deleted","data": {"id": id}});
-    let _ = st.hub.tx.send(event.to_string());
+/// Bulk reorder; with a scope, todos outside that column are a bad request.
+async fn reorder(
+    State(st): State<AppState>,
+    actor: Actor,
+    Query(p): Query<ReorderParams>,
+    JsonBody(items): JsonBody<Vec<ReorderItem>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let scope = ReorderScope {
+        category_id: p.category_id.map(|c| (c != "uncategorized").then_some(c)),
+        status: p.status,
+    };
+    st.todos.as_actor(actor).reorder(&items, &scope).await?;
     Ok(Json(json!({"ok": true})))
 }
 
-async fn reorder(
+#[derive(Deserialize)]
+struct ArchiveParams {
+    older_than_days: Option<i64>, // Only todos completed at least this long ago
+}
+
+/// Archive finished todos now (the auto-archive job does this daily).
+async fn archive_done(
     State(st): State<AppState>,
-    Json(items): Json<Vec<ReorderItem>>,
+    actor: Actor,
+    Query(p): Query<ArchiveParams>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    let mut tx = st.pool.begin().await?;
-    for it in items.iter() {
-        sqlx::query("UPDATE todos SET sort_order=?2, updated_at=CURRENT_TIMESTAMP WHERE id=?1")
-            .bind(&it.id)
-            .bind(it.sort_order)
-            .execute(&mut *tx)
-            .await?;
+    if p.older_than_days.is_some_and(|d| d < 0) {
+        return Err(ApiError::BadRequest(
+            "older_than_days must not be negative".into(),
+        ));
     }
-    tx.commit().await?;
-
-    let event = json!({"type":"todos.reordered","data": items});
-    let _ = st.hub.tx.send(event.to_string());
-    Ok(Json(json!({"ok": true})))
+    let before = p
+        .older_than_days
+        .map(|days| Utc::now() - chrono::Duration::days(days));
+    let archived = st.todos.as_actor(actor).archive_done(before).await?;
+    let ids: Vec<&str> = archived.iter().map(|t| t.id.as_str()).collect();
+    Ok(Json(json!({"archived": ids.len(), "ids": ids})))
 }
 
 // Category endpoints
 
-async fn list_categories(State(st): State<AppState>) -> ApiResult<Json<Vec<Category>>> {
-    let rows = sqlx::query_as::<_, Category>(
-        "SELECT * FROM categories WHERE deleted = 0 ORDER BY sort_order ASC, name ASC",
-    )
-    .fetch_all(&st.pool)
-    .await?;
-    Ok(Json(rows))
+#[derive(Deserialize)]
+struct CategoryListParams {
+    project_id: Option<String>,     // Only categories in this project
+    include_archived: Option<bool>, // Also list archived categories
+}
+
+impl CategoryListParams {
+    fn retain(&self, categories: &mut Vec<Category>) {
+        if let Some(project) = &self.project_id {
+            categories.retain(|c| c.project_id.as_ref() == Some(project));
+        }
+        if !self.include_archived.unwrap_or(false) {
+            categories.retain(|c| !c.archived);
+        }
+    }
+}
+
+async fn list_categories(
+    State(st): State<AppState>,
+    Query(p): Query<CategoryListParams>,
+    Query(page): Query<PageParams>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let mut page = Page::new(page, uri)?;
+    let mut categories = st.categories.list().await?;
+    p.retain(&mut categories);
+    page.cut(&mut categories);
+    let tag = etag::collection(categories.iter().map(|c| &c.updated_at));
+    let tag = page.etag(tag, categories.iter().map(|c| c.id.as_str()));
+    let mut response = etag::respond(&headers, tag, categories);
+    page.apply(&mut response);
+    Ok(response)
+}
+
+#[derive(Serialize)]
+struct CategoryWithCounts {
+    #[serde(flatten)]
+    category: Category,
+    counts: CategoryTodoCounts, // Todo badges for the sidebar
+}
+
+/// Categories (as in the list) with their todo counters, from one grouped query.
+async fn categories_with_counts(
+    State(st): State<AppState>,
+    Query(p): Query<CategoryListParams>,
+) -> ApiResult<Json<Vec<CategoryWithCounts>>> {
+    let mut categories = st.categories.list().await?;
+    p.retain(&mut categories);
+    let mut counts = st.todos.counts_by_category().await?;
+    Ok(Json(
+        categories
+            .into_iter()
+            .map(|category| {
+                let counts = counts
+                    .iter()
+                    .position(|c| c.category_id.as_ref() == Some(&category.id))
+                    .map(|i| counts.swap_remove(i))
+                    .unwrap_or_default();
+                CategoryWithCounts { category, counts }
+            })
+            .collect(),
+    ))
+}
+
+/// Palette colors not used by any category yet, for the color picker.
+async fn category_palette(State(st): State<AppState>) -> ApiResult<Json<Vec<&'static str>>> {
+    Ok(Json(st.categories.unused_colors().await?))
+}
+
+/// Categories nested under their parents; orphans of deleted parents are top level.
+async fn category_tree(
+    State(st): State<AppState>,
+    Query(p): Query<CategoryListParams>,
+) -> ApiResult<Json<Vec<CategoryNode>>> {
+    let mut categories = st.categories.list().await?;
+    p.retain(&mut categories);
+    fn children(all: &[Category], parent: Option<&str>, depth: usize) -> Vec<CategoryNode> {
+        let is_root = |c: &Category| {
+            c.parent_id
+                .as_deref()
+                .is_none_or(|p| !all.iter().any(|x| x.id == p))
+        };
+        all.iter()
+            .filter(|c| match parent {
+                None => is_root(c),
+                Some(p) => c.parent_id.as_deref() == Some(p),
+            })
+            .map(|c| CategoryNode {
+                category: c.clone(),
+                children: if depth < all.len() {
+                    children(all, Some(&c.id), depth + 1)
+                } else {
+                    Vec::new()
+                },
+            })
+            .collect()
+    }
+    Ok(Json(children(&categories, None, 0)))
 }
 
 async fn create_category(
     State(st): State<AppState>,
-    Json(body): Json<CategoryCreate>,
+    actor: Actor,
+    JsonBody(body): JsonBody<CategoryCreate>,
 ) -> ApiResult<Json<Category>> {
-    let category = Category::new_from_create(body);
-    sqlx::query(
-        r#"
-        INSERT INTO categories (id,name,color,description,sort_order,created_at,updated_at,deleted)
-        VALUES (?1,?2,?3,?4,?5,?6,?7,?8)
-    "#,
-    )
-    .bind(&category.id)
-    .bind(&category.name)
-    .bind(&category.color)
-    .bind(&category.description)
-    .bind(category.sort_order)
-    .bind(category.created_at)
-    .bind(category.updated_at)
-    .bind(category.deleted)
-    .execute(&st.pool)
-    .await?;
-
-    let event = json!({"type":"category.created","data": &category});
-    let _ = st.hub.tx.send(event.to_string());
-    Ok(Json(category))
+    Ok(Json(st.categories.as_actor(actor).create(body).await?))
 }
 
 async fn get_category(
     State(st): State<AppState>,
     Path(id): Path<String>,
-) -> ApiResult<Json<Category>> {
-    let row = sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id=?1")
-        .bind(&id)
-        .fetch_optional(&st.pool)
-        .await?;
-    match row {
-        Some(c) => Ok(Json(c)),
-        None => Err(ApiError::NotFound),
-    }
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    // Categories have no version counter; updated_at alone identifies a revision
+    let category = st.categories.get(&id).await?;
+    let tag = etag::item(0, category.updated_at);
+    Ok(etag::respond(&headers, tag, category))
 }
 
 async fn update_category(
     State(st): State<AppState>,
+    actor: Actor,
     Path(id): Path<String>,
-    Json(body): Json<CategoryUpdate>,
+    JsonBody(body): JsonBody<CategoryUpdate>,
 ) -> ApiResult<Json<Category>> {
-    let mut c: Category = sqlx::query_as("SELECT * FROM categories WHERE id=?1")
-        .bind(&id)
-        .fetch_optional(&st.pool)
-        .await?
-        .ok_or(ApiError::NotFound)?;
+    Ok(Json(st.categories.as_actor(actor).update(&id, body).await?))
+}
 
-    if let Some(v) = body.name {
-        c.name = v;
-    }
-    if let Some(v) = body.color {
-        c.color = Some(v);
-    }
-    if let Some(v) = body.description {
-        c.description = Some(v);
-    }
-    if let Some(v) = body.sort_order {
-        c.sort_order = v;
-    }
-    if let Some(v) = body.deleted {
-        c.deleted = v;
-    }
-    c.updated_at = Utc::now();
-
-    sqlx::query(
-        r#"
-        UPDATE categories SET
-        name=?2, color=?3, description=?4, sort_order=?5, updated_at=?6, deleted=?7
-        WHERE id=?1
-    "#,
-    )
-    .bind(&c.id)
-    .bind(&c.name)
-    .bind(&c.color)
-    .bind(&c.description)
-    .bind(c.sort_order)
-    .bind(c.updated_at)
-    .bind(c.deleted)
-    .execute(&st.pool)
-    .await?;
-
-    let event = json!({"type":"category.updated","data": &c});
-    let _ = st.hub.tx.send(event.to_string());
-    Ok(Json(c))
+/// Make a category the default for new todos that are created without one.
+async fn set_default_category(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Category>> {
+    Ok(Json(
+        st.categories.as_actor(actor).set_default(&id, true).await?,
+    ))
 }
 
-async fn delete_category(
+/// Stop using a category as the default; new todos stay uncategorized.
+async fn unset_default_category(
     State(st): State<AppState>,
+    actor: Actor,
     Path(id): Path<String>,
-) -> ApiResult<Json<serde_json::Value>> {
-    let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM categories WHERE id=?1")
-        .bind(&id)
-        .fetch_optional(&st.pool)
-        .await?;
-    if exists.is_none() {
-        return Err(ApiError::NotFound);
-    }
+) -> ApiResult<Json<Category>> {
+    Ok(Json(
+        st.categories
+            .as_actor(actor)
+            .set_default(&id, false)
+            .await?,
+    ))
+}
 
-    // Check if there are todos using this category
-    let todo_count: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM todos WHERE category_id=?1 AND deleted=0")
-            .bind(&id)
-            .fetch_one(&st.pool)
-            .await?;
+/// Hide a category from the list without touching its todos.
+async fn archive_category(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Category>> {
+    Ok(Json(
+        st.categories
+            .as_actor(actor)
+            .set_archived(&id, true)
+            .await?,
+    ))
+}
 
-    if todo_count > 0 {
-        return Err(ApiError::BadRequest(
-            "Cannot delete category that has todos assigned to it".into(),
-        ));
-    }
+async fn unarchive_category(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Category>> {
+    Ok(Json(
+        st.categories
+            .as_actor(actor)
+            .set_archived(&id, false)
+            .await?,
+    ))
+}
 
-    sqlx::query("UPDATE categories SET deleted=1, updated_at=CURRENT_TIMESTAMP WHERE id=?1")
-        .bind(&id)
-        .execute(&st.pool)
-        .await?;
+#[derive(Deserialize)]
+struct CategoryDeleteParams {
+    reassign_to: Option<String>, // Category id for its todos, or "uncategorized"
+}
 
-    let event = json!({"type":"category.deleted","data": {"id": id}});
-    let _ = st.hub.tx.send(event.to_string());
+async fn delete_category(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    Query(p): Query<CategoryDeleteParams>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let categories = st.categories.as_actor(actor);
+    match p.reassign_to.as_deref() {
+        None => categories.delete(&id).await?,
+        Some("uncategorized") => categories.delete_reassigning(&id, None).await?,
+        Some(target) => categories.delete_reassigning(&id, Some(target)).await?,
+    }
     Ok(Json(json!({"ok": true})))
 }
@@ -0,0 +1,428 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use serde_json::json;
+
+use super::TodoFilter;
+use crate::{
+    audit::{Actor, AuditLog},
+    error::{ApiError, ApiResult},
+    model::{Category, CategoryCreate, CategoryUpdate},
+    outbox::{Outbox, event},
+    projects::Projects,
+    repository::{CategoryRepository, TodoRepository},
+};
+
+/// Curated category colors (Tailwind 500 shades), offered by the color picker.
+pub const PALETTE: [&str; 16] = [
+    "#6B7280", "#EF4444", "#F97316", "#F59E0B", "#EAB308", "#84CC16", "#22C55E", "#10B981",
+    "#14B8A6", "#06B6D4", "#0EA5E9", "#3B82F6", "#6366F1", "#8B5CF6", "#D946EF", "#EC4899",
+];
+
+/// Deepest allowed nesting (a top-level category is level 1).
+pub const MAX_CATEGORY_DEPTH: usize = 4;
+
+/**
+ * Category business logic: persistence, delete guard, change events and audit
+ */
+#[derive(Clone)]
+pub struct CategoryService {
+    repo: Arc<dyn CategoryRepository>,
+    todos: Arc<dyn TodoRepository>, // For the "still in use" delete guard
+    outbox: Arc<Outbox>,            // Publishes the events stored with each write
+    audit: Option<AuditLog>,        // Change log, when enabled
+    projects: Option<Projects>,     // Project ids to validate against, when enabled
+    actor: Actor,                   // Recorded as the author of changes
+}
+
+impl CategoryService {
+    pub fn new(
+        repo: Arc<dyn CategoryRepository>,
+        todos: Arc<dyn TodoRepository>,
+        outbox: Arc<Outbox>,
+    ) -> Self {
+        Self {
+            repo,
+            todos,
+            outbox,
+            audit: None,
+            projects: None,
+            actor: Actor::system(),
+        }
+    }
+
+    /// Record every change in the audit log.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Reject categories assigned to projects that do not exist.
+    pub fn with_projects(mut self, projects: Projects) -> Self {
+        self.projects = Some(projects);
+        self
+    }
+
+    /// A copy of the service whose changes are attributed to `actor`.
+    pub fn as_actor(&self, actor: Actor) -> Self {
+        Self {
+            actor,
+            ..self.clone()
+        }
+    }
+
+    async fn record(
+        &self,
+        action: &str,
+        id: &str,
+        before: Option<&Category>,
+        after: Option<&Category>,
+    ) {
+        if let Some(audit) = &self.audit {
+            audit
+                .record(&self.actor, "category", id, action, before, after)
+                .await;
+        }
+    }
+
+    /// Bad request unless `project_id` exists (always fine without a projects table).
+    async fn check_project(&self, project_id: Option<&str>) -> ApiResult<()> {
+        match (&self.projects, project_id) {
+            (Some(projects), Some(id)) => projects.check(id).await,
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn list(&self) -> ApiResult<Vec<Category>> {
+        self.repo.list().await
+    }
+
+    pub async fn get(&self, id: &str) -> ApiResult<Category> {
+        self.repo.get(id).await?.ok_or(ApiError::NotFound)
+    }
+
+    /// Bad request unless `id` (None = a new category) may move under `parent`:
+    /// the parent must exist, must not be `id` or one of its descendants, and
+    /// the subtree must stay within MAX_CATEGORY_DEPTH levels.
+    async fn check_parent(&self, id: Option<&str>, parent: &str) -> ApiResult<()> {
+        let all = self.repo.list().await?;
+        let parent_of = |c: &str| {
+            all.iter()
+                .find(|x| x.id == c)
+                .and_then(|x| x.parent_id.as_deref())
+        };
+        if !all.iter().any(|c| c.id == parent) {
+            return Err(ApiError::BadRequest(format!(
+                "unknown parent category `{parent}`"
+            )));
+        }
+        // Walk up from the parent; bounded so stored cycles cannot loop forever
+        let mut depth = 0; // Level of `parent`
+        let mut cursor = Some(parent);
+        while let Some(c) = cursor.filter(|_| depth <= all.len()) {
+            if Some(c) == id {
+                return Err(ApiError::BadRequest(
+                    "a category cannot be moved under itself or its subcategories".into(),
+                ));
+            }
+            depth += 1;
+            cursor = parent_of(c);
+        }
+        let height = match id {
+            Some(id) => subtree_height(&all, id),
+            None => 1,
+        };
+        if depth + height > MAX_CATEGORY_DEPTH {
+            return Err(ApiError::BadRequest(format!(
+                "categories can be nested at most {MAX_CATEGORY_DEPTH} levels deep"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Palette colors no active category uses yet, in palette order.
+    pub async fn unused_colors(&self) -> ApiResult<Vec<&'static str>> {
+        let used: Vec<String> = self
+            .repo
+            .list()
+            .await?
+            .into_iter()
+            .filter_map(|c| c.color.map(|color| color.to_ascii_uppercase()))
+            .collect();
+        Ok(PALETTE
+            .into_iter()
+            .filter(|color| !used.iter().any(|u| u == color))
+            .collect())
+    }
+
+    /// `id` and the ids of all its (non-deleted) subcategories.
+    pub async fn descendants(&self, id: &str) -> ApiResult<Vec<String>> {
+        let all = self.repo.list().await?;
+        let mut ids = vec![id.to_string()];
+        let mut i = 0;
+        while i < ids.len() {
+            for c in &all {
+                if c.parent_id.as_deref() == Some(ids[i].as_str()) && !ids.contains(&c.id) {
+                    ids.push(c.id.clone());
+                }
+            }
+            i += 1;
+        }
+        Ok(ids)
+    }
+
+    /// Persist a new category and broadcast `category.created`.
+    pub async fn create(&self, body: CategoryCreate) -> ApiResult<Category> {
+        self.check_project(body.project_id.as_deref()).await?;
+        validate_color(body.color.as_deref())?;
+        if let Some(parent) = &body.parent_id {
+            self.check_parent(None, parent).await?;
+        }
+        let category = Category::new_from_create(body);
+        self.repo
+            .insert(&category, &[event("category.created", &category)])
+            .await?;
+        self.outbox.notify();
+        self.record("created", &category.id, None, Some(&category))
+            .await;
+        Ok(category)
+    }
+
+    /// Apply a partial update and broadcast `category.updated`.
+    pub async fn update(&self, id: &str, body: CategoryUpdate) -> ApiResult<Category> {
+        let before = self.get(id).await?;
+        let mut c = before.clone();
+
+        if let Some(v) = body.name {
+            c.name = v;
+        }
+        if let Some(v) = body.color {
+            validate_color(Some(&v))?;
+            c.color = Some(v);
+        }
+        if let Some(v) = body.description {
+            c.description = Some(v);
+        }
+        if let Some(v) = body.project_id {
+            self.check_project(Some(&v)).await?;
+            c.project_id = Some(v);
+        }
+        if let Some(v) = body.parent_id {
+            if v.is_empty() {
+                c.parent_id = None;
+            } else {
+                self.check_parent(Some(id), &v).await?;
+                c.parent_id = Some(v);
+            }
+        }
+        if let Some(v) = body.sort_order {
+            c.sort_order = v;
+        }
+        if let Some(v) = body.deleted {
+            c.deleted = v;
+        }
+        c.updated_at = Utc::now();
+
+        self.repo
+            .update(&c, &[event("category.updated", &c)])
+            .await?;
+        self.outbox.notify();
+        self.record("updated", &c.id, Some(&before), Some(&c)).await;
+        Ok(c)
+    }
+
+    /// Make `id` the default category (or, with `on` false, stop it being one).
+    ///
+    /// There is at most one default: the previous one is unmarked first.
+    /// Every changed category broadcasts `category.updated`.
+    pub async fn set_default(&self, id: &str, on: bool) -> ApiResult<Category> {
+        let target = self.get(id).await?;
+        if target.deleted != 0 {
+            return Err(ApiError::NotFound);
+        }
+        if on {
+            for c in self.repo.list().await? {
+                if c.is_default && c.id != id {
+                    self.mark_default(c, false).await?;
+                }
+            }
+        }
+        if target.is_default == on {
+            return Ok(target);
+        }
+        self.mark_default(target, on).await
+    }
+
+    async fn mark_default(&self, before: Category, on: bool) -> ApiResult<Category> {
+        let mut c = before.clone();
+        c.is_default = on;
+        c.updated_at = Utc::now();
+        self.repo
+            .update(&c, &[event("category.updated", &c)])
+            .await?;
+        self.outbox.notify();
+        self.record("updated", &c.id, Some(&before), Some(&c)).await;
+        Ok(c)
+    }
+
+    /// Archive (or restore) a category and broadcast `category.updated`.
+    ///
+    /// Unlike delete this keeps the category and its todos; it only drops
+    /// out of the category list until it is unarchived.
+    pub async fn set_archived(&self, id: &str, archived: bool) -> ApiResult<Category> {
+        let before = self.get(id).await?;
+        if before.deleted != 0 {
+            return Err(ApiError::NotFound);
+        }
+        if before.archived == archived {
+            return Ok(before);
+        }
+        let mut c = before.clone();
+        c.archived = archived;
+        c.updated_at = Utc::now();
+        self.repo
+            .update(&c, &[event("category.updated", &c)])
+            .await?;
+        self.outbox.notify();
+        let action = if archived { "archived" } else { "unarchived" };
+        self.record(action, &c.id, Some(&before), Some(&c)).await;
+        Ok(c)
+    }
+
+    /// Soft delete, refusing while active todos still use the category.
+    pub async fn delete(&self, id: &str) -> ApiResult<()> {
+        let before = self.get(id).await?;
+
+        if self
+            .repo
+            .list()
+            .await?
+            .iter()
+            .any(|c| c.parent_id.as_deref() == Some(id))
+        {
+            return Err(ApiError::BadRequest(
+                "Cannot delete category that has subcategories; move or delete them first".into(),
+            ));
+        }
+
+        // Check if there are todos using this category
+        if self.todos.count_in_category(id).await? > 0 {
+            return Err(ApiError::BadRequest(
+                "Cannot delete category that has todos assigned to it \
+                 (use ?reassign_to=<category id> or ?reassign_to=uncategorized)"
+                    .into(),
+            ));
+        }
+
+        self.repo
+            .soft_delete(id, &[event("category.deleted", &json!({"id": id}))])
+            .await?;
+        self.outbox.notify();
+        let after = self.repo.get(id).await?;
+        self.record("deleted", id, Some(&before), after.as_ref())
+            .await;
+        Ok(())
+    }
+
+    /// Move the category's todos to `to` (None = uncategorized), then soft delete it.
+    ///
+    /// The todos move in one transaction; each broadcasts `todo.updated`,
+    /// followed by `category.deleted`.
+    pub async fn delete_reassigning(&self, id: &str, to: Option<&str>) -> ApiResult<()> {
+        self.get(id).await?;
+        if let Some(target) = to {
+            if target == id {
+                return Err(ApiError::BadRequest(
+                    "cannot reassign todos to the category being deleted".into(),
+                ));
+            }
+            match self.repo.get(target).await? {
+                Some(c) if c.deleted == 0 => {}
+                _ => {
+                    return Err(ApiError::BadRequest(format!("unknown category `{target}`")));
+                }
+            }
+        }
+
+        let mut before = Vec::new();
+        if self.audit.is_some() {
+            for t in self.todos.list(&TodoFilter::default()).await? {
+                if t.category_id.as_deref() == Some(id) {
+                    before.push(t);
+                }
+            }
+        }
+        let moved = self.todos.move_category(id, to).await?;
+        let mut after = Vec::with_capacity(moved.len());
+        for todo_id in &moved {
+            if let Some(t) = self.todos.get(todo_id).await? {
+                after.push(t);
+            }
+        }
+        let updated: Vec<String> = after.iter().map(|t| event("todo.updated", t)).collect();
+        self.outbox.enqueue(&updated).await?;
+        if let Some(audit) = &self.audit {
+            let rows = after
+                .iter()
+                .map(|new| {
+                    let old = before.iter().find(|old| old.id == new.id);
+                    (new.id.as_str(), old, Some(new))
+                })
+                .collect::<Vec<_>>();
+            audit
+                .record_batch(&self.actor, "todo", "recategorized", &rows)
+                .await;
+        }
+        self.delete(id).await
+    }
+
+    /// Write an earlier snapshot back, broadcasting like `restore` on todos.
+    pub async fn restore(&self, mut c: Category, action: &str) -> ApiResult<Category> {
+        let before = self.get(&c.id).await?;
+        c.created_at = before.created_at;
+        c.updated_at = Utc::now();
+
+        let change = match (before.deleted != 0, c.deleted != 0) {
+            (false, true) => event("category.deleted", &json!({"id": c.id})),
+            (true, false) => event("category.created", &c),
+            _ => event("category.updated", &c),
+        };
+        self.repo.update(&c, &[change]).await?;
+        self.outbox.notify();
+        self.record(action, &c.id, Some(&before), Some(&c)).await;
+        Ok(c)
+    }
+}
+
+/// Levels in the subtree rooted at `id` (1 = no subcategories).
+fn subtree_height(all: &[Category], id: &str) -> usize {
+    // Bounded by the category count so corrupt cycles cannot recurse forever
+    fn height(all: &[Category], id: &str, budget: usize) -> usize {
+        if budget == 0 {
+            return 1;
+        }
+        1 + all
+            .iter()
+            .filter(|c| c.parent_id.as_deref() == Some(id))
+            .map(|c| height(all, &c.id, budget - 1))
+            .max()
+            .unwrap_or(0)
+    }
+    height(all, id, all.len())
+}
+
+/// Bad request unless `color` is unset or a `#RRGGBB` hex value.
+fn validate_color(color: Option<&str>) -> ApiResult<()> {
+    match color {
+        Some(c)
+            if c.len() != 7
+                || !c.starts_with('#')
+                || !c[1..].chars().all(|ch| ch.is_ascii_hexdigit()) =>
+        {
+            Err(ApiError::BadRequest(format!(
+                "color `{c}` is not a #RRGGBB hex value"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,31 @@
+/**
+ * Service layer
+ *
+ * Business logic for todos and categories, independent of HTTP and of the
+ * storage backend (see repository). Handlers,
+ * the MQTT bridge, the Telegram bot and embedding binaries all go through
+ * these services, so validation and WebSocket broadcasts behave the same
+ * no matter where a change comes from.
+ *
+ * Todo and category changes reach WebSocket clients through the outbox:
+ * each write stores its events in the same transaction (see crate::outbox).
+ *
+ * Services are cheap to clone (Arcs to the repository and the outbox).
+ */
+mod categories;
+mod todos;
+mod workflow;
+
+pub use categories::CategoryService;
+pub(crate) use todos::client_id;
+pub use todos::{Dedupe, ReorderScope, TodoFilter, TodoService};
+pub use workflow::Workflow;
+
+use serde::Serialize;
+
+use crate::{outbox::event, ws::WsHub};
+
+/// Broadcast a `{"type": ..., "data": ...}` event to WebSocket clients.
+pub(crate) fn emit<T: Serialize + ?Sized>(hub: &WsHub, event_type: &str, data: &T) {
+    let _ = hub.tx.send(event(event_type, data));
+}
@@ -0,0 +1,1190 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use json_patch::{PatchErrorKind, PatchOperation};
+use serde_json::json;
+use uuid::Uuid;
+
+use super::Workflow;
+use crate::{
+    audit::{Actor, AuditLog},
+    crypto,
+    error::{ApiError, ApiResult},
+    filter::{Field, FilterExpr},
+    links::TodoLinks,
+    members::Members,
+    model::{ReorderItem, Todo, TodoCreate, TodoMove, TodoPlace, TodoUpdate},
+    notifications::Notifications,
+    outbox::{Outbox, event},
+    projects::Projects,
+    repository::{CategoryRepository, CategoryTodoCounts, TodoCounts, TodoRepository, is_open},
+    statuses::{DEFAULT_DONE, Statuses},
+};
+
+/// Status finished todos are filed away under by archive_done.
+const ARCHIVED: &str = "archived";
+
+/// Tries before a partial update racing other writes gives up.
+const UPDATE_ATTEMPTS: usize = 3;
+
+/// Fields a JSON Patch may change (those of TodoUpdate).
+const PATCHABLE: &[&str] = &[
+    "title",
+    "note",
+    "status",
+    "priority",
+    "pinned",
+    "due_at",
+    "start_at",
+    "estimate_minutes",
+    "tags",
+    "category_id",
+    "project_id",
+    "assignee_id",
+    "sort_order",
+    "deleted",
+    "latitude",
+    "longitude",
+    "location_name",
+];
+
+/// Closest two ranks may get before all ranks are renumbered.
+const MIN_RANK_GAP: f64 = 1e-9;
+
+/**
+ * Filter for listing todos
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TodoFilter {
+    pub status: Option<String>,     // Only todos in this status
+    pub include_deleted: bool,      // Include soft-deleted todos
+    pub project_id: Option<String>, // Only todos in this project
+    pub expr: Option<FilterExpr>,   // Parsed ?filter= expression
+    pub limit: Option<usize>,       // At most this many, in board order; None = all
+    pub offset: usize,              // Matching todos to skip first
+}
+
+/// The filter expression when it searches notes while notes are encrypted:
+/// SQL cannot match those, so it is applied after decrypting.
+fn note_search(filter: &TodoFilter) -> Option<&FilterExpr> {
+    filter
+        .expr
+        .as_ref()
+        .filter(|e| crypto::enabled() && e.uses(Field::Note))
+}
+
+/**
+ * Column a reorder applies to; every reordered todo must belong to it
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ReorderScope {
+    pub category_id: Option<Option<String>>, // Only this category (Some(None) = uncategorized)
+    pub status: Option<String>,              // Only this status
+}
+
+/**
+ * What create_deduped does when an open todo with the same title exists
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Dedupe {
+    #[default]
+    Off, // Always insert
+    Return,   // Return the existing todo instead
+    Conflict, // 409 naming the existing todo
+}
+
+impl Dedupe {
+    /// "off"/"false", "return"/"true" or "conflict" (config and `?dedupe=`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" | "false" => Some(Self::Off),
+            "return" | "true" => Some(Self::Return),
+            "conflict" => Some(Self::Conflict),
+            _ => None,
+        }
+    }
+}
+
+impl ReorderScope {
+    /// Bad request unless `t` lies within the scope.
+    fn check(&self, t: &Todo) -> ApiResult<()> {
+        if let Some(category) = &self.category_id
+            && &t.category_id != category
+        {
+            return Err(ApiError::BadRequest(format!(
+                "todo `{}` is not in the category being reordered",
+                t.id
+            )));
+        }
+        if self.status.as_ref().is_some_and(|s| s != &t.status) {
+            return Err(ApiError::BadRequest(format!(
+                "todo `{}` is not in the status being reordered",
+                t.id
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Todo business logic: validation, persistence, change events and audit
+ */
+#[derive(Clone)]
+pub struct TodoService {
+    repo: Arc<dyn TodoRepository>,
+    outbox: Arc<Outbox>,        // Publishes the events stored with each write
+    audit: Option<AuditLog>,    // Change log, when enabled
+    links: Option<TodoLinks>,   // Dependency links, when enabled
+    workflow: Workflow,         // Allowed status transitions
+    statuses: Option<Statuses>, // Custom statuses, when enabled
+    projects: Option<Projects>, // Project ids to validate against, when enabled
+    members: Option<Members>,   // Category membership and assignees, when enabled
+    notifications: Option<Notifications>, // Tells new assignees, when enabled
+    actor: Actor,               // Recorded as the author of changes
+    // Source of the default category for new todos, when enabled
+    categories: Option<Arc<dyn CategoryRepository>>,
+}
+
+impl TodoService {
+    pub fn new(repo: Arc<dyn TodoRepository>, outbox: Arc<Outbox>) -> Self {
+        Self {
+            repo,
+            outbox,
+            audit: None,
+            links: None,
+            workflow: Workflow::default(),
+            statuses: None,
+            projects: None,
+            members: None,
+            notifications: None,
+            actor: Actor::system(),
+            categories: None,
+        }
+    }
+
+    /// Record every change in the audit log.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Track "blocks" links and announce unblocked todos.
+    pub fn with_links(mut self, links: TodoLinks) -> Self {
+        self.links = Some(links);
+        self
+    }
+
+    /// Validate statuses against, and take `is_done` from, the statuses table.
+    pub fn with_statuses(mut self, statuses: Statuses) -> Self {
+        self.statuses = Some(statuses);
+        self
+    }
+
+    /// Reject todos assigned to projects that do not exist.
+    pub fn with_projects(mut self, projects: Projects) -> Self {
+        self.projects = Some(projects);
+        self
+    }
+
+    /// Limit changes in member-only categories and check assignees.
+    pub fn with_members(mut self, members: Members) -> Self {
+        self.members = Some(members);
+        self
+    }
+
+    /// Notify members when a todo is assigned to them.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = Some(notifications);
+        self
+    }
+
+    /// File new todos without a category under the default category.
+    pub fn with_categories(mut self, categories: Arc<dyn CategoryRepository>) -> Self {
+        self.categories = Some(categories);
+        self
+    }
+
+    /// Enforce these status transitions instead of the default workflow.
+    pub fn with_workflow(mut self, workflow: Workflow) -> Self {
+        self.workflow = workflow;
+        self
+    }
+
+    /// A copy of the service whose changes are attributed to `actor`.
+    pub fn as_actor(&self, actor: Actor) -> Self {
+        Self {
+            actor,
+            ..self.clone()
+        }
+    }
+
+    async fn record(&self, action: &str, id: &str, before: Option<&Todo>, after: Option<&Todo>) {
+        if let Some(audit) = &self.audit {
+            audit
+                .record(&self.actor, "todo", id, action, before, after)
+                .await;
+        }
+    }
+
+    /// Names of the statuses that count as finished.
+    pub async fn done_statuses(&self) -> ApiResult<Vec<String>> {
+        match &self.statuses {
+            Some(statuses) => statuses.done().await,
+            None => Ok(DEFAULT_DONE.map(String::from).to_vec()),
+        }
+    }
+
+    /// Bad request unless `status` exists (always fine without a statuses table).
+    async fn check_status(&self, status: &str) -> ApiResult<()> {
+        match &self.statuses {
+            Some(statuses) => statuses.check(status).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Bad request unless `project_id` exists (always fine without a projects table).
+    async fn check_project(&self, project_id: Option<&str>) -> ApiResult<()> {
+        match (&self.projects, project_id) {
+            (Some(projects), Some(id)) => projects.check(id).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Forbidden unless the actor may change todos in `category_id` (see members.rs).
+    async fn check_member(&self, category_id: Option<&str>) -> ApiResult<()> {
+        match (&self.members, category_id) {
+            (Some(members), Some(id)) => members.check(&self.actor, id).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Bad request unless `assignee_id` is an existing user.
+    async fn check_assignee(&self, assignee_id: Option<&str>) -> ApiResult<()> {
+        match (&self.members, assignee_id) {
+            (Some(members), Some(id)) => members.check_user(id).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// `todo.assigned` when the assignee changed (`before` is None for new todos).
+    fn assignment(&self, before: Option<&Todo>, t: &Todo) -> Option<String> {
+        let from = before.and_then(|b| b.assignee_id.as_deref());
+        (from != t.assignee_id.as_deref()).then(|| {
+            let data = json!({
+                "todo_id": t.id,
+                "project_id": t.project_id,
+                "from": from,
+                "to": t.assignee_id,
+                "by": self.actor.as_str(),
+            });
+            event("todo.assigned", &data)
+        })
+    }
+
+    /// Tell the new assignee about a change of assignee; failures are logged.
+    async fn notify_assignee(&self, before: Option<&Todo>, t: &Todo) {
+        let from = before.and_then(|b| b.assignee_id.as_deref());
+        if let Some(notifications) = &self.notifications
+            && from != t.assignee_id.as_deref()
+            && let Err(e) = notifications.assigned(&self.actor, t).await
+        {
+            tracing::warn!(todo = %t.id, error = %e, "assignment notification failed");
+        }
+    }
+
+    pub async fn list(&self, filter: &TodoFilter) -> ApiResult<Vec<Todo>> {
+        if let Some(expr) = note_search(filter) {
+            let mut todos = self.list_decrypted(filter, expr).await?;
+            todos.drain(..filter.offset.min(todos.len()));
+            if let Some(limit) = filter.limit {
+                todos.truncate(limit);
+            }
+            return Ok(todos);
+        }
+        self.repo.list(filter).await
+    }
+
+    /// Number of todos `filter` matches, whatever its page.
+    pub async fn count_matching(&self, filter: &TodoFilter) -> ApiResult<usize> {
+        if let Some(expr) = note_search(filter) {
+            return Ok(self.list_decrypted(filter, expr).await?.len());
+        }
+        Ok(self.repo.count_matching(filter).await? as usize)
+    }
+
+    /// Every todo `filter` matches, `expr` applied after decrypting notes.
+    async fn list_decrypted(&self, filter: &TodoFilter, expr: &FilterExpr) -> ApiResult<Vec<Todo>> {
+        let unfiltered = TodoFilter {
+            expr: None,
+            limit: None,
+            offset: 0,
+            ..filter.clone()
+        };
+        let mut todos = self.repo.list(&unfiltered).await?;
+        todos.retain(|t| expr.matches(t));
+        Ok(todos)
+    }
+
+    pub async fn get(&self, id: &str) -> ApiResult<Todo> {
+        self.repo.get(id).await?.ok_or(ApiError::NotFound)
+    }
+
+    /// Open todos due before the given instant (overdue included).
+    pub async fn open_due_before(&self, before: DateTime<Utc>) -> ApiResult<Vec<Todo>> {
+        let done = self.done_statuses().await?;
+        self.repo.open_due_before(before, &done).await
+    }
+
+    /// Open todos that have a location, nearest-due first.
+    pub async fn open_with_location(&self) -> ApiResult<Vec<Todo>> {
+        let done = self.done_statuses().await?;
+        self.repo.open_with_location(&done).await
+    }
+
+    /// Board-wide counters (active/deleted/open/overdue as of now).
+    pub async fn counts(&self) -> ApiResult<TodoCounts> {
+        let done = self.done_statuses().await?;
+        self.repo.counts(Utc::now(), &done).await
+    }
+
+    /// Active/open/done/overdue counters per category id (None = uncategorized).
+    pub async fn counts_by_category(&self) -> ApiResult<Vec<CategoryTodoCounts>> {
+        let done = self.done_statuses().await?;
+        self.repo.counts_by_category(Utc::now(), &done).await
+    }
+
+    /// Id of the default category, if one is set and usable in `project_id`
+    /// (a category without a project is usable everywhere).
+    async fn default_category(&self, project_id: Option<&str>) -> ApiResult<Option<String>> {
+        let Some(categories) = &self.categories else {
+            return Ok(None);
+        };
+        Ok(categories
+            .list()
+            .await?
+            .into_iter()
+            .find(|c| c.is_default && !c.archived)
+            .filter(|c| c.project_id.is_none() || c.project_id.as_deref() == project_id)
+            .map(|c| c.id))
+    }
+
+    /// Build a todo from the create DTO, persist it and broadcast `todo.created`.
+    ///
+    /// A client-supplied id (offline-first clients) is kept; creating the
+    /// same id again returns the stored todo instead of a duplicate.
+    pub async fn create(&self, mut body: TodoCreate) -> ApiResult<Todo> {
+        if let Some(id) = client_id(&body)? {
+            if let Some(existing) = self.repo.get(&id).await? {
+                return Ok(existing);
+            }
+            body.id = Some(id);
+        }
+        self.check_project(body.project_id.as_deref()).await?;
+        if body.category_id.is_none() {
+            body.category_id = self.default_category(body.project_id.as_deref()).await?;
+        }
+        body.assignee_id = body.assignee_id.filter(|a| !a.is_empty());
+        let mut todo = Todo::new_from_create(body);
+        if let Some(statuses) = &self.statuses
+            && statuses.get(&todo.status).await?.is_none()
+            && let Some(initial) = statuses.initial().await?
+        {
+            todo.status = initial;
+        }
+        match self.insert(&todo).await {
+            // Lost a race against a concurrent sync of the same item
+            Err(ApiError::Sqlx(e)) if is_unique_violation(&e) => self.get(&todo.id).await,
+            result => result.map(|()| todo),
+        }
+    }
+
+    /// Create, unless an open todo in the same category already has the title
+    /// (ignoring case); `dedupe` decides between returning it and a conflict.
+    pub async fn create_deduped(&self, mut body: TodoCreate, dedupe: Dedupe) -> ApiResult<Todo> {
+        if dedupe == Dedupe::Off {
+            return self.create(body).await;
+        }
+        // Compare within the category the new todo would land in
+        if body.category_id.is_none() {
+            self.check_project(body.project_id.as_deref()).await?;
+            body.category_id = self.default_category(body.project_id.as_deref()).await?;
+        }
+        let done = self.done_statuses().await?;
+        let existing = self
+            .repo
+            .find_open_by_title(body.title.trim(), body.category_id.as_deref(), &done)
+            .await?;
+        match (existing, dedupe) {
+            (Some(todo), Dedupe::Conflict) => Err(ApiError::Conflict(format!(
+                "todo `{}` already has this title",
+                todo.id
+            ))),
+            (Some(todo), _) => Ok(todo),
+            (None, _) => self.create(body).await,
+        }
+    }
+
+    /// Copy a todo's content into a new open todo and broadcast `todo.created`.
+    ///
+    /// Title, note, priority, tags, category and location are copied; id,
+    /// status, due date, timestamps and checklist progress start fresh.
+    pub async fn duplicate(&self, id: &str) -> ApiResult<Todo> {
+        let original = self.get(id).await?;
+        self.create(TodoCreate {
+            id: None,
+            title: original.title,
+            note: original.note,
+            priority: Some(original.priority),
+            due_at: None,
+            start_at: None,
+            estimate_minutes: original.estimate_minutes,
+            tags: original.tags,
+            category_id: original.category_id,
+            project_id: original.project_id,
+            assignee_id: original.assignee_id,
+            latitude: original.latitude,
+            longitude: original.longitude,
+            location_name: original.location_name,
+        })
+        .await
+    }
+
+    /// Persist a fully built todo and broadcast `todo.created`.
+    pub async fn insert(&self, todo: &Todo) -> ApiResult<()> {
+        validate_location(todo.latitude, todo.longitude)?;
+        validate_estimate(todo.estimate_minutes)?;
+        self.check_member(todo.category_id.as_deref()).await?;
+        self.check_assignee(todo.assignee_id.as_deref()).await?;
+        let mut events = vec![event("todo.created", todo)];
+        events.extend(self.assignment(None, todo));
+        self.repo.insert(todo, &events).await?;
+        self.outbox.notify();
+        self.record("created", &todo.id, None, Some(todo)).await;
+        self.notify_assignee(None, todo).await;
+        Ok(())
+    }
+
+    /// Persist a todo without broadcasting; bulk callers announce progress themselves.
+    pub async fn insert_quiet(&self, todo: &Todo) -> ApiResult<()> {
+        validate_location(todo.latitude, todo.longitude)?;
+        validate_estimate(todo.estimate_minutes)?;
+        self.repo.insert(todo, &[]).await?;
+        self.record("created", &todo.id, None, Some(todo)).await;
+        Ok(())
+    }
+
+    /// Apply a partial update and broadcast `todo.updated`.
+    ///
+    /// The fields are applied to the latest stored version: when another
+    /// write lands between reading and writing the todo, it is read again
+    /// and the update reapplied (a few times, then a conflict).
+    pub async fn update(&self, id: &str, body: TodoUpdate) -> ApiResult<Todo> {
+        for _ in 1..UPDATE_ATTEMPTS {
+            if let Some(t) = self.try_update(id, body.clone()).await? {
+                return Ok(t);
+            }
+        }
+        self.try_update(id, body)
+            .await?
+            .ok_or_else(|| changed_meanwhile(id))
+    }
+
+    /// One read-modify-write round of update; None when it lost a race.
+    async fn try_update(&self, id: &str, body: TodoUpdate) -> ApiResult<Option<Todo>> {
+        let before = self.get(id).await?;
+        self.check_member(before.category_id.as_deref()).await?;
+        let mut t = before.clone();
+
+        if let Some(v) = body.title {
+            t.title = v;
+        }
+        if let Some(v) = body.note {
+            t.note = Some(v);
+        }
+        if let Some(v) = body.status {
+            self.check_status(&v).await?;
+            self.workflow.check(&before.status, &v)?;
+            t.status = v;
+        }
+        if let Some(v) = body.priority {
+            t.priority = v;
+        }
+        if let Some(v) = body.pinned {
+            t.pinned = v;
+        }
+        if let Some(v) = body.due_at {
+            t.due_at = Some(v);
+        }
+        if let Some(v) = body.start_at {
+            t.start_at = Some(v);
+        }
+        if let Some(v) = body.estimate_minutes {
+            t.estimate_minutes = Some(v);
+        }
+        if let Some(v) = body.tags {
+            t.tags = Some(v);
+        }
+        if let Some(v) = body.category_id {
+            t.category_id = Some(v);
+        }
+        if let Some(v) = body.project_id {
+            self.check_project(Some(&v)).await?;
+            t.project_id = Some(v);
+        }
+        if let Some(v) = body.assignee_id {
+            t.assignee_id = (!v.is_empty()).then_some(v);
+            self.check_assignee(t.assignee_id.as_deref()).await?;
+        }
+        if let Some(v) = body.sort_order {
+            t.sort_order = v;
+            t.rank = v as f64;
+        }
+        if let Some(v) = body.deleted {
+            t.deleted = v;
+        }
+        if let Some(v) = body.latitude {
+            t.latitude = Some(v);
+        }
+        if let Some(v) = body.longitude {
+            t.longitude = Some(v);
+        }
+        if let Some(v) = body.location_name {
+            t.location_name = Some(v);
+        }
+        self.finish_update(before, t).await
+    }
+
+    /// Apply a JSON Patch (RFC 6902) and broadcast `todo.updated`.
+    ///
+    /// Operations may change the fields of [`TodoUpdate`]; `remove` (or
+    /// `replace` with null) clears an optional one. The patch applies as a
+    /// whole or not at all: a failed `test` is a conflict, and nothing is
+    /// written. Races with other writes are retried as in [`Self::update`],
+    /// so a `test` is always checked against the version being replaced.
+    pub async fn patch(&self, id: &str, ops: &[PatchOperation]) -> ApiResult<Todo> {
+        check_patch(ops)?;
+        for _ in 1..UPDATE_ATTEMPTS {
+            if let Some(t) = self.try_patch(id, ops).await? {
+                return Ok(t);
+            }
+        }
+        self.try_patch(id, ops)
+            .await?
+            .ok_or_else(|| changed_meanwhile(id))
+    }
+
+    /// One read-modify-write round of patch; None when it lost a race.
+    async fn try_patch(&self, id: &str, ops: &[PatchOperation]) -> ApiResult<Option<Todo>> {
+        let before = self.get(id).await?;
+        self.check_member(before.category_id.as_deref()).await?;
+        let mut doc = serde_json::to_value(&before).map_err(anyhow::Error::from)?;
+        json_patch::patch(&mut doc, ops).map_err(|e| match e.kind {
+            PatchErrorKind::TestFailed => ApiError::Conflict(format!(
+                "test at `{}` (operation {}) failed",
+                e.path, e.operation
+            )),
+            _ => ApiError::BadRequest(format!("operation {}: {e}", e.operation)),
+        })?;
+        let mut t: Todo = serde_json::from_value(doc)
+            .map_err(|e| ApiError::BadRequest(format!("patched todo is invalid: {e}")))?;
+
+        if t.status != before.status {
+            self.check_status(&t.status).await?;
+            self.workflow.check(&before.status, &t.status)?;
+        }
+        if t.project_id != before.project_id {
+            self.check_project(t.project_id.as_deref()).await?;
+        }
+        if t.assignee_id != before.assignee_id {
+            t.assignee_id = t.assignee_id.filter(|v| !v.is_empty());
+            self.check_assignee(t.assignee_id.as_deref()).await?;
+        }
+        if t.sort_order != before.sort_order {
+            t.rank = t.sort_order as f64;
+        }
+        self.finish_update(before, t).await
+    }
+
+    /// Validate and write `t`, built from `before` by update or patch.
+    async fn finish_update(&self, before: Todo, mut t: Todo) -> ApiResult<Option<Todo>> {
+        validate_location(t.latitude, t.longitude)?;
+        validate_estimate(t.estimate_minutes)?;
+        t.updated_at = Utc::now();
+        t.version += 1;
+        track_completion(&before, &mut t, &self.done_statuses().await?);
+        if t.category_id != before.category_id {
+            self.check_member(t.category_id.as_deref()).await?;
+        }
+
+        let mut events = vec![event("todo.updated", &t)];
+        events.extend(self.assignment(Some(&before), &t));
+        if !self.repo.update(&t, &events).await? {
+            return Ok(None);
+        }
+        self.outbox.notify();
+        self.record("updated", &t.id, Some(&before), Some(&t)).await;
+        self.notify_assignee(Some(&before), &t).await;
+        self.announce_unblocked(&before, &t).await?;
+        Ok(Some(t))
+    }
+
+    /// Write `t` over the version it was built from; conflict when another
+    /// write got there first.
+    async fn save(&self, t: &Todo, events: &[String]) -> ApiResult<()> {
+        self.check_member(t.category_id.as_deref()).await?;
+        if !self.repo.update(t, events).await? {
+            return Err(changed_meanwhile(&t.id));
+        }
+        self.outbox.notify();
+        Ok(())
+    }
+
+    /// Change a todo's workflow status and broadcast `todo.updated`.
+    ///
+    /// Moves the workflow does not allow are a conflict. Like
+    /// [`Self::update`], the status is set on the latest stored version:
+    /// a write landing in between makes it read the todo again and retry.
+    pub async fn set_status(&self, id: &str, status: String) -> ApiResult<Todo> {
+        for _ in 1..UPDATE_ATTEMPTS {
+            if let Some(t) = self.try_set_status(id, &status).await? {
+                return Ok(t);
+            }
+        }
+        self.try_set_status(id, &status)
+            .await?
+            .ok_or_else(|| changed_meanwhile(id))
+    }
+
+    /// One read-modify-write round of set_status; None when it lost a race.
+    async fn try_set_status(&self, id: &str, status: &str) -> ApiResult<Option<Todo>> {
+        let before = self.get(id).await?;
+        self.check_member(before.category_id.as_deref()).await?;
+        self.check_status(status).await?;
+        self.workflow.check(&before.status, status)?;
+        let mut t = before.clone();
+        t.status = status.to_string();
+        t.updated_at = Utc::now();
+        t.version += 1;
+        track_completion(&before, &mut t, &self.done_statuses().await?);
+
+        if !self
+            .repo
+            .set_status(&t, &[event("todo.updated", &t)])
+            .await?
+        {
+            return Ok(None);
+        }
+        self.outbox.notify();
+        self.record("status", &t.id, Some(&before), Some(&t)).await;
+        self.announce_unblocked(&before, &t).await?;
+        Ok(Some(t))
+    }
+
+    /// Push the due date back to `until` and count the snooze.
+    ///
+    /// Reminders and escalation are keyed by due date, so they fire again for
+    /// the new one.
+    pub async fn snooze(&self, id: &str, until: DateTime<Utc>) -> ApiResult<Todo> {
+        let before = self.get(id).await?;
+        if until <= Utc::now() {
+            return Err(ApiError::BadRequest("snooze must end in the future".into()));
+        }
+        let mut t = before.clone();
+        t.due_at = Some(until);
+        t.snooze_count += 1;
+        t.updated_at = Utc::now();
+        t.version += 1;
+
+        self.save(&t, &[event("todo.updated", &t)]).await?;
+        self.record("snoozed", &t.id, Some(&before), Some(&t)).await;
+        Ok(t)
+    }
+
+    /// Move finished todos to `archived`, each broadcasting `todo.updated`.
+    ///
+    /// Only todos completed before `completed_before` when given (todos
+    /// finished before completion times were recorded count by their last
+    /// change). Returns the archived todos.
+    pub async fn archive_done(
+        &self,
+        completed_before: Option<DateTime<Utc>>,
+    ) -> ApiResult<Vec<Todo>> {
+        self.check_status(ARCHIVED).await?;
+        let done = self.done_statuses().await?;
+        let mut archived = Vec::new();
+        for t in self.list(&TodoFilter::default()).await? {
+            let finished = t.completed_at.unwrap_or(t.updated_at);
+            if t.status == ARCHIVED
+                || !done.contains(&t.status)
+                || completed_before.is_some_and(|before| finished >= before)
+            {
+                continue;
+            }
+            archived.push(self.set_status(&t.id, ARCHIVED.into()).await?);
+        }
+        Ok(archived)
+    }
+
+    /// Store checklist progress on the todo; broadcasts `todo.updated` when it changed.
+    pub async fn set_checklist_progress(&self, id: &str, done: i64, total: i64) -> ApiResult<Todo> {
+        let before = self.get(id).await?;
+        if (before.checklist_done, before.checklist_total) == (done, total) {
+            return Ok(before);
+        }
+        let mut t = before.clone();
+        t.checklist_done = done;
+        t.checklist_total = total;
+        t.updated_at = Utc::now();
+        t.version += 1;
+
+        self.save(&t, &[event("todo.updated", &t)]).await?;
+        self.record("checklist", &t.id, Some(&before), Some(&t))
+            .await;
+        Ok(t)
+    }
+
+    /// Store time tracking state on the todo; broadcasts `todo.updated` when it changed.
+    pub async fn set_time_tracking(
+        &self,
+        id: &str,
+        timer_started_at: Option<DateTime<Utc>>,
+        tracked_secs: i64,
+    ) -> ApiResult<Todo> {
+        let before = self.get(id).await?;
+        if (before.timer_started_at, before.tracked_secs) == (timer_started_at, tracked_secs) {
+            return Ok(before);
+        }
+        let mut t = before.clone();
+        t.timer_started_at = timer_started_at;
+        t.tracked_secs = tracked_secs;
+        t.updated_at = Utc::now();
+        t.version += 1;
+
+        self.save(&t, &[event("todo.updated", &t)]).await?;
+        self.record("timer", &t.id, Some(&before), Some(&t)).await;
+        Ok(t)
+    }
+
+    /// Soft delete and broadcast `todo.deleted`.
+    pub async fn delete(&self, id: &str) -> ApiResult<()> {
+        let before = self.repo.get(id).await?;
+        if let Some(before) = &before {
+            self.check_member(before.category_id.as_deref()).await?;
+        }
+        let deleted = [event("todo.deleted", &json!({"id": id}))];
+        if !self.repo.soft_delete(id, &deleted).await? {
+            return Err(ApiError::NotFound);
+        }
+        self.outbox.notify();
+        let after = self.repo.get(id).await?;
+        self.record("deleted", id, before.as_ref(), after.as_ref())
+            .await;
+        if let (Some(before), Some(after)) = (&before, &after) {
+            self.announce_unblocked(before, after).await?;
+        }
+        Ok(())
+    }
+
+    /// Apply new sort positions in one transaction and broadcast `todos.reordered`.
+    ///
+    /// Nothing is written unless every id is listed once, names an existing
+    /// todo that is not deleted, and lies within `scope`.
+    pub async fn reorder(&self, items: &[ReorderItem], scope: &ReorderScope) -> ApiResult<()> {
+        let mut seen = HashSet::with_capacity(items.len());
+        let mut before = Vec::with_capacity(items.len());
+        for it in items {
+            if !seen.insert(it.id.as_str()) {
+                return Err(ApiError::BadRequest(format!(
+                    "todo `{}` is listed more than once",
+                    it.id
+                )));
+            }
+            match self.repo.get(&it.id).await? {
+                Some(t) if t.deleted == 0 => {
+                    scope.check(&t)?;
+                    before.push(t);
+                }
+                _ => return Err(ApiError::BadRequest(format!("unknown todo `{}`", it.id))),
+            }
+        }
+        self.repo
+            .reorder(items, &[event("todos.reordered", items)])
+            .await?;
+        self.outbox.notify();
+        if let Some(audit) = &self.audit {
+            let mut changes = Vec::with_capacity(before.len());
+            for old in before {
+                let new = self.repo.get(&old.id).await?;
+                changes.push((old, new));
+            }
+            let rows = changes
+                .iter()
+                .map(|(old, new)| (old.id.as_str(), Some(old), new.as_ref()))
+                .collect::<Vec<_>>();
+            audit
+                .record_batch(&self.actor, "todo", "reordered", &rows)
+                .await;
+        }
+        Ok(())
+    }
+
+    /// Move a todo directly before or after a sibling and broadcast `todo.updated`.
+    ///
+    /// The todo gets a rank halfway between the sibling and its neighbour,
+    /// so a drag writes one row. Only when those ranks are too close (or
+    /// equal, as for todos never dragged) are all ranks renumbered first,
+    /// keeping the current order.
+    pub async fn place(&self, id: &str, place: TodoPlace) -> ApiResult<Todo> {
+        if place.before.is_none() && place.after.is_none() {
+            return Err(ApiError::BadRequest(
+                "give exactly one of `before` and `after`".into(),
+            ));
+        }
+        let body = TodoMove {
+            before: place.before,
+            after: place.after,
+            ..Default::default()
+        };
+        self.reposition(id, body, "reordered").await
+    }
+
+    /// Change category and/or status and position in one write; broadcasts
+    /// a single `todo.updated` (kanban drag between columns).
+    ///
+    /// A sibling must already be in the target column.
+    pub async fn move_to(&self, id: &str, body: TodoMove) -> ApiResult<Todo> {
+        self.reposition(id, body, "moved").await
+    }
+
+    async fn reposition(&self, id: &str, m: TodoMove, action: &str) -> ApiResult<Todo> {
+        let sibling = match (m.before, m.after) {
+            (None, None) => None,
+            (Some(sibling), None) => Some((sibling, false)),
+            (None, Some(sibling)) => Some((sibling, true)),
+            (Some(_), Some(_)) => {
+                return Err(ApiError::BadRequest(
+                    "give only one of `before` and `after`".into(),
+                ));
+            }
+        };
+        let before = self.get(id).await?;
+        self.check_member(before.category_id.as_deref()).await?;
+        let mut t = before.clone();
+        let status_set = m.status.is_some();
+        if let Some(v) = m.status {
+            self.check_status(&v).await?;
+            self.workflow.check(&before.status, &v)?;
+            t.status = v;
+        }
+        let category_set = m.category_id.is_some();
+        if let Some(v) = m.category_id {
+            t.category_id = (!v.is_empty()).then_some(v);
+        }
+        if let Some((sibling, after)) = sibling {
+            if sibling == id {
+                return Err(ApiError::BadRequest(
+                    "a todo cannot be placed next to itself".into(),
+                ));
+            }
+            let Some(s) = self.repo.get(&sibling).await?.filter(|s| s.deleted == 0) else {
+                return Err(ApiError::BadRequest(format!("unknown todo `{sibling}`")));
+            };
+            if (status_set && s.status != t.status)
+                || (category_set && s.category_id != t.category_id)
+            {
+                return Err(ApiError::BadRequest(format!(
+                    "todo `{sibling}` is not in the target column"
+                )));
+            }
+            t.rank = match self.rank_next_to(id, &sibling, after).await? {
+                Some(rank) => rank,
+                None => {
+                    self.renumber_ranks().await?;
+                    self.rank_next_to(id, &sibling, after)
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("no rank gap left after renumbering"))?
+                }
+            };
+        }
+        t.updated_at = Utc::now();
+        t.version += 1;
+        track_completion(&before, &mut t, &self.done_statuses().await?);
+
+        self.save(&t, &[event("todo.updated", &t)]).await?;
+        self.record(action, &t.id, Some(&before), Some(&t)).await;
+        self.announce_unblocked(&before, &t).await?;
+        Ok(t)
+    }
+
+    /// Rank between `sibling` and the todo after (or before) it in rank order,
+    /// not counting `id`; None when the two ranks are too close to split.
+    async fn rank_next_to(&self, id: &str, sibling: &str, after: bool) -> ApiResult<Option<f64>> {
+        let mut ranked = self.repo.list(&TodoFilter::default()).await?;
+        ranked.retain(|t| t.id != id);
+        ranked.sort_by(|a, b| a.rank.total_cmp(&b.rank)); // Stable: ties keep list order
+        let Some(pos) = ranked.iter().position(|t| t.id == sibling) else {
+            return Err(ApiError::BadRequest(format!("unknown todo `{sibling}`")));
+        };
+        let here = ranked[pos].rank;
+        let neighbour = if after {
+            ranked.get(pos + 1)
+        } else {
+            pos.checked_sub(1).map(|p| &ranked[p])
+        };
+        let rank = match neighbour {
+            Some(n) => (here + n.rank) / 2.0,
+            None if after => here + 1.0,
+            None => here - 1.0,
+        };
+        let roomy = |other: f64| (rank - other).abs() >= MIN_RANK_GAP;
+        Ok((roomy(here) && neighbour.is_none_or(|n| roomy(n.rank))).then_some(rank))
+    }
+
+    /// Give every todo a whole-number rank in its current rank order.
+    async fn renumber_ranks(&self) -> ApiResult<()> {
+        let mut todos = self.repo.list(&TodoFilter::default()).await?;
+        todos.sort_by(|a, b| a.rank.total_cmp(&b.rank));
+        let ranks: Vec<(String, f64)> = todos
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| (t.id, (i + 1) as f64))
+            .collect();
+        self.repo.set_ranks(&ranks).await
+    }
+
+    /**
+     * Write an earlier snapshot back as the todo's newest version
+     *
+     * Broadcasts what clients need to catch up: `todo.deleted` or
+     * `todo.created` when the restore flips the deleted flag, else
+     * `todo.updated`.
+     */
+    pub async fn restore(&self, mut t: Todo, action: &str) -> ApiResult<Todo> {
+        let before = self.get(&t.id).await?;
+        validate_location(t.latitude, t.longitude)?;
+        t.created_at = before.created_at;
+        t.updated_at = Utc::now();
+        t.version = before.version + 1;
+        // Progress follows the checklist items, which are not part of the snapshot
+        t.checklist_done = before.checklist_done;
+        t.checklist_total = before.checklist_total;
+
+        let change = match (before.deleted != 0, t.deleted != 0) {
+            (false, true) => event("todo.deleted", &json!({"id": t.id})),
+            (true, false) => event("todo.created", &t),
+            _ => event("todo.updated", &t),
+        };
+        self.save(&t, &[change]).await?;
+        self.record(action, &t.id, Some(&before), Some(&t)).await;
+        self.announce_unblocked(&before, &t).await?;
+        Ok(t)
+    }
+
+    fn links(&self) -> ApiResult<&TodoLinks> {
+        self.links
+            .as_ref()
+            .ok_or_else(|| ApiError::BadRequest("todo links are not enabled".into()))
+    }
+
+    /// Record that `blocker` blocks `blocked` and broadcast `todo.linked`.
+    pub async fn link(&self, blocker: &str, blocked: &str) -> ApiResult<()> {
+        self.get(blocker).await?;
+        self.get(blocked).await?;
+        self.links()?.add(blocker, blocked).await?;
+        self.outbox
+            .enqueue(&[event(
+                "todo.linked",
+                &json!({"blocker_id": blocker, "blocked_id": blocked}),
+            )])
+            .await?;
+        Ok(())
+    }
+
+    /// Remove the link between two todos and broadcast `todo.unlinked`.
+    pub async fn unlink(&self, a: &str, b: &str) -> ApiResult<()> {
+        if !self.links()?.remove(a, b).await? {
+            return Err(ApiError::NotFound);
+        }
+        self.outbox
+            .enqueue(&[event("todo.unlinked", &json!({"ids": [a, b]}))])
+            .await?;
+        Ok(())
+    }
+
+    /// (todos `id` blocks, todos blocking `id`); links to vanished todos are skipped.
+    pub async fn links_of(&self, id: &str) -> ApiResult<(Vec<Todo>, Vec<Todo>)> {
+        self.get(id).await?;
+        let links = self.links()?;
+        let mut blocks = Vec::new();
+        for other in links.dependents(id).await? {
+            blocks.extend(self.repo.get(&other).await?);
+        }
+        let mut blocked_by = Vec::new();
+        for other in links.blockers(id).await? {
+            blocked_by.extend(self.repo.get(&other).await?);
+        }
+        Ok((blocks, blocked_by))
+    }
+
+    /// Ids of todos with at least one open blocker.
+    pub async fn blocked_ids(&self) -> ApiResult<HashSet<String>> {
+        let mut blocked = HashSet::new();
+        let Some(links) = &self.links else {
+            return Ok(blocked);
+        };
+        let done = self.done_statuses().await?;
+        let mut open = HashMap::new();
+        for (blocker, dependent) in links.all().await? {
+            if blocked.contains(&dependent) {
+                continue;
+            }
+            let blocker_open = match open.get(&blocker) {
+                Some(&v) => v,
+                None => {
+                    let v = self
+                        .repo
+                        .get(&blocker)
+                        .await?
+                        .is_some_and(|t| is_open(&t, &done));
+                    open.insert(blocker, v);
+                    v
+                }
+            };
+            if blocker_open {
+                blocked.insert(dependent);
+            }
+        }
+        Ok(blocked)
+    }
+
+    /// Broadcast `todo.unblocked` for dependents whose last open blocker just closed.
+    async fn announce_unblocked(&self, before: &Todo, after: &Todo) -> ApiResult<()> {
+        let Some(links) = &self.links else {
+            return Ok(());
+        };
+        let done = self.done_statuses().await?;
+        if !is_open(before, &done) || is_open(after, &done) {
+            return Ok(());
+        }
+        for dependent in links.dependents(&after.id).await? {
+            let mut still_blocked = false;
+            for blocker in links.blockers(&dependent).await? {
+                if self
+                    .repo
+                    .get(&blocker)
+                    .await?
+                    .is_some_and(|t| is_open(&t, &done))
+                {
+                    still_blocked = true;
+                    break;
+                }
+            }
+            if still_blocked {
+                continue;
+            }
+            if let Some(todo) = self.repo.get(&dependent).await? {
+                self.outbox
+                    .enqueue(&[event(
+                        "todo.unblocked",
+                        &json!({"todo": todo, "by": after.id}),
+                    )])
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Maintain `completed_at` across a status change
+ *
+ * Set when the todo enters one of the `done` statuses, kept while it moves
+ * between them (e.g. done -> archived files finished work away), cleared
+ * when it is reopened.
+ */
+fn track_completion(before: &Todo, after: &mut Todo, done: &[String]) {
+    match (done.contains(&before.status), done.contains(&after.status)) {
+        (false, true) => after.completed_at = Some(after.updated_at),
+        (true, true) => {}
+        (_, false) => after.completed_at = None,
+    }
+}
+
+/// Validated, canonical (lower-case hyphenated) form of a client-supplied id.
+pub(crate) fn client_id(body: &TodoCreate) -> ApiResult<Option<String>> {
+    body.id
+        .as_deref()
+        .map(|id| {
+            Uuid::parse_str(id.trim())
+                .map(|u| u.hyphenated().to_string())
+                .map_err(|_| ApiError::BadRequest(format!("id `{id}` is not a valid UUID")))
+        })
+        .transpose()
+}
+
+/// Reject patches that touch anything but the fields of TodoUpdate.
+///
+/// `test` may look at any field; `move` and `copy` are not supported.
+fn check_patch(ops: &[PatchOperation]) -> ApiResult<()> {
+    for (i, op) in ops.iter().enumerate() {
+        let path = match op {
+            PatchOperation::Test(_) => continue,
+            PatchOperation::Add(_) | PatchOperation::Remove(_) | PatchOperation::Replace(_) => {
+                op.path()
+            }
+            PatchOperation::Move(_) | PatchOperation::Copy(_) => {
+                return Err(ApiError::BadRequest(format!(
+                    "operation {i}: only add, remove, replace and test are supported"
+                )));
+            }
+        };
+        let field = path.first().map(|t| t.decoded().into_owned());
+        if !field.is_some_and(|f| PATCHABLE.contains(&f.as_str())) {
+            return Err(ApiError::BadRequest(format!(
+                "operation {i}: `{path}` cannot be changed"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Lost optimistic-concurrency race (see TodoRepository::update).
+fn changed_meanwhile(id: &str) -> ApiError {
+    ApiError::Conflict(format!(
+        "todo `{id}` was changed by another request; reload and retry"
+    ))
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .is_some_and(|d| d.is_unique_violation())
+}
+
+/// Estimates are whole minutes, zero or more.
+fn validate_estimate(minutes: Option<i64>) -> ApiResult<()> {
+    match minutes {
+        Some(m) if m < 0 => Err(ApiError::BadRequest(
+            "estimate_minutes must not be negative".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Coordinates must come as a pair and lie within WGS84 bounds.
+fn validate_location(latitude: Option<f64>, longitude: Option<f64>) -> ApiResult<()> {
+    match (latitude, longitude) {
+        (None, None) => Ok(()),
+        (Some(lat), Some(lon))
+            if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) =>
+        {
+            Ok(())
+        }
+        (Some(_), Some(_)) => Err(ApiError::BadRequest("coordinates out of range".into())),
+        _ => Err(ApiError::BadRequest(
+            "latitude and longitude must be set together".into(),
+        )),
+    }
+}
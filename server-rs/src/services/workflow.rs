@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    config::ServerConfig,
+    error::{ApiError, ApiResult},
+};
+
+/**
+ * Allowed status transitions
+ *
+ * Maps a status to the statuses a todo may move to from there. Staying in
+ * the same status is always allowed and `"*"` allows any target. Statuses
+ * without an entry (e.g. custom ones added later) are unrestricted, both
+ * as source and as target.
+ */
+#[derive(Debug, Clone)]
+pub struct Workflow {
+    transitions: BTreeMap<String, Vec<String>>,
+}
+
+impl Workflow {
+    pub fn new(transitions: BTreeMap<String, Vec<String>>) -> Self {
+        Self { transitions }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(config.status_transitions.clone())
+    }
+
+    /// Statuses reachable from `from`, or None when unrestricted.
+    pub fn allowed(&self, from: &str) -> Option<&[String]> {
+        self.transitions
+            .get(from)
+            .filter(|to| !to.iter().any(|s| s == "*"))
+            .map(Vec::as_slice)
+    }
+
+    /// Conflict unless a todo in `from` may move to `to`.
+    pub fn check(&self, from: &str, to: &str) -> ApiResult<()> {
+        if !self.transitions.contains_key(to) {
+            return Ok(());
+        }
+        match self.allowed(from) {
+            Some(allowed) if from != to && !allowed.iter().any(|s| s == to) => {
+                Err(ApiError::Conflict(format!(
+                    "cannot move a todo from `{from}` to `{to}`; allowed: {}",
+                    if allowed.is_empty() {
+                        "none".to_string()
+                    } else {
+                        allowed.join(", ")
+                    }
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Self::from_config(&ServerConfig::default())
+    }
+}
@@ -0,0 +1,418 @@
+/**
+ * Device sessions with rotating refresh tokens
+ *
+ * The server trusts its LAN (auth "none"), so signing in only picks the
+ * household member; what a session adds is a credential per device that
+ * can be listed and revoked ("kick the old tablet off my account"):
+ *
+ * - POST   /api/sessions             {"user_id"} -> tokens (sign this device in)
+ * - POST   /api/sessions/refresh     {"refresh_token"} -> new tokens
+ * - GET    /api/sessions             the caller's active sessions, `current` marked
+ * - DELETE /api/sessions/{id}        revoke one (sign a device out)
+ * - DELETE /api/sessions             revoke all but the current one
+ *
+ * Requests with `Authorization: Bearer <access_token>` act as the
+ * session's member, whatever X-User-Id says; an unknown, expired or
+ * revoked token is answered with 401. Access tokens last 15 minutes and
+ * refresh tokens 30 days. Every refresh returns a new pair and retires the
+ * old refresh token; presenting a retired one again means it was copied,
+ * and revokes the session. With OIDC configured, devices sign in at the
+ * identity provider instead of POST /api/sessions (see oidc.rs).
+ *
+ * Only SHA-256 hashes of the tokens are stored. The device list shows the
+ * User-Agent and client address (X-Forwarded-For / X-Real-IP behind a
+ * proxy) of the latest request, updated at most once a minute.
+ */
+use std::{convert::Infallible, net::SocketAddr};
+
+use axum::{
+    Extension, Json, Router,
+    extract::{ConnectInfo, FromRequestParts, Path, Request, State},
+    http::{HeaderValue, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use chrono::{DateTime, Duration, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    audit::{Actor, USER_HEADER},
+    error::{ApiError, ApiResult, JsonBody},
+    model::{Session, SessionCreate, SessionRefresh},
+    routes::AppState,
+};
+
+/// How long an access token is accepted.
+const ACCESS_TTL: Duration = Duration::minutes(15);
+/// How long a session lasts without a refresh.
+const REFRESH_TTL: Duration = Duration::days(30);
+/// Requests closer together than this do not update `last_seen_at`.
+const SEEN_INTERVAL: Duration = Duration::minutes(1);
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/sessions",
+            get(list_sessions)
+                .post(create_session)
+                .delete(revoke_others),
+        )
+        .route("/api/sessions/refresh", post(refresh_session))
+        .route("/api/sessions/{id}", delete(revoke_session))
+}
+
+/**
+ * The device a request comes from
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Device {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>, // Forwarded client address, else the peer address
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Device {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let forwarded = header("x-forwarded-for")
+            .and_then(|v| v.split(',').next())
+            .or_else(|| header("x-real-ip"))
+            .map(|ip| ip.trim().to_string());
+        let peer = || {
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|c| c.0.ip().to_string())
+        };
+        Ok(Self {
+            user_agent: header("user-agent").map(String::from),
+            ip: forwarded.or_else(peer),
+        })
+    }
+}
+
+/// The session behind a request's bearer token (request extension).
+#[derive(Debug, Clone)]
+pub struct CurrentSession(pub String);
+
+/**
+ * Tokens handed out at sign-in and on every refresh
+ */
+#[derive(Debug, Serialize)]
+pub struct SessionTokens {
+    pub session_id: String,
+    pub user_id: String,
+    pub access_token: String,
+    pub access_expires_at: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_expires_at: DateTime<Utc>,
+}
+
+/**
+ * Session as listed to its member
+ */
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub session: Session,
+    pub current: bool, // The session making this request
+}
+
+/// A new random token: 32 bytes (256 bits) from the OS generator, hex.
+fn token() -> String {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    hex::encode(bytes)
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/**
+ * Sign `user_id` in on `device` and return the session's first tokens
+ *
+ * Also drops sessions that ended more than REFRESH_TTL ago.
+ */
+pub async fn sign_in(st: &AppState, user_id: &str, device: Device) -> ApiResult<SessionTokens> {
+    let known: Option<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE id=?1 AND deleted=0")
+            .bind(user_id)
+            .fetch_optional(&st.pool)
+            .await?;
+    if known.is_none() {
+        return Err(ApiError::BadRequest(format!("unknown user `{user_id}`")));
+    }
+
+    let now = Utc::now();
+    sqlx::query("DELETE FROM sessions WHERE refresh_expires_at < ?1 OR revoked_at < ?1")
+        .bind(now - REFRESH_TTL)
+        .execute(&st.pool)
+        .await?;
+
+    let (access, refresh) = (token(), token());
+    let tokens = SessionTokens {
+        session_id: Uuid::new_v4().to_string(),
+        user_id: user_id.to_string(),
+        access_expires_at: now + ACCESS_TTL,
+        refresh_expires_at: now + REFRESH_TTL,
+        access_token: access,
+        refresh_token: refresh,
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO sessions (id,user_id,access_hash,refresh_hash,prev_refresh_hash,user_agent,ip,
+                              created_at,last_seen_at,access_expires_at,refresh_expires_at,revoked_at)
+        VALUES (?1,?2,?3,?4,NULL,?5,?6,?7,?7,?8,?9,NULL)
+    "#,
+    )
+    .bind(&tokens.session_id)
+    .bind(user_id)
+    .bind(hash(&tokens.access_token))
+    .bind(hash(&tokens.refresh_token))
+    .bind(device.user_agent)
+    .bind(device.ip)
+    .bind(now)
+    .bind(tokens.access_expires_at)
+    .bind(tokens.refresh_expires_at)
+    .execute(&st.pool)
+    .await?;
+    Ok(tokens)
+}
+
+/**
+ * Middleware resolving `Authorization: Bearer` to the session's member
+ *
+ * Sets X-User-Id for the Actor extractor and the CurrentSession
+ * extension; requests without a bearer token pass unchanged.
+ */
+pub async fn authenticate(
+    State(st): State<AppState>,
+    device: Device,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|t| hash(t.trim()));
+    // Refreshing works with an expired access token still attached
+    let Some(access_hash) = bearer.filter(|_| req.uri().path() != "/api/sessions/refresh") else {
+        return next.run(req).await;
+    };
+    let now = Utc::now();
+    let session = sqlx::query_as::<_, Session>(
+        "SELECT * FROM sessions WHERE access_hash=?1 AND revoked_at IS NULL AND access_expires_at > ?2",
+    )
+    .bind(&access_hash)
+    .bind(now)
+    .fetch_optional(&st.pool)
+    .await;
+    let session = match session {
+        Ok(Some(session)) => session,
+        Ok(None) => {
+            return ApiError::Unauthorized("invalid or expired access token".into())
+                .into_response();
+        }
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    if session.last_seen_at + SEEN_INTERVAL <= now
+        || session.ip != device.ip
+        || session.user_agent != device.user_agent
+    {
+        // Best effort: fails harmlessly in read-only mode
+        if let Err(e) =
+            sqlx::query("UPDATE sessions SET last_seen_at=?1, ip=?2, user_agent=?3 WHERE id=?4")
+                .bind(now)
+                .bind(&device.ip)
+                .bind(&device.user_agent)
+                .bind(&session.id)
+                .execute(&st.pool)
+                .await
+        {
+            tracing::debug!(error = %e, "session last-seen update failed");
+        }
+    }
+
+    match HeaderValue::from_str(&session.user_id) {
+        Ok(user) => {
+            req.headers_mut().insert(USER_HEADER, user);
+        }
+        Err(_) => return ApiError::Unauthorized("invalid session".into()).into_response(),
+    }
+    req.extensions_mut().insert(CurrentSession(session.id));
+    next.run(req).await
+}
+
+async fn create_session(
+    State(st): State<AppState>,
+    device: Device,
+    JsonBody(body): JsonBody<SessionCreate>,
+) -> ApiResult<Json<SessionTokens>> {
+    if st.oidc.is_some() {
+        return Err(ApiError::Forbidden(
+            "sign in at /api/auth/oidc/login instead".into(),
+        ));
+    }
+    Ok(Json(sign_in(&st, body.user_id.trim(), device).await?))
+}
+
+async fn refresh_session(
+    State(st): State<AppState>,
+    device: Device,
+    JsonBody(body): JsonBody<SessionRefresh>,
+) -> ApiResult<Json<SessionTokens>> {
+    let presented = hash(body.refresh_token.trim());
+    let now = Utc::now();
+    let session = sqlx::query_as::<_, Session>(
+        "SELECT * FROM sessions WHERE refresh_hash=?1 AND revoked_at IS NULL",
+    )
+    .bind(&presented)
+    .fetch_optional(&st.pool)
+    .await?;
+    let Some(session) = session else {
+        // A retired refresh token: someone else has used it first
+        let res = sqlx::query(
+            "UPDATE sessions SET revoked_at=?1 WHERE prev_refresh_hash=?2 AND revoked_at IS NULL",
+        )
+        .bind(now)
+        .bind(&presented)
+        .execute(&st.pool)
+        .await?;
+        if res.rows_affected() > 0 {
+            tracing::warn!("refresh token reused; session revoked");
+        }
+        return Err(ApiError::Unauthorized("invalid refresh token".into()));
+    };
+    if session.refresh_expires_at <= now {
+        return Err(ApiError::Unauthorized("session expired".into()));
+    }
+
+    let (access, refresh) = (token(), token());
+    let tokens = SessionTokens {
+        session_id: session.id,
+        user_id: session.user_id,
+        access_expires_at: now + ACCESS_TTL,
+        refresh_expires_at: now + REFRESH_TTL,
+        access_token: access,
+        refresh_token: refresh,
+    };
+    // Conditional on the presented token, so concurrent refreshes rotate once
+    let res = sqlx::query(
+        r#"
+        UPDATE sessions SET access_hash=?1, refresh_hash=?2, prev_refresh_hash=?3, user_agent=?4,
+                            ip=?5, last_seen_at=?6, access_expires_at=?7, refresh_expires_at=?8
+        WHERE id=?9 AND refresh_hash=?3 AND revoked_at IS NULL
+    "#,
+    )
+    .bind(hash(&tokens.access_token))
+    .bind(hash(&tokens.refresh_token))
+    .bind(&presented)
+    .bind(device.user_agent)
+    .bind(device.ip)
+    .bind(now)
+    .bind(tokens.access_expires_at)
+    .bind(tokens.refresh_expires_at)
+    .bind(&tokens.session_id)
+    .execute(&st.pool)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::Unauthorized("invalid refresh token".into()));
+    }
+    Ok(Json(tokens))
+}
+
+/// The caller's user id; session lists need a member.
+fn member(actor: &Actor) -> ApiResult<&str> {
+    actor.user_id().ok_or_else(|| {
+        ApiError::BadRequest("sessions need a bearer token or an X-User-Id header".into())
+    })
+}
+
+async fn list_sessions(
+    State(st): State<AppState>,
+    actor: Actor,
+    current: Option<Extension<CurrentSession>>,
+) -> ApiResult<Json<Vec<SessionInfo>>> {
+    let user_id = member(&actor)?;
+    let sessions = sqlx::query_as::<_, Session>(
+        r#"
+        SELECT * FROM sessions
+        WHERE user_id=?1 AND revoked_at IS NULL AND refresh_expires_at > ?2
+        ORDER BY last_seen_at DESC
+    "#,
+    )
+    .bind(user_id)
+    .bind(Utc::now())
+    .fetch_all(&st.pool)
+    .await?;
+    let current = current.map(|Extension(c)| c.0);
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| SessionInfo {
+                current: current.as_ref() == Some(&session.id),
+                session,
+            })
+            .collect(),
+    ))
+}
+
+async fn revoke_session(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let user_id = member(&actor)?;
+    let res = sqlx::query(
+        "UPDATE sessions SET revoked_at=?1 WHERE id=?2 AND user_id=?3 AND revoked_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(&id)
+    .bind(user_id)
+    .execute(&st.pool)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(json!({"ok": true})))
+}
+
+async fn revoke_others(
+    State(st): State<AppState>,
+    actor: Actor,
+    current: Option<Extension<CurrentSession>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let user_id = member(&actor)?;
+    let current = current.map(|Extension(c)| c.0);
+    let res = sqlx::query(
+        r#"
+        UPDATE sessions SET revoked_at=?1
+        WHERE user_id=?2 AND revoked_at IS NULL AND (?3 IS NULL OR id != ?3)
+    "#,
+    )
+    .bind(Utc::now())
+    .bind(user_id)
+    .bind(current)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(json!({"ok": true, "revoked": res.rows_affected()})))
+}
@@ -0,0 +1,274 @@
+/**
+ * Per-user preferences
+ *
+ * Key/value settings kept on the server, so a member's preferences follow
+ * them from the phone to the tablet instead of living in localStorage.
+ * `{id}` may be `me` for the caller (X-User-Id or a session).
+ *
+ * - GET    /api/users/{id}/settings          every setting, defaults filled in
+ * - PATCH  /api/users/{id}/settings          {"key": value, ...}; null resets a key
+ * - GET    /api/users/{id}/settings/{key}
+ * - PUT    /api/users/{id}/settings/{key}    {"value": ...}
+ * - DELETE /api/users/{id}/settings/{key}    back to the default
+ *
+ * Known keys are validated:
+ *
+ * ```text
+ * timezone               IANA name ("Europe/Berlin"); null = the server's
+ * default_view           "list" | "board" | "calendar" | "today"    (default "list")
+ * week_start             "monday" | "sunday" | "saturday"           (default "monday")
+ * notification_channels  subset of ["push", "email"]                (default both)
+ * theme                  "system" | "light" | "dark"                (default "system")
+ * ```
+ *
+ * Clients may store other keys (lowercase letters, digits, `_`, `-` and
+ * `.`; at most 64 characters) with any JSON value up to 4 KiB. The
+ * in-app notification center is always on; `notification_channels` picks
+ * whether mentions also go to the push channels and reminders to e-mail.
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::get,
+};
+use chrono::Utc;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult, JsonBody},
+    routes::AppState,
+};
+
+/// Longest accepted key.
+const MAX_KEY_CHARS: usize = 64;
+/// Largest accepted value (bytes of JSON).
+const MAX_VALUE_BYTES: usize = 4096;
+
+/// Known keys and their defaults.
+fn defaults() -> Map<String, Value> {
+    let mut map = Map::new();
+    map.insert("timezone".into(), Value::Null);
+    map.insert("default_view".into(), json!("list"));
+    map.insert("week_start".into(), json!("monday"));
+    map.insert("notification_channels".into(), json!(["push", "email"]));
+    map.insert("theme".into(), json!("system"));
+    map
+}
+
+/// Bad request unless `value` is acceptable for `key`.
+fn validate(key: &str, value: &Value) -> ApiResult<()> {
+    let bad = |msg: &str| Err(ApiError::BadRequest(format!("{key}: {msg}")));
+    let one_of = |allowed: &[&str]| match value.as_str() {
+        Some(v) if allowed.contains(&v) => Ok(()),
+        _ => bad(&format!("must be one of {}", allowed.join(", "))),
+    };
+    if key.is_empty()
+        || key.chars().count() > MAX_KEY_CHARS
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "invalid setting key `{key}` (lowercase letters, digits, `_`, `-`, `.`; at most {MAX_KEY_CHARS})"
+        )));
+    }
+    if value.to_string().len() > MAX_VALUE_BYTES {
+        return bad(&format!("value must be at most {MAX_VALUE_BYTES} bytes"));
+    }
+    match key {
+        "timezone" => match value.as_str().map(str::parse::<Tz>) {
+            Some(Ok(_)) => Ok(()),
+            _ => bad("must be an IANA time zone such as Europe/Berlin"),
+        },
+        "default_view" => one_of(&["list", "board", "calendar", "today"]),
+        "week_start" => one_of(&["monday", "sunday", "saturday"]),
+        "theme" => one_of(&["system", "light", "dark"]),
+        "notification_channels" => match value.as_array() {
+            Some(channels)
+                if channels
+                    .iter()
+                    .all(|c| matches!(c.as_str(), Some("push" | "email"))) =>
+            {
+                Ok(())
+            }
+            _ => bad("must be a list of \"push\" and \"email\""),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Every setting of `user_id`, stored values over the defaults.
+pub async fn all(pool: &SqlitePool, user_id: &str) -> ApiResult<Map<String, Value>> {
+    let rows: Vec<(String, String)> =
+        sqlx::query_as("SELECT key, value FROM user_settings WHERE user_id=?1 ORDER BY key")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+    let mut settings = defaults();
+    for (key, value) in rows {
+        settings.insert(key, serde_json::from_str(&value).unwrap_or(Value::Null));
+    }
+    Ok(settings)
+}
+
+/// One setting of `user_id` (its default when unset).
+pub async fn value(pool: &SqlitePool, user_id: &str, key: &str) -> ApiResult<Value> {
+    let stored: Option<String> =
+        sqlx::query_scalar("SELECT value FROM user_settings WHERE user_id=?1 AND key=?2")
+            .bind(user_id)
+            .bind(key)
+            .fetch_optional(pool)
+            .await?;
+    Ok(match stored {
+        Some(value) => serde_json::from_str(&value).unwrap_or(Value::Null),
+        None => defaults().remove(key).unwrap_or(Value::Null),
+    })
+}
+
+/// Whether `user_id` wants notifications on `channel` ("push" or "email").
+pub async fn wants_channel(pool: &SqlitePool, user_id: &str, channel: &str) -> ApiResult<bool> {
+    let channels = value(pool, user_id, "notification_channels").await?;
+    Ok(channels
+        .as_array()
+        .is_some_and(|c| c.iter().any(|c| c == channel)))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/users/{id}/settings",
+            get(get_settings).patch(patch_settings),
+        )
+        .route(
+            "/api/users/{id}/settings/{key}",
+            get(get_setting).put(put_setting).delete(delete_setting),
+        )
+}
+
+/// The user a path refers to: `me` is the caller; 404 for unknown users.
+async fn user(st: &AppState, actor: &Actor, id: &str) -> ApiResult<String> {
+    let id = match id {
+        "me" => actor
+            .user_id()
+            .ok_or_else(|| ApiError::BadRequest("`me` needs an X-User-Id header".into()))?,
+        id => id,
+    };
+    let found: Option<String> =
+        sqlx::query_scalar("SELECT id FROM users WHERE id=?1 AND deleted=0")
+            .bind(id)
+            .fetch_optional(&st.pool)
+            .await?;
+    found.ok_or(ApiError::NotFound)
+}
+
+async fn get_settings(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Map<String, Value>>> {
+    let user_id = user(&st, &actor, &id).await?;
+    Ok(Json(all(&st.pool, &user_id).await?))
+}
+
+async fn patch_settings(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<Map<String, Value>>,
+) -> ApiResult<Json<Map<String, Value>>> {
+    let user_id = user(&st, &actor, &id).await?;
+    // All or nothing: check every key before storing any
+    for (key, value) in &body {
+        if !value.is_null() {
+            validate(key, value)?;
+        }
+    }
+    let mut tx = st.pool.begin().await?;
+    for (key, value) in &body {
+        put(&mut tx, &user_id, key, value).await?;
+    }
+    tx.commit().await?;
+    Ok(Json(all(&st.pool, &user_id).await?))
+}
+
+/// Store (or with null, reset) one validated setting.
+async fn put(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    user_id: &str,
+    key: &str,
+    value: &Value,
+) -> ApiResult<()> {
+    if value.is_null() {
+        sqlx::query("DELETE FROM user_settings WHERE user_id=?1 AND key=?2")
+            .bind(user_id)
+            .bind(key)
+            .execute(&mut **tx)
+            .await?;
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO user_settings (user_id,key,value,updated_at) VALUES (?1,?2,?3,?4)
+        ON CONFLICT(user_id,key) DO UPDATE SET value=excluded.value, updated_at=excluded.updated_at
+    "#,
+    )
+    .bind(user_id)
+    .bind(key)
+    .bind(value.to_string())
+    .bind(Utc::now())
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn get_setting(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((id, key)): Path<(String, String)>,
+) -> ApiResult<Json<Value>> {
+    let user_id = user(&st, &actor, &id).await?;
+    Ok(Json(
+        json!({"key": key, "value": value(&st.pool, &user_id, &key).await?}),
+    ))
+}
+
+#[derive(Deserialize)]
+struct SettingPut {
+    value: Value,
+}
+
+async fn put_setting(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((id, key)): Path<(String, String)>,
+    JsonBody(body): JsonBody<SettingPut>,
+) -> ApiResult<Json<Value>> {
+    let user_id = user(&st, &actor, &id).await?;
+    if !body.value.is_null() {
+        validate(&key, &body.value)?;
+    }
+    let mut tx = st.pool.begin().await?;
+    put(&mut tx, &user_id, &key, &body.value).await?;
+    tx.commit().await?;
+    Ok(Json(
+        json!({"key": key, "value": value(&st.pool, &user_id, &key).await?}),
+    ))
+}
+
+async fn delete_setting(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((id, key)): Path<(String, String)>,
+) -> ApiResult<Json<Value>> {
+    let user_id = user(&st, &actor, &id).await?;
+    let mut tx = st.pool.begin().await?;
+    put(&mut tx, &user_id, &key, &Value::Null).await?;
+    tx.commit().await?;
+    Ok(Json(
+        json!({"key": key, "value": value(&st.pool, &user_id, &key).await?}),
+    ))
+}
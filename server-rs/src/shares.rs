@@ -0,0 +1,267 @@
+/**
+ * Shareable read-only links
+ *
+ * A share exposes one filtered todo list to guests without an account
+ * ("the shopping list, for the weekend"). Creating one returns a secret
+ * token; whoever knows it can read the list and follow its changes, and
+ * nothing else:
+ *
+ * - GET    /api/shares                  list shares
+ * - POST   /api/shares                  {"name", "project_id"?, "category_id"?, "filter"?, "expires_at"?}
+ * - DELETE /api/shares/{id}             revoke
+ * - GET    /api/shared/{token}          {"name", "expires_at", "todos"} (guest)
+ * - GET    /ws/shared/{token}           WebSocket (guest)
+ *
+ * The list holds the non-deleted todos matching every criterion the share
+ * sets (`filter` is a ?filter= expression, see filter.rs). The guest
+ * WebSocket opens with a snapshot of that list and then only carries
+ * `todo.created` / `todo.updated` for matching todos, plus `todo.deleted`
+ * for todos that were removed or stopped matching; presence, editing and
+ * other events are not sent, and messages from the guest are ignored.
+ * Guest sockets are closed once the share expires; revoking a share stops
+ * new requests and connections.
+ */
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State, WebSocketUpgrade},
+    response::Response,
+    routing::{delete, get},
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{
+    error::{ApiError, ApiResult, JsonBody},
+    filter::FilterExpr,
+    model::{Share, ShareCreate, Todo},
+    routes::AppState,
+    services::TodoFilter,
+    versions,
+    ws::{self, WsScope},
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/shares", get(list_shares).post(create_share))
+        .route("/api/shares/{id}", delete(delete_share))
+        .route("/api/shared/{token}", get(shared_list))
+        .route("/ws/shared/{token}", get(shared_ws))
+}
+
+/**
+ * Share as returned to its owner, with the guest paths
+ */
+#[derive(Debug, Serialize)]
+pub struct ShareLink {
+    #[serde(flatten)]
+    pub share: Share,
+    pub path: String,    // Guest list: /api/v1/shared/{token}
+    pub ws_path: String, // Guest WebSocket: /ws/shared/{token}
+}
+
+impl From<Share> for ShareLink {
+    fn from(share: Share) -> Self {
+        Self {
+            path: format!(
+                "{}/shared/{}",
+                versions::prefix(versions::CURRENT),
+                share.token
+            ),
+            ws_path: format!("/ws/shared/{}", share.token),
+            share,
+        }
+    }
+}
+
+/**
+ * What a share exposes, resolved for one request or connection
+ */
+#[derive(Debug, Clone)]
+pub struct SharedView {
+    pub name: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    project_id: Option<String>,
+    category_id: Option<String>,
+    expr: Option<FilterExpr>, // Relative dates are fixed when resolved
+}
+
+impl SharedView {
+    /// Calendar days in the filter (`due:today`) count in `tz`.
+    fn new(share: Share, tz: Tz) -> ApiResult<Self> {
+        let expr = share
+            .filter
+            .as_deref()
+            .map(|f| FilterExpr::parse(f, Utc::now(), tz))
+            .transpose()
+            .map_err(|e| ApiError::BadRequest(format!("invalid filter: {e}")))?;
+        Ok(Self {
+            name: share.name,
+            expires_at: share.expires_at,
+            project_id: share.project_id,
+            category_id: share.category_id,
+            expr,
+        })
+    }
+
+    /// Whether a todo belongs on the shared list.
+    pub fn matches(&self, t: &Todo) -> bool {
+        t.deleted == 0
+            && self
+                .project_id
+                .as_ref()
+                .is_none_or(|p| t.project_id.as_ref() == Some(p))
+            && self
+                .category_id
+                .as_ref()
+                .is_none_or(|c| t.category_id.as_ref() == Some(c))
+            && self.expr.as_ref().is_none_or(|e| e.matches(t))
+    }
+
+    /// The shared list, in the usual list order.
+    pub async fn todos(&self, st: &AppState) -> ApiResult<Vec<Todo>> {
+        let filter = TodoFilter {
+            project_id: self.project_id.clone(),
+            expr: self.expr.clone(),
+            ..Default::default()
+        };
+        let mut todos = st.todos.list(&filter).await?;
+        todos.retain(|t| self.matches(t));
+        Ok(todos)
+    }
+
+    /**
+     * The message a guest gets for a hub event, if any
+     *
+     * Changes to matching todos pass unchanged; an update to any other todo
+     * becomes a `todo.deleted` (it may have just left the list).
+     */
+    pub(crate) fn translate(&self, event: &Value) -> Option<Value> {
+        let data = &event["data"];
+        match event["type"].as_str()? {
+            "todo.deleted" => Some(event.clone()),
+            kind @ ("todo.created" | "todo.updated") => {
+                let todo: Todo = serde_json::from_value(data.clone()).ok()?;
+                if self.matches(&todo) {
+                    Some(event.clone())
+                } else if kind == "todo.updated" {
+                    Some(
+                        json!({"type": "todo.deleted", "seq": event["seq"], "data": {"id": todo.id}}),
+                    )
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The live share behind a token; 404 for unknown, revoked or expired ones.
+async fn resolve(st: &AppState, token: &str) -> ApiResult<SharedView> {
+    let share: Share = sqlx::query_as("SELECT * FROM shares WHERE token=?1")
+        .bind(token)
+        .fetch_optional(&st.pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if share.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(ApiError::NotFound);
+    }
+    SharedView::new(share, st.config.zone())
+}
+
+async fn list_shares(State(st): State<AppState>) -> ApiResult<Json<Vec<ShareLink>>> {
+    let shares: Vec<Share> = sqlx::query_as("SELECT * FROM shares ORDER BY created_at DESC")
+        .fetch_all(&st.pool)
+        .await?;
+    Ok(Json(shares.into_iter().map(ShareLink::from).collect()))
+}
+
+async fn create_share(
+    State(st): State<AppState>,
+    JsonBody(mut body): JsonBody<ShareCreate>,
+) -> ApiResult<Json<ShareLink>> {
+    body.name = body.name.trim().to_string();
+    if body.name.is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    if body.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(ApiError::BadRequest(
+            "expires_at must be in the future".into(),
+        ));
+    }
+    if let Some(id) = &body.category_id {
+        st.categories.get(id).await?;
+    }
+    let share = Share::new_from_create(body);
+    SharedView::new(share.clone(), st.config.zone())?; // Reject filters that do not parse
+    sqlx::query(
+        r#"
+        INSERT INTO shares (id,token,name,project_id,category_id,filter,expires_at,created_at)
+        VALUES (?1,?2,?3,?4,?5,?6,?7,?8)
+    "#,
+    )
+    .bind(&share.id)
+    .bind(&share.token)
+    .bind(&share.name)
+    .bind(&share.project_id)
+    .bind(&share.category_id)
+    .bind(&share.filter)
+    .bind(share.expires_at)
+    .bind(share.created_at)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(share.into()))
+}
+
+async fn delete_share(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Value>> {
+    let res = sqlx::query("DELETE FROM shares WHERE id=?1")
+        .bind(&id)
+        .execute(&st.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(json!({"ok": true})))
+}
+
+/**
+ * Response for GET /api/shared/{token}
+ */
+#[derive(Debug, Serialize)]
+pub struct SharedList {
+    pub name: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub todos: Vec<Todo>,
+}
+
+async fn shared_list(
+    State(st): State<AppState>,
+    Path(token): Path<String>,
+) -> ApiResult<Json<SharedList>> {
+    let view = resolve(&st, &token).await?;
+    Ok(Json(SharedList {
+        todos: view.todos(&st).await?,
+        name: view.name,
+        expires_at: view.expires_at,
+    }))
+}
+
+async fn shared_ws(
+    ws: WebSocketUpgrade,
+    State(st): State<AppState>,
+    Path(token): Path<String>,
+) -> ApiResult<Response> {
+    let view = resolve(&st, &token).await?;
+    let scope = WsScope {
+        share: Some(Arc::new(view)),
+        ..Default::default()
+    };
+    Ok(ws::ws_snapshot_handler(ws, st, scope).await)
+}
@@ -0,0 +1,188 @@
+/**
+ * Server-rendered fallback UI at /simple
+ *
+ * A plain HTML page for browsers the React app does not run in (old
+ * tablets, e-readers, text browsers) and for the day a broken frontend
+ * build needs working around:
+ *
+ * - GET  /simple                      open todos and an "add" form
+ * - POST /simple/todos                add one (form field `title`)
+ * - POST /simple/todos/{id}/done      mark one done
+ *
+ * There is no JavaScript: every action is a form post answered with a
+ * redirect back to the list (post/redirect/get), so reloading never
+ * submits twice. Changes go through TodoService like API writes, so other
+ * clients see them live. Turned off with `simple_ui = false`.
+ */
+use axum::{
+    Form, Router,
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use chrono::Utc;
+use maud::{DOCTYPE, Markup, PreEscaped, html};
+use serde::Deserialize;
+
+use crate::{
+    audit::Actor,
+    error::{ApiError, ApiResult},
+    model::{Todo, TodoCreate},
+    routes::AppState,
+    services::TodoFilter,
+    timezone::Zone,
+};
+
+/// Status "Done" moves a todo to, when the statuses table has it.
+const DONE: &str = "done";
+
+/// Kept small and old-browser safe: no flexbox, grid or custom properties.
+const STYLE: &str = "
+body { font-family: sans-serif; max-width: 40em; margin: 1em auto; padding: 0 0.5em; }
+h1 { font-size: 1.4em; }
+ul { list-style: none; padding: 0; }
+li { border-bottom: 1px solid #ccc; padding: 0.5em 0; }
+li form { display: inline; float: right; }
+input[type=text] { width: 70%; }
+.meta { color: #555; font-size: 0.9em; }
+.overdue { color: #b00; }
+.error { color: #b00; }
+";
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/simple", get(list_page))
+        .route("/simple/todos", post(add_todo))
+        .route("/simple/todos/{id}/done", post(complete_todo))
+}
+
+#[derive(Deserialize)]
+struct AddForm {
+    title: String,
+}
+
+/// Surrounding document of every page.
+fn page(title: &str, body: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { (title) }
+                style { (PreEscaped(STYLE)) }
+            }
+            body { (body) }
+        }
+    }
+}
+
+/// An API error as a page with a way back, keeping its status code.
+fn error_page(e: ApiError) -> Response {
+    let message = e.to_string();
+    let status = e.into_response().status();
+    let body = page(
+        "Error",
+        html! {
+            h1 { "Something went wrong" }
+            p.error { (message) }
+            p { a href="/simple" { "Back to the list" } }
+        },
+    );
+    (status, body).into_response()
+}
+
+/// Open todos: pinned first, then by due date (undated last), then priority.
+async fn open_todos(st: &AppState) -> ApiResult<Vec<Todo>> {
+    let done = st.todos.done_statuses().await?;
+    let mut todos = st.todos.list(&TodoFilter::default()).await?;
+    todos.retain(|t| !done.contains(&t.status));
+    todos.sort_by(|a, b| {
+        b.pinned
+            .cmp(&a.pinned)
+            .then(a.due_at.is_none().cmp(&b.due_at.is_none()))
+            .then(a.due_at.cmp(&b.due_at))
+            .then(b.priority.cmp(&a.priority))
+    });
+    Ok(todos)
+}
+
+async fn list_page(State(st): State<AppState>, zone: Zone) -> Response {
+    let todos = match open_todos(&st).await {
+        Ok(todos) => todos,
+        Err(e) => return error_page(e),
+    };
+    let now = Utc::now();
+    let body = html! {
+        h1 { "Todos (" (todos.len()) ")" }
+        form method="post" action="/simple/todos" {
+            input type="text" name="title" placeholder="New todo" required;
+            " "
+            button type="submit" { "Add" }
+        }
+        @if todos.is_empty() {
+            p { "Nothing to do." }
+        }
+        ul {
+            @for t in &todos {
+                li {
+                    form method="post" action={ "/simple/todos/" (t.id) "/done" } {
+                        button type="submit" { "Done" }
+                    }
+                    @if t.pinned { "📌 " }
+                    (t.title)
+                    // Priority 1 is the default; 2 and 3 stand out
+                    @if t.priority > 1 { " " ("!".repeat(t.priority.min(3) as usize - 1)) }
+                    br;
+                    span.meta {
+                        @if let Some(due) = t.due_at {
+                            span class=[(due < now).then_some("overdue")] {
+                                "due " (zone.date(due).format("%a %-d %b"))
+                            }
+                            " "
+                        }
+                        @if let Some(tags) = t.tags.as_deref().filter(|s| !s.is_empty()) {
+                            (tags)
+                        }
+                    }
+                }
+            }
+        }
+        p.meta { a href="/" { "Full app" } }
+    };
+    page("Todos", body).into_response()
+}
+
+async fn add_todo(State(st): State<AppState>, actor: Actor, Form(form): Form<AddForm>) -> Response {
+    let create = TodoCreate {
+        title: form.title.trim().to_string(),
+        ..Default::default()
+    };
+    if create.title.is_empty() {
+        return error_page(ApiError::BadRequest("the title must not be empty".into()));
+    }
+    match st.todos.as_actor(actor).create(create).await {
+        Ok(_) => Redirect::to("/simple").into_response(),
+        Err(e) => error_page(e),
+    }
+}
+
+async fn complete_todo(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Response {
+    let done = match st.todos.done_statuses().await {
+        Ok(done) => done,
+        Err(e) => return error_page(e),
+    };
+    let Some(status) = done.iter().find(|s| *s == DONE).or(done.first()).cloned() else {
+        return error_page(ApiError::Conflict(
+            "no status counts as done; add one at /api/statuses".into(),
+        ));
+    };
+    match st.todos.as_actor(actor).set_status(&id, status).await {
+        Ok(_) => Redirect::to("/simple").into_response(),
+        Err(e) => error_page(e),
+    }
+}
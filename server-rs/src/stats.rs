@@ -0,0 +1,194 @@
+/**
+ * Completion statistics
+ *
+ * Built from `completed_at`, which the todo service sets when a todo
+ * becomes done (and keeps when it is archived).
+ *
+ * - GET /api/stats/completion?days=30   completions in the last `days` days:
+ *   count, average/median time from creation to completion, per day
+ *   (UTC dates) and per category
+ * - GET /api/stats/workload?date=&days=7&capacity_minutes=480
+ *   estimated minutes of open todos due on each day from `date` (default
+ *   today; days in the request's zone, see timezone.rs); days above the
+ *   capacity are flagged overcommitted
+ */
+use std::collections::BTreeMap;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use chrono::{Days, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::ApiResult, routes::AppState, services::TodoFilter, timezone::Zone};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/stats/completion", get(completion))
+        .route("/api/stats/workload", get(workload))
+}
+
+#[derive(Deserialize)]
+struct CompletionQuery {
+    days: Option<i64>, // Window size, default 30, at most 366
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompletionStats {
+    pub days: i64,
+    pub completed: usize,
+    pub avg_hours_to_complete: Option<f64>,
+    pub median_hours_to_complete: Option<f64>,
+    pub per_day: Vec<DayCount>,
+    pub by_category: Vec<CategoryCompletion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub completed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryCompletion {
+    pub category_id: Option<String>, // None = uncategorized
+    pub completed: usize,
+    pub avg_hours_to_complete: Option<f64>,
+}
+
+fn average(hours: &[f64]) -> Option<f64> {
+    (!hours.is_empty()).then(|| hours.iter().sum::<f64>() / hours.len() as f64)
+}
+
+fn median(hours: &mut [f64]) -> Option<f64> {
+    if hours.is_empty() {
+        return None;
+    }
+    hours.sort_by(f64::total_cmp);
+    let mid = hours.len() / 2;
+    Some(if hours.len().is_multiple_of(2) {
+        (hours[mid - 1] + hours[mid]) / 2.0
+    } else {
+        hours[mid]
+    })
+}
+
+async fn completion(
+    State(st): State<AppState>,
+    Query(q): Query<CompletionQuery>,
+) -> ApiResult<Json<CompletionStats>> {
+    let days = q.days.unwrap_or(30).clamp(1, 366);
+    let since = Utc::now() - Duration::days(days);
+    let todos = st.todos.list(&TodoFilter::default()).await?;
+
+    let mut hours = Vec::new();
+    let mut per_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    let mut per_category: BTreeMap<Option<String>, Vec<f64>> = BTreeMap::new();
+    for t in &todos {
+        let Some(done) = t.completed_at.filter(|d| *d >= since) else {
+            continue;
+        };
+        let h = (done - t.created_at).num_seconds().max(0) as f64 / 3600.0;
+        hours.push(h);
+        *per_day.entry(done.date_naive()).or_default() += 1;
+        per_category
+            .entry(t.category_id.clone())
+            .or_default()
+            .push(h);
+    }
+
+    Ok(Json(CompletionStats {
+        days,
+        completed: hours.len(),
+        avg_hours_to_complete: average(&hours),
+        median_hours_to_complete: median(&mut hours),
+        per_day: per_day
+            .into_iter()
+            .map(|(date, completed)| DayCount { date, completed })
+            .collect(),
+        by_category: per_category
+            .into_iter()
+            .map(|(category_id, hours)| CategoryCompletion {
+                category_id,
+                completed: hours.len(),
+                avg_hours_to_complete: average(&hours),
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize)]
+struct WorkloadQuery {
+    date: Option<NaiveDate>,       // First day (request zone), default today
+    days: Option<u64>,             // Number of days, default 7, at most 62
+    capacity_minutes: Option<i64>, // Minutes of work per day before it is overcommitted
+}
+
+#[derive(Debug, Serialize)]
+pub struct DayWorkload {
+    pub date: NaiveDate,
+    pub todos: usize,          // Open todos due that day
+    pub estimate_minutes: i64, // Sum of their estimates
+    pub unestimated: usize,    // Todos without an estimate (not in the sum)
+    pub overcommitted: bool,   // estimate_minutes > capacity_minutes
+}
+
+#[derive(Debug, Serialize)]
+pub struct Workload {
+    pub capacity_minutes: i64,
+    pub days: Vec<DayWorkload>,
+}
+
+const DEFAULT_CAPACITY_MINUTES: i64 = 8 * 60;
+
+async fn workload(
+    State(st): State<AppState>,
+    zone: Zone,
+    Query(q): Query<WorkloadQuery>,
+) -> ApiResult<Json<Workload>> {
+    let first = q.date.unwrap_or_else(|| zone.today());
+    let count = q.days.unwrap_or(7).clamp(1, 62);
+    let capacity = q
+        .capacity_minutes
+        .unwrap_or(DEFAULT_CAPACITY_MINUTES)
+        .max(0);
+    let mut days: BTreeMap<NaiveDate, DayWorkload> = (0..count)
+        .filter_map(|i| first.checked_add_days(Days::new(i)))
+        .map(|date| {
+            let day = DayWorkload {
+                date,
+                todos: 0,
+                estimate_minutes: 0,
+                unestimated: 0,
+                overcommitted: false,
+            };
+            (date, day)
+        })
+        .collect();
+
+    let done = st.todos.done_statuses().await?;
+    for t in st.todos.list(&TodoFilter::default()).await? {
+        let Some(due) = t.due_at else { continue };
+        if done.contains(&t.status) {
+            continue;
+        }
+        let Some(day) = days.get_mut(&zone.date(due)) else {
+            continue;
+        };
+        day.todos += 1;
+        match t.estimate_minutes {
+            Some(m) => day.estimate_minutes += m,
+            None => day.unestimated += 1,
+        }
+    }
+    for day in days.values_mut() {
+        day.overcommitted = day.estimate_minutes > capacity;
+    }
+
+    Ok(Json(Workload {
+        capacity_minutes: capacity,
+        days: days.into_values().collect(),
+    }))
+}
@@ -0,0 +1,251 @@
+/**
+ * Custom statuses (board columns)
+ *
+ * The workflow states live in the `statuses` table instead of being
+ * compiled in: name, color, column order and whether the status counts as
+ * finished (`is_done`). Fresh databases start with todo/doing/done/archived.
+ * Todos in an `is_done` status are not open: they never become overdue,
+ * they do not block other todos and they carry `completed_at`.
+ *
+ * Names are the keys todos refer to and cannot be changed; to rename,
+ * create the new status, move the todos and delete the old one. A status
+ * cannot be deleted while todos (including deleted ones) use it, and at
+ * least one open status must remain.
+ *
+ * - GET    /api/statuses           in column order
+ * - POST   /api/statuses           {"name", "color"?, "sort_order"?, "is_done"?}
+ * - PUT    /api/statuses/{name}    {"color"?, "sort_order"?, "is_done"?}
+ * - DELETE /api/statuses/{name}
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::get,
+};
+use chrono::Utc;
+use serde_json::json;
+
+use crate::{
+    db::SqlitePool,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{Status, StatusCreate, StatusUpdate},
+    routes::AppState,
+    services::{TodoFilter, emit},
+};
+
+/// Finished statuses when no status table is attached (custom repositories).
+pub const DEFAULT_DONE: [&str; 2] = ["done", "archived"];
+
+/**
+ * Storage for the statuses table
+ */
+#[derive(Clone)]
+pub struct Statuses {
+    pool: SqlitePool,
+}
+
+impl Statuses {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Every status in column order.
+    pub async fn list(&self) -> ApiResult<Vec<Status>> {
+        Ok(sqlx::query_as::<_, Status>(
+            "SELECT * FROM statuses ORDER BY sort_order ASC, created_at ASC",
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get(&self, name: &str) -> ApiResult<Option<Status>> {
+        Ok(
+            sqlx::query_as::<_, Status>("SELECT * FROM statuses WHERE name=?1")
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Names of the finished statuses.
+    pub async fn done(&self) -> ApiResult<Vec<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT name FROM statuses WHERE is_done != 0")
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// First open status in column order, where new todos start.
+    pub async fn initial(&self) -> ApiResult<Option<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT name FROM statuses WHERE is_done = 0 ORDER BY sort_order ASC, created_at ASC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Bad request unless `name` is a known status.
+    pub async fn check(&self, name: &str) -> ApiResult<()> {
+        match self.get(name).await? {
+            Some(_) => Ok(()),
+            None => Err(ApiError::BadRequest(format!("unknown status `{name}`"))),
+        }
+    }
+
+    async fn open_count(&self) -> ApiResult<i64> {
+        Ok(
+            sqlx::query_scalar("SELECT COUNT(*) FROM statuses WHERE is_done = 0")
+                .fetch_one(&self.pool)
+                .await?,
+        )
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/statuses", get(list_statuses).post(create_status))
+        .route(
+            "/api/statuses/{name}",
+            axum::routing::put(update_status).delete(delete_status),
+        )
+}
+
+fn validate(status: &Status) -> ApiResult<()> {
+    if status.name.is_empty() || status.name.len() > 32 {
+        return Err(ApiError::BadRequest(
+            "name must be 1 to 32 characters".into(),
+        ));
+    }
+    if status.name == "*" {
+        return Err(ApiError::BadRequest(
+            "`*` is reserved for status_transitions".into(),
+        ));
+    }
+    if !(0..=1).contains(&status.is_done) {
+        return Err(ApiError::BadRequest("is_done must be 0 or 1".into()));
+    }
+    Ok(())
+}
+
+async fn list_statuses(State(st): State<AppState>) -> ApiResult<Json<Vec<Status>>> {
+    Ok(Json(Statuses::new(st.pool.clone()).list().await?))
+}
+
+async fn create_status(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<StatusCreate>,
+) -> ApiResult<Json<Status>> {
+    let statuses = Statuses::new(st.pool.clone());
+    let sort_order = match body.sort_order {
+        Some(v) => v,
+        None => {
+            sqlx::query_scalar::<_, i64>("SELECT COALESCE(MAX(sort_order) + 1, 0) FROM statuses")
+                .fetch_one(&st.pool)
+                .await?
+        }
+    };
+    let now = Utc::now();
+    let status = Status {
+        name: body.name.trim().to_string(),
+        color: body.color,
+        sort_order,
+        is_done: body.is_done.unwrap_or(0),
+        created_at: now,
+        updated_at: now,
+    };
+    validate(&status)?;
+    if statuses.get(&status.name).await?.is_some() {
+        return Err(ApiError::Conflict(format!(
+            "status `{}` already exists",
+            status.name
+        )));
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO statuses (name, color, sort_order, is_done, created_at, updated_at)
+        VALUES (?1,?2,?3,?4,?5,?6)
+    "#,
+    )
+    .bind(&status.name)
+    .bind(&status.color)
+    .bind(status.sort_order)
+    .bind(status.is_done)
+    .bind(status.created_at)
+    .bind(status.updated_at)
+    .execute(&st.pool)
+    .await?;
+    emit(&st.hub, "status.created", &status);
+    Ok(Json(status))
+}
+
+async fn update_status(
+    State(st): State<AppState>,
+    Path(name): Path<String>,
+    JsonBody(body): JsonBody<StatusUpdate>,
+) -> ApiResult<Json<Status>> {
+    let statuses = Statuses::new(st.pool.clone());
+    let before = statuses.get(&name).await?.ok_or(ApiError::NotFound)?;
+    let mut status = before.clone();
+    if let Some(v) = body.color {
+        status.color = Some(v);
+    }
+    if let Some(v) = body.sort_order {
+        status.sort_order = v;
+    }
+    if let Some(v) = body.is_done {
+        status.is_done = v;
+    }
+    validate(&status)?;
+    if before.is_done == 0 && status.is_done != 0 && statuses.open_count().await? <= 1 {
+        return Err(ApiError::Conflict(
+            "at least one open status must remain".into(),
+        ));
+    }
+    status.updated_at = Utc::now();
+    sqlx::query(
+        "UPDATE statuses SET color=?2, sort_order=?3, is_done=?4, updated_at=?5 WHERE name=?1",
+    )
+    .bind(&status.name)
+    .bind(&status.color)
+    .bind(status.sort_order)
+    .bind(status.is_done)
+    .bind(status.updated_at)
+    .execute(&st.pool)
+    .await?;
+    emit(&st.hub, "status.updated", &status);
+    Ok(Json(status))
+}
+
+async fn delete_status(
+    State(st): State<AppState>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let statuses = Statuses::new(st.pool.clone());
+    let status = statuses.get(&name).await?.ok_or(ApiError::NotFound)?;
+    let in_use = st
+        .todos
+        .list(&TodoFilter {
+            status: Some(name.clone()),
+            include_deleted: true,
+            ..Default::default()
+        })
+        .await?
+        .len();
+    if in_use > 0 {
+        return Err(ApiError::Conflict(format!(
+            "{in_use} todo(s) still use status `{name}`; move them first"
+        )));
+    }
+    if status.is_done == 0 && statuses.open_count().await? <= 1 {
+        return Err(ApiError::Conflict(
+            "at least one open status must remain".into(),
+        ));
+    }
+    sqlx::query("DELETE FROM statuses WHERE name=?1")
+        .bind(&name)
+        .execute(&st.pool)
+        .await?;
+    emit(&st.hub, "status.deleted", &json!({"name": name}));
+    Ok(Json(json!({"ok": true})))
+}
@@ -0,0 +1,143 @@
+/**
+ * Health of the machine the server runs on
+ *
+ * When the server misbehaves on a Raspberry Pi the cause is usually the
+ * board, not the code: a full SD card, a throttling CPU, no memory left.
+ * These values are shown in GET /api/health and exported as gauges at
+ * /metrics:
+ *
+ * ```text
+ * cpu_temp_c           /sys/class/thermal/thermal_zone0/temp (JOBS_THERMAL_PATH)
+ * load_1m, _5m, _15m   /proc/loadavg
+ * memory_*_bytes       /proc/meminfo (MemTotal, MemAvailable)
+ * disk_*_bytes         statvfs of the directory holding the SQLite database
+ * ```
+ *
+ * Every value is None where the platform does not provide it (no thermal
+ * zone in a container, no /proc off Linux). Reading them costs a few small
+ * file reads, cheap enough for every health check and scrape.
+ */
+use std::{
+    env,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Serialize;
+use sqlx::sqlite::SqliteConnectOptions;
+
+use crate::config::ServerConfig;
+
+/// Below this much free space on the data partition the server reports
+/// itself unhealthy: SQLite, backups and attachments are about to fail.
+pub const LOW_DISK_BYTES: u64 = 100 * 1024 * 1024;
+
+/**
+ * Host values for /api/health
+ */
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SystemHealth {
+    pub cpu_temp_c: Option<f64>,         // SoC temperature in degrees Celsius
+    pub load_1m: Option<f64>,            // Load averages over 1, 5 and 15 minutes
+    pub load_5m: Option<f64>,            // ...
+    pub load_15m: Option<f64>,           // ...
+    pub memory_total_bytes: Option<u64>, // Installed RAM
+    pub memory_available_bytes: Option<u64>, // RAM available without swapping
+    pub data_dir: String,                // Directory the disk values are for
+    pub disk_total_bytes: Option<u64>,   // Size of the data partition
+    pub disk_available_bytes: Option<u64>, // Free space the server may use
+}
+
+impl SystemHealth {
+    /// Read the current values; disk space is that of `data_dir`'s partition.
+    pub fn read(data_dir: &Path) -> Self {
+        let load = load_average();
+        let (memory_total_bytes, memory_available_bytes) = memory();
+        let (disk_total_bytes, disk_available_bytes) = disk(data_dir);
+        Self {
+            cpu_temp_c: cpu_temp(&thermal_path()),
+            load_1m: load.map(|l| l[0]),
+            load_5m: load.map(|l| l[1]),
+            load_15m: load.map(|l| l[2]),
+            memory_total_bytes,
+            memory_available_bytes,
+            data_dir: data_dir.display().to_string(),
+            disk_total_bytes,
+            disk_available_bytes,
+        }
+    }
+
+    /// Whether the data partition is (nearly) full.
+    pub fn disk_low(&self) -> bool {
+        self.disk_available_bytes
+            .is_some_and(|free| free < LOW_DISK_BYTES)
+    }
+}
+
+/// Thermal zone file: JOBS_THERMAL_PATH, default thermal_zone0.
+pub fn thermal_path() -> PathBuf {
+    env::var("JOBS_THERMAL_PATH")
+        .unwrap_or_else(|_| "/sys/class/thermal/thermal_zone0/temp".into())
+        .into()
+}
+
+/// CPU temperature in C from a sysfs thermal zone, if there is one.
+pub fn cpu_temp(thermal_path: &Path) -> Option<f64> {
+    let raw = std::fs::read_to_string(thermal_path).ok()?;
+    // sysfs reports millidegrees
+    raw.trim().parse::<f64>().ok().map(|m| m / 1000.0)
+}
+
+/// Directory of the server's SQLite database (the local one with Postgres).
+pub fn data_dir(config: &ServerConfig) -> PathBuf {
+    let url = if config.database_url.starts_with("postgres") {
+        &config.local_database_url
+    } else {
+        &config.database_url
+    };
+    let file = SqliteConnectOptions::from_str(url)
+        .map(|o| o.get_filename().to_path_buf())
+        .unwrap_or_default();
+    match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."), // In-memory database or a bare file name
+    }
+}
+
+/// 1, 5 and 15 minute load averages.
+fn load_average() -> Option<[f64; 3]> {
+    let raw = std::fs::read_to_string("/proc/loadavg").ok()?;
+    let mut fields = raw.split_whitespace().map(|f| f.parse::<f64>().ok());
+    Some([fields.next()??, fields.next()??, fields.next()??])
+}
+
+/// MemTotal and MemAvailable in bytes.
+fn memory() -> (Option<u64>, Option<u64>) {
+    let Ok(raw) = std::fs::read_to_string("/proc/meminfo") else {
+        return (None, None);
+    };
+    let field = |name: &str| {
+        raw.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|rest| {
+                rest.trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .map(|kib| kib * 1024)
+    };
+    (field("MemTotal"), field("MemAvailable"))
+}
+
+/// Size and space available to unprivileged users of `dir`'s filesystem.
+fn disk(dir: &Path) -> (Option<u64>, Option<u64>) {
+    match rustix::fs::statvfs(dir) {
+        Ok(s) => (
+            Some(s.f_blocks.saturating_mul(s.f_frsize)),
+            Some(s.f_bavail.saturating_mul(s.f_frsize)),
+        ),
+        Err(_) => (None, None),
+    }
+}
@@ -0,0 +1,98 @@
+/**
+ * systemd service notifications (sd_notify) and watchdog
+ *
+ * With `Type=notify` the unit only counts as started once the listener is
+ * bound (READY=1), and with `WatchdogSec=` systemd restarts the server when
+ * the WATCHDOG=1 pings stop. Pings are only sent while an internal health
+ * self-check passes, so a server whose database is wedged gets restarted
+ * even though the process is still alive.
+ *
+ * Outside systemd (no NOTIFY_SOCKET) every call is a no-op.
+ */
+use std::{os::unix::net::UnixDatagram, time::Duration};
+
+use crate::routes::AppState;
+
+/// Send a raw sd_notify message; false when not running under systemd.
+pub fn notify(message: &str) -> bool {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let sent = UnixDatagram::unbound().and_then(|socket| {
+        let bytes = path.as_encoded_bytes();
+        match bytes.strip_prefix(b"@") {
+            // Abstract namespace socket (systemd's default for user services)
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                socket.send_to_addr(message.as_bytes(), &addr)
+            }
+            None => socket.send_to(message.as_bytes(), &path),
+        }
+    });
+    match sent {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!(error = %e, message, "sd_notify failed");
+            false
+        }
+    }
+}
+
+/// Tell systemd the server is accepting connections.
+pub fn ready() {
+    if notify("READY=1") {
+        tracing::debug!("notified systemd: ready");
+    }
+}
+
+/// Tell systemd a graceful shutdown has started.
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+/// Watchdog timeout requested by the unit (WatchdogSec=), if it is meant for us.
+pub fn watchdog_timeout() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse::<u32>().ok()? != std::process::id()
+    {
+        return None;
+    }
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Health self-check behind the watchdog: the database answers in time.
+pub async fn self_check(state: &AppState, timeout: Duration) -> anyhow::Result<()> {
+    tokio::time::timeout(timeout, sqlx::query("SELECT 1").execute(&state.pool))
+        .await
+        .map_err(|_| anyhow::anyhow!("database did not answer within {timeout:?}"))??;
+    Ok(())
+}
+
+/**
+ * Start the watchdog task when the unit has WatchdogSec= set
+ *
+ * Pings at half the timeout, as recommended by sd_watchdog_enabled(3).
+ */
+pub fn spawn_watchdog(state: AppState) {
+    let Some(timeout) = watchdog_timeout() else {
+        return;
+    };
+    let interval = timeout / 2;
+    tracing::info!(?timeout, "systemd watchdog enabled");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self_check(&state, interval).await {
+                Ok(()) => {
+                    notify("WATCHDOG=1");
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "health self-check failed, withholding watchdog ping")
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,332 @@
+/**
+ * Telegram bot
+ *
+ * Optional long-polling bot for managing the board from a phone:
+ * - /add buy milk tomorrow   creates a todo; a trailing "today", "tomorrow"
+ *   or weekday name becomes the due date (18:00 in the household zone)
+ * - /today                   lists open todos due today or overdue
+ * - /done 2                  completes item 2 of the last /today list
+ *   (a todo id or id prefix works too)
+ *
+ * Only chats listed in TELEGRAM_ALLOWED_CHATS are served; other chats get
+ * their chat id back so it can be added to the list. Changes go through
+ * the same helpers as the REST API, so WebSocket clients see them live.
+ */
+use std::{collections::HashMap, env, time::Duration};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    audit::Actor, error::ApiError, model::TodoCreate, routes::AppState, services::TodoFilter,
+    timezone,
+};
+
+const POLL_TIMEOUT_SECS: u64 = 50;
+
+/**
+ * Bot settings read from the environment
+ */
+#[derive(Debug, Clone)]
+pub struct TelegramConfig {
+    pub token: String,           // TELEGRAM_BOT_TOKEN - enables the bot when set
+    pub allowed_chats: Vec<i64>, // TELEGRAM_ALLOWED_CHATS, comma separated chat ids
+    pub api_url: String,         // TELEGRAM_API_URL, default https://api.telegram.org
+}
+
+impl TelegramConfig {
+    /// Returns None when TELEGRAM_BOT_TOKEN is not set.
+    pub fn from_env() -> Option<Self> {
+        let token = env::var("TELEGRAM_BOT_TOKEN").ok()?;
+        Some(Self {
+            token,
+            allowed_chats: env::var("TELEGRAM_ALLOWED_CHATS")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|s| s.trim().parse().ok())
+                .collect(),
+            api_url: env::var("TELEGRAM_API_URL")
+                .unwrap_or_else(|_| "https://api.telegram.org".into()),
+        })
+    }
+}
+
+/**
+ * Subset of the Bot API types we read
+ */
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/**
+ * Long-polling client; keeps the last /today listing per chat so
+ * "/done 2" can refer to it.
+ */
+struct Bot {
+    client: reqwest::Client,
+    config: TelegramConfig,
+    state: AppState,
+    listings: HashMap<i64, Vec<String>>,
+}
+
+/// Start the bot loop.
+pub fn spawn(config: TelegramConfig, state: AppState) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(POLL_TIMEOUT_SECS + 10))
+        .build()
+        .unwrap_or_default();
+    let bot = Bot {
+        client,
+        config,
+        state,
+        listings: HashMap::new(),
+    };
+    tokio::spawn(bot.run());
+}
+
+impl Bot {
+    fn method_url(&self, method: &str) -> String {
+        format!(
+            "{}/bot{}/{method}",
+            self.config.api_url.trim_end_matches('/'),
+            self.config.token
+        )
+    }
+
+    async fn run(mut self) {
+        tracing::info!("Telegram bot started");
+        let mut offset = 0;
+        loop {
+            match self.get_updates(offset).await {
+                Ok(updates) => {
+                    for update in updates {
+                        offset = offset.max(update.update_id + 1);
+                        if let Some(message) = update.message {
+                            self.handle(message).await;
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "Telegram polling failed, retrying");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    }
+
+    async fn get_updates(&self, offset: i64) -> anyhow::Result<Vec<Update>> {
+        let response: ApiResponse<Vec<Update>> = self
+            .client
+            .post(self.method_url("getUpdates"))
+            .json(&json!({
+                "offset": offset,
+                "timeout": POLL_TIMEOUT_SECS,
+                "allowed_updates": ["message"],
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if !response.ok {
+            return Err(anyhow!(
+                "getUpdates failed: {}",
+                response.description.unwrap_or_default()
+            ));
+        }
+        Ok(response.result.unwrap_or_default())
+    }
+
+    async fn send_message(&self, chat_id: i64, text: &str) {
+        let result = self
+            .client
+            .post(self.method_url("sendMessage"))
+            .json(&json!({ "chat_id": chat_id, "text": text }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "Telegram sendMessage failed");
+        }
+    }
+
+    async fn handle(&mut self, message: Message) {
+        let chat_id = message.chat.id;
+        let Some(text) = message.text else { return };
+        if !self.config.allowed_chats.contains(&chat_id) {
+            tracing::warn!(chat_id, "Telegram message from unknown chat ignored");
+            let reply =
+                format!("This chat is not authorized. Add {chat_id} to TELEGRAM_ALLOWED_CHATS.");
+            self.send_message(chat_id, &reply).await;
+            return;
+        }
+
+        let (command, args) = text.trim().split_once(' ').unwrap_or((text.trim(), ""));
+        // Commands in groups arrive as /add@my_bot
+        let command = command.split('@').next().unwrap_or_default();
+        let reply = match command {
+            "/add" => self.add(args.trim()).await,
+            "/today" => self.today(chat_id).await,
+            "/done" => self.done(chat_id, args.trim()).await,
+            "/start" | "/help" => Ok(HELP.to_string()),
+            _ => Ok(format!("Unknown command.\n\n{HELP}")),
+        };
+        let reply = reply.unwrap_or_else(|e| format!("Error: {e}"));
+        self.send_message(chat_id, &reply).await;
+    }
+
+    async fn add(&self, args: &str) -> anyhow::Result<String> {
+        let zone = self.state.config.zone();
+        let (title, due_at) = parse_add(args, Utc::now().with_timezone(&zone).date_naive(), zone);
+        if title.is_empty() {
+            return Ok("Usage: /add <title> [today|tomorrow|monday..sunday]".into());
+        }
+        let todo = self
+            .state
+            .todos
+            .as_actor(Actor::new("telegram"))
+            .create(TodoCreate {
+                title,
+                due_at,
+                ..Default::default()
+            })
+            .await?;
+        Ok(match todo.due_at {
+            Some(due) => format!(
+                "Added \"{}\" (due {})",
+                todo.title,
+                due.with_timezone(&zone).format("%a %d %b %H:%M")
+            ),
+            None => format!("Added \"{}\"", todo.title),
+        })
+    }
+
+    async fn today(&mut self, chat_id: i64) -> anyhow::Result<String> {
+        let zone = self.state.config.zone();
+        let today = Utc::now().with_timezone(&zone).date_naive();
+        let end_of_today = timezone::midnight(zone, today + Days::new(1));
+        let todos = self.state.todos.open_due_before(end_of_today).await?;
+
+        if todos.is_empty() {
+            self.listings.remove(&chat_id);
+            return Ok("Nothing due today.".into());
+        }
+        let now = Utc::now();
+        let mut out = String::from("Due today:\n");
+        for (i, t) in todos.iter().enumerate() {
+            let overdue = if t.due_at < Some(now) {
+                " (overdue)"
+            } else {
+                ""
+            };
+            out.push_str(&format!("{}. {}{overdue}\n", i + 1, t.title));
+        }
+        out.push_str("\nReply /done <number> to complete an item.");
+        self.listings
+            .insert(chat_id, todos.into_iter().map(|t| t.id).collect());
+        Ok(out)
+    }
+
+    async fn done(&self, chat_id: i64, args: &str) -> anyhow::Result<String> {
+        if args.is_empty() {
+            return Ok("Usage: /done <number from /today or todo id>".into());
+        }
+        let id = match args.parse::<usize>() {
+            Ok(n) => self
+                .listings
+                .get(&chat_id)
+                .and_then(|ids| ids.get(n.wrapping_sub(1)))
+                .cloned()
+                .ok_or_else(|| anyhow!("no item {n} in the last /today list"))?,
+            Err(_) => self.resolve_id(args).await?,
+        };
+        match self
+            .state
+            .todos
+            .as_actor(Actor::new("telegram"))
+            .set_status(&id, "done".into())
+            .await
+        {
+            Ok(todo) => Ok(format!("Done: {}", todo.title)),
+            Err(ApiError::NotFound) => Ok("That todo no longer exists.".into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Resolve a full id or unambiguous id prefix.
+    async fn resolve_id(&self, prefix: &str) -> anyhow::Result<String> {
+        let ids: Vec<String> = self
+            .state
+            .todos
+            .list(&TodoFilter::default())
+            .await?
+            .into_iter()
+            .map(|t| t.id)
+            .filter(|id| id.starts_with(prefix))
+            .take(2)
+            .collect();
+        match ids.as_slice() {
+            [id] => Ok(id.clone()),
+            [] => Err(anyhow!("no todo matches `{prefix}`")),
+            _ => Err(anyhow!("`{prefix}` matches several todos")),
+        }
+    }
+}
+
+const HELP: &str = "Commands:\n\
+/add <title> [today|tomorrow|weekday] - add a todo\n\
+/today - list todos due today\n\
+/done <number> - complete an item from /today";
+
+/// Split "buy milk tomorrow" into the title and an optional due date.
+fn parse_add(args: &str, today: NaiveDate, zone: Tz) -> (String, Option<DateTime<Utc>>) {
+    let args = args.trim();
+    let Some((title, last)) = args.rsplit_once(' ') else {
+        return (args.to_string(), None);
+    };
+    let date = match last.to_lowercase().as_str() {
+        "today" | "tonight" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        day => day.parse::<Weekday>().ok().map(|weekday| {
+            // Next occurrence, never today ("friday" on a Friday means next week)
+            let ahead =
+                (weekday.num_days_from_monday() + 6 - today.weekday().num_days_from_monday()) % 7
+                    + 1;
+            today + chrono::Duration::days(ahead.into())
+        }),
+    };
+    match date.and_then(|d| due_time(d, zone)) {
+        Some(due) => (title.trim().to_string(), Some(due)),
+        None => (args.to_string(), None),
+    }
+}
+
+/// Default due time for dates given in words: 18:00 in the household zone.
+fn due_time(date: NaiveDate, zone: Tz) -> Option<DateTime<Utc>> {
+    date.and_time(NaiveTime::from_hms_opt(18, 0, 0)?)
+        .and_local_timezone(zone)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+}
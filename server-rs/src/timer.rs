@@ -0,0 +1,258 @@
+/**
+ * Time tracking on todos
+ *
+ * A start/stop timer per todo, stored as time entries. Starting a timer
+ * stops the caller's other running timers, so one person tracks one thing
+ * at a time. The todo carries `timer_started_at` (running timer) and
+ * `tracked_secs` (finished entries), so boards can show a running clock
+ * and totals without loading the entries.
+ *
+ * - GET    /api/todos/{id}/time                 entries and total
+ * - POST   /api/todos/{id}/timer/start
+ * - POST   /api/todos/{id}/timer/stop
+ * - DELETE /api/todos/{id}/time/{entry}
+ * - GET    /api/time/report?from=&to=&project_id=   tracked seconds per todo
+ *
+ * Events: `timer.started` / `timer.stopped` with the entry, plus
+ * `todo.updated` for the mirrored fields.
+ */
+use std::collections::BTreeMap;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    routing::{delete, get, post},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    audit::Actor,
+    error::{ApiError, ApiResult},
+    model::TimeEntry,
+    routes::AppState,
+    services::{TodoFilter, emit},
+    timezone::Zone,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/todos/{id}/time", get(list_entries))
+        .route("/api/todos/{id}/timer/start", post(start_timer))
+        .route("/api/todos/{id}/timer/stop", post(stop_timer))
+        .route("/api/todos/{id}/time/{entry}", delete(delete_entry))
+        .route("/api/time/report", get(report))
+}
+
+async fn entries(st: &AppState, todo_id: &str) -> ApiResult<Vec<TimeEntry>> {
+    Ok(sqlx::query_as::<_, TimeEntry>(
+        "SELECT * FROM time_entries WHERE todo_id=?1 ORDER BY started_at ASC",
+    )
+    .bind(todo_id)
+    .fetch_all(&st.pool)
+    .await?)
+}
+
+/// Seconds of `entry` that fall into `from..to`; running entries count up to now.
+fn secs_within(entry: &TimeEntry, from: DateTime<Utc>, to: DateTime<Utc>) -> i64 {
+    let end = entry.ended_at.unwrap_or_else(Utc::now).min(to);
+    (end - entry.started_at.max(from)).num_seconds().max(0)
+}
+
+/// Mirror the running timer and finished total onto the todo.
+async fn sync(st: &AppState, actor: Actor, todo_id: &str) -> ApiResult<Vec<TimeEntry>> {
+    let entries = entries(st, todo_id).await?;
+    let running = entries
+        .iter()
+        .filter(|e| e.ended_at.is_none())
+        .map(|e| e.started_at)
+        .min();
+    let tracked = entries
+        .iter()
+        .filter(|e| e.ended_at.is_some())
+        .map(|e| secs_within(e, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC))
+        .sum();
+    st.todos
+        .as_actor(actor)
+        .set_time_tracking(todo_id, running, tracked)
+        .await?;
+    Ok(entries)
+}
+
+async fn list_entries(
+    State(st): State<AppState>,
+    Path(todo_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let todo = st.todos.get(&todo_id).await?;
+    let entries = entries(&st, &todo_id).await?;
+    let total: i64 = entries
+        .iter()
+        .map(|e| secs_within(e, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC))
+        .sum();
+    Ok(Json(json!({
+        "todo_id": todo.id,
+        "timer_started_at": todo.timer_started_at,
+        "total_secs": total,
+        "entries": entries,
+    })))
+}
+
+/// Stop the running entries matching `condition` (SQL on time_entries).
+async fn stop_running(st: &AppState, actor: &Actor, condition: &str, value: &str) -> ApiResult<()> {
+    let running = sqlx::query_as::<_, TimeEntry>(&format!(
+        "SELECT * FROM time_entries WHERE ended_at IS NULL AND {condition}"
+    ))
+    .bind(value)
+    .fetch_all(&st.pool)
+    .await?;
+    for mut entry in running {
+        entry.ended_at = Some(Utc::now());
+        sqlx::query("UPDATE time_entries SET ended_at=?2 WHERE id=?1")
+            .bind(&entry.id)
+            .bind(entry.ended_at)
+            .execute(&st.pool)
+            .await?;
+        sync(st, actor.clone(), &entry.todo_id).await?;
+        emit(&st.hub, "timer.stopped", &entry);
+    }
+    Ok(())
+}
+
+async fn start_timer(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(todo_id): Path<String>,
+) -> ApiResult<Json<TimeEntry>> {
+    let todo = st.todos.get(&todo_id).await?;
+    if todo.timer_started_at.is_some() {
+        return Err(ApiError::Conflict("timer is already running".into()));
+    }
+    stop_running(&st, &actor, "actor=?1", actor.as_str()).await?;
+    let entry = TimeEntry {
+        id: Uuid::new_v4().to_string(),
+        todo_id,
+        started_at: Utc::now(),
+        ended_at: None,
+        actor: actor.as_str().to_string(),
+    };
+    sqlx::query(
+        "INSERT INTO time_entries (id,todo_id,started_at,ended_at,actor) VALUES (?1,?2,?3,NULL,?4)",
+    )
+    .bind(&entry.id)
+    .bind(&entry.todo_id)
+    .bind(entry.started_at)
+    .bind(&entry.actor)
+    .execute(&st.pool)
+    .await?;
+    sync(&st, actor, &entry.todo_id).await?;
+    emit(&st.hub, "timer.started", &entry);
+    Ok(Json(entry))
+}
+
+async fn stop_timer(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path(todo_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let todo = st.todos.get(&todo_id).await?;
+    if todo.timer_started_at.is_none() {
+        return Err(ApiError::Conflict("no timer is running".into()));
+    }
+    stop_running(&st, &actor, "todo_id=?1", &todo_id).await?;
+    let todo = st.todos.get(&todo_id).await?;
+    Ok(Json(
+        json!({"todo_id": todo.id, "tracked_secs": todo.tracked_secs}),
+    ))
+}
+
+async fn delete_entry(
+    State(st): State<AppState>,
+    actor: Actor,
+    Path((todo_id, id)): Path<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let removed = sqlx::query("DELETE FROM time_entries WHERE id=?1 AND todo_id=?2")
+        .bind(&id)
+        .bind(&todo_id)
+        .execute(&st.pool)
+        .await?
+        .rows_affected();
+    if removed == 0 {
+        return Err(ApiError::NotFound);
+    }
+    sync(&st, actor, &todo_id).await?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+struct ReportParams {
+    from: Option<NaiveDate>, // First day (request zone), inclusive; default: no limit
+    to: Option<NaiveDate>,   // Last day (request zone), inclusive; default: no limit
+    project_id: Option<String>, // Only todos in this project
+}
+
+/// Tracked seconds per todo (and in total) between two dates in the
+/// request's zone (see timezone.rs).
+async fn report(
+    State(st): State<AppState>,
+    zone: Zone,
+    Query(p): Query<ReportParams>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let from = p
+        .from
+        .map(|d| zone.midnight(d))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let to =
+        p.to.map(|d| zone.midnight(d + chrono::Days::new(1)))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+    if to <= from {
+        return Err(ApiError::BadRequest(
+            "`to` must be on or after `from`".into(),
+        ));
+    }
+    let filter = TodoFilter {
+        include_deleted: true,
+        project_id: p.project_id,
+        ..Default::default()
+    };
+    let todos: BTreeMap<String, _> = st
+        .todos
+        .list(&filter)
+        .await?
+        .into_iter()
+        .map(|t| (t.id.clone(), t))
+        .collect();
+
+    let mut secs: BTreeMap<&str, i64> = BTreeMap::new();
+    let all = sqlx::query_as::<_, TimeEntry>("SELECT * FROM time_entries")
+        .fetch_all(&st.pool)
+        .await?;
+    for entry in &all {
+        if let Some((id, _)) = todos.get_key_value(&entry.todo_id) {
+            *secs.entry(id).or_default() += secs_within(entry, from, to);
+        }
+    }
+    let mut rows: Vec<serde_json::Value> = secs
+        .into_iter()
+        .filter(|(_, s)| *s > 0)
+        .map(|(id, s)| {
+            let t = &todos[id];
+            json!({
+                "todo_id": id,
+                "title": t.title,
+                "category_id": t.category_id,
+                "project_id": t.project_id,
+                "tracked_secs": s,
+            })
+        })
+        .collect();
+    rows.sort_by_key(|r| std::cmp::Reverse(r["tracked_secs"].as_i64()));
+    let total: i64 = rows.iter().filter_map(|r| r["tracked_secs"].as_i64()).sum();
+    Ok(Json(json!({
+        "from": p.from,
+        "to": p.to,
+        "total_secs": total,
+        "todos": rows,
+    })))
+}
@@ -0,0 +1,131 @@
+/**
+ * Time zones for calendar days
+ *
+ * Due dates are stored as UTC instants; only "which day is today" and
+ * "which day is this todo on" depend on a zone. Endpoints that group by
+ * day ("today", calendar, workload, filters like `due:today`, time
+ * reports) resolve the zone of the request, first match wins:
+ *
+ * ```text
+ * ?tz=Europe/Berlin             one request (e.g. a travelling phone)
+ * X-Timezone: Europe/Berlin     a client that always sends its zone
+ * the caller's `timezone`       setting (X-User-Id or a session, see settings.rs)
+ * config `timezone`             household default
+ * the system zone               /etc/localtime or TZ; UTC when unknown
+ * ```
+ *
+ * Background work (reminder texts, the daily digest) uses the recipient's
+ * setting, else the household default, so reminders read 9:00 for a 9:00
+ * due date however the Pi's clock is configured.
+ */
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult},
+    routes::AppState,
+    settings,
+};
+
+/// Request header carrying the client's IANA time zone.
+pub const ZONE_HEADER: &str = "x-timezone";
+
+/// The system's zone, UTC when it cannot be determined.
+pub fn system() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|tz| tz.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// Parse an IANA zone name from a client.
+pub fn parse(name: &str) -> ApiResult<Tz> {
+    name.trim().parse().map_err(|_| {
+        ApiError::BadRequest(format!(
+            "unknown time zone `{name}` (expected an IANA name such as Europe/Berlin)"
+        ))
+    })
+}
+
+/// The zone of `user_id`: their `timezone` setting, else `default`.
+pub async fn of_user(pool: &SqlitePool, user_id: &str, default: Tz) -> ApiResult<Tz> {
+    let setting = settings::value(pool, user_id, "timezone").await?;
+    Ok(setting
+        .as_str()
+        .and_then(|tz| tz.parse().ok())
+        .unwrap_or(default))
+}
+
+/// Start of `day` in `tz`, in UTC (the first valid instant when midnight
+/// falls into a DST gap).
+pub fn midnight(tz: Tz, day: NaiveDate) -> DateTime<Utc> {
+    let local = day.and_time(NaiveTime::MIN);
+    (0..=2)
+        .find_map(|h| {
+            (local + chrono::Duration::hours(h))
+                .and_local_timezone(tz)
+                .earliest()
+        })
+        .map_or_else(|| local.and_utc(), |d| d.with_timezone(&Utc))
+}
+
+#[derive(Deserialize)]
+struct ZoneParams {
+    tz: Option<String>, // IANA zone for this request
+}
+
+/**
+ * Extractor: the zone a request's days are counted in (see module docs)
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Zone(pub Tz);
+
+impl Zone {
+    /// Today's date in this zone.
+    pub fn today(self) -> NaiveDate {
+        Utc::now().with_timezone(&self.0).date_naive()
+    }
+
+    /// The date of `t` in this zone.
+    pub fn date(self, t: DateTime<Utc>) -> NaiveDate {
+        t.with_timezone(&self.0).date_naive()
+    }
+
+    /// Start of `day` in this zone, in UTC.
+    pub fn midnight(self, day: NaiveDate) -> DateTime<Utc> {
+        midnight(self.0, day)
+    }
+}
+
+impl FromRequestParts<AppState> for Zone {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, st: &AppState) -> Result<Self, Self::Rejection> {
+        let query = Query::<ZoneParams>::try_from_uri(&parts.uri)
+            .ok()
+            .and_then(|Query(p)| p.tz)
+            .filter(|v| !v.trim().is_empty());
+        let header = parts
+            .headers
+            .get(ZONE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.trim().is_empty())
+            .map(str::to_string);
+        if let Some(name) = query.or(header) {
+            return Ok(Zone(parse(&name)?));
+        }
+        let Ok(actor) = Actor::from_request_parts(parts, st).await;
+        let default = st.config.zone();
+        Ok(Zone(match actor.user_id() {
+            Some(user_id) => of_user(&st.pool, user_id, default).await?,
+            None => default,
+        }))
+    }
+}
@@ -0,0 +1,71 @@
+/**
+ * HTTPS with a static certificate
+ *
+ * For certificates managed outside the server (certbot, a home CA, ...):
+ * `tls_cert`/`tls_key` (TLS_CERT/TLS_KEY) switch the main listener to
+ * HTTPS. The files are re-read every 12 hours so renewals are picked up
+ * without a restart. With `tls_redirect_port` set, a plain HTTP listener
+ * answers every request with a permanent redirect to the HTTPS URL.
+ *
+ * Certificates issued by the built-in ACME client are handled in acme.rs.
+ */
+use std::time::Duration;
+
+use axum::{
+    Router,
+    extract::Request,
+    http::{StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_server::tls_rustls::RustlsConfig;
+
+const RELOAD_INTERVAL: Duration = Duration::from_secs(12 * 3600);
+
+/// Load the PEM files (rustls provider must be installed by the caller).
+pub async fn load(cert: &str, key: &str) -> anyhow::Result<RustlsConfig> {
+    Ok(RustlsConfig::from_pem_file(cert, key).await?)
+}
+
+/// Periodically re-read the certificate so renewals take effect.
+pub async fn reload_loop(tls: RustlsConfig, cert: String, key: String) {
+    loop {
+        tokio::time::sleep(RELOAD_INTERVAL).await;
+        match tls.reload_from_pem_file(&cert, &key).await {
+            Ok(()) => tracing::debug!("TLS certificate reloaded"),
+            Err(e) => {
+                tracing::warn!(error = %e, "TLS certificate reload failed, keeping the old one")
+            }
+        }
+    }
+}
+
+/// Router redirecting every request to the same URL on `https://host:https_port`.
+pub fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(move |req: Request| async move { redirect(req, https_port) })
+}
+
+fn redirect(req: Request, https_port: u16) -> Response {
+    let Some(host) = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "missing Host header").into_response();
+    };
+    // Drop any port from the Host header; keep IPv6 brackets intact
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let target = if https_port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{https_port}{path}")
+    };
+    Redirect::permanent(&target).into_response()
+}
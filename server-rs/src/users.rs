@@ -0,0 +1,309 @@
+/**
+ * User endpoints
+ *
+ * Users are household members; for now they only carry notification
+ * preferences (e-mail address, reminder and digest opt-ins). Everything
+ * else a client wants to remember lives in their settings (settings.rs).
+ *
+ * Privacy requests ("delete my stuff" when a housemate moves out):
+ * - GET  /api/users/{id}/export      everything stored about the user
+ * - POST /api/users/{id}/anonymize   export, then scrub the user and hand
+ *   their webhooks to `reassign_to` (or remove them)
+ *
+ * Members can do the same for themselves at /api/me (see account.rs).
+ */
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::types::chrono::Utc;
+
+use crate::{
+    audit::Actor,
+    db::SqlitePool,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{AuditEntry, CategoryMember, User, UserCreate, UserNotification, UserUpdate, Webhook},
+    routes::AppState,
+    settings,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/users", get(list_users).post(create_user))
+        .route(
+            "/api/users/{id}",
+            get(get_user).put(update_user).delete(delete_user),
+        )
+        .route("/api/users/{id}/export", get(export_user_route))
+        .route("/api/users/{id}/anonymize", post(anonymize_user_route))
+}
+
+async fn list_users(State(st): State<AppState>) -> ApiResult<Json<Vec<User>>> {
+    let rows = sqlx::query_as::<_, User>("SELECT * FROM users WHERE deleted = 0 ORDER BY name ASC")
+        .fetch_all(&st.pool)
+        .await?;
+    Ok(Json(rows))
+}
+
+async fn create_user(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<UserCreate>,
+) -> ApiResult<Json<User>> {
+    if body.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    let user = User::new_from_create(body);
+    sqlx::query(
+        r#"
+        INSERT INTO users (id,name,email,email_reminders,email_digest,created_at,updated_at,deleted)
+        VALUES (?1,?2,?3,?4,?5,?6,?7,?8)
+    "#,
+    )
+    .bind(&user.id)
+    .bind(&user.name)
+    .bind(&user.email)
+    .bind(user.email_reminders)
+    .bind(user.email_digest)
+    .bind(user.created_at)
+    .bind(user.updated_at)
+    .bind(user.deleted)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(user))
+}
+
+async fn get_user(State(st): State<AppState>, Path(id): Path<String>) -> ApiResult<Json<User>> {
+    let row = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id=?1")
+        .bind(&id)
+        .fetch_optional(&st.pool)
+        .await?;
+    row.map(Json).ok_or(ApiError::NotFound)
+}
+
+async fn update_user(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<UserUpdate>,
+) -> ApiResult<Json<User>> {
+    let mut u: User = sqlx::query_as("SELECT * FROM users WHERE id=?1")
+        .bind(&id)
+        .fetch_optional(&st.pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if let Some(v) = body.name {
+        u.name = v;
+    }
+    if let Some(v) = body.email {
+        u.email = Some(v);
+    }
+    if let Some(v) = body.email_reminders {
+        u.email_reminders = v;
+    }
+    if let Some(v) = body.email_digest {
+        u.email_digest = v;
+    }
+    if let Some(v) = body.deleted {
+        u.deleted = v;
+    }
+    u.updated_at = Utc::now();
+
+    sqlx::query(
+        r#"
+        UPDATE users SET
+        name=?2, email=?3, email_reminders=?4, email_digest=?5, updated_at=?6, deleted=?7
+        WHERE id=?1
+    "#,
+    )
+    .bind(&u.id)
+    .bind(&u.name)
+    .bind(&u.email)
+    .bind(u.email_reminders)
+    .bind(u.email_digest)
+    .bind(u.updated_at)
+    .bind(u.deleted)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(u))
+}
+
+async fn delete_user(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let res = sqlx::query("UPDATE users SET deleted=1, updated_at=?2 WHERE id=?1")
+        .bind(&id)
+        .bind(Utc::now())
+        .execute(&st.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    // Signed-in devices of a removed member stop working
+    sqlx::query("UPDATE sessions SET revoked_at=?2 WHERE user_id=?1 AND revoked_at IS NULL")
+        .bind(&id)
+        .bind(Utc::now())
+        .execute(&st.pool)
+        .await?;
+    Ok(Json(json!({"ok": true})))
+}
+
+/// Everything stored about a user, as one machine-readable document.
+pub async fn export_user(pool: &SqlitePool, id: &str) -> ApiResult<serde_json::Value> {
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id=?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let webhooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE user_id=?1")
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+    let changes =
+        sqlx::query_as::<_, AuditEntry>("SELECT * FROM audit_log WHERE actor=?1 ORDER BY id ASC")
+            .bind(Actor::user(id).as_str())
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(AuditEntry::opened)
+            .collect::<Vec<_>>();
+    let memberships =
+        sqlx::query_as::<_, CategoryMember>("SELECT * FROM category_members WHERE user_id=?1")
+            .bind(id)
+            .fetch_all(pool)
+            .await?;
+    let notifications = sqlx::query_as::<_, UserNotification>(
+        "SELECT * FROM notifications WHERE user_id=?1 ORDER BY created_at ASC",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+    let settings = settings::all(pool, id).await?;
+    Ok(json!({
+        "exported_at": Utc::now(),
+        "user": user,
+        "webhooks": webhooks,
+        "memberships": memberships,
+        "notifications": notifications,
+        "settings": settings,
+        "changes": changes,
+    }))
+}
+
+/**
+ * Export a user's data, then scrub it
+ *
+ * The user row stays (soft-deleted, name and address removed) so
+ * references keep resolving (assigned todos too); owned webhooks move to
+ * `reassign_to` or are deleted, category memberships, notifications,
+ * preferences, sessions and linked sign-in accounts end. Returns the
+ * export taken before scrubbing.
+ */
+pub async fn anonymize_user(
+    pool: &SqlitePool,
+    id: &str,
+    reassign_to: Option<&str>,
+) -> ApiResult<serde_json::Value> {
+    let export = export_user(pool, id).await?;
+    if let Some(target) = reassign_to {
+        if target == id {
+            return Err(ApiError::BadRequest(
+                "cannot reassign to the same user".into(),
+            ));
+        }
+        sqlx::query_scalar::<_, String>("SELECT id FROM users WHERE id=?1 AND deleted=0")
+            .bind(target)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| ApiError::BadRequest(format!("unknown user `{target}`")))?;
+    }
+
+    let mut tx = pool.begin().await?;
+    scrub(&mut tx, id, reassign_to).await?;
+    tx.commit().await?;
+    tracing::info!(user = %id, "user anonymized");
+    Ok(export)
+}
+
+/// The scrubbing part of anonymize_user, inside the caller's transaction
+/// (`reassign_to` is checked by the caller).
+pub(crate) async fn scrub(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    id: &str,
+    reassign_to: Option<&str>,
+) -> ApiResult<()> {
+    match reassign_to {
+        Some(target) => {
+            sqlx::query("UPDATE webhooks SET user_id=?2, updated_at=?3 WHERE user_id=?1")
+                .bind(id)
+                .bind(target)
+                .bind(Utc::now())
+                .execute(&mut **tx)
+                .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM webhooks WHERE user_id=?1")
+                .bind(id)
+                .execute(&mut **tx)
+                .await?;
+        }
+    }
+    sqlx::query("DELETE FROM category_members WHERE user_id=?1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM notifications WHERE user_id=?1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM user_settings WHERE user_id=?1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM sessions WHERE user_id=?1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM user_identities WHERE user_id=?1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        r#"
+        UPDATE users SET name='Former member', email=NULL, email_reminders=0, email_digest=0,
+                         deleted=1, updated_at=?2
+        WHERE id=?1
+    "#,
+    )
+    .bind(id)
+    .bind(Utc::now())
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn export_user_route(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    Ok(Json(export_user(&st.pool, &id).await?))
+}
+
+#[derive(Deserialize, Default)]
+struct AnonymizeBody {
+    reassign_to: Option<String>,
+}
+
+async fn anonymize_user_route(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+    body: Option<Json<AnonymizeBody>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let body = body.map(|Json(b)| b).unwrap_or_default();
+    Ok(Json(
+        anonymize_user(&st.pool, &id, body.reassign_to.as_deref()).await?,
+    ))
+}
@@ -0,0 +1,100 @@
+/**
+ * REST API versions
+ *
+ * Every endpoint is served under a version prefix:
+ *
+ * ```text
+ * /api/v1/todos        current contract (API_VERSION in capabilities.rs)
+ * /api/todos           deprecated alias of v1, answered with
+ *                      `Deprecation: true` and a successor-version Link
+ * ```
+ *
+ * The route table (routes::api_router and the module routers) keeps its
+ * `/api/...` paths. A versioned request is rewritten to that path before
+ * routing, with its version stored in the request (the [`ApiVersion`]
+ * extractor), so middleware and handlers see one set of paths for every
+ * version. Unversioned requests count as v1.
+ *
+ * A breaking change (typed enums, new error bodies) ships as v2: mount,
+ * next to v1 in lib.rs, a router holding only the changed routes with the
+ * v1 router as its fallback, and bump CURRENT. Handlers whose behaviour
+ * differs only slightly may branch on ApiVersion instead.
+ */
+use axum::{
+    Router,
+    extract::{FromRequestParts, Request},
+    http::{Uri, request::Parts},
+};
+use tower::ServiceExt;
+
+/// Newest version; paths the server hands out (share links) use it.
+pub const CURRENT: u32 = 1;
+
+/// Version of requests without a prefix (the deprecated /api/... alias).
+pub const UNVERSIONED: u32 = 1;
+
+/**
+ * Extractor: the API version a request was made against
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiVersion(pub u32);
+
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or(ApiVersion(UNVERSIONED)))
+    }
+}
+
+/// "/api/v1" for 1.
+pub fn prefix(version: u32) -> String {
+    format!("/api/v{version}")
+}
+
+/// The version in a path starting with /api/v{n}/ (or equal to /api/v{n}).
+pub fn of_path(path: &str) -> Option<u32> {
+    let rest = path.strip_prefix("/api/v")?;
+    let end = rest.find('/').unwrap_or(rest.len());
+    let (digits, _) = rest.split_at(end);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Whether `path` uses the deprecated unversioned /api/... form.
+pub fn is_unversioned(path: &str) -> bool {
+    path.starts_with("/api/") && of_path(path).is_none()
+}
+
+/**
+ * `api` (a router with /api/... paths) served under /api/v{version}
+ *
+ * Nested under the prefix, so `api` sees the path without it; `/api` is
+ * put back in front and the version recorded before `api` routes it.
+ */
+pub fn mount(version: u32, api: Router) -> Router {
+    let versioned = tower::service_fn(move |mut req: Request| {
+        let api = api.clone();
+        async move {
+            let path_and_query = req
+                .uri()
+                .path_and_query()
+                .map_or("/", |p| p.as_str())
+                .to_string();
+            let mut parts = req.uri().clone().into_parts();
+            parts.path_and_query = format!("/api{path_and_query}").parse().ok();
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *req.uri_mut() = uri;
+            }
+            req.extensions_mut().insert(ApiVersion(version));
+            api.oneshot(req).await
+        }
+    });
+    Router::new().nest_service(&prefix(version), versioned)
+}
@@ -0,0 +1,277 @@
+/**
+ * Outgoing webhooks
+ *
+ * Every hub event (todo.created, category.updated, ...) is POSTed as the
+ * same `{"type": ..., "data": ...}` JSON the WebSocket clients receive, to
+ * each enabled webhook whose filters match:
+ * - event_types    comma separated; "todo.*" matches every todo event
+ * - category_ids   the todo's category must be one of these
+ * - min_priority   the todo's priority must be at least this
+ * - assignee_id    the todo must be assigned to this user
+ *
+ * Unset filters match everything. Category, priority and assignee filters
+ * only pass events that carry a todo with the field set, so a
+ * "high-priority work items" hook never sees category or delete events.
+ *
+ * - GET/POST   /api/webhooks        list (optionally ?user_id=) / create
+ * - PUT/DELETE /api/webhooks/{id}   update filters, pause / remove
+ */
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    routing::{get, put},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sqlx::types::chrono::Utc;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    db::SqlitePool,
+    error::{ApiError, ApiResult, JsonBody},
+    model::{Webhook, WebhookCreate, WebhookUpdate},
+    routes::AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/webhooks", get(list_webhooks).post(create_webhook))
+        .route(
+            "/api/webhooks/{id}",
+            put(update_webhook).delete(delete_webhook),
+        )
+}
+
+/// Comma separated list filter; None or an empty list matches everything.
+fn list_filter(list: Option<&str>) -> Vec<&str> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+impl Webhook {
+    /// Whether this hook wants the given hub event.
+    pub fn matches(&self, event_type: &str, data: &Value) -> bool {
+        let types = list_filter(self.event_types.as_deref());
+        if !types.is_empty()
+            && !types.iter().any(|t| match t.strip_suffix('*') {
+                Some(prefix) => event_type.starts_with(prefix),
+                None => *t == event_type,
+            })
+        {
+            return false;
+        }
+
+        let is_todo = event_type.starts_with("todo.") && data.get("title").is_some();
+        let categories = list_filter(self.category_ids.as_deref());
+        if !categories.is_empty() {
+            let category = data["category_id"].as_str();
+            if !is_todo || !category.is_some_and(|c| categories.contains(&c)) {
+                return false;
+            }
+        }
+        let priority_ok = self
+            .min_priority
+            .is_none_or(|min| is_todo && data["priority"].as_i64().is_some_and(|p| p >= min));
+        let assignee_ok = self
+            .assignee_id
+            .as_deref()
+            .is_none_or(|a| is_todo && data["assignee_id"].as_str() == Some(a));
+        priority_ok && assignee_ok
+    }
+}
+
+fn validate_url(url: &str) -> ApiResult<()> {
+    match reqwest::Url::parse(url) {
+        Ok(u) if matches!(u.scheme(), "http" | "https") => Ok(()),
+        _ => Err(ApiError::BadRequest(format!(
+            "webhook url `{url}` must be an http(s) URL"
+        ))),
+    }
+}
+
+/**
+ * Start the delivery task: forwards matching hub events to the webhooks
+ * stored in `pool`. Deliveries are fire-and-forget with a 10s timeout.
+ */
+pub fn spawn(state: AppState) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    let mut rx = state.hub.tx.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let msg = match rx.recv().await {
+                Ok(msg) => msg,
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "webhook dispatcher lagged, events dropped");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if let Err(e) = dispatch(&client, &state.pool, &msg).await {
+                tracing::warn!(error = %e, "webhook dispatch failed");
+            }
+        }
+    });
+}
+
+async fn dispatch(client: &reqwest::Client, pool: &SqlitePool, msg: &str) -> anyhow::Result<()> {
+    let event: Value = serde_json::from_str(msg)?;
+    let event_type = event["type"].as_str().unwrap_or_default();
+    let hooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE enabled = 1")
+        .fetch_all(pool)
+        .await?;
+    for hook in hooks
+        .into_iter()
+        .filter(|h| h.matches(event_type, &event["data"]))
+    {
+        let request = client
+            .post(&hook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", event_type)
+            .body(msg.to_string());
+        tokio::spawn(async move {
+            let result = request.send().await.and_then(|r| r.error_for_status());
+            if let Err(e) = result {
+                tracing::warn!(webhook = %hook.name, error = %e, "webhook delivery failed");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    user_id: Option<String>,
+}
+
+async fn list_webhooks(
+    State(st): State<AppState>,
+    Query(q): Query<ListQuery>,
+) -> ApiResult<Json<Vec<Webhook>>> {
+    let rows = match q.user_id {
+        Some(user_id) => {
+            sqlx::query_as::<_, Webhook>(
+                "SELECT * FROM webhooks WHERE user_id=?1 ORDER BY created_at ASC",
+            )
+            .bind(user_id)
+            .fetch_all(&st.pool)
+            .await?
+        }
+        None => {
+            sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY created_at ASC")
+                .fetch_all(&st.pool)
+                .await?
+        }
+    };
+    Ok(Json(rows))
+}
+
+async fn create_webhook(
+    State(st): State<AppState>,
+    JsonBody(body): JsonBody<WebhookCreate>,
+) -> ApiResult<Json<Webhook>> {
+    if body.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    validate_url(&body.url)?;
+    let hook = Webhook::new_from_create(body);
+    sqlx::query(
+        r#"
+        INSERT INTO webhooks (id,name,url,user_id,event_types,category_ids,min_priority,
+                              assignee_id,enabled,created_at,updated_at)
+        VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)
+    "#,
+    )
+    .bind(&hook.id)
+    .bind(&hook.name)
+    .bind(&hook.url)
+    .bind(&hook.user_id)
+    .bind(&hook.event_types)
+    .bind(&hook.category_ids)
+    .bind(hook.min_priority)
+    .bind(&hook.assignee_id)
+    .bind(hook.enabled)
+    .bind(hook.created_at)
+    .bind(hook.updated_at)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(hook))
+}
+
+async fn update_webhook(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+    JsonBody(body): JsonBody<WebhookUpdate>,
+) -> ApiResult<Json<Webhook>> {
+    let mut hook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id=?1")
+        .bind(&id)
+        .fetch_optional(&st.pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if let Some(v) = body.name {
+        hook.name = v;
+    }
+    if let Some(v) = body.url {
+        validate_url(&v)?;
+        hook.url = v;
+    }
+    // Filters: an empty string clears the filter
+    if let Some(v) = body.event_types {
+        hook.event_types = (!v.trim().is_empty()).then_some(v);
+    }
+    if let Some(v) = body.category_ids {
+        hook.category_ids = (!v.trim().is_empty()).then_some(v);
+    }
+    if body.min_priority.is_some() {
+        hook.min_priority = body.min_priority;
+    }
+    if let Some(v) = body.assignee_id {
+        hook.assignee_id = (!v.trim().is_empty()).then_some(v);
+    }
+    if let Some(v) = body.enabled {
+        hook.enabled = v;
+    }
+    hook.updated_at = Utc::now();
+
+    sqlx::query(
+        r#"
+        UPDATE webhooks SET name=?2,url=?3,event_types=?4,category_ids=?5,min_priority=?6,
+                            assignee_id=?7,enabled=?8,updated_at=?9
+        WHERE id=?1
+    "#,
+    )
+    .bind(&hook.id)
+    .bind(&hook.name)
+    .bind(&hook.url)
+    .bind(&hook.event_types)
+    .bind(&hook.category_ids)
+    .bind(hook.min_priority)
+    .bind(&hook.assignee_id)
+    .bind(hook.enabled)
+    .bind(hook.updated_at)
+    .execute(&st.pool)
+    .await?;
+    Ok(Json(hook))
+}
+
+async fn delete_webhook(
+    State(st): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let res = sqlx::query("DELETE FROM webhooks WHERE id=?1")
+        .bind(&id)
+        .execute(&st.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(json!({"ok": true})))
+}
@@ -8,14 +8,61 @@
  *
  * Architecture Pattern: Observer/Publisher-Subscriber
  * Similar to Qt signals/slots or event-driven systems in C++
+ *
+ * Connections made through the app's /ws/updates route first receive a
+ * snapshot of the board and the `seq` it corresponds to:
+ *
+ * ```text
+ * {"type": "snapshot", "seq": 41, "data": {"todos": [...], "categories": [...]}}
+ * ```
+ *
+ * followed by live events carrying their own `seq` (see outbox.rs). Events
+ * already contained in the snapshot are skipped, and since the client is
+ * subscribed before the snapshot is read nothing falls into the gap.
+ * `?snapshot=false` opts out. Guests of a share link (/ws/shared/{token},
+ * see shares.rs) get a snapshot and the events of the shared list only.
+ *
+ * Messages are JSON text frames unless the client asks for a binary
+ * encoding with the `Sec-WebSocket-Protocol` header: `todo.msgpack`
+ * (MessagePack) or `todo.cbor` (CBOR) get the same messages re-encoded as
+ * binary frames, which small display clients parse far more cheaply.
+ *
+ * Limits keep a misbehaving client from taking the Pi down with it:
+ * upgrades beyond `max_connections` get 503, each client has a bounded
+ * send queue and is disconnected once it falls that far behind or stops
+ * reading for SEND_TIMEOUT, and incoming frames are capped in size.
  */
 use axum::{
-    extract::ws::{Message, WebSocket, WebSocketUpgrade}, // WebSocket types
-    response::Response,                                  // HTTP response type
+    extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code}, // WebSocket types
+    http::{HeaderValue, StatusCode},    // Negotiated subprotocol, 503
+    response::{IntoResponse, Response}, // HTTP response type
 };
+use chrono::Utc;
 use futures::{SinkExt, StreamExt}; // Async stream handling
-use std::sync::Arc; // Atomic reference counting
-use tokio::sync::broadcast; // Multi-producer, multi-consumer channel
+use serde::Deserialize; // Query string parsing
+use serde_json::{Value, json};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+}; // Atomic reference counting
+use tokio::sync::{broadcast, mpsc, watch}; // Multi-producer, multi-consumer channel
+
+/// A client that has not accepted a frame for this long is disconnected.
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest frame accepted from clients (they only send heartbeats).
+const MAX_INCOMING_BYTES: usize = 64 * 1024;
+
+use crate::{
+    error::ApiResult,
+    outbox::event,
+    presence::Presences,
+    routes::AppState,
+    services::{TodoFilter, emit},
+    shares::SharedView,
+};
 
 /**
  * WebSocket Hub - Central message broadcaster
@@ -29,6 +76,11 @@ use tokio::sync::broadcast; // Multi-producer, multi-consumer channel
 #[derive(Clone)]
 pub struct WsHub {
     pub tx: broadcast::Sender<String>, // Broadcaster for sending messages to all clients
+    max_connections: usize,            // Concurrent clients; 0 = unlimited
+    send_queue: usize,                 // Frames queued per client before it is dropped
+    connections: Arc<AtomicUsize>,     // Currently connected clients
+    pub presence: Presences,           // Who is connected (GET /api/presence)
+    closing: watch::Sender<bool>,      // Set on shutdown; every connection closes
 }
 
 impl WsHub {
@@ -41,8 +93,169 @@ impl WsHub {
      * Pattern: Factory method
      */
     pub fn new() -> Self {
-        let (tx, _rx) = broadcast::channel(256); // Create broadcast channel
-        Self { tx }
+        Self::with_capacity(256)
+    }
+
+    /// Hub with a custom buffer; slow clients lag (and drop events) beyond it.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity); // Create broadcast channel
+        Self {
+            tx,
+            max_connections: 0,
+            send_queue: 64,
+            connections: Arc::default(),
+            presence: Presences::default(),
+            closing: watch::Sender::new(false),
+        }
+    }
+
+    /// Cap concurrent clients (0 = unlimited) and each client's send queue.
+    pub fn with_limits(mut self, max_connections: usize, send_queue: usize) -> Self {
+        self.max_connections = max_connections;
+        self.send_queue = send_queue.max(1);
+        self
+    }
+
+    /**
+     * Close every connection with 1001 (going away), for shutdown
+     *
+     * Connections never end on their own, so a graceful shutdown would
+     * wait for them forever. Clients reconnect once the server is back.
+     * Connections opened afterwards are closed right away.
+     */
+    pub fn close_all(&self) {
+        self.closing.send_replace(true);
+    }
+
+    /// Number of connected WebSocket clients.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Reserve a connection slot, unless the limit is reached.
+    fn connect(&self) -> Option<Connection> {
+        let limit = self.max_connections;
+        self.connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (limit == 0 || n < limit).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Connection(self.connections.clone()))
+    }
+}
+
+/// A connection slot, released when the socket handler ends.
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Default for WsHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Subprotocols a client may request; the first one it lists that we know wins.
+pub const PROTOCOLS: [&str; 3] = ["todo.json", "todo.msgpack", "todo.cbor"];
+
+/**
+ * Payload encoding negotiated through the WebSocket subprotocol
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Json, // Text frames (no or `todo.json` subprotocol)
+    MessagePack, // Binary frames, `todo.msgpack`
+    Cbor,        // Binary frames, `todo.cbor`
+}
+
+impl Encoding {
+    fn from_protocol(protocol: Option<&HeaderValue>) -> Self {
+        match protocol.and_then(|p| p.to_str().ok()) {
+            Some("todo.msgpack") => Self::MessagePack,
+            Some("todo.cbor") => Self::Cbor,
+            _ => Self::Json,
+        }
+    }
+
+    /// JSON value of a frame sent by the client, in this encoding.
+    fn decode(self, frame: &Message) -> Option<Value> {
+        match (frame, self) {
+            (Message::Text(text), _) => serde_json::from_str(text.as_str()).ok(),
+            (Message::Binary(bytes), Self::MessagePack) => rmp_serde::from_slice(bytes).ok(),
+            (Message::Binary(bytes), Self::Cbor) => ciborium::from_reader(&bytes[..]).ok(),
+            (Message::Binary(bytes), Self::Json) => serde_json::from_slice(bytes).ok(),
+            _ => None, // Ping/pong/close
+        }
+    }
+
+    /// Frame carrying a JSON `message` in this encoding.
+    fn frame(self, message: String) -> Message {
+        let binary = |value: Value| -> Option<Vec<u8>> {
+            match self {
+                Self::Json => None,
+                Self::MessagePack => rmp_serde::to_vec(&value).ok(),
+                Self::Cbor => {
+                    let mut buf = Vec::new();
+                    ciborium::into_writer(&value, &mut buf).ok()?;
+                    Some(buf)
+                }
+            }
+        };
+        if self == Self::Json {
+            return Message::Text(message.into());
+        }
+        match serde_json::from_str(&message).ok().and_then(binary) {
+            Some(bytes) => Message::Binary(bytes.into()),
+            None => Message::Text(message.into()), // Not JSON; pass it on unchanged
+        }
+    }
+}
+
+/**
+ * Subscription scope requested by a client (`/ws/updates?project_id=...`)
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WsScope {
+    pub project_id: Option<String>, // Only events of this project (plus global ones)
+    pub snapshot: Option<bool>,     // Start with a snapshot (default true, app route only)
+    #[serde(skip)]
+    pub share: Option<Arc<SharedView>>, // Guest of a share link: only its list, read-only
+}
+
+impl WsScope {
+    /**
+     * Whether an event should reach a client with this scope
+     *
+     * Events carrying a project (todos and categories, directly in `data`
+     * or in `data.todo`) are delivered only for the subscribed project;
+     * events without one (deletions by id, statuses, projects) reach
+     * everyone.
+     */
+    pub(crate) fn wants(&self, msg: &str) -> bool {
+        match serde_json::from_str::<Value>(msg) {
+            Ok(event) => self.wants_event(&event),
+            Err(_) => true,
+        }
+    }
+
+    /// Like wants, for an already parsed message.
+    fn wants_event(&self, event: &Value) -> bool {
+        let Some(project) = &self.project_id else {
+            return true;
+        };
+        let data = &event["data"];
+        let owner = [data, &data["todo"]]
+            .into_iter()
+            .find_map(|d| d.as_object().and_then(|o| o.get("project_id")));
+        match owner {
+            Some(owner) => owner.as_str() == Some(project.as_str()),
+            None => true,
+        }
     }
 }
 
@@ -59,57 +272,267 @@ impl WsHub {
  * Pattern: Adapter - converts HTTP upgrade request to WebSocket connection
  */
 pub async fn ws_handler(ws: WebSocketUpgrade, hub: Arc<WsHub>) -> Response {
+    ws_scoped_handler(ws, hub, WsScope::default()).await
+}
+
+/// Like ws_handler, delivering only the events `scope` asks for.
+pub async fn ws_scoped_handler(ws: WebSocketUpgrade, hub: Arc<WsHub>, scope: WsScope) -> Response {
+    upgrade(ws, hub, scope, None)
+}
+
+/// Like ws_scoped_handler, opening with a snapshot of `state`'s board.
+pub async fn ws_snapshot_handler(
+    ws: WebSocketUpgrade,
+    state: AppState,
+    scope: WsScope,
+) -> Response {
+    let hub = state.hub.clone();
+    let state = scope.snapshot.unwrap_or(true).then_some(state);
+    upgrade(ws, hub, scope, state)
+}
+
+/// Upgrade to a WebSocket unless the connection limit is reached (503).
+fn upgrade(
+    ws: WebSocketUpgrade,
+    hub: Arc<WsHub>,
+    scope: WsScope,
+    state: Option<AppState>,
+) -> Response {
+    let Some(slot) = hub.connect() else {
+        tracing::warn!(
+            limit = hub.max_connections,
+            "WebSocket connection limit reached"
+        );
+        let body = json!({
+            "error": "too_many_connections",
+            "message": "too many WebSocket clients; try again later or use /api/events",
+        });
+        return (StatusCode::SERVICE_UNAVAILABLE, axum::Json(body)).into_response();
+    };
     // Upgrade the HTTP connection to WebSocket protocol
     // This is like accepting a TCP connection in C++ socket programming
-    ws.on_upgrade(move |sock| handle_socket(sock, hub))
+    let ws = ws.protocols(PROTOCOLS).max_message_size(MAX_INCOMING_BYTES);
+    let encoding = Encoding::from_protocol(ws.selected_protocol());
+    ws.on_upgrade(move |sock| async move {
+        handle_socket(sock, hub, scope, state, encoding).await;
+        drop(slot);
+    })
+}
+
+/**
+ * Snapshot message for a newly connected client, with its seq
+ *
+ * The seq is read first: every event up to it has been applied to the
+ * data loaded afterwards, later ones are delivered live.
+ */
+async fn snapshot(state: &AppState, scope: &WsScope) -> ApiResult<(String, i64)> {
+    let seq = state.outbox.last_seq().await?;
+    if let Some(share) = &scope.share {
+        let todos = share.todos(state).await?;
+        let message = json!({
+            "type": "snapshot",
+            "seq": seq,
+            "data": {"todos": todos, "categories": []},
+        });
+        return Ok((message.to_string(), seq));
+    }
+    let filter = TodoFilter {
+        project_id: scope.project_id.clone(),
+        ..Default::default()
+    };
+    let todos = state.todos.list(&filter).await?;
+    let mut categories = state.categories.list().await?;
+    categories.retain(|c| {
+        !c.archived
+            && scope
+                .project_id
+                .as_ref()
+                .is_none_or(|p| c.project_id.as_ref() == Some(p))
+    });
+    let message = json!({
+        "type": "snapshot",
+        "seq": seq,
+        "data": {"todos": todos, "categories": categories},
+    });
+    Ok((message.to_string(), seq))
 }
 
 /**
  * Handle individual WebSocket connection
  *
  * This function manages the lifetime of a single WebSocket connection.
- * It splits the socket into sender/receiver halves and creates two concurrent tasks:
- * 1. Send task: Forwards broadcast messages to this client
- * 2. Receive task: Handles incoming messages from this client
+ * It splits the socket into sender/receiver halves and creates three concurrent tasks:
+ * 1. Forward task: Queues broadcast messages for this client (bounded)
+ * 2. Send task: Writes queued frames to the socket
+ * 3. Receive task: Handles incoming messages from this client
+ *
+ * A full queue, a lagging broadcast receiver or a send that takes longer
+ * than SEND_TIMEOUT ends the connection; the client reconnects and starts
+ * from a fresh snapshot.
  *
  * Pattern: Actor model - each connection is an independent actor
  * Similar to having separate threads for reading/writing in C++
  */
-async fn handle_socket(socket: WebSocket, hub: Arc<WsHub>) {
+async fn handle_socket(
+    socket: WebSocket,
+    hub: Arc<WsHub>,
+    scope: WsScope,
+    state: Option<AppState>,
+    encoding: Encoding,
+) {
     // Split WebSocket into independent send/receive halves
     // This allows concurrent reading and writing (like full-duplex communication)
     let (mut sender, mut receiver) = socket.split();
 
     // Subscribe to broadcast channel to receive messages for all clients
     let mut rx = hub.tx.subscribe();
+    let mut closing = hub.closing.subscribe();
+
+    // Snapshot after subscribing, so no event falls between the two
+    let mut seen = 0;
+    if let Some(state) = state {
+        match snapshot(&state, &scope).await {
+            Ok((message, seq)) => {
+                let sent = tokio::time::timeout(SEND_TIMEOUT, sender.send(encoding.frame(message)));
+                if !matches!(sent.await, Ok(Ok(()))) {
+                    return;
+                }
+                seen = seq;
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to load WebSocket snapshot");
+                return; // The client reconnects and tries again
+            }
+        }
+    }
+
+    // Listed in GET /api/presence until the socket closes
+    let presence_id = hub.presence.connect(scope.project_id.clone());
 
     // Task 1: Forward broadcast messages to this specific client
-    // This runs concurrently and sends any broadcast message to the client
-    let send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            // Wait for broadcast message
-            // Send message to client; if it fails, client disconnected
-            if sender.send(Message::Text(msg.into())).await.is_err() {
-                break; // Client disconnected, exit the loop
+    // Frames go through a bounded queue, so a slow client cannot pile up memory
+    let (queue, mut queued) = mpsc::channel::<Message>(hub.send_queue);
+    let replies = queue.clone();
+    let goodbye = queue.clone();
+    let own_id = presence_id.clone();
+    let guest = scope.share.is_some();
+    let expires_at = scope.share.as_ref().and_then(|s| s.expires_at);
+    let mut forward_task = tokio::spawn(async move {
+        loop {
+            let next = rx.recv();
+            let received = match expires_at {
+                Some(at) => {
+                    let left = (at - Utc::now()).to_std().unwrap_or_default();
+                    match tokio::time::timeout(left, next).await {
+                        Ok(received) => received,
+                        Err(_) => break, // Share link expired
+                    }
+                }
+                None => next.await,
+            };
+            let msg = match received {
+                Ok(msg) => msg, // Wait for broadcast message
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "WebSocket client lagged behind; disconnecting");
+                    break;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let mut event: Value = serde_json::from_str(&msg).unwrap_or_default();
+            let mut msg = msg;
+            if let Some(share) = &scope.share {
+                match share.translate(&event) {
+                    Some(shared) => {
+                        msg = shared.to_string();
+                        event = shared;
+                    }
+                    None => continue, // Not part of the shared list
+                }
+            } else if !scope.wants_event(&event) {
+                continue; // Another project's event
+            }
+            if seen > 0 && event["seq"].as_i64().is_some_and(|seq| seq <= seen) {
+                continue; // Already part of the snapshot
+            }
+            if event["type"] == "editing" && event["data"]["client_id"] == own_id.as_str() {
+                continue; // Our own editing relay
+            }
+            match queue.try_send(encoding.frame(msg)) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!("WebSocket send queue full; disconnecting slow client");
+                    break;
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => break, // Client disconnected
             }
         }
     });
 
-    // Task 2: Handle incoming messages from this client
-    // Currently just consumes messages (echo server would send them back)
-    let recv_task = tokio::spawn(async move {
-        while let Some(Ok(_msg)) = receiver.next().await { // Wait for client message
-            // TODO: Handle incoming messages if needed
-            // This is where you'd implement client-to-server communication
-            // For example: client sending new todos, status updates, etc.
+    // Task 2: Write queued frames; a client that stops reading is dropped
+    let mut send_task = tokio::spawn(async move {
+        while let Some(frame) = queued.recv().await {
+            match tokio::time::timeout(SEND_TIMEOUT, sender.send(frame)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(_)) => break, // Client disconnected, exit the loop
+                Err(_) => {
+                    tracing::warn!("WebSocket client stopped reading; disconnecting");
+                    break;
+                }
+            }
+        }
+    });
+
+    // Task 3: Handle incoming messages from this client
+    // `hello` and `editing` (see presence.rs); anything else (heartbeats) is ignored
+    let client_hub = hub.clone();
+    let client_id = presence_id.clone();
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = receiver.next().await {
+            // Wait for client message
+            let Some(message) = encoding.decode(&msg) else {
+                continue;
+            };
+            if guest {
+                continue; // Share guests are read-only
+            }
+            if message["type"] == "hello" {
+                let name = message["name"].as_str().unwrap_or_default();
+                if let Some(presence) = client_hub.presence.hello(&client_id, name) {
+                    let reply = event("hello", &json!({"id": client_id}));
+                    let _ = replies.try_send(encoding.frame(reply));
+                    emit(&client_hub, "presence.join", &presence);
+                }
+            } else if message["type"] == "editing"
+                && let Some(editing) = client_hub.presence.editing(&client_id, &message)
+            {
+                emit(&client_hub, "editing", &editing); // Relayed, never stored
+            }
         }
     });
 
     // Wait for either task to complete (usually means client disconnected)
     // This is like pthread_join in C++ - wait for threads to finish
     tokio::select! {
-        _ = send_task => { }  // Send task completed (client disconnected)
-        _ = recv_task => { }  // Receive task completed (client disconnected)
+        _ = &mut forward_task => { } // Client too slow (or shutting down)
+        _ = &mut send_task => { }    // Send task completed (client disconnected)
+        _ = &mut recv_task => { }    // Receive task completed (client disconnected)
+        Ok(()) = async { closing.wait_for(|closing| *closing).await.map(drop) } => {
+            // Server shutting down: stop queueing, send a Close frame last
+            forward_task.abort();
+            recv_task.abort();
+            let _ = goodbye.try_send(Message::Close(Some(CloseFrame {
+                code: close_code::AWAY,
+                reason: "server shutting down".into(),
+            })));
+            drop(goodbye); // With the other senders gone, the send task ends after the frame
+            let _ = tokio::time::timeout(SEND_TIMEOUT, &mut send_task).await;
+        }
+    }
+    // Stop the others, which drops the socket halves and closes the connection
+    forward_task.abort();
+    send_task.abort();
+    recv_task.abort();
+    if let Some(presence) = hub.presence.disconnect(&presence_id) {
+        emit(&hub, "presence.leave", &presence);
     }
-    // When we reach here, the WebSocket connection is closed and cleaned up
 }
@@ -13,41 +13,41 @@ async function http<T>(path: string, init?: RequestInit): Promise<T> {
 
 export const api = {
   listTodos: (status?: string): Promise<Todo[]> =>
-    http<Todo[]>(`/api/todos${status ? `?status=${status}` : ''}`),
+    http<Todo[]>(`/api/v1/todos${status ? `?status=${status}` : ''}`),
   createTodo: (data: Partial<Todo>): Promise<Todo> =>
-    http<Todo>('/api/todos', { method: 'POST', body: JSON.stringify(data) }),
-  getTodo: (id: string): Promise<Todo> => http<Todo>(`/api/todos/${id}`),
+    http<Todo>('/api/v1/todos', { method: 'POST', body: JSON.stringify(data) }),
+  getTodo: (id: string): Promise<Todo> => http<Todo>(`/api/v1/todos/${id}`),
   updateTodo: (id: string, data: Partial<Todo>): Promise<Todo> =>
-    http<Todo>(`/api/todos/${id}`, {
+    http<Todo>(`/api/v1/todos/${id}`, {
       method: 'PUT',
       body: JSON.stringify(data),
     }),
   updateStatus: (id: string, status: string): Promise<Todo> =>
-    http<Todo>(`/api/todos/${id}/status?status=${status}`, { method: 'PATCH' }),
+    http<Todo>(`/api/v1/todos/${id}/status?status=${status}`, { method: 'PATCH' }),
   deleteTodo: (id: string): Promise<void> =>
-    http<void>(`/api/todos/${id}`, { method: 'DELETE' }),
+    http<void>(`/api/v1/todos/${id}`, { method: 'DELETE' }),
   reorder: (items: { id: string; sort_order: number }[]): Promise<void> =>
-    http<void>('/api/todos/reorder', {
+    http<void>('/api/v1/todos/reorder', {
       method: 'POST',
       body: JSON.stringify(items),
     }),
   // Category endpoints
   listCategories: (): Promise<Category[]> =>
-    http<Category[]>('/api/categories'),
+    http<Category[]>('/api/v1/categories'),
   createCategory: (data: Partial<Category>): Promise<Category> =>
-    http<Category>('/api/categories', {
+    http<Category>('/api/v1/categories', {
       method: 'POST',
       body: JSON.stringify(data),
     }),
   getCategory: (id: string): Promise<Category> =>
-    http<Category>(`/api/categories/${id}`),
+    http<Category>(`/api/v1/categories/${id}`),
   updateCategory: (id: string, data: Partial<Category>): Promise<Category> =>
-    http<Category>(`/api/categories/${id}`, {
+    http<Category>(`/api/v1/categories/${id}`, {
       method: 'PUT',
       body: JSON.stringify(data),
     }),
   deleteCategory: (id: string): Promise<void> =>
-    http<void>(`/api/categories/${id}`, { method: 'DELETE' }),
+    http<void>(`/api/v1/categories/${id}`, { method: 'DELETE' }),
 }
 
 export { API_BASE }